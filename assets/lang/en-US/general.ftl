@@ -4,8 +4,32 @@ quit = Quit
 menu-server-local = Local Game
  .test = New Test Server
 menu-server-join = Join Server
+ .address = Address:
+ .player-name = Player Name:
+ .connect = Connect
+ .disconnect = Disconnect
+ .disconnected = Not connected
+ .failed = Disconnected: { $reason }
+ .connecting = Connecting...
+ .joining = Joining...
+ .connected = Connected: { $motd }
+menu-load-game = Load Game
+ .refresh = Refresh
+ .name = Name:
+ .new = New Game
+ .play = Play
+ .duplicate = Duplicate
+ .rename = Rename
+ .delete = Delete
+ .export = Export
+ .empty = No saves yet
+ .needs-upgrade = Upgraded when played
+ .unloadable = Can't be loaded
 menu-server-starting = Launching Server
  .cancel = Cancel
+menu-server-pause = Pause
+ .resume = Resume
+ .stop = Save and Quit
 
 settings-title = Settings
 settings-cancel = Cancel
@@ -17,4 +41,18 @@ local-server-state = Local Server:
  .loading = Loading {$completion}
  .loading_off = Loading { NUMBER($completion, style: "percent") }
  .running = Running
+ .paused = Paused
  .shutting-down = Shutting Down
+
+command-help = Lists the commands you can use, or explains one of them.
+ .aliases = Also:
+command-quit = Exits the game, saving and stopping the local server first.
+command-stop = Saves everything then stops the server.
+command-save = Saves everything now instead of waiting for the autosave.
+command-list = Lists the connected players.
+command-kick = Disconnects a player.
+command-say = Sends a message to every player.
+command-teleport = Moves a player to a position in the world they are in.
+command-settile = Changes the tile at a position in the world players spawn in.
+command-time = Shows the time of day, or sets it or skips ahead by a number of ticks.
+command-gamerule = Shows the game rules, or changes one of them.
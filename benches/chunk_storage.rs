@@ -0,0 +1,118 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use mechaenetia::game_data::chunks::chunk::{Chunk, ChunkCoord, LocalTileCoord};
+use mechaenetia::game_data::tiles::TileId;
+use std::num::NonZeroU8;
+
+const EDGE_LEN: u8 = 32;
+
+/// The flat layout chunks used before palette compression, one `TileId` per tile.
+struct NaiveChunk {
+	edge_len: NonZeroU8,
+	data: Vec<TileId>,
+}
+
+impl NaiveChunk {
+	fn new(edge_len: NonZeroU8) -> Self {
+		let len = edge_len.get() as usize;
+		NaiveChunk {
+			edge_len,
+			data: vec![TileId::AIR; len * len * len],
+		}
+	}
+
+	fn get_tile(&self, coord: LocalTileCoord) -> Option<TileId> {
+		coord.to_index(self.edge_len).map(|index| self.data[index])
+	}
+
+	fn set_tile(&mut self, coord: LocalTileCoord, tile: TileId) -> Option<TileId> {
+		coord
+			.to_index(self.edge_len)
+			.map(|index| std::mem::replace(&mut self.data[index], tile))
+	}
+}
+
+/// Layered terrain with `distinct` tile types, the common case for generated chunks.
+fn layered_tile(coord: LocalTileCoord, distinct: u16) -> TileId {
+	TileId::new(coord.z() as u16 * distinct / EDGE_LEN as u16)
+}
+
+fn coords() -> impl Iterator<Item = LocalTileCoord> {
+	let len = NonZeroU8::new(EDGE_LEN).unwrap();
+	let tile_count = EDGE_LEN as usize * EDGE_LEN as usize * EDGE_LEN as usize;
+	(0..tile_count).map(move |index| LocalTileCoord::from_index(index, len).unwrap())
+}
+
+fn bench_fill(c: &mut Criterion) {
+	let edge_len = NonZeroU8::new(EDGE_LEN).unwrap();
+	let mut group = c.benchmark_group("chunk_fill_layered");
+	for distinct in [1u16, 4, 16, 256].iter().copied() {
+		group.bench_with_input(
+			BenchmarkId::new("paletted", distinct),
+			&distinct,
+			|b, &distinct| {
+				b.iter(|| {
+					let mut chunk = Chunk::new(ChunkCoord::new(0, 0, 0), edge_len);
+					for coord in coords() {
+						chunk
+							.set_tile(coord, layered_tile(coord, distinct))
+							.unwrap();
+					}
+					black_box(chunk)
+				})
+			},
+		);
+		group.bench_with_input(
+			BenchmarkId::new("naive", distinct),
+			&distinct,
+			|b, &distinct| {
+				b.iter(|| {
+					let mut chunk = NaiveChunk::new(edge_len);
+					for coord in coords() {
+						chunk
+							.set_tile(coord, layered_tile(coord, distinct))
+							.unwrap();
+					}
+					black_box(chunk.data.len())
+				})
+			},
+		);
+	}
+	group.finish();
+}
+
+fn bench_read(c: &mut Criterion) {
+	let edge_len = NonZeroU8::new(EDGE_LEN).unwrap();
+	let mut group = c.benchmark_group("chunk_read_all");
+	for distinct in [1u16, 4, 16, 256].iter().copied() {
+		let mut chunk = Chunk::new(ChunkCoord::new(0, 0, 0), edge_len);
+		let mut naive = NaiveChunk::new(edge_len);
+		for coord in coords() {
+			chunk
+				.set_tile(coord, layered_tile(coord, distinct))
+				.unwrap();
+			naive
+				.set_tile(coord, layered_tile(coord, distinct))
+				.unwrap();
+		}
+		let stats = chunk.storage_stats();
+		println!(
+			"{} distinct tiles: paletted {} bytes ({} bits per tile), naive {} bytes",
+			distinct,
+			stats.heap_bytes,
+			stats.bits_per_entry,
+			stats.naive_heap_bytes()
+		);
+		group.bench_with_input(
+			BenchmarkId::new("paletted", distinct),
+			&chunk,
+			|b, chunk| b.iter(|| coords().filter_map(|coord| chunk.get_tile(coord)).count()),
+		);
+		group.bench_with_input(BenchmarkId::new("naive", distinct), &naive, |b, naive| {
+			b.iter(|| coords().filter_map(|coord| naive.get_tile(coord)).count())
+		});
+	}
+	group.finish();
+}
+
+criterion_group!(benches, bench_fill, bench_read);
+criterion_main!(benches);
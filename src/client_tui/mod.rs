@@ -4,6 +4,10 @@ mod tui_plugin;
 use crate::client_tui::tui_plugin::TUI;
 use crate::universal::conditional_map::ConditionalMap;
 use crate::universal::exit::RequestExit;
+use crate::universal::local_server::LocalServerPublicState;
+use crate::universal::network::client::{
+	NetworkClientCommand, ServerConnectionState, DEFAULT_PLAYER_NAME,
+};
 use bevy::app::{PluginGroupBuilder, ScheduleRunnerSettings};
 use bevy::input::keyboard::KeyboardInput;
 use bevy::input::ElementState;
@@ -49,6 +53,7 @@ impl Plugin for ClientTuiPlugin {
 		)))
 		.add_system(exit_on_window_close.system())
 		.add_system(exit_on_escape.system())
+		.add_system(join_local_server.system())
 		.add_system_to_stage(CoreStage::PostUpdate, draw.exclusive_system());
 	}
 }
@@ -73,6 +78,22 @@ fn exit_on_window_close(
 	}
 }
 
+/// Plays the local game through the same connection as any other server once it is running.
+fn join_local_server(
+	mut local_server_state: EventReader<LocalServerPublicState>,
+	connection_state: Res<ServerConnectionState>,
+	mut network_cmd: EventWriter<NetworkClientCommand>,
+) {
+	let running = local_server_state
+		.iter()
+		.any(|state| *state == LocalServerPublicState::Running);
+	if running && matches!(*connection_state, ServerConnectionState::Disconnected(_)) {
+		network_cmd.send(NetworkClientCommand::JoinLocal {
+			player_name: DEFAULT_PLAYER_NAME.to_owned(),
+		});
+	}
+}
+
 fn draw(world: &mut World) {
 	let world = world.cell();
 	let mut tui = world.get_resource_mut::<TUI>().unwrap();
@@ -0,0 +1,64 @@
+use crate::client_tui::tui_plugin::Frame;
+use crate::universal::exit::Exiting;
+use crate::universal::network::client::ServerConnectionState;
+use crate::universal::network::WorldCache;
+use bevy::ecs::world::WorldCell;
+use bevy::prelude::*;
+
+pub fn register_systems(app: &mut AppBuilder) {
+	let state = super::ClientState::Joined;
+	app.add_system_set(SystemSet::on_enter(state.clone()).with_system(on_enter.system()))
+		.add_system_set(
+			SystemSet::on_update(state.clone())
+				.with_system(on_update.system())
+				.with_system(on_shutdown.system()),
+		)
+		.add_system_set(SystemSet::on_exit(state.clone()).with_system(on_exit.system()));
+}
+
+fn on_enter() {
+	trace!("Client Joined State: Enter");
+}
+
+fn on_update(
+	connection_state: Res<ServerConnectionState>,
+	exiting: Option<Res<Exiting>>,
+	mut state: ResMut<State<super::ClientState>>,
+) {
+	// trace!("Client Joined State: Update");
+	if exiting.is_none() && !matches!(*connection_state, ServerConnectionState::Connected { .. }) {
+		state
+			.set(super::ClientState::NotConnected)
+			.expect("Failed to transition Client to not connected state");
+	}
+}
+
+fn on_exit() {
+	trace!("Client Joined State: Exit");
+}
+
+fn on_shutdown(exiting: Option<Res<Exiting>>, mut state: ResMut<State<super::ClientState>>) {
+	if let Some(_exiting) = exiting {
+		state
+			.overwrite_replace(super::ClientState::Exiting)
+			.expect("Failed to transition Client to exiting state");
+	}
+}
+
+/// The world around the player from above, as far as the server sent it.
+pub fn draw(world: &WorldCell, f: &mut Frame) {
+	use tui::text::Spans;
+	use tui::widgets::*;
+	let cache = world.get_resource::<WorldCache>().unwrap();
+	let size = f.size();
+	let block = Block::default()
+		.title(cache.world().unwrap_or("Joined"))
+		.borders(Borders::ALL);
+	let inner = block.inner(size);
+	let map: Vec<Spans> = cache
+		.surface_map(inner.width as usize, inner.height as usize)
+		.into_iter()
+		.map(Spans::from)
+		.collect();
+	f.render_widget(Paragraph::new(map).block(block), size);
+}
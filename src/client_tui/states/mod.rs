@@ -3,6 +3,7 @@
 // mod main_menu;
 
 mod exiting;
+mod joined;
 mod not_connected;
 
 use crate::client_tui::tui_plugin::Frame;
@@ -15,7 +16,7 @@ pub enum ClientState {
 	// MainMenu,
 	// JoinGame,
 	// Joining,
-	// Joined,
+	Joined,
 	// Paused,
 	Exiting,
 }
@@ -28,6 +29,7 @@ impl Plugin for ClientStatePlugin {
 		// Add the Client state into the system.
 		app.add_state(ClientState::NotConnected);
 		not_connected::register_systems(app);
+		joined::register_systems(app);
 		// main_menu::register_systems(app);
 		exiting::register_systems(app);
 	}
@@ -37,6 +39,7 @@ impl ClientState {
 	pub fn draw(&self, world: &WorldCell, f: &mut Frame) {
 		match self {
 			ClientState::NotConnected => not_connected::draw(world, f),
+			ClientState::Joined => joined::draw(world, f),
 			ClientState::Exiting => exiting::draw(world, f),
 		}
 	}
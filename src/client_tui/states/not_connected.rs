@@ -1,5 +1,6 @@
 use crate::client_tui::tui_plugin::Frame;
 use crate::universal::exit::Exiting;
+use crate::universal::network::client::ServerConnectionState;
 use bevy::ecs::world::WorldCell;
 use bevy::prelude::*;
 
@@ -18,8 +19,17 @@ fn on_enter() {
 	trace!("Client Loading State: Enter");
 }
 
-fn on_update() {
+fn on_update(
+	connection_state: Res<ServerConnectionState>,
+	exiting: Option<Res<Exiting>>,
+	mut state: ResMut<State<super::ClientState>>,
+) {
 	// trace!("Client Loading State: Update");
+	if exiting.is_none() && matches!(*connection_state, ServerConnectionState::Connected { .. }) {
+		state
+			.set(super::ClientState::Joined)
+			.expect("Failed to transition Client to joined state");
+	}
 }
 
 fn on_exit() {
@@ -1,14 +1,28 @@
+#[cfg(feature = "server")]
+use crate::server::save::browser::{
+	delete_save, duplicate_save, export_save, list_saves, rename_save, SaveInfo, EXPORTS_DIR,
+	SAVES_DIR,
+};
+#[cfg(feature = "server")]
+use crate::server::save::{is_valid_name, SaveConfigError, SAVE_FORMAT_VERSION};
 use crate::universal::exit::{Exiting, RequestExit};
 use crate::universal::i18n::{
 	scan_languages_on_fs, I18nChangeLanguageTo, I18nLanguageChangedEvent, MsgCache, MsgKey,
 };
 use crate::universal::local_server::{LocalServerCommand, LocalServerPublicState};
+use crate::universal::network::client::{
+	NetworkClientCommand, ServerConnectionState, DEFAULT_PLAYER_NAME,
+};
+use crate::universal::network::protocol::is_valid_player_name;
+use crate::universal::network::WorldCache;
 use crate::universal::I18n;
 use bevy::prelude::*;
 use bevy_egui::egui::Ui;
 use bevy_egui::{egui, EguiContext, EguiPlugin, EguiSettings};
 use fluent::types::{FluentNumber, FluentNumberOptions, FluentNumberStyle};
 use std::path::PathBuf;
+#[cfg(feature = "server")]
+use std::time::SystemTime;
 
 pub fn register_systems(app: &mut AppBuilder) {
 	let state = super::ClientState::MainMenu;
@@ -21,6 +35,7 @@ pub fn register_systems(app: &mut AppBuilder) {
 				.with_system(on_update.system())
 				.with_system(update_language.system())
 				.with_system(update_local_server_state.system())
+				.with_system(update_server_connection_state.system())
 				.with_system(on_shutdown.system()),
 		)
 		.add_system_set(SystemSet::on_exit(state.clone()).with_system(on_exit.system()));
@@ -48,6 +63,7 @@ fn update_local_server_state(
 	mut main_menu_state: ResMut<Option<MainMenuState>>,
 	lang: Res<I18n>,
 	mut state: EventReader<LocalServerPublicState>,
+	mut network_cmd: EventWriter<NetworkClientCommand>,
 ) {
 	if let Some(state) = state.iter().last() {
 		if let Some(main_menu_state) = &mut *main_menu_state {
@@ -81,6 +97,19 @@ fn update_local_server_state(
 						.local_server_state_msg
 						.attr("running")
 						.update(&*lang);
+					// The local game is played through the same connection as any other server.
+					let launched = main_menu_state.screen == MainMenuScreen::LoadJoinLocalServer;
+					if launched && main_menu_state.join_server.is_disconnected() {
+						network_cmd.send(NetworkClientCommand::JoinLocal {
+							player_name: main_menu_state.join_server.host_player_name(),
+						});
+					}
+				}
+				LocalServerPublicState::Paused => {
+					main_menu_state
+						.local_server_state_msg
+						.attr("paused")
+						.update(&*lang);
 				}
 				LocalServerPublicState::ShuttingDown => {
 					main_menu_state
@@ -93,11 +122,27 @@ fn update_local_server_state(
 	}
 }
 
+fn update_server_connection_state(
+	mut main_menu_state: ResMut<Option<MainMenuState>>,
+	lang: Res<I18n>,
+	mut state: EventReader<ServerConnectionState>,
+) {
+	if let Some(state) = state.iter().last() {
+		if let Some(main_menu_state) = &mut *main_menu_state {
+			main_menu_state
+				.join_server
+				.update_state(&lang, state.clone());
+		}
+	}
+}
+
 #[derive(PartialEq, Eq)]
 enum MainMenuScreen {
 	Empty,
 	LocalServer,
 	LoadJoinLocalServer,
+	#[cfg(feature = "server")]
+	LoadGame,
 	JoinServer,
 	Settings,
 }
@@ -118,12 +163,111 @@ struct MainMenuState {
 	l_server_local_starting_cancel: MsgCache,
 	l_server_local_test: MsgCache,
 	l_server_join: MsgCache,
+	l_server_pause: MsgCache,
+	l_server_pause_resume: MsgCache,
+	l_server_pause_stop: MsgCache,
 	l_settings_title: MsgCache,
 	l_settings_cancel: MsgCache,
 	l_settings_current_language: MsgCache,
 	l_settings_choose_language: MsgCache,
 	screen: MainMenuScreen,
 	local_server_state_msg: MsgCache,
+	/// The pause menu of the local game is open, the local server is paused while it is unless
+	/// remote players are connected.
+	pause_menu: bool,
+	#[cfg(feature = "server")]
+	load_game: LoadGameScreen,
+	join_server: JoinServerScreen,
+}
+
+/// The saves the player can pick from and what they are doing with them.
+#[cfg(feature = "server")]
+struct LoadGameScreen {
+	l_title: MsgCache,
+	l_refresh: MsgCache,
+	l_name: MsgCache,
+	l_new: MsgCache,
+	l_play: MsgCache,
+	l_duplicate: MsgCache,
+	l_rename: MsgCache,
+	l_delete: MsgCache,
+	l_export: MsgCache,
+	l_empty: MsgCache,
+	l_needs_upgrade: MsgCache,
+	l_unloadable: MsgCache,
+	saves: Vec<SaveInfo>,
+	selected: Option<PathBuf>,
+	/// The name for new, duplicated and renamed saves.
+	name: String,
+	/// The outcome of the last thing done with a save, shown until the next one.
+	status: Option<String>,
+}
+
+#[cfg(feature = "server")]
+impl Default for LoadGameScreen {
+	fn default() -> Self {
+		let msg = |attr: &'static str| MsgCache::new(MsgKey::new("menu-load-game").with_attr(attr));
+		Self {
+			l_title: MsgCache::new(MsgKey::new("menu-load-game")),
+			l_refresh: msg("refresh"),
+			l_name: msg("name"),
+			l_new: msg("new"),
+			l_play: msg("play"),
+			l_duplicate: msg("duplicate"),
+			l_rename: msg("rename"),
+			l_delete: msg("delete"),
+			l_export: msg("export"),
+			l_empty: msg("empty"),
+			l_needs_upgrade: msg("needs-upgrade"),
+			l_unloadable: msg("unloadable"),
+			saves: vec![],
+			selected: None,
+			name: "".to_string(),
+			status: None,
+		}
+	}
+}
+
+/// Where to join a server in another process and how that is going.
+struct JoinServerScreen {
+	l_address: MsgCache,
+	l_player_name: MsgCache,
+	l_connect: MsgCache,
+	l_disconnect: MsgCache,
+	state_msg: MsgCache,
+	address: String,
+	player_name: String,
+	state: ServerConnectionState,
+}
+
+impl Default for JoinServerScreen {
+	fn default() -> Self {
+		let msg =
+			|attr: &'static str| MsgCache::new(MsgKey::new("menu-server-join").with_attr(attr));
+		Self {
+			l_address: msg("address"),
+			l_player_name: msg("player-name"),
+			l_connect: msg("connect"),
+			l_disconnect: msg("disconnect"),
+			state_msg: msg("disconnected"),
+			address: "localhost".to_string(),
+			player_name: "".to_string(),
+			state: ServerConnectionState::Disconnected(None),
+		}
+	}
+}
+
+/// What was clicked on the load game screen, done once it is rendered.
+#[cfg(feature = "server")]
+enum SaveAction {
+	Refresh,
+	Select(SaveInfo),
+	New,
+	Play(PathBuf),
+	Duplicate(PathBuf),
+	Rename(PathBuf),
+	Delete(PathBuf),
+	Export(PathBuf),
 }
 
 impl Default for MainMenuState {
@@ -140,12 +284,21 @@ impl Default for MainMenuState {
 			),
 			l_server_local_test: MsgCache::new(MsgKey::new("menu-server-local").with_attr("test")),
 			l_server_join: MsgCache::new(MsgKey::new("menu-server-join")),
+			l_server_pause: MsgCache::new(MsgKey::new("menu-server-pause")),
+			l_server_pause_resume: MsgCache::new(
+				MsgKey::new("menu-server-pause").with_attr("resume"),
+			),
+			l_server_pause_stop: MsgCache::new(MsgKey::new("menu-server-pause").with_attr("stop")),
 			l_settings_title: MsgCache::new(MsgKey::new("settings-title")),
 			l_settings_cancel: MsgCache::new(MsgKey::new("settings-cancel")),
 			l_settings_current_language: MsgCache::new(MsgKey::new("settings_current_language")),
 			l_settings_choose_language: MsgCache::new(MsgKey::new("settings_choose_language")),
 			screen: Default::default(),
 			local_server_state_msg: MsgCache::new(MsgKey::new("local-server-state")),
+			pause_menu: false,
+			#[cfg(feature = "server")]
+			load_game: Default::default(),
+			join_server: Default::default(),
 		}
 	}
 }
@@ -173,10 +326,16 @@ impl MainMenuState {
 		self.l_server_local_starting_cancel.update(lang);
 		self.l_server_local_test.update(lang);
 		self.l_server_join.update(lang);
+		self.l_server_pause.update(lang);
+		self.l_server_pause_resume.update(lang);
+		self.l_server_pause_stop.update(lang);
 		self.l_settings_title.update(lang);
 		self.l_settings_cancel.update(lang);
 		self.l_settings_current_language.update(lang);
 		self.l_settings_choose_language.update(lang);
+		#[cfg(feature = "server")]
+		self.load_game.update_language(lang);
+		self.join_server.update_language(lang);
 	}
 
 	fn render(
@@ -187,7 +346,10 @@ impl MainMenuState {
 		change_lang: &mut EventWriter<I18nChangeLanguageTo>,
 		local_server_state: &Option<Res<LocalServerPublicState>>,
 		local_server_cmd: &mut EventWriter<LocalServerCommand>,
+		network_cmd: &mut EventWriter<NetworkClientCommand>,
+		world_cache: &WorldCache,
 		exit: &mut EventWriter<RequestExit>,
+		keys: &Input<KeyCode>,
 	) {
 		egui::TopPanel::top("top_title").show(e.ctx(), |ui| {
 			ui.centered_and_justified(|ui| {
@@ -195,7 +357,13 @@ impl MainMenuState {
 			});
 		});
 		if self.screen == MainMenuScreen::LoadJoinLocalServer {
-			self.loading_local_server(e.ctx(), local_server_state, local_server_cmd);
+			self.loading_local_server(
+				e.ctx(),
+				local_server_state,
+				local_server_cmd,
+				world_cache,
+				keys,
+			);
 		} else {
 			egui::SidePanel::left("news_panel", 150.0).show(e.ctx(), |ui| {
 				self.render_main_menu(ui, local_server_state, exit);
@@ -207,7 +375,15 @@ impl MainMenuState {
 						self.render_server_local(ui, local_server_state, local_server_cmd)
 					}
 					MainMenuScreen::LoadJoinLocalServer => (),
-					MainMenuScreen::JoinServer => self.render_server_join(ui, state),
+					#[cfg(feature = "server")]
+					MainMenuScreen::LoadGame => {
+						if self.load_game.render(ui, local_server_cmd) {
+							self.screen = MainMenuScreen::LoadJoinLocalServer;
+						}
+					}
+					MainMenuScreen::JoinServer => {
+						self.join_server.render(ui, network_cmd, world_cache)
+					}
 					MainMenuScreen::Settings => self.render_settings(ui, state, change_lang),
 				};
 			});
@@ -219,12 +395,36 @@ impl MainMenuState {
 		ctx: &egui::CtxRef,
 		local_server_state: &Option<Res<LocalServerPublicState>>,
 		local_server_cmd: &mut EventWriter<LocalServerCommand>,
+		world_cache: &WorldCache,
+		keys: &Input<KeyCode>,
 	) {
-		if let Some(_local_server_state) = local_server_state {
-			// if let LocalServerPublicState::Loading(msg)
+		if let Some(local_server_state) = local_server_state {
+			let in_game = matches!(
+				**local_server_state,
+				LocalServerPublicState::Running | LocalServerPublicState::Paused
+			);
+			if in_game && keys.just_pressed(KeyCode::Escape) {
+				self.set_pause_menu(!self.pause_menu, local_server_cmd);
+			}
 			egui::CentralPanel::default().show(ctx, |ui| {
+				if self.pause_menu {
+					ui.heading(self.l_server_pause.as_str());
+					ui.label(self.local_server_state_msg.as_str());
+					if ui.button(self.l_server_pause_resume.as_str()).clicked() {
+						self.set_pause_menu(false, local_server_cmd);
+					}
+					if ui.button(self.l_server_pause_stop.as_str()).clicked() {
+						self.pause_menu = false;
+						self.screen = MainMenuScreen::Empty;
+						local_server_cmd.send(LocalServerCommand::StopServer { force: false });
+					}
+					return;
+				}
 				ui.heading(self.l_server_local_starting.as_str());
 				ui.label(self.local_server_state_msg.as_str());
+				if in_game && ui.button(self.l_server_pause.as_str()).clicked() {
+					self.set_pause_menu(true, local_server_cmd);
+				}
 				if ui
 					.button(self.l_server_local_starting_cancel.as_str())
 					.clicked()
@@ -232,12 +432,33 @@ impl MainMenuState {
 					self.screen = MainMenuScreen::Empty;
 					local_server_cmd.send(LocalServerCommand::StopServer { force: true });
 				}
+				if in_game {
+					ui.separator();
+					render_surface_map(ui, world_cache);
+				}
 			});
 		} else {
 			self.screen = MainMenuScreen::Empty;
 		}
 	}
 
+	/// Opening the pause menu pauses the single-player game, closing it resumes it again.
+	fn set_pause_menu(
+		&mut self,
+		open: bool,
+		local_server_cmd: &mut EventWriter<LocalServerCommand>,
+	) {
+		if self.pause_menu == open {
+			return;
+		}
+		self.pause_menu = open;
+		local_server_cmd.send(if open {
+			LocalServerCommand::Pause { automatic: true }
+		} else {
+			LocalServerCommand::Resume
+		});
+	}
+
 	fn render_main_menu(
 		&mut self,
 		ui: &mut Ui,
@@ -262,6 +483,17 @@ impl MainMenuState {
 					MainMenuScreen::LocalServer,
 					self.l_server_local.as_str(),
 				);
+				#[cfg(feature = "server")]
+				if ui
+					.add(
+						egui::Button::new(self.load_game.l_title.as_str())
+							.enabled(self.screen != MainMenuScreen::LoadGame),
+					)
+					.clicked()
+				{
+					self.load_game.refresh();
+					self.screen = MainMenuScreen::LoadGame;
+				}
 			}
 			menu_btn(
 				ui,
@@ -303,10 +535,6 @@ impl MainMenuState {
 		});
 	}
 
-	fn render_server_join(&mut self, ui: &mut Ui, _state: &mut ResMut<State<super::ClientState>>) {
-		ui.label("todo");
-	}
-
 	fn render_settings(
 		&mut self,
 		ui: &mut Ui,
@@ -354,10 +582,309 @@ impl MainMenuState {
 	}
 }
 
-fn on_enter(mut main_menu_state: ResMut<Option<MainMenuState>>, lang: Res<I18n>) {
+impl JoinServerScreen {
+	fn update_language(&mut self, lang: &I18n) {
+		self.l_address.update(lang);
+		self.l_player_name.update(lang);
+		self.l_connect.update(lang);
+		self.l_disconnect.update(lang);
+		self.update_state(lang, self.state.clone());
+	}
+
+	fn update_state(&mut self, lang: &I18n, state: ServerConnectionState) {
+		match &state {
+			ServerConnectionState::Disconnected(None) => {
+				self.state_msg.attr("disconnected").update(lang);
+			}
+			ServerConnectionState::Disconnected(Some(reason)) => {
+				self.state_msg
+					.attr("failed")
+					.update_args_iter(lang, std::iter::once(("reason", reason.clone())));
+			}
+			ServerConnectionState::Connecting => {
+				self.state_msg.attr("connecting").update(lang);
+			}
+			ServerConnectionState::Joining => {
+				self.state_msg.attr("joining").update(lang);
+			}
+			ServerConnectionState::Connected { motd } => {
+				self.state_msg
+					.attr("connected")
+					.update_args_iter(lang, std::iter::once(("motd", motd.clone())));
+			}
+		}
+		self.state = state;
+	}
+
+	fn is_disconnected(&self) -> bool {
+		matches!(self.state, ServerConnectionState::Disconnected(_))
+	}
+
+	/// The name picked for joining servers, if it is valid, for playing the local game.
+	fn host_player_name(&self) -> String {
+		if is_valid_player_name(&self.player_name) {
+			self.player_name.clone()
+		} else {
+			DEFAULT_PLAYER_NAME.to_string()
+		}
+	}
+
+	fn render(
+		&mut self,
+		ui: &mut Ui,
+		network_cmd: &mut EventWriter<NetworkClientCommand>,
+		world_cache: &WorldCache,
+	) {
+		let disconnected = self.is_disconnected();
+		ui.vertical(|ui| {
+			ui.horizontal(|ui| {
+				ui.label(self.l_address.as_str());
+				ui.text_edit_singleline(&mut self.address);
+			});
+			ui.horizontal(|ui| {
+				ui.label(self.l_player_name.as_str());
+				ui.text_edit_singleline(&mut self.player_name);
+			});
+			ui.label(self.state_msg.as_str());
+			ui.separator();
+			if disconnected {
+				let valid =
+					!self.address.trim().is_empty() && is_valid_player_name(&self.player_name);
+				if ui
+					.add(egui::Button::new(self.l_connect.as_str()).enabled(valid))
+					.clicked()
+				{
+					network_cmd.send(NetworkClientCommand::Connect {
+						address: self.address.clone(),
+						player_name: self.player_name.clone(),
+					});
+				}
+			} else if ui.button(self.l_disconnect.as_str()).clicked() {
+				network_cmd.send(NetworkClientCommand::Disconnect);
+			}
+			if let ServerConnectionState::Connected { .. } = self.state {
+				ui.separator();
+				render_surface_map(ui, world_cache);
+			}
+		});
+	}
+}
+
+#[cfg(feature = "server")]
+impl LoadGameScreen {
+	fn update_language(&mut self, lang: &I18n) {
+		self.l_title.update(lang);
+		self.l_refresh.update(lang);
+		self.l_name.update(lang);
+		self.l_new.update(lang);
+		self.l_play.update(lang);
+		self.l_duplicate.update(lang);
+		self.l_rename.update(lang);
+		self.l_delete.update(lang);
+		self.l_export.update(lang);
+		self.l_empty.update(lang);
+		self.l_needs_upgrade.update(lang);
+		self.l_unloadable.update(lang);
+	}
+
+	fn refresh(&mut self) {
+		match list_saves(PathBuf::from(SAVES_DIR).as_path()) {
+			Ok(saves) => self.saves = saves,
+			Err(e) => {
+				error!("Failed listing saves: {}", e);
+				self.status = Some(e.to_string());
+			}
+		}
+		if let Some(selected) = &self.selected {
+			if !self.saves.iter().any(|save| &save.path == selected) {
+				self.selected = None;
+			}
+		}
+	}
+
+	/// Returns true once a save is being launched.
+	fn render(
+		&mut self,
+		ui: &mut Ui,
+		local_server_cmd: &mut EventWriter<LocalServerCommand>,
+	) -> bool {
+		let mut action = None;
+		ui.vertical(|ui| {
+			ui.horizontal(|ui| {
+				ui.heading(self.l_title.as_str());
+				if ui.button(self.l_refresh.as_str()).clicked() {
+					action = Some(SaveAction::Refresh);
+				}
+			});
+			ui.horizontal(|ui| {
+				ui.label(self.l_name.as_str());
+				ui.text_edit_singleline(&mut self.name);
+				let valid_name = is_valid_name(&self.name);
+				if ui
+					.add(egui::Button::new(self.l_new.as_str()).enabled(valid_name))
+					.clicked()
+				{
+					action = Some(SaveAction::New);
+				}
+			});
+			if let Some(status) = &self.status {
+				ui.label(status);
+			}
+			ui.separator();
+			if self.saves.is_empty() {
+				ui.label(self.l_empty.as_str());
+			}
+			egui::ScrollArea::auto_sized().show(ui, |ui| {
+				for save in &self.saves {
+					let selected = self.selected.as_ref() == Some(&save.path);
+					let details = self.save_details(save);
+					if ui
+						.selectable_label(selected, format!("{}\n{}", save.name, details))
+						.clicked()
+					{
+						action = Some(SaveAction::Select(save.clone()));
+					}
+				}
+			});
+			ui.separator();
+			let selected = self
+				.selected
+				.as_ref()
+				.and_then(|selected| self.saves.iter().find(|save| &save.path == selected));
+			if let Some(save) = selected {
+				let valid_name = is_valid_name(&self.name) && self.name != save.name;
+				ui.horizontal(|ui| {
+					let path = || save.path.clone();
+					let button = |ui: &mut Ui, text: &str, enabled: bool| {
+						ui.add(egui::Button::new(text).enabled(enabled)).clicked()
+					};
+					if button(ui, self.l_play.as_str(), save.is_loadable()) {
+						action = Some(SaveAction::Play(path()));
+					}
+					if button(ui, self.l_duplicate.as_str(), valid_name) {
+						action = Some(SaveAction::Duplicate(path()));
+					}
+					if button(ui, self.l_rename.as_str(), valid_name) {
+						action = Some(SaveAction::Rename(path()));
+					}
+					if button(ui, self.l_export.as_str(), true) {
+						action = Some(SaveAction::Export(path()));
+					}
+					if button(ui, self.l_delete.as_str(), true) {
+						action = Some(SaveAction::Delete(path()));
+					}
+				});
+			}
+		});
+		match action {
+			Some(action) => self.act(action, local_server_cmd),
+			None => false,
+		}
+	}
+
+	fn save_details(&self, save: &SaveInfo) -> String {
+		let age = SystemTime::now()
+			.duration_since(save.last_played)
+			.map(|age| age.as_secs())
+			.unwrap_or(0);
+		let age = match age {
+			age if age < 60 * 60 => format!("{} min", age / 60),
+			age if age < 60 * 60 * 24 => format!("{} h", age / (60 * 60)),
+			age => format!("{} d", age / (60 * 60 * 24)),
+		};
+		let size = match save.size {
+			size if size < 1 << 20 => format!("{} KiB", size >> 10),
+			size if size < 1 << 30 => format!("{} MiB", size >> 20),
+			size => format!("{:.1} GiB", size as f64 / (1u64 << 30) as f64),
+		};
+		let state = match save.format_version {
+			Some(version) if version == SAVE_FORMAT_VERSION => {
+				save.generator.unwrap_or("").to_string()
+			}
+			_ if save.is_loadable() => self.l_needs_upgrade.as_str().to_string(),
+			_ => self.l_unloadable.as_str().to_string(),
+		};
+		format!(
+			"{}  |  {}  |  v{}  |  {}",
+			age,
+			size,
+			save.format_version.unwrap_or(0),
+			state
+		)
+	}
+
+	fn act(
+		&mut self,
+		action: SaveAction,
+		local_server_cmd: &mut EventWriter<LocalServerCommand>,
+	) -> bool {
+		let root = PathBuf::from(SAVES_DIR);
+		let done: Result<Option<PathBuf>, SaveConfigError> = match action {
+			SaveAction::Refresh => Ok(None),
+			SaveAction::Select(save) => {
+				self.status = None;
+				self.name = save.name;
+				self.selected = Some(save.path);
+				return false;
+			}
+			SaveAction::New if root.join(&self.name).exists() => {
+				Err(SaveConfigError::SaveExists(root.join(&self.name)))
+			}
+			SaveAction::New => return Self::launch(root.join(&self.name), local_server_cmd),
+			SaveAction::Play(path) => return Self::launch(path, local_server_cmd),
+			SaveAction::Duplicate(path) => duplicate_save(&path, &self.name).map(Some),
+			SaveAction::Rename(path) => rename_save(&path, &self.name).map(Some),
+			SaveAction::Delete(path) => delete_save(&path).map(|_| None),
+			SaveAction::Export(path) => {
+				export_save(&path, &PathBuf::from(EXPORTS_DIR)).map(|exported| {
+					self.status = Some(format!("{}", exported.display()));
+					None
+				})
+			}
+		};
+		match done {
+			Ok(selected) => {
+				if selected.is_some() {
+					self.selected = selected;
+				}
+			}
+			Err(e) => {
+				warn!("Failed managing saves: {}", e);
+				self.status = Some(e.to_string());
+			}
+		}
+		self.refresh();
+		false
+	}
+
+	fn launch(path: PathBuf, local_server_cmd: &mut EventWriter<LocalServerCommand>) -> bool {
+		info!("Launching save at {:?}", path);
+		local_server_cmd.send(LocalServerCommand::CreateStartServer {
+			path,
+			config_only_if_not_existing: false,
+		});
+		true
+	}
+}
+
+/// The world around the player from above, until there is more to render it with.
+fn render_surface_map(ui: &mut Ui, world_cache: &WorldCache) {
+	for line in world_cache.surface_map(64, 32) {
+		ui.monospace(line);
+	}
+}
+
+fn on_enter(
+	mut main_menu_state: ResMut<Option<MainMenuState>>,
+	lang: Res<I18n>,
+	connection_state: Res<ServerConnectionState>,
+) {
 	trace!("Client MainMenu State: Enter");
 	// Make the main menu entity
-	*main_menu_state = Some(MainMenuState::new(&lang));
+	let mut menu = MainMenuState::new(&lang);
+	menu.join_server
+		.update_state(&lang, connection_state.clone());
+	*main_menu_state = Some(menu);
 }
 
 fn on_update(
@@ -368,7 +895,10 @@ fn on_update(
 	mut change_lang: EventWriter<I18nChangeLanguageTo>,
 	local_server_state: Option<Res<LocalServerPublicState>>,
 	mut local_server_cmd: EventWriter<LocalServerCommand>,
+	mut network_cmd: EventWriter<NetworkClientCommand>,
+	world_cache: Res<WorldCache>,
 	mut exit: EventWriter<RequestExit>,
+	keys: Res<Input<KeyCode>>,
 ) {
 	// trace!("Client MainMenu State: Update");
 	if let Some(m) = &mut *main_menu_state {
@@ -379,7 +909,10 @@ fn on_update(
 			&mut change_lang,
 			&local_server_state,
 			&mut local_server_cmd,
+			&mut network_cmd,
+			&*world_cache,
 			&mut exit,
+			&*keys,
 		);
 	}
 }
@@ -1,6 +1,7 @@
 //! The `crates::core` module is for the code that is used to set up everything else, but then is
 //! not touched by anything else.  The code here is minimal.
 
+use crate::universal::commands::console::{ConsoleMode, ConsolePlugin};
 use crate::universal::exit::RequestExit;
 use crate::universal::local_server::{LocalServerCommand, LocalServerPublicState};
 use bevy::app::Events;
@@ -64,6 +65,8 @@ pub struct Engine {
 	pub include_server: bool,
 	pub client_type: ClientType,
 	pub game_configuration_path: Option<PathBuf>,
+	/// Reads admin commands from the standard input if set, for dedicated servers.
+	pub console: Option<ConsoleMode>,
 }
 
 /// Central engine entrance point, start by calling `Engine::new()` and call its functions
@@ -79,6 +82,7 @@ impl Engine {
 			client_type: ClientType::Logger,
 			#[cfg(feature = "server")]
 			game_configuration_path: None,
+			console: None,
 		})
 	}
 
@@ -129,6 +133,14 @@ impl Engine {
 			}
 		}
 
+		if let Some(mode) = self.console {
+			app_builder.add_plugin(ConsolePlugin { mode });
+			// Dedicated servers are there for players to join.
+			#[cfg(feature = "server")]
+			app_builder
+				.insert_resource(crate::server::network::NetworkServerConfig { listen: true });
+		}
+
 		#[cfg(feature = "server")]
 		if let Some(path) = self.game_configuration_path.clone() {
 			app_builder
@@ -160,6 +172,11 @@ impl Engine {
 		self
 	}
 
+	pub fn set_console(&mut self, console: Option<ConsoleMode>) -> &mut Self {
+		self.console = console;
+		self
+	}
+
 	#[cfg(feature = "server")]
 	pub fn load_game_configuration(
 		&mut self,
@@ -125,10 +125,14 @@ fn unload_mainmenu() {
 }
 
 fn create_test_world(mut commands: Commands, mut worlds: NonSendMut<Worlds>) {
-	let test_world_id =
-		worlds.create_world("test".into(), Box::new(LayeredWorldGenerator::new(vec![])));
+	let test_world_id = worlds.create_world(
+		"test".into(),
+		Box::new(LayeredWorldGenerator::new(0, vec![])),
+	);
 	let mut test_world = worlds.get_world_mut(test_world_id);
-	let chunk = test_world.get_or_generate_chunk_now(ChunkCoord::new(0, 0, 0));
+	let chunk = test_world
+		.get_or_generate_chunk_now(ChunkCoord::new(0, 0, 0))
+		.expect("test world has no storage to fail loading from");
 
 	commands
 		.spawn()
@@ -0,0 +1,70 @@
+//! Writing save files so that a crash or power loss halfway through leaves either the old or the
+//! new file behind, never a mix of both or a truncated one.
+
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Writes the contents to a temporary file next to the path, flushes it to disk and then renames
+/// it over the path.  The temporary file is removed again if anything fails.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+	let temp = temp_path(path);
+	let written = File::create(&temp)
+		.and_then(|mut file| {
+			file.write_all(contents)?;
+			file.sync_all()
+		})
+		.and_then(|_| std::fs::rename(&temp, path));
+	if written.is_err() {
+		let _ = std::fs::remove_file(&temp);
+	}
+	written?;
+	sync_directory(path);
+	Ok(())
+}
+
+/// Where `write_atomically` writes the file before renaming it, leftovers of a crash can be
+/// recognized by it.
+pub fn temp_path(path: &Path) -> PathBuf {
+	let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+	name.push(".tmp");
+	path.with_file_name(name)
+}
+
+/// Makes the rename itself durable, on a best effort basis.
+#[cfg(unix)]
+fn sync_directory(path: &Path) {
+	if let Some(directory) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+		if let Ok(directory) = File::open(directory) {
+			let _ = directory.sync_all();
+		}
+	}
+}
+
+/// Directories can't be opened to sync them everywhere, the rename has to do.
+#[cfg(not(unix))]
+fn sync_directory(_path: &Path) {}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::atomic_file::{temp_path, write_atomically};
+	use crate::game_data::test_dir::TestDir;
+	use std::path::Path;
+
+	#[test]
+	fn replace_files_atomically() {
+		let dir = TestDir::new("atomic-file");
+		std::fs::create_dir_all(dir.path()).unwrap();
+		let path = dir.path().join("config.ron");
+		assert_eq!(temp_path(&path), dir.path().join("config.ron.tmp"));
+		write_atomically(&path, b"first").unwrap();
+		write_atomically(&path, b"second").unwrap();
+		assert_eq!(std::fs::read(&path).unwrap(), b"second");
+		assert!(!temp_path(&path).exists());
+
+		let missing = dir.path().join("missing").join("config.ron");
+		assert!(write_atomically(&missing, b"first").is_err());
+		assert_eq!(temp_path(Path::new("a/b.ron")), Path::new("a/b.ron.tmp"));
+	}
+}
@@ -0,0 +1,1192 @@
+use crate::game_data::chunks::tile_storage::{PalettedTileStorage, TileStorageStats, TilesMut};
+use crate::game_data::tiles::TileId;
+use std::convert::TryFrom;
+use std::fmt;
+use std::iter::Enumerate;
+use std::num::NonZeroU8;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+pub type ChunkCoordType = i16;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkCoord([ChunkCoordType; 3]);
+
+impl ChunkCoord {
+	pub fn new(x: ChunkCoordType, y: ChunkCoordType, z: ChunkCoordType) -> Self {
+		ChunkCoord([x, y, z])
+	}
+
+	pub fn x(&self) -> ChunkCoordType {
+		self.0[0]
+	}
+
+	pub fn y(&self) -> ChunkCoordType {
+		self.0[1]
+	}
+
+	pub fn z(&self) -> ChunkCoordType {
+		self.0[2]
+	}
+
+	/// The chunk on the other side of the given face, `None` if that would overflow the coordinate.
+	pub fn neighbor(&self, face: Face) -> Option<ChunkCoord> {
+		let mut coord = *self;
+		let axis = &mut coord.0[face.axis()];
+		*axis = if face.is_positive() {
+			axis.checked_add(1)?
+		} else {
+			axis.checked_sub(1)?
+		};
+		Some(coord)
+	}
+
+	fn zip_with(
+		self,
+		other: ChunkCoord,
+		f: impl Fn(ChunkCoordType, ChunkCoordType) -> ChunkCoordType,
+	) -> ChunkCoord {
+		ChunkCoord([
+			f(self.x(), other.x()),
+			f(self.y(), other.y()),
+			f(self.z(), other.z()),
+		])
+	}
+
+	/// The distance of this coordinate from the origin along each axis.
+	pub fn area(&self) -> ChunkCoordArea {
+		(*self - ChunkCoord::default()).abs()
+	}
+}
+
+pub type ChunkCoordOffsetType = i32;
+
+/// The signed difference between two `ChunkCoord`s, as returned by subtracting them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkCoordOffset([ChunkCoordOffsetType; 3]);
+
+impl ChunkCoordOffset {
+	pub fn x(&self) -> ChunkCoordOffsetType {
+		self.0[0]
+	}
+
+	pub fn y(&self) -> ChunkCoordOffsetType {
+		self.0[1]
+	}
+
+	pub fn z(&self) -> ChunkCoordOffsetType {
+		self.0[2]
+	}
+
+	pub fn length_squared(&self) -> i64 {
+		self.0.iter().map(|&v| v as i64 * v as i64).sum()
+	}
+
+	/// The size of this offset along each axis, ignoring its direction.
+	pub fn abs(&self) -> ChunkCoordArea {
+		let axis = |v: ChunkCoordOffsetType| v.unsigned_abs() as ChunkCoordAreaType;
+		ChunkCoordArea([axis(self.x()), axis(self.y()), axis(self.z())])
+	}
+}
+
+pub type ChunkCoordAreaType = u16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkCoordArea([ChunkCoordAreaType; 3]);
+
+impl ChunkCoordArea {
+	pub fn width(&self) -> ChunkCoordAreaType {
+		self.0[0]
+	}
+
+	pub fn length(&self) -> ChunkCoordAreaType {
+		self.0[1]
+	}
+
+	pub fn height(&self) -> ChunkCoordAreaType {
+		self.0[2]
+	}
+
+	pub fn area(&self) -> usize {
+		self.0[0] as usize * self.0[1] as usize * self.0[2] as usize
+	}
+}
+
+/// An axis aligned box of chunks, both corners are inclusive and `bottom` is never above `top` on
+/// any axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkCoordAABB {
+	bottom: ChunkCoord,
+	top: ChunkCoord,
+}
+
+impl ChunkCoordAABB {
+	/// The box spanned by two corners given in any order.
+	pub fn new(a: ChunkCoord, b: ChunkCoord) -> Self {
+		ChunkCoordAABB {
+			bottom: a.zip_with(b, Ord::min),
+			top: a.zip_with(b, Ord::max),
+		}
+	}
+
+	/// The box holding every chunk within `radius` chunks of `center` along each axis, clipped to
+	/// the range of a `ChunkCoord`.
+	pub fn around(center: ChunkCoord, radius: ChunkCoordAreaType) -> Self {
+		ChunkCoordAABB::new(center, center).expand(radius)
+	}
+
+	pub fn bottom(&self) -> ChunkCoord {
+		self.bottom
+	}
+
+	pub fn top(&self) -> ChunkCoord {
+		self.top
+	}
+
+	pub fn contains(&self, coord: ChunkCoord) -> bool {
+		(0..3).all(|axis| self.bottom.0[axis] <= coord.0[axis] && coord.0[axis] <= self.top.0[axis])
+	}
+
+	pub fn intersects(&self, other: &ChunkCoordAABB) -> bool {
+		(0..3).all(|axis| {
+			self.bottom.0[axis] <= other.top.0[axis] && other.bottom.0[axis] <= self.top.0[axis]
+		})
+	}
+
+	/// The box covered by both boxes, `None` if they don't overlap.
+	pub fn intersection(&self, other: &ChunkCoordAABB) -> Option<ChunkCoordAABB> {
+		if !self.intersects(other) {
+			return None;
+		}
+		Some(ChunkCoordAABB {
+			bottom: self.bottom.zip_with(other.bottom, Ord::max),
+			top: self.top.zip_with(other.top, Ord::min),
+		})
+	}
+
+	/// The smallest box holding both boxes.
+	pub fn union(&self, other: &ChunkCoordAABB) -> ChunkCoordAABB {
+		self.include(other.bottom).include(other.top)
+	}
+
+	/// Grows the box to hold the given coordinate.
+	pub fn include(self, coord: ChunkCoord) -> ChunkCoordAABB {
+		ChunkCoordAABB {
+			bottom: self.bottom.zip_with(coord, Ord::min),
+			top: self.top.zip_with(coord, Ord::max),
+		}
+	}
+
+	/// Grows the box by `by` chunks on every side, clipped to the range of a `ChunkCoord`.
+	pub fn expand(self, by: ChunkCoordAreaType) -> ChunkCoordAABB {
+		let by = by as ChunkCoordOffsetType;
+		let clip = |v: ChunkCoordOffsetType| {
+			v.max(ChunkCoordType::MIN.into())
+				.min(ChunkCoordType::MAX.into()) as ChunkCoordType
+		};
+		let axis = |c: ChunkCoord, by: ChunkCoordOffsetType| {
+			let axis = |v: ChunkCoordType| clip(ChunkCoordOffsetType::from(v) + by);
+			ChunkCoord([axis(c.x()), axis(c.y()), axis(c.z())])
+		};
+		ChunkCoordAABB {
+			bottom: axis(self.bottom, -by),
+			top: axis(self.top, by),
+		}
+	}
+
+	/// The amount of chunks along each axis, note that a box spanning the whole range of a
+	/// `ChunkCoord` is one more than fits in a `ChunkCoordAreaType`.
+	pub fn size(&self) -> [usize; 3] {
+		let axis =
+			|axis: usize| (self.top.0[axis] as isize - self.bottom.0[axis] as isize) as usize + 1;
+		[axis(0), axis(1), axis(2)]
+	}
+
+	/// The amount of chunks in the box.
+	pub fn volume(&self) -> usize {
+		self.size().iter().product()
+	}
+
+	/// Iterates over every chunk in the box, x-major then y then z.
+	pub fn iter(&self) -> IterChunkCoordAABB {
+		IterChunkCoordAABB {
+			aabb: *self,
+			next: Some(self.bottom),
+		}
+	}
+}
+
+impl IntoIterator for ChunkCoordAABB {
+	type Item = ChunkCoord;
+	type IntoIter = IterChunkCoordAABB;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.iter()
+	}
+}
+
+pub struct IterChunkCoordAABB {
+	aabb: ChunkCoordAABB,
+	next: Option<ChunkCoord>,
+}
+
+impl Iterator for IterChunkCoordAABB {
+	type Item = ChunkCoord;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let coord = self.next?;
+		let (bottom, top) = (self.aabb.bottom, self.aabb.top);
+		self.next = if coord.x() < top.x() {
+			Some(ChunkCoord::new(coord.x() + 1, coord.y(), coord.z()))
+		} else if coord.y() < top.y() {
+			Some(ChunkCoord::new(bottom.x(), coord.y() + 1, coord.z()))
+		} else if coord.z() < top.z() {
+			Some(ChunkCoord::new(bottom.x(), bottom.y(), coord.z() + 1))
+		} else {
+			None
+		};
+		Some(coord)
+	}
+}
+
+/// One of the six faces of a tile or chunk, used to step to whatever is adjacent to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Face {
+	NegX,
+	PosX,
+	NegY,
+	PosY,
+	NegZ,
+	PosZ,
+}
+
+impl Face {
+	pub const ALL: [Face; 6] = [
+		Face::NegX,
+		Face::PosX,
+		Face::NegY,
+		Face::PosY,
+		Face::NegZ,
+		Face::PosZ,
+	];
+
+	/// The index of the axis this face is perpendicular to, 0 for x, 1 for y and 2 for z.
+	pub fn axis(self) -> usize {
+		match self {
+			Face::NegX | Face::PosX => 0,
+			Face::NegY | Face::PosY => 1,
+			Face::NegZ | Face::PosZ => 2,
+		}
+	}
+
+	pub fn is_positive(self) -> bool {
+		matches!(self, Face::PosX | Face::PosY | Face::PosZ)
+	}
+
+	pub fn opposite(self) -> Face {
+		match self {
+			Face::NegX => Face::PosX,
+			Face::PosX => Face::NegX,
+			Face::NegY => Face::PosY,
+			Face::PosY => Face::NegY,
+			Face::NegZ => Face::PosZ,
+			Face::PosZ => Face::NegZ,
+		}
+	}
+}
+
+pub type LocalTileCoordType = u8;
+
+/// A tile position inside of a single chunk, each axis is in the range `0..chunk_edge_len`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LocalTileCoord([LocalTileCoordType; 3]);
+
+impl LocalTileCoord {
+	pub fn new(x: LocalTileCoordType, y: LocalTileCoordType, z: LocalTileCoordType) -> Self {
+		LocalTileCoord([x, y, z])
+	}
+
+	pub fn x(&self) -> LocalTileCoordType {
+		self.0[0]
+	}
+
+	pub fn y(&self) -> LocalTileCoordType {
+		self.0[1]
+	}
+
+	pub fn z(&self) -> LocalTileCoordType {
+		self.0[2]
+	}
+
+	pub fn is_in_chunk(&self, chunk_edge_len: NonZeroU8) -> bool {
+		self.0.iter().all(|&v| v < chunk_edge_len.get())
+	}
+
+	/// The index of this coordinate in a chunk's tile storage, x-major then y then z, or `None` if
+	/// it is outside of a chunk of the given edge length.
+	pub fn to_index(&self, chunk_edge_len: NonZeroU8) -> Option<usize> {
+		if self.is_in_chunk(chunk_edge_len) {
+			let len = chunk_edge_len.get() as usize;
+			Some(self.x() as usize + (self.y() as usize + self.z() as usize * len) * len)
+		} else {
+			None
+		}
+	}
+
+	/// The coordinate adjacent to the given face, wrapping around to the opposite side of the chunk
+	/// if that steps over the chunk border, in which case `true` is returned alongside it.
+	pub fn step(&self, face: Face, chunk_edge_len: NonZeroU8) -> (LocalTileCoord, bool) {
+		let max = chunk_edge_len.get() - 1;
+		let mut coord = *self;
+		let axis = &mut coord.0[face.axis()];
+		let crossed = if face.is_positive() {
+			let crossed = *axis >= max;
+			*axis = if crossed { 0 } else { *axis + 1 };
+			crossed
+		} else {
+			let crossed = *axis == 0;
+			*axis = if crossed { max } else { *axis - 1 };
+			crossed
+		};
+		(coord, crossed)
+	}
+
+	/// The inverse of `to_index`, `None` if the index is past the end of the chunk's tile storage.
+	pub fn from_index(index: usize, chunk_edge_len: NonZeroU8) -> Option<Self> {
+		let len = chunk_edge_len.get() as usize;
+		if index < len * len * len {
+			Some(LocalTileCoord([
+				(index % len) as LocalTileCoordType,
+				(index / len % len) as LocalTileCoordType,
+				(index / (len * len)) as LocalTileCoordType,
+			]))
+		} else {
+			None
+		}
+	}
+}
+
+pub type TileCoordType = i32;
+
+/// A tile position in a world, independent of the chunk it's in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TileCoord([TileCoordType; 3]);
+
+impl TileCoord {
+	pub fn new(x: TileCoordType, y: TileCoordType, z: TileCoordType) -> Self {
+		TileCoord([x, y, z])
+	}
+
+	pub fn x(&self) -> TileCoordType {
+		self.0[0]
+	}
+
+	pub fn y(&self) -> TileCoordType {
+		self.0[1]
+	}
+
+	pub fn z(&self) -> TileCoordType {
+		self.0[2]
+	}
+
+	/// The tile on the other side of the given face.
+	pub fn step(&self, face: Face) -> TileCoord {
+		let mut coord = *self;
+		coord.0[face.axis()] += if face.is_positive() { 1 } else { -1 };
+		coord
+	}
+
+	/// Splits this into the chunk it is in and its position inside of that chunk, rounding towards
+	/// negative infinity so that `-1` is the last tile of chunk `-1` rather than part of chunk `0`.
+	/// Returns `None` if the chunk would be outside of the range of a `ChunkCoord`.
+	pub fn to_chunk_local(
+		&self,
+		chunk_edge_len: NonZeroU8,
+	) -> Option<(ChunkCoord, LocalTileCoord)> {
+		let len = chunk_edge_len.get() as TileCoordType;
+		let mut chunk = [0; 3];
+		let mut local = [0; 3];
+		for axis in 0..3 {
+			chunk[axis] = ChunkCoordType::try_from(self.0[axis].div_euclid(len)).ok()?;
+			local[axis] = self.0[axis].rem_euclid(len) as LocalTileCoordType;
+		}
+		Some((ChunkCoord(chunk), LocalTileCoord(local)))
+	}
+
+	/// The inverse of `to_chunk_local`, every chunk and local coordinate pair has a `TileCoord`.
+	pub fn from_chunk_local(
+		chunk: ChunkCoord,
+		local: LocalTileCoord,
+		chunk_edge_len: NonZeroU8,
+	) -> Self {
+		let len = chunk_edge_len.get() as TileCoordType;
+		let axis =
+			|axis: usize| chunk.0[axis] as TileCoordType * len + local.0[axis] as TileCoordType;
+		TileCoord([axis(0), axis(1), axis(2)])
+	}
+}
+
+impl Add for TileCoord {
+	type Output = TileCoord;
+
+	fn add(self, rhs: Self) -> Self::Output {
+		TileCoord([self.x() + rhs.x(), self.y() + rhs.y(), self.z() + rhs.z()])
+	}
+}
+
+impl AddAssign for TileCoord {
+	fn add_assign(&mut self, rhs: Self) {
+		*self = *self + rhs;
+	}
+}
+
+impl Sub for TileCoord {
+	type Output = TileCoord;
+
+	fn sub(self, rhs: Self) -> Self::Output {
+		TileCoord([self.x() - rhs.x(), self.y() - rhs.y(), self.z() - rhs.z()])
+	}
+}
+
+impl SubAssign for TileCoord {
+	fn sub_assign(&mut self, rhs: Self) {
+		*self = *self - rhs;
+	}
+}
+
+impl Neg for TileCoord {
+	type Output = TileCoord;
+
+	fn neg(self) -> Self::Output {
+		TileCoord([-self.x(), -self.y(), -self.z()])
+	}
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ChunkError {
+	#[error("local tile coordinate {0:?} is out of bounds of a chunk with an edge length of {1}")]
+	OutOfBounds(LocalTileCoord, NonZeroU8),
+}
+
+/// How far along world generation a chunk is, each stage is done after all the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GenerationStage {
+	/// Nothing has been generated, the chunk is all air.
+	Empty,
+	/// The base terrain is in place.
+	Terrain,
+	/// Caves and anything else that removes from the terrain is carved out.
+	Carved,
+	/// Features like trees and structures that can reach into neighboring chunks are placed.
+	Featured,
+	/// The finishing touches are done, the chunk is ready to be used.
+	Decorated,
+}
+
+impl GenerationStage {
+	/// Every stage in the order they are generated in.
+	pub const ALL: [GenerationStage; 5] = [
+		GenerationStage::Empty,
+		GenerationStage::Terrain,
+		GenerationStage::Carved,
+		GenerationStage::Featured,
+		GenerationStage::Decorated,
+	];
+
+	/// The stage a chunk is at once world generation is done with it.
+	pub const LAST: GenerationStage = GenerationStage::Decorated;
+
+	pub fn previous(self) -> Option<GenerationStage> {
+		match self {
+			GenerationStage::Empty => None,
+			GenerationStage::Terrain => Some(GenerationStage::Empty),
+			GenerationStage::Carved => Some(GenerationStage::Terrain),
+			GenerationStage::Featured => Some(GenerationStage::Carved),
+			GenerationStage::Decorated => Some(GenerationStage::Featured),
+		}
+	}
+
+	/// Whether this stage only ever touches the chunk being generated, the other stages can write
+	/// into neighboring chunks as well.
+	pub fn is_chunk_local(self) -> bool {
+		self <= GenerationStage::Carved
+	}
+}
+
+// Cubic Chunks
+pub struct Chunk {
+	chunk_edge_len: NonZeroU8,
+	location: ChunkCoord,
+	generation_stage: GenerationStage,
+	tiles: PalettedTileStorage,
+	revision: u64,
+	saved_revision: u64,
+}
+
+impl fmt::Debug for Chunk {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Chunk")
+			.field("location", &self.location)
+			.field("chunk_edge_len", &self.chunk_edge_len)
+			.field("generation_stage", &self.generation_stage)
+			.field("revision", &self.revision)
+			.field("saved_revision", &self.saved_revision)
+			.field("tiles", &"<snip>")
+			.finish()
+	}
+}
+
+impl Chunk {
+	pub fn new(location: ChunkCoord, chunk_edge_len: NonZeroU8) -> Self {
+		let len = chunk_edge_len.get() as usize;
+		let area = len * len * len;
+		Chunk {
+			location,
+			chunk_edge_len,
+			generation_stage: GenerationStage::Empty,
+			tiles: PalettedTileStorage::new(area, TileId::AIR),
+			revision: 0,
+			saved_revision: 0,
+		}
+	}
+
+	/// The last generation stage that was completed for this chunk.
+	pub fn generation_stage(&self) -> GenerationStage {
+		self.generation_stage
+	}
+
+	pub fn set_generation_stage(&mut self, stage: GenerationStage) {
+		if self.generation_stage != stage {
+			self.generation_stage = stage;
+			self.mark_dirty();
+		}
+	}
+
+	/// Counts up whenever the tiles or the generation stage of the chunk change.
+	pub fn revision(&self) -> u64 {
+		self.revision
+	}
+
+	/// Whether the chunk changed since it was last saved.  A new chunk that was never modified
+	/// isn't dirty as there is nothing in it worth saving.
+	pub fn is_dirty(&self) -> bool {
+		self.revision != self.saved_revision
+	}
+
+	/// Records that the chunk was saved as it was at `revision`, if it changed since then it stays
+	/// dirty.
+	pub fn mark_saved(&mut self, revision: u64) {
+		self.saved_revision = revision;
+	}
+
+	/// Makes the chunk dirty without changing it, like when saving it failed.
+	pub fn mark_dirty(&mut self) {
+		self.revision += 1;
+	}
+
+	pub fn location(&self) -> ChunkCoord {
+		self.location
+	}
+
+	pub fn chunk_edge_len(&self) -> NonZeroU8 {
+		self.chunk_edge_len
+	}
+
+	/// The world position of a tile in this chunk.
+	pub fn to_tile_coord(&self, coord: LocalTileCoord) -> TileCoord {
+		TileCoord::from_chunk_local(self.location, coord, self.chunk_edge_len)
+	}
+
+	/// The amount of tiles this chunk holds, the cube of its edge length.
+	pub fn tile_count(&self) -> usize {
+		self.tiles.len()
+	}
+
+	pub fn get_tile(&self, coord: LocalTileCoord) -> Option<TileId> {
+		coord
+			.to_index(self.chunk_edge_len)
+			.and_then(|index| self.tiles.get(index))
+	}
+
+	/// Sets the tile at the coordinate, returning the tile that was previously there.
+	pub fn set_tile(&mut self, coord: LocalTileCoord, tile: TileId) -> Result<TileId, ChunkError> {
+		let index = coord
+			.to_index(self.chunk_edge_len)
+			.ok_or(ChunkError::OutOfBounds(coord, self.chunk_edge_len))?;
+		let previous = self
+			.tiles
+			.set(index, tile)
+			.expect("index was already bounds checked");
+		if previous != tile {
+			self.mark_dirty();
+		}
+		Ok(previous)
+	}
+
+	/// Sets every tile in the chunk to the given tile.
+	pub fn fill(&mut self, tile: TileId) {
+		self.tiles.fill(tile);
+		self.mark_dirty();
+	}
+
+	/// Drops unused palette entries from the tile storage, worth calling after bulk modifications.
+	pub fn compact(&mut self) {
+		self.tiles.compact();
+	}
+
+	/// Whether both chunks hold the same tiles, regardless of how they are stored.
+	pub fn tiles_eq(&self, other: &Chunk) -> bool {
+		self.chunk_edge_len == other.chunk_edge_len && self.iter_tiles().eq(other.iter_tiles())
+	}
+
+	/// A hash of the tiles in this chunk that is stable across runs and platforms, regardless of how
+	/// the tiles are stored.
+	pub fn fingerprint(&self) -> u64 {
+		// FNV-1a
+		let hash = |hash: u64, byte: u8| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+		let mut fingerprint = hash(0xcbf2_9ce4_8422_2325, self.chunk_edge_len.get());
+		for (_coord, tile) in self.iter_tiles() {
+			for &byte in tile.get().to_le_bytes().iter() {
+				fingerprint = hash(fingerprint, byte);
+			}
+		}
+		fingerprint
+	}
+
+	pub fn storage_stats(&self) -> TileStorageStats {
+		self.tiles.stats()
+	}
+
+	/// Iterates over every tile in storage order, x-major then y then z.
+	pub fn iter_tiles(&self) -> IterChunkTiles<'_> {
+		IterChunkTiles {
+			chunk: self,
+			index: 0,
+		}
+	}
+
+	/// Unpacks the tiles to be modified in place, see `ChunkTilesMut`.
+	pub fn tiles_mut(&mut self) -> ChunkTilesMut<'_> {
+		ChunkTilesMut {
+			chunk_edge_len: self.chunk_edge_len,
+			tiles: self.tiles.tiles_mut(),
+			revision: &mut self.revision,
+		}
+	}
+
+	/// Iterates over the tiles in the box spanned by the two corners, both inclusive, in storage
+	/// order.  The corners can be given in any order and the box is clipped to the chunk.
+	pub fn iter_tiles_in_box(
+		&self,
+		a: LocalTileCoord,
+		b: LocalTileCoord,
+	) -> IterChunkTilesInBox<'_> {
+		let (bottom, top) = clip_box(a, b, self.chunk_edge_len);
+		IterChunkTilesInBox {
+			chunk: self,
+			bottom,
+			top,
+			next: Some(bottom).filter(|c| c.is_in_chunk(self.chunk_edge_len)),
+		}
+	}
+}
+
+/// The lowest and the highest corner of the box spanned by the two corners, with the highest
+/// clipped to the chunk.  The lowest can still be outside of the chunk if the whole box is.
+fn clip_box(
+	a: LocalTileCoord,
+	b: LocalTileCoord,
+	chunk_edge_len: NonZeroU8,
+) -> (LocalTileCoord, LocalTileCoord) {
+	let max = chunk_edge_len.get() - 1;
+	let bottom = LocalTileCoord::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+	let top = LocalTileCoord::new(
+		a.x().max(b.x()).min(max),
+		a.y().max(b.y()).min(max),
+		a.z().max(b.z()).min(max),
+	);
+	(bottom, top)
+}
+
+/// The tiles of a chunk unpacked into a flat array to be modified in place.  When this is dropped
+/// the tiles are packed again, and the chunk counts as modified if any of them changed.
+pub struct ChunkTilesMut<'a> {
+	chunk_edge_len: NonZeroU8,
+	tiles: TilesMut<'a>,
+	revision: &'a mut u64,
+}
+
+impl<'a> ChunkTilesMut<'a> {
+	/// Iterates over every tile mutably in storage order, x-major then y then z.
+	pub fn iter_mut(&mut self) -> IterChunkTilesMut<'_> {
+		IterChunkTilesMut {
+			chunk_edge_len: self.chunk_edge_len,
+			tiles: self.tiles.iter_mut().enumerate(),
+		}
+	}
+
+	/// The mutable version of `Chunk::iter_tiles_in_box`, only the rows of tiles in the box are
+	/// walked.
+	pub fn iter_in_box_mut(
+		&mut self,
+		a: LocalTileCoord,
+		b: LocalTileCoord,
+	) -> impl Iterator<Item = (LocalTileCoord, &mut TileId)> + '_ {
+		let len = self.chunk_edge_len.get() as usize;
+		let (bottom, top) = clip_box(a, b, self.chunk_edge_len);
+		// Empty if the box starts past the end of the chunk on any axis.
+		let range = |axis: usize| {
+			let end = top.0[axis] as usize + 1;
+			(bottom.0[axis] as usize).min(end)..end
+		};
+		let (xs, ys, zs) = (range(0), range(1), range(2));
+		self.tiles
+			.chunks_exact_mut(len * len)
+			.enumerate()
+			.skip(zs.start)
+			.take(zs.len())
+			.flat_map(move |(z, plane)| {
+				let xs = xs.clone();
+				plane
+					.chunks_exact_mut(len)
+					.enumerate()
+					.skip(ys.start)
+					.take(ys.len())
+					.flat_map(move |(y, row)| {
+						let start = xs.start;
+						row[xs.clone()]
+							.iter_mut()
+							.enumerate()
+							.map(move |(x, tile)| {
+								let coord = LocalTileCoord::new(
+									(start + x) as LocalTileCoordType,
+									y as LocalTileCoordType,
+									z as LocalTileCoordType,
+								);
+								(coord, tile)
+							})
+					})
+			})
+	}
+}
+
+impl<'a> Drop for ChunkTilesMut<'a> {
+	fn drop(&mut self) {
+		if self.tiles.repack() {
+			*self.revision += 1;
+		}
+	}
+}
+
+pub struct IterChunkTiles<'a> {
+	chunk: &'a Chunk,
+	index: usize,
+}
+
+impl<'a> Iterator for IterChunkTiles<'a> {
+	type Item = (LocalTileCoord, TileId);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let coord = LocalTileCoord::from_index(self.index, self.chunk.chunk_edge_len)?;
+		let tile = self.chunk.tiles.get(self.index)?;
+		self.index += 1;
+		Some((coord, tile))
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let remaining = self.chunk.tile_count() - self.index;
+		(remaining, Some(remaining))
+	}
+}
+
+impl<'a> ExactSizeIterator for IterChunkTiles<'a> {}
+
+pub struct IterChunkTilesMut<'a> {
+	chunk_edge_len: NonZeroU8,
+	tiles: Enumerate<std::slice::IterMut<'a, TileId>>,
+}
+
+impl<'a> Iterator for IterChunkTilesMut<'a> {
+	type Item = (LocalTileCoord, &'a mut TileId);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let (index, tile) = self.tiles.next()?;
+		let coord = LocalTileCoord::from_index(index, self.chunk_edge_len)
+			.expect("tile storage is sized to the chunk");
+		Some((coord, tile))
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		self.tiles.size_hint()
+	}
+}
+
+impl<'a> ExactSizeIterator for IterChunkTilesMut<'a> {}
+
+pub struct IterChunkTilesInBox<'a> {
+	chunk: &'a Chunk,
+	bottom: LocalTileCoord,
+	top: LocalTileCoord,
+	next: Option<LocalTileCoord>,
+}
+
+impl<'a> Iterator for IterChunkTilesInBox<'a> {
+	type Item = (LocalTileCoord, TileId);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let coord = self.next?;
+		let (bottom, top) = (self.bottom, self.top);
+		self.next = if coord.x() < top.x() {
+			Some(LocalTileCoord::new(coord.x() + 1, coord.y(), coord.z()))
+		} else if coord.y() < top.y() {
+			Some(LocalTileCoord::new(bottom.x(), coord.y() + 1, coord.z()))
+		} else if coord.z() < top.z() {
+			Some(LocalTileCoord::new(bottom.x(), bottom.y(), coord.z() + 1))
+		} else {
+			None
+		};
+		let tile = self
+			.chunk
+			.get_tile(coord)
+			.expect("box was clipped to the chunk");
+		Some((coord, tile))
+	}
+}
+
+impl Sub for ChunkCoord {
+	type Output = ChunkCoordOffset;
+
+	fn sub(self, rhs: Self) -> Self::Output {
+		let axis = |axis: usize| {
+			ChunkCoordOffsetType::from(self.0[axis]) - ChunkCoordOffsetType::from(rhs.0[axis])
+		};
+		ChunkCoordOffset([axis(0), axis(1), axis(2)])
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::chunks::chunk::{
+		Chunk, ChunkCoord, ChunkCoordAABB, ChunkError, Face, LocalTileCoord, TileCoord,
+	};
+	use crate::game_data::tiles::TileId;
+	use proptest::prelude::*;
+	use std::convert::TryFrom;
+	use std::num::NonZeroU8;
+
+	#[test]
+	fn chunk_coord_accessors() {
+		let coord = ChunkCoord::new(1, -2, 3);
+		assert_eq!(coord.x(), 1);
+		assert_eq!(coord.y(), -2);
+		assert_eq!(coord.z(), 3);
+	}
+
+	#[test]
+	fn chunk_coord_sub() {
+		let offset = ChunkCoord::new(4, -2, 3) - ChunkCoord::new(1, 2, 3);
+		assert_eq!((offset.x(), offset.y(), offset.z()), (3, -4, 0));
+		let area = offset.abs();
+		assert_eq!(area.width(), 3);
+		assert_eq!(area.length(), 4);
+		assert_eq!(area.height(), 0);
+		assert_eq!(area.area(), 0);
+		assert_eq!(
+			(ChunkCoord::new(2, 3, 4) - ChunkCoord::new(0, 0, 0))
+				.abs()
+				.area(),
+			24
+		);
+		assert_eq!(ChunkCoord::new(-2, 3, -4).area().area(), 24);
+		let extremes = ChunkCoord::new(i16::MAX, 0, 0) - ChunkCoord::new(i16::MIN, 0, 0);
+		assert_eq!(extremes.x(), 65535);
+		assert_eq!(extremes.abs().width(), 65535);
+	}
+
+	#[test]
+	fn chunk_coord_aabb() {
+		let aabb = ChunkCoordAABB::new(ChunkCoord::new(1, -1, 2), ChunkCoord::new(-1, 1, 0));
+		assert_eq!(aabb.bottom(), ChunkCoord::new(-1, -1, 0));
+		assert_eq!(aabb.top(), ChunkCoord::new(1, 1, 2));
+		assert_eq!(aabb.size(), [3, 3, 3]);
+		assert_eq!(aabb.volume(), 27);
+		assert!(aabb.contains(ChunkCoord::new(-1, 0, 2)));
+		assert!(!aabb.contains(ChunkCoord::new(-2, 0, 2)));
+		let coords: Vec<_> = aabb.iter().collect();
+		assert_eq!(coords.len(), 27);
+		assert_eq!(coords[0], ChunkCoord::new(-1, -1, 0));
+		assert_eq!(coords[1], ChunkCoord::new(0, -1, 0));
+		assert_eq!(coords[3], ChunkCoord::new(-1, 0, 0));
+		assert_eq!(coords[26], ChunkCoord::new(1, 1, 2));
+		assert!(coords.iter().all(|&c| aabb.contains(c)));
+
+		let other = ChunkCoordAABB::new(ChunkCoord::new(1, 1, 2), ChunkCoord::new(5, 5, 5));
+		assert!(aabb.intersects(&other));
+		assert_eq!(
+			aabb.intersection(&other),
+			Some(ChunkCoordAABB::new(
+				ChunkCoord::new(1, 1, 2),
+				ChunkCoord::new(1, 1, 2)
+			))
+		);
+		let apart = ChunkCoordAABB::new(ChunkCoord::new(2, 0, 0), ChunkCoord::new(3, 0, 0));
+		assert!(!aabb.intersects(&apart));
+		assert_eq!(aabb.intersection(&apart), None);
+		let union = aabb.union(&other);
+		assert_eq!(union.bottom(), ChunkCoord::new(-1, -1, 0));
+		assert_eq!(union.top(), ChunkCoord::new(5, 5, 5));
+		assert_eq!(aabb.expand(1).volume(), 125);
+	}
+
+	#[test]
+	fn chunk_coord_aabb_edges() {
+		let around = ChunkCoordAABB::around(ChunkCoord::new(i16::MIN, 0, i16::MAX), 2);
+		assert_eq!(around.bottom(), ChunkCoord::new(i16::MIN, -2, i16::MAX - 2));
+		assert_eq!(around.top(), ChunkCoord::new(i16::MIN + 2, 2, i16::MAX));
+		assert_eq!(around.size(), [3, 5, 3]);
+		assert_eq!(around.iter().count(), 45);
+		let everything = ChunkCoordAABB::around(ChunkCoord::default(), u16::MAX);
+		assert_eq!(everything.size(), [65536; 3]);
+		assert_eq!(
+			everything.bottom(),
+			ChunkCoord::new(i16::MIN, i16::MIN, i16::MIN)
+		);
+		let single = ChunkCoordAABB::new(
+			ChunkCoord::new(i16::MAX, i16::MAX, i16::MAX),
+			ChunkCoord::new(i16::MAX, i16::MAX, i16::MAX),
+		);
+		assert_eq!(single.into_iter().count(), 1);
+	}
+
+	#[test]
+	fn chunk_new() {
+		let chunk = Chunk::new(ChunkCoord::new(1, 2, 3), NonZeroU8::new(16).unwrap());
+		assert_eq!(chunk.location(), ChunkCoord::new(1, 2, 3));
+		assert_eq!(chunk.chunk_edge_len().get(), 16);
+		assert_eq!(chunk.tile_count(), 16 * 16 * 16);
+		assert_eq!(
+			chunk.get_tile(LocalTileCoord::new(0, 0, 0)),
+			Some(TileId::AIR)
+		);
+	}
+
+	#[test]
+	fn local_tile_coord_index() {
+		let len = NonZeroU8::new(4).unwrap();
+		assert_eq!(LocalTileCoord::new(0, 0, 0).to_index(len), Some(0));
+		assert_eq!(LocalTileCoord::new(1, 0, 0).to_index(len), Some(1));
+		assert_eq!(LocalTileCoord::new(0, 1, 0).to_index(len), Some(4));
+		assert_eq!(LocalTileCoord::new(0, 0, 1).to_index(len), Some(16));
+		assert_eq!(LocalTileCoord::new(3, 3, 3).to_index(len), Some(63));
+		assert_eq!(LocalTileCoord::new(4, 0, 0).to_index(len), None);
+		assert_eq!(LocalTileCoord::from_index(64, len), None);
+		for index in 0..64 {
+			let coord = LocalTileCoord::from_index(index, len).unwrap();
+			assert_eq!(coord.to_index(len), Some(index));
+		}
+	}
+
+	#[test]
+	fn get_set_tiles() {
+		let len = NonZeroU8::new(4).unwrap();
+		let mut chunk = Chunk::new(ChunkCoord::new(0, 0, 0), len);
+		let stone = TileId::new(1);
+		let coord = LocalTileCoord::new(1, 2, 3);
+		assert_eq!(chunk.set_tile(coord, stone), Ok(TileId::AIR));
+		assert_eq!(chunk.get_tile(coord), Some(stone));
+		assert_eq!(chunk.set_tile(coord, TileId::AIR), Ok(stone));
+		let outside = LocalTileCoord::new(0, 4, 0);
+		assert_eq!(chunk.get_tile(outside), None);
+		assert_eq!(
+			chunk.set_tile(outside, stone),
+			Err(ChunkError::OutOfBounds(outside, len))
+		);
+		assert_eq!(chunk.storage_stats().palette_len, 2);
+		chunk.compact();
+		assert_eq!(chunk.storage_stats().palette_len, 1);
+		chunk.fill(stone);
+		assert_eq!(chunk.get_tile(LocalTileCoord::new(3, 3, 3)), Some(stone));
+		assert_eq!(chunk.storage_stats().heap_bytes, 0);
+	}
+
+	#[test]
+	fn iter_tiles() {
+		let len = NonZeroU8::new(4).unwrap();
+		let mut chunk = Chunk::new(ChunkCoord::new(0, 0, 0), len);
+		for (coord, tile) in chunk.tiles_mut().iter_mut() {
+			*tile = TileId::new(coord.z() as u16);
+		}
+		assert_eq!(chunk.storage_stats().palette_len, 4);
+		assert_eq!(chunk.iter_tiles().len(), 64);
+		for (index, (coord, tile)) in chunk.iter_tiles().enumerate() {
+			assert_eq!(coord.to_index(len), Some(index));
+			assert_eq!(tile, TileId::new(coord.z() as u16));
+		}
+	}
+
+	#[test]
+	fn iter_tiles_in_box() {
+		let len = NonZeroU8::new(4).unwrap();
+		let mut chunk = Chunk::new(ChunkCoord::new(0, 0, 0), len);
+		let coords: Vec<_> = chunk
+			.iter_tiles_in_box(LocalTileCoord::new(2, 3, 1), LocalTileCoord::new(1, 2, 9))
+			.map(|(coord, _tile)| coord)
+			.collect();
+		assert_eq!(coords.len(), 2 * 2 * 3);
+		assert_eq!(coords[0], LocalTileCoord::new(1, 2, 1));
+		assert_eq!(coords[1], LocalTileCoord::new(2, 2, 1));
+		assert_eq!(coords[2], LocalTileCoord::new(1, 3, 1));
+		assert_eq!(coords[11], LocalTileCoord::new(2, 3, 3));
+		let outside = LocalTileCoord::new(4, 4, 4);
+		assert_eq!(chunk.iter_tiles_in_box(outside, outside).count(), 0);
+		let mut tiles = chunk.tiles_mut();
+		let coords_mut: Vec<_> = tiles
+			.iter_in_box_mut(LocalTileCoord::new(2, 3, 1), LocalTileCoord::new(1, 2, 9))
+			.map(|(coord, _tile)| coord)
+			.collect();
+		assert_eq!(coords_mut, coords);
+		assert_eq!(tiles.iter_in_box_mut(outside, outside).count(), 0);
+		drop(tiles);
+		let stone = TileId::new(1);
+		chunk
+			.tiles_mut()
+			.iter_in_box_mut(LocalTileCoord::new(0, 0, 0), LocalTileCoord::new(1, 1, 1))
+			.for_each(|(_coord, tile)| *tile = stone);
+		assert_eq!(chunk.iter_tiles().filter(|&(_, t)| t == stone).count(), 8);
+		assert_eq!(chunk.get_tile(LocalTileCoord::new(1, 1, 1)), Some(stone));
+		assert_eq!(
+			chunk.get_tile(LocalTileCoord::new(2, 1, 1)),
+			Some(TileId::AIR)
+		);
+	}
+
+	#[test]
+	fn step_faces() {
+		let len = NonZeroU8::new(4).unwrap();
+		let coord = LocalTileCoord::new(0, 1, 3);
+		assert_eq!(
+			coord.step(Face::PosX, len),
+			(LocalTileCoord::new(1, 1, 3), false)
+		);
+		assert_eq!(
+			coord.step(Face::NegX, len),
+			(LocalTileCoord::new(3, 1, 3), true)
+		);
+		assert_eq!(
+			coord.step(Face::PosZ, len),
+			(LocalTileCoord::new(0, 1, 0), true)
+		);
+		for face in Face::ALL.iter().copied() {
+			assert_eq!(face.opposite().opposite(), face);
+			let (stepped, _crossed) = coord.step(face, len);
+			assert_eq!(stepped.step(face.opposite(), len).0, coord);
+		}
+		assert_eq!(
+			ChunkCoord::new(0, 0, -1).neighbor(Face::NegY),
+			Some(ChunkCoord::new(0, -1, -1))
+		);
+		assert_eq!(ChunkCoord::new(i16::MAX, 0, 0).neighbor(Face::PosX), None);
+	}
+
+	#[test]
+	fn tile_coord_negative_split() {
+		let len = NonZeroU8::new(16).unwrap();
+		let split = |x, y, z| TileCoord::new(x, y, z).to_chunk_local(len).unwrap();
+		assert_eq!(
+			split(0, 15, 16),
+			(ChunkCoord::new(0, 0, 1), LocalTileCoord::new(0, 15, 0))
+		);
+		assert_eq!(
+			split(-1, -16, -17),
+			(ChunkCoord::new(-1, -1, -2), LocalTileCoord::new(15, 0, 15))
+		);
+		assert_eq!(TileCoord::new(i32::MIN, 0, 0).to_chunk_local(len), None);
+		let chunk = Chunk::new(ChunkCoord::new(-1, 0, 2), len);
+		assert_eq!(
+			chunk.to_tile_coord(LocalTileCoord::new(15, 1, 0)),
+			TileCoord::new(-1, 1, 32)
+		);
+	}
+
+	#[test]
+	fn tile_coord_arithmetic() {
+		let mut coord = TileCoord::new(1, -2, 3);
+		coord += TileCoord::new(1, 1, 1);
+		assert_eq!(coord, TileCoord::new(2, -1, 4));
+		coord -= TileCoord::new(4, 4, 4);
+		assert_eq!(coord, TileCoord::new(-2, -5, 0));
+		assert_eq!(-coord, TileCoord::new(2, 5, 0));
+		assert_eq!(coord.step(Face::NegZ), TileCoord::new(-2, -5, -1));
+		assert_eq!(coord - coord, TileCoord::default());
+	}
+
+	#[test]
+	fn chunk_dirty_tracking() {
+		let mut chunk = Chunk::new(ChunkCoord::new(0, 0, 0), NonZeroU8::new(4).unwrap());
+		assert!(!chunk.is_dirty());
+		let tile = LocalTileCoord::new(1, 2, 3);
+		chunk.set_tile(tile, TileId::AIR).unwrap();
+		assert!(!chunk.is_dirty());
+		chunk.set_tile(tile, TileId::new(1)).unwrap();
+		assert!(chunk.is_dirty());
+		let saving = chunk.revision();
+		chunk.mark_saved(saving);
+		assert!(!chunk.is_dirty());
+
+		// A change while the chunk is being written keeps it dirty.
+		chunk.fill(TileId::new(2));
+		chunk.mark_saved(saving);
+		assert!(chunk.is_dirty());
+		chunk.mark_saved(chunk.revision());
+		chunk.mark_dirty();
+		assert!(chunk.is_dirty());
+
+		// Borrowing the tiles mutably only counts as a change once a tile is written.
+		chunk.mark_saved(chunk.revision());
+		for (_coord, tile) in chunk.tiles_mut().iter_mut() {
+			*tile = TileId::new(2);
+		}
+		assert!(!chunk.is_dirty());
+		*chunk.tiles_mut().iter_mut().nth(5).unwrap().1 = TileId::new(3);
+		assert!(chunk.is_dirty());
+	}
+
+	proptest! {
+		#[test]
+		fn tile_coord_round_trip(
+			x in -(1 << 20)..(1 << 20),
+			y in -(1 << 20)..(1 << 20),
+			z in -(1 << 20)..(1 << 20),
+			len in 1u8..=255,
+		) {
+			let len = NonZeroU8::new(len).unwrap();
+			let coord = TileCoord::new(x, y, z);
+			match coord.to_chunk_local(len) {
+				Some((chunk, local)) => {
+					prop_assert!(local.is_in_chunk(len));
+					prop_assert_eq!(TileCoord::from_chunk_local(chunk, local, len), coord);
+				}
+				None => {
+					let len = len.get() as i32;
+					let fits = |v: i32| i16::try_from(v.div_euclid(len)).is_ok();
+					prop_assert!(!(fits(x) && fits(y) && fits(z)));
+				}
+			}
+		}
+
+		#[test]
+		fn tile_coord_step_matches_local_step(
+			x in any::<i16>(),
+			y in any::<i16>(),
+			z in any::<i16>(),
+			face in 0usize..6,
+		) {
+			let len = NonZeroU8::new(32).unwrap();
+			let face = Face::ALL[face];
+			let coord = TileCoord::new(x.into(), y.into(), z.into());
+			let (chunk, local) = coord.to_chunk_local(len).unwrap();
+			let (stepped, crossed) = local.step(face, len);
+			let chunk = if crossed { chunk.neighbor(face).unwrap() } else { chunk };
+			prop_assert_eq!(TileCoord::from_chunk_local(chunk, stepped, len), coord.step(face));
+		}
+	}
+}
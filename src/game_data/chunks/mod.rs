@@ -0,0 +1,207 @@
+pub mod chunk;
+pub mod region_file;
+pub mod tile_storage;
+
+use crate::game_data::chunks::chunk::{ChunkCoord, Face, LocalTileCoord, TileCoord};
+use crate::game_data::tiles::TileId;
+use chunk::Chunk;
+use std::collections::HashMap;
+use std::num::NonZeroU8;
+
+pub struct Chunks {
+	chunk_edge_len: NonZeroU8,
+	chunks: HashMap<ChunkCoord, Chunk>,
+}
+
+impl Chunks {
+	pub fn new(chunk_edge_len: NonZeroU8) -> Self {
+		Self {
+			chunk_edge_len,
+			chunks: HashMap::default(),
+		}
+	}
+
+	pub fn chunk_edge_len(&self) -> NonZeroU8 {
+		self.chunk_edge_len
+	}
+
+	pub fn len(&self) -> usize {
+		self.chunks.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.chunks.is_empty()
+	}
+
+	pub fn contains_chunk(&self, coord: &ChunkCoord) -> bool {
+		self.chunks.contains_key(coord)
+	}
+
+	pub fn get_chunk(&self, coord: &ChunkCoord) -> Option<&Chunk> {
+		self.chunks.get(coord)
+	}
+
+	pub fn get_chunk_mut(&mut self, coord: &ChunkCoord) -> Option<&mut Chunk> {
+		self.chunks.get_mut(coord)
+	}
+
+	/// Adds a chunk at its location, returning the chunk that was there before.
+	pub fn insert_chunk(&mut self, chunk: Chunk) -> Option<Chunk> {
+		assert_eq!(
+			chunk.chunk_edge_len(),
+			self.chunk_edge_len,
+			"chunk edge length doesn't match the other chunks"
+		);
+		self.chunks.insert(chunk.location(), chunk)
+	}
+
+	/// Every chunk in no particular order.
+	pub fn iter_chunks(&self) -> impl Iterator<Item = &Chunk> + '_ {
+		self.chunks.values()
+	}
+
+	pub fn iter_chunks_mut(&mut self) -> impl Iterator<Item = &mut Chunk> + '_ {
+		self.chunks.values_mut()
+	}
+
+	pub fn remove_chunk(&mut self, coord: &ChunkCoord) -> Option<Chunk> {
+		self.chunks.remove(coord)
+	}
+
+	pub fn get_or_create_chunk(&mut self, coord: ChunkCoord) -> (&mut Chunk, bool) {
+		let inserted = !self.chunks.contains_key(&coord);
+		let chunk_edge_len = self.chunk_edge_len;
+		let chunk = self
+			.chunks
+			.entry(coord)
+			.or_insert_with_key(|k| Chunk::new(k.clone(), chunk_edge_len));
+		(chunk, inserted)
+	}
+
+	/// The tile at a world position, `None` if the chunk holding it isn't loaded.
+	pub fn get_tile(&self, coord: TileCoord) -> Option<TileId> {
+		let (chunk, local) = coord.to_chunk_local(self.chunk_edge_len)?;
+		self.get_chunk(&chunk)?.get_tile(local)
+	}
+
+	/// The tile on the other side of the given face of a tile, looking into the adjacent chunk if
+	/// the tile is on the border of its own, `None` if the chunk holding it isn't loaded.
+	pub fn get_neighbor_tile(
+		&self,
+		chunk: ChunkCoord,
+		coord: LocalTileCoord,
+		face: Face,
+	) -> Option<TileId> {
+		let (neighbor, crossed) = coord.step(face, self.chunk_edge_len);
+		let chunk = if crossed {
+			chunk.neighbor(face)?
+		} else {
+			chunk
+		};
+		self.get_chunk(&chunk)?.get_tile(neighbor)
+	}
+
+	/// All six face neighbors of a tile, see `get_neighbor_tile`.
+	pub fn iter_neighbor_tiles(
+		&self,
+		chunk: ChunkCoord,
+		coord: LocalTileCoord,
+	) -> impl Iterator<Item = (Face, Option<TileId>)> + '_ {
+		Face::ALL
+			.iter()
+			.map(move |&face| (face, self.get_neighbor_tile(chunk, coord, face)))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::chunks::chunk::{ChunkCoord, Face, LocalTileCoord, TileCoord};
+	use crate::game_data::chunks::Chunks;
+	use crate::game_data::tiles::TileId;
+	use std::num::NonZeroU8;
+
+	#[test]
+	fn get_or_create_chunk() {
+		let mut chunks = Chunks::new(NonZeroU8::new(8).unwrap());
+		assert!(chunks.is_empty());
+		let coord = ChunkCoord::new(0, -1, 2);
+		assert!(chunks.get_chunk(&coord).is_none());
+		let (chunk, inserted) = chunks.get_or_create_chunk(coord);
+		assert!(inserted);
+		assert_eq!(chunk.location(), coord);
+		assert_eq!(chunk.chunk_edge_len().get(), 8);
+		let (_chunk, inserted) = chunks.get_or_create_chunk(coord);
+		assert!(!inserted);
+		assert_eq!(chunks.len(), 1);
+		assert!(chunks.get_chunk(&coord).is_some());
+		assert!(chunks.get_chunk_mut(&coord).is_some());
+		assert!(chunks.get_chunk(&ChunkCoord::new(0, 0, 0)).is_none());
+	}
+
+	#[test]
+	fn neighbor_tiles_across_chunks() {
+		let mut chunks = Chunks::new(NonZeroU8::new(4).unwrap());
+		let origin = ChunkCoord::new(0, 0, 0);
+		let stone = TileId::new(1);
+		chunks.get_or_create_chunk(origin);
+		let (east, _) = chunks.get_or_create_chunk(ChunkCoord::new(1, 0, 0));
+		east.set_tile(LocalTileCoord::new(0, 2, 3), stone).unwrap();
+		let border = LocalTileCoord::new(3, 2, 3);
+		assert_eq!(
+			chunks.get_neighbor_tile(origin, border, Face::PosX),
+			Some(stone)
+		);
+		assert_eq!(
+			chunks.get_neighbor_tile(origin, border, Face::NegX),
+			Some(TileId::AIR)
+		);
+		assert_eq!(chunks.get_neighbor_tile(origin, border, Face::PosZ), None);
+		let neighbors: Vec<_> = chunks.iter_neighbor_tiles(origin, border).collect();
+		assert_eq!(neighbors.len(), 6);
+		assert_eq!(neighbors.iter().filter(|(_, t)| t.is_some()).count(), 5);
+		assert_eq!(chunks.get_tile(TileCoord::new(4, 2, 3)), Some(stone));
+		assert_eq!(chunks.get_tile(TileCoord::new(-1, 2, 3)), None);
+	}
+}
+
+// // Definitely not gonna be the final Chunk System. But imagine the Game was that small. XD
+// // Each of the Bits represents one of the Vertices being active/inactive for Marching Cubes Render.
+// pub struct Chunk {
+// 	pub data: [[[u8; 4]; 4]; 4],
+// }
+//
+// impl Chunk {
+// 	// in this example I only have Full Blocks and Empty Blocks, no Slopes or such.
+// 	// the Result of this Array should be reminiscent of an Egg.
+// 	// I can only guess which way the Egg is gonna be rotated, but this is just some hardcoded Test.
+// 	pub fn new() -> Self {
+// 		Chunk {
+// 			data: [
+// 				[
+// 					[0, 0, 0, 0],
+// 					[0, 255, 255, 0],
+// 					[255, 255, 255, 255],
+// 					[0, 255, 255, 0],
+// 				],
+// 				[
+// 					[0, 255, 255, 0],
+// 					[255, 255, 255, 255],
+// 					[255, 255, 255, 255],
+// 					[255, 255, 255, 255],
+// 				],
+// 				[
+// 					[0, 255, 255, 0],
+// 					[255, 255, 255, 255],
+// 					[255, 255, 255, 255],
+// 					[255, 255, 255, 255],
+// 				],
+// 				[
+// 					[0, 0, 0, 0],
+// 					[0, 255, 255, 0],
+// 					[255, 255, 255, 255],
+// 					[0, 255, 255, 0],
+// 				],
+// 			],
+// 		}
+// 	}
+// }
@@ -0,0 +1,674 @@
+//! Chunks are saved in region files that each hold a cube of `REGION_EDGE_LEN`³ chunks.
+//!
+//! A region file starts with a header of the magic bytes, the format version, the chunk and region
+//! edge lengths and an index with the offset and length of every chunk in the region, an offset of
+//! 0 meaning the chunk isn't saved.  Each saved chunk is a byte giving its compression followed by
+//! the encoded chunk: its generation stage, its palette of tiles and then the palette index of
+//! every tile in storage order, as bytes if the palette is small enough and left out if the
+//! palette has a single tile.  All numbers are little endian.
+//!
+//! The palette names its tiles, each name a byte giving its length followed by the name, as a
+//! `TileId` only means something to the `TileRegistry` it came from and that is built anew from the
+//! save's configuration whenever it is loaded.
+
+use crate::game_data::atomic_file::write_atomically;
+use crate::game_data::chunks::chunk::{Chunk, ChunkCoord, ChunkCoordType, GenerationStage};
+use crate::game_data::inflate::inflate_zlib;
+use crate::game_data::tiles::{TileId, TileIdType, TileRegistry};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::num::NonZeroU8;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Bumped whenever the layout of region files changes, files with another version aren't read.
+pub const REGION_FORMAT_VERSION: u16 = 1;
+
+/// The amount of chunks along each edge of a region.
+pub const REGION_EDGE_LEN: ChunkCoordType = 8;
+
+const REGION_CHUNKS: usize = (REGION_EDGE_LEN * REGION_EDGE_LEN * REGION_EDGE_LEN) as usize;
+const MAGIC: &[u8; 4] = b"MREG";
+const INDEX_START: usize = 8;
+const HEADER_LEN: usize = INDEX_START + REGION_CHUNKS * 8;
+
+const COMPRESSION_NONE: u8 = 0;
+const COMPRESSION_ZLIB: u8 = 1;
+const COMPRESSION_LEVEL: u8 = 6;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RegionFileError {
+	#[error("IO error while {1}")]
+	Io(#[source] std::io::Error, &'static str),
+	#[error("not a region file: {0:?}")]
+	NotARegionFile(PathBuf),
+	#[error(
+		"region file {0:?} has format version {1} but only {} is supported",
+		REGION_FORMAT_VERSION
+	)]
+	UnsupportedVersion(PathBuf, u16),
+	#[error("region file {0:?} holds chunks with an edge length of {1} instead of {2}")]
+	ChunkEdgeLenMismatch(PathBuf, u8, NonZeroU8),
+	#[error("chunk {1:?} in region file {0:?} is corrupt: {2}")]
+	CorruptChunk(PathBuf, ChunkCoord, &'static str),
+	#[error("chunk {1:?} in region file {0:?} holds the tile `{2}` which isn't registered")]
+	UnknownTile(PathBuf, ChunkCoord, String),
+	#[error("chunk {0:?} holds the tile {1:?} which has no name it can be saved by")]
+	UnnamedTile(ChunkCoord, TileId),
+}
+
+/// A chunk encoded the way it is stored in its region file, so the chunk itself can be modified
+/// again while it is compressed and written on another thread.  Chunks are sent to clients in the
+/// same encoding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodedChunk {
+	location: ChunkCoord,
+	chunk_edge_len: NonZeroU8,
+	data: Vec<u8>,
+}
+
+impl EncodedChunk {
+	pub fn new(chunk: &Chunk) -> Self {
+		EncodedChunk {
+			location: chunk.location(),
+			chunk_edge_len: chunk.chunk_edge_len(),
+			data: encode_chunk(chunk),
+		}
+	}
+
+	/// A chunk encoded somewhere else, like on a server, which is only checked when decoding it.
+	pub fn from_data(location: ChunkCoord, chunk_edge_len: NonZeroU8, data: Vec<u8>) -> Self {
+		EncodedChunk {
+			location,
+			chunk_edge_len,
+			data,
+		}
+	}
+
+	pub fn location(&self) -> ChunkCoord {
+		self.location
+	}
+
+	pub fn chunk_edge_len(&self) -> NonZeroU8 {
+		self.chunk_edge_len
+	}
+
+	pub fn data(&self) -> &[u8] {
+		&self.data
+	}
+
+	/// The chunk back, the error says what is wrong with the data.
+	pub fn decode(&self) -> Result<Chunk, &'static str> {
+		decode_chunk(self.location, self.chunk_edge_len, &self.data)
+	}
+}
+
+/// Reads and writes the region files of a single world in one directory.  Region files are
+/// replaced atomically and clones share a lock so no two threads rewrite a region file at once.
+///
+/// Tiles are saved by their names in `tiles` and loaded as the `TileId` those names have there, so
+/// only chunks of the registered tiles can be saved or loaded.
+#[derive(Debug, Clone)]
+pub struct RegionStorage {
+	directory: PathBuf,
+	chunk_edge_len: NonZeroU8,
+	tiles: Arc<TileRegistry>,
+	lock: Arc<Mutex<()>>,
+}
+
+impl RegionStorage {
+	pub fn new(
+		directory: impl Into<PathBuf>,
+		chunk_edge_len: NonZeroU8,
+		tiles: Arc<TileRegistry>,
+	) -> Self {
+		RegionStorage {
+			directory: directory.into(),
+			chunk_edge_len,
+			tiles,
+			lock: Arc::default(),
+		}
+	}
+
+	pub fn directory(&self) -> &Path {
+		&self.directory
+	}
+
+	pub fn chunk_edge_len(&self) -> NonZeroU8 {
+		self.chunk_edge_len
+	}
+
+	pub fn tiles(&self) -> &TileRegistry {
+		&self.tiles
+	}
+
+	/// The path of the region file that holds the chunk.
+	pub fn region_path(&self, coord: ChunkCoord) -> PathBuf {
+		let region = |v: ChunkCoordType| v.div_euclid(REGION_EDGE_LEN);
+		self.directory.join(format!(
+			"r.{}.{}.{}.mreg",
+			region(coord.x()),
+			region(coord.y()),
+			region(coord.z())
+		))
+	}
+
+	/// Reads the chunk from its region file, only reading the header and the chunk itself.
+	/// Returns `None` if the chunk was never saved, a loaded chunk isn't dirty.
+	pub fn load_chunk(&self, coord: ChunkCoord) -> Result<Option<Chunk>, RegionFileError> {
+		let path = self.region_path(coord);
+		let mut file = match File::open(&path) {
+			Ok(file) => file,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+			Err(e) => return Err(RegionFileError::Io(e, "opening region file")),
+		};
+		let mut header = vec![0; HEADER_LEN];
+		file.read_exact(&mut header)
+			.map_err(|_| RegionFileError::NotARegionFile(path.clone()))?;
+		let (offset, length) = self.read_header(&path, &header)?[region_index(coord)];
+		if offset == 0 {
+			return Ok(None);
+		}
+		// Checked before allocating, so a corrupt header can't make it take up any amount of memory.
+		let file_len = file
+			.metadata()
+			.map_err(|e| RegionFileError::Io(e, "reading region file"))?
+			.len();
+		let corrupt = |reason| RegionFileError::CorruptChunk(path.clone(), coord, reason);
+		if length as usize > 1 + self.max_stored_len() {
+			return Err(corrupt("entry is too long"));
+		}
+		if u64::from(offset) + u64::from(length) > file_len {
+			return Err(corrupt("entry is past the end of the file"));
+		}
+		let mut data = vec![0; length as usize];
+		file.seek(SeekFrom::Start(offset.into()))
+			.and_then(|_| file.read_exact(&mut data))
+			.map_err(|e| RegionFileError::Io(e, "reading chunk from region file"))?;
+		let stored = decompress(&path, coord, &data, self.max_stored_len())?;
+		let encoded = number_palette(&path, coord, &stored, &self.tiles)?;
+		let mut chunk = decode_chunk(coord, self.chunk_edge_len, &encoded)
+			.map_err(|reason| RegionFileError::CorruptChunk(path.clone(), coord, reason))?;
+		chunk.mark_saved(chunk.revision());
+		Ok(Some(chunk))
+	}
+
+	/// Writes the chunks to their region files, keeping whatever else was saved in those regions.
+	/// Each region file that holds any of the chunks is rewritten once.  This doesn't touch the
+	/// dirty state of the chunks, see `ChunkSaver` for that.
+	pub fn save_chunks<'a>(
+		&self,
+		chunks: impl IntoIterator<Item = &'a Chunk>,
+	) -> Result<usize, RegionFileError> {
+		self.save_encoded_chunks(chunks.into_iter().map(EncodedChunk::new))
+	}
+
+	/// Like `save_chunks` for chunks that were already encoded, only the compression and writing
+	/// happen here.
+	pub fn save_encoded_chunks(
+		&self,
+		chunks: impl IntoIterator<Item = EncodedChunk>,
+	) -> Result<usize, RegionFileError> {
+		let mut regions: HashMap<PathBuf, Vec<EncodedChunk>> = HashMap::default();
+		for chunk in chunks {
+			assert_eq!(
+				chunk.chunk_edge_len, self.chunk_edge_len,
+				"chunk edge length doesn't match the region storage"
+			);
+			regions
+				.entry(self.region_path(chunk.location))
+				.or_default()
+				.push(chunk);
+		}
+		if regions.is_empty() {
+			return Ok(0);
+		}
+		let _writing = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+		std::fs::create_dir_all(&self.directory)
+			.map_err(|e| RegionFileError::Io(e, "creating region directory"))?;
+		let mut saved = 0;
+		for (path, chunks) in regions {
+			let mut entries = self.read_entries(&path)?;
+			for chunk in chunks {
+				let stored = name_palette(chunk.location, &chunk.data, &self.tiles)?;
+				entries[region_index(chunk.location)] = Some(compress(&stored));
+				saved += 1;
+			}
+			write_atomically(&path, &self.write_region(&entries))
+				.map_err(|e| RegionFileError::Io(e, "writing region file"))?;
+		}
+		Ok(saved)
+	}
+
+	/// Every saved chunk of a region file as it is stored, compression byte and all.
+	fn read_entries(&self, path: &Path) -> Result<Vec<Option<Vec<u8>>>, RegionFileError> {
+		let file = match std::fs::read(path) {
+			Ok(file) => file,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+				return Ok(vec![None; REGION_CHUNKS])
+			}
+			Err(e) => return Err(RegionFileError::Io(e, "reading region file")),
+		};
+		if file.len() < HEADER_LEN {
+			return Err(RegionFileError::NotARegionFile(path.to_owned()));
+		}
+		self.read_header(path, &file[..HEADER_LEN])?
+			.into_iter()
+			.map(|(offset, length)| {
+				if offset == 0 {
+					return Ok(None);
+				}
+				let start = offset as usize;
+				file.get(start..start + length as usize)
+					.map(|data| Some(data.to_vec()))
+					.ok_or_else(|| RegionFileError::NotARegionFile(path.to_owned()))
+			})
+			.collect()
+	}
+
+	/// The most bytes a chunk can take up stored, with every registered tile in its palette and two
+	/// bytes for each of its tiles.
+	fn max_stored_len(&self) -> usize {
+		let palette: usize = self.tiles.iter().map(|(_id, name)| 1 + name.len()).sum();
+		let tile_count = (self.chunk_edge_len.get() as usize).pow(3);
+		1 + 2 + palette + tile_count * 2
+	}
+
+	/// The offset and length of every chunk in the region from the header.
+	fn read_header(&self, path: &Path, header: &[u8]) -> Result<Vec<(u32, u32)>, RegionFileError> {
+		if &header[0..4] != MAGIC {
+			return Err(RegionFileError::NotARegionFile(path.to_owned()));
+		}
+		let version = u16::from_le_bytes([header[4], header[5]]);
+		if version != REGION_FORMAT_VERSION {
+			return Err(RegionFileError::UnsupportedVersion(
+				path.to_owned(),
+				version,
+			));
+		}
+		if header[6] != self.chunk_edge_len.get() {
+			return Err(RegionFileError::ChunkEdgeLenMismatch(
+				path.to_owned(),
+				header[6],
+				self.chunk_edge_len,
+			));
+		}
+		if header[7] as ChunkCoordType != REGION_EDGE_LEN {
+			return Err(RegionFileError::NotARegionFile(path.to_owned()));
+		}
+		Ok(header[INDEX_START..HEADER_LEN]
+			.chunks_exact(8)
+			.map(|entry| {
+				(
+					u32::from_le_bytes(entry[0..4].try_into().unwrap()),
+					u32::from_le_bytes(entry[4..8].try_into().unwrap()),
+				)
+			})
+			.collect())
+	}
+
+	fn write_region(&self, entries: &[Option<Vec<u8>>]) -> Vec<u8> {
+		let mut header = Vec::with_capacity(HEADER_LEN);
+		header.extend_from_slice(MAGIC);
+		header.extend_from_slice(&REGION_FORMAT_VERSION.to_le_bytes());
+		header.push(self.chunk_edge_len.get());
+		header.push(REGION_EDGE_LEN as u8);
+		let mut data = Vec::new();
+		for entry in entries {
+			let (offset, length) = match entry {
+				Some(entry) => {
+					let offset = HEADER_LEN + data.len();
+					data.extend_from_slice(entry);
+					(offset as u32, entry.len() as u32)
+				}
+				None => (0, 0),
+			};
+			header.extend_from_slice(&offset.to_le_bytes());
+			header.extend_from_slice(&length.to_le_bytes());
+		}
+		header.extend_from_slice(&data);
+		header
+	}
+}
+
+/// Where the chunk is in the index of its region file.
+fn region_index(coord: ChunkCoord) -> usize {
+	let local = |v: ChunkCoordType| v.rem_euclid(REGION_EDGE_LEN) as usize;
+	let edge = REGION_EDGE_LEN as usize;
+	local(coord.x()) + local(coord.y()) * edge + local(coord.z()) * edge * edge
+}
+
+/// The chunk as it is stored, from an entry in its region file.  Entries that would decompress to
+/// more than `max_len` bytes are corrupt, so they can't take up any amount of memory.
+fn decompress(
+	path: &Path,
+	coord: ChunkCoord,
+	entry: &[u8],
+	max_len: usize,
+) -> Result<Vec<u8>, RegionFileError> {
+	let corrupt = |reason| RegionFileError::CorruptChunk(path.to_owned(), coord, reason);
+	match entry.split_first() {
+		Some((&COMPRESSION_NONE, stored)) => Ok(stored.to_vec()),
+		Some((&COMPRESSION_ZLIB, compressed)) => {
+			inflate_zlib(compressed, max_len).ok_or_else(|| corrupt("failed to decompress"))
+		}
+		Some(_) => Err(corrupt("unknown compression")),
+		None => Err(corrupt("no data")),
+	}
+}
+
+/// Compresses the encoded chunk unless that doesn't make it any smaller.
+fn compress(encoded: &[u8]) -> Vec<u8> {
+	let compressed = miniz_oxide::deflate::compress_to_vec_zlib(encoded, COMPRESSION_LEVEL);
+	let (compression, data) = if compressed.len() < encoded.len() {
+		(COMPRESSION_ZLIB, &compressed[..])
+	} else {
+		(COMPRESSION_NONE, encoded)
+	};
+	let mut entry = Vec::with_capacity(data.len() + 1);
+	entry.push(compression);
+	entry.extend_from_slice(data);
+	entry
+}
+
+fn encode_chunk(chunk: &Chunk) -> Vec<u8> {
+	let mut palette: Vec<TileId> = Vec::new();
+	let mut palette_indices: HashMap<TileId, usize> = HashMap::default();
+	let indices: Vec<usize> = chunk
+		.iter_tiles()
+		.map(|(_coord, tile)| {
+			*palette_indices.entry(tile).or_insert_with(|| {
+				palette.push(tile);
+				palette.len() - 1
+			})
+		})
+		.collect();
+	let mut encoded = vec![chunk.generation_stage() as u8];
+	encoded.extend_from_slice(&(palette.len() as u16).to_le_bytes());
+	for tile in palette.iter() {
+		encoded.extend_from_slice(&tile.get().to_le_bytes());
+	}
+	if palette.len() > 256 {
+		for index in indices {
+			encoded.extend_from_slice(&(index as u16).to_le_bytes());
+		}
+	} else if palette.len() > 1 {
+		encoded.extend(indices.into_iter().map(|index| index as u8));
+	}
+	encoded
+}
+
+/// The encoded chunk with the `TileId`s of its palette replaced by the names of the tiles.
+fn name_palette(
+	coord: ChunkCoord,
+	encoded: &[u8],
+	tiles: &TileRegistry,
+) -> Result<Vec<u8>, RegionFileError> {
+	let palette_len = u16::from_le_bytes([encoded[1], encoded[2]]) as usize;
+	let palette_end = 3 + palette_len * 2;
+	let mut stored = encoded[..3].to_vec();
+	for id in encoded[3..palette_end].chunks_exact(2) {
+		let tile = TileId::new(TileIdType::from_le_bytes([id[0], id[1]]));
+		if !tiles.contains(tile) || tiles.get_tile_name(tile).len() > u8::MAX as usize {
+			return Err(RegionFileError::UnnamedTile(coord, tile));
+		}
+		let name = tiles.get_tile_name(tile);
+		stored.push(name.len() as u8);
+		stored.extend_from_slice(name.as_bytes());
+	}
+	stored.extend_from_slice(&encoded[palette_end..]);
+	Ok(stored)
+}
+
+/// The stored chunk with the tile names of its palette replaced by their `TileId`s, the reverse of
+/// `name_palette`.
+fn number_palette(
+	path: &Path,
+	coord: ChunkCoord,
+	stored: &[u8],
+	tiles: &TileRegistry,
+) -> Result<Vec<u8>, RegionFileError> {
+	let corrupt = |reason| RegionFileError::CorruptChunk(path.to_owned(), coord, reason);
+	if stored.len() < 3 {
+		return Err(corrupt("missing palette"));
+	}
+	let palette_len = u16::from_le_bytes([stored[1], stored[2]]) as usize;
+	let mut encoded = stored[..3].to_vec();
+	let mut rest = &stored[3..];
+	for _ in 0..palette_len {
+		let (&name_len, after) = rest
+			.split_first()
+			.ok_or_else(|| corrupt("missing palette"))?;
+		if after.len() < name_len as usize {
+			return Err(corrupt("missing palette"));
+		}
+		let (name, after) = after.split_at(name_len as usize);
+		let name = std::str::from_utf8(name).map_err(|_| corrupt("tile name isn't UTF-8"))?;
+		let tile = tiles
+			.get_tile_id_by_name(name)
+			.ok_or_else(|| RegionFileError::UnknownTile(path.to_owned(), coord, name.to_owned()))?;
+		encoded.extend_from_slice(&tile.get().to_le_bytes());
+		rest = after;
+	}
+	encoded.extend_from_slice(rest);
+	Ok(encoded)
+}
+
+fn decode_chunk(
+	coord: ChunkCoord,
+	chunk_edge_len: NonZeroU8,
+	encoded: &[u8],
+) -> Result<Chunk, &'static str> {
+	let mut chunk = Chunk::new(coord, chunk_edge_len);
+	let (&stage, rest) = encoded.split_first().ok_or("missing generation stage")?;
+	let stage = *GenerationStage::ALL
+		.get(stage as usize)
+		.ok_or("unknown generation stage")?;
+	chunk.set_generation_stage(stage);
+	if rest.len() < 2 {
+		return Err("missing palette");
+	}
+	let (palette_len, rest) = rest.split_at(2);
+	let palette_len = u16::from_le_bytes([palette_len[0], palette_len[1]]) as usize;
+	if palette_len == 0 || rest.len() < palette_len * 2 {
+		return Err("missing palette");
+	}
+	let (palette, indices) = rest.split_at(palette_len * 2);
+	let palette: Vec<TileId> = palette
+		.chunks_exact(2)
+		.map(|tile| TileId::new(TileIdType::from_le_bytes([tile[0], tile[1]])))
+		.collect();
+	if palette_len == 1 {
+		if !indices.is_empty() {
+			return Err("trailing data");
+		}
+		chunk.fill(palette[0]);
+		return Ok(chunk);
+	}
+	let indices: Vec<usize> = if palette_len > 256 {
+		indices
+			.chunks_exact(2)
+			.map(|index| u16::from_le_bytes([index[0], index[1]]) as usize)
+			.collect()
+	} else {
+		indices.iter().map(|&index| index as usize).collect()
+	};
+	if indices.len() != chunk.tile_count() {
+		return Err("wrong amount of tiles");
+	}
+	for ((_coord, tile), index) in chunk.tiles_mut().iter_mut().zip(indices) {
+		*tile = *palette.get(index).ok_or("palette index out of range")?;
+	}
+	Ok(chunk)
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::chunks::chunk::{Chunk, ChunkCoord, GenerationStage, LocalTileCoord};
+	use crate::game_data::chunks::region_file::{
+		compress, region_index, RegionFileError, RegionStorage, HEADER_LEN, INDEX_START,
+		REGION_CHUNKS, REGION_FORMAT_VERSION,
+	};
+	use crate::game_data::test_dir::TestDir;
+	use crate::game_data::tiles::{TileId, TileRegistry};
+	use std::num::NonZeroU8;
+	use std::sync::Arc;
+
+	fn edge() -> NonZeroU8 {
+		NonZeroU8::new(8).unwrap()
+	}
+
+	fn tiles() -> Arc<TileRegistry> {
+		Arc::new(TileRegistry::numbered(512))
+	}
+
+	#[test]
+	fn save_and_load_chunks() {
+		let dir = TestDir::new("region-round-trip");
+		let storage = RegionStorage::new(dir.path(), edge(), tiles());
+		let mut single = Chunk::new(ChunkCoord::new(0, 0, 0), edge());
+		single.fill(TileId::new(3));
+		single.set_generation_stage(GenerationStage::Decorated);
+		let mut few = Chunk::new(ChunkCoord::new(-1, 7, 8), edge());
+		few.set_tile(LocalTileCoord::new(1, 2, 3), TileId::new(9))
+			.unwrap();
+		few.set_generation_stage(GenerationStage::Featured);
+		let mut many = Chunk::new(ChunkCoord::new(1, 0, 0), edge());
+		for (coord, tile) in many.tiles_mut().iter_mut() {
+			*tile = TileId::new(coord.to_index(edge()).unwrap() as u16);
+		}
+		assert!(storage
+			.load_chunk(ChunkCoord::new(0, 0, 0))
+			.unwrap()
+			.is_none());
+		assert_eq!(storage.save_chunks(vec![&single, &few, &many]).unwrap(), 3);
+		for chunk in [&single, &few, &many].iter() {
+			let loaded = storage.load_chunk(chunk.location()).unwrap().unwrap();
+			assert!(loaded.tiles_eq(chunk));
+			assert!(!loaded.is_dirty());
+			assert_eq!(loaded.generation_stage(), chunk.generation_stage());
+		}
+		assert!(storage
+			.load_chunk(ChunkCoord::new(2, 0, 0))
+			.unwrap()
+			.is_none());
+
+		// Resaving one chunk of a region keeps the others.
+		single.fill(TileId::new(4));
+		storage.save_chunks(vec![&single]).unwrap();
+		let loaded = storage.load_chunk(single.location()).unwrap().unwrap();
+		assert_eq!(
+			loaded.get_tile(LocalTileCoord::new(0, 0, 0)),
+			Some(TileId::new(4))
+		);
+		assert!(storage
+			.load_chunk(many.location())
+			.unwrap()
+			.unwrap()
+			.tiles_eq(&many));
+	}
+
+	#[test]
+	fn reject_bad_region_files() {
+		let dir = TestDir::new("region-errors");
+		let storage = RegionStorage::new(dir.path(), edge(), tiles());
+		let coord = ChunkCoord::new(0, 0, 0);
+		storage
+			.save_chunks(vec![&Chunk::new(coord, edge())])
+			.unwrap();
+		let other = RegionStorage::new(dir.path(), NonZeroU8::new(16).unwrap(), tiles());
+		assert!(matches!(
+			other.load_chunk(coord),
+			Err(RegionFileError::ChunkEdgeLenMismatch(_, 8, _))
+		));
+
+		let path = storage.region_path(coord);
+		let mut file = std::fs::read(&path).unwrap();
+		file[4..6].copy_from_slice(&(REGION_FORMAT_VERSION + 1).to_le_bytes());
+		std::fs::write(&path, &file).unwrap();
+		assert!(matches!(
+			storage.load_chunk(coord),
+			Err(RegionFileError::UnsupportedVersion(_, _))
+		));
+		assert!(matches!(
+			storage.save_chunks(vec![&Chunk::new(coord, edge())]),
+			Err(RegionFileError::UnsupportedVersion(_, _))
+		));
+
+		std::fs::write(&path, b"not a region").unwrap();
+		assert!(matches!(
+			storage.load_chunk(coord),
+			Err(RegionFileError::NotARegionFile(_))
+		));
+
+		// An entry that inflates beyond what any chunk takes up isn't decompressed.
+		let mut entries = vec![None; REGION_CHUNKS];
+		entries[region_index(coord)] = Some(compress(&vec![0; storage.max_stored_len() + 1]));
+		std::fs::write(&path, storage.write_region(&entries)).unwrap();
+		assert!(matches!(
+			storage.load_chunk(coord),
+			Err(RegionFileError::CorruptChunk(_, _, "failed to decompress"))
+		));
+
+		// Lengths in the header are checked before anything is read.
+		let mut file = storage.write_region(&vec![None; REGION_CHUNKS]);
+		let entry = INDEX_START + region_index(coord) * 8;
+		file[entry..entry + 4].copy_from_slice(&(HEADER_LEN as u32).to_le_bytes());
+		file[entry + 4..entry + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+		std::fs::write(&path, &file).unwrap();
+		assert!(matches!(
+			storage.load_chunk(coord),
+			Err(RegionFileError::CorruptChunk(_, _, "entry is too long"))
+		));
+		file[entry + 4..entry + 8].copy_from_slice(&16u32.to_le_bytes());
+		std::fs::write(&path, &file).unwrap();
+		assert!(matches!(
+			storage.load_chunk(coord),
+			Err(RegionFileError::CorruptChunk(
+				_,
+				_,
+				"entry is past the end of the file"
+			))
+		));
+	}
+
+	#[test]
+	fn load_tiles_by_name() {
+		let dir = TestDir::new("region-tile-names");
+		let mut saved_tiles = TileRegistry::default();
+		let stone = saved_tiles.register_tile("stone".into());
+		let dirt = saved_tiles.register_tile("dirt".into());
+		let saved = RegionStorage::new(dir.path(), edge(), Arc::new(saved_tiles));
+		let mut dirt_chunk = Chunk::new(ChunkCoord::new(0, 0, 0), edge());
+		dirt_chunk.fill(dirt);
+		let mut stone_chunk = Chunk::new(ChunkCoord::new(1, 0, 0), edge());
+		stone_chunk
+			.set_tile(LocalTileCoord::new(0, 0, 0), stone)
+			.unwrap();
+		saved.save_chunks(vec![&dirt_chunk, &stone_chunk]).unwrap();
+		let mut unregistered = Chunk::new(ChunkCoord::new(2, 0, 0), edge());
+		unregistered.fill(TileId::new(3));
+		assert!(matches!(
+			saved.save_chunks(vec![&unregistered]),
+			Err(RegionFileError::UnnamedTile(_, _))
+		));
+
+		// The same names get other IDs once the tiles are registered differently.
+		let mut loaded_tiles = TileRegistry::default();
+		let loaded_dirt = loaded_tiles.register_tile("dirt".into());
+		let loaded = RegionStorage::new(dir.path(), edge(), Arc::new(loaded_tiles));
+		let chunk = loaded.load_chunk(dirt_chunk.location()).unwrap().unwrap();
+		assert_eq!(
+			chunk.get_tile(LocalTileCoord::new(3, 2, 1)),
+			Some(loaded_dirt)
+		);
+		match loaded.load_chunk(stone_chunk.location()) {
+			Err(RegionFileError::UnknownTile(_, coord, name)) => {
+				assert_eq!((coord, name.as_str()), (stone_chunk.location(), "stone"))
+			}
+			other => panic!("loaded a chunk of an unknown tile: {:?}", other),
+		}
+	}
+}
@@ -0,0 +1,392 @@
+use crate::game_data::tiles::TileId;
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::ops::{Deref, DerefMut};
+
+type Word = u64;
+const WORD_BITS: usize = 64;
+
+/// Tile storage for a chunk, a per-chunk palette of the distinct tiles in it and a bit-packed array
+/// of indices into that palette.  The index width grows as more distinct tiles get set, and a chunk
+/// made of only a single tile type doesn't store an index array at all.  Borrowing the tiles
+/// mutably unpacks them into a flat array, which is packed again once the borrow ends.
+#[derive(Clone)]
+pub struct PalettedTileStorage {
+	len: usize,
+	storage: Storage,
+}
+
+#[derive(Clone)]
+enum Storage {
+	Single(TileId),
+	Packed(PackedTiles),
+}
+
+#[derive(Clone)]
+struct PackedTiles {
+	palette: Vec<TileId>,
+	bits_per_entry: u8,
+	words: Vec<Word>,
+}
+
+/// Memory use of a `PalettedTileStorage`, see `naive_heap_bytes` for what a flat array would use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileStorageStats {
+	pub tile_count: usize,
+	pub palette_len: usize,
+	pub bits_per_entry: u8,
+	pub heap_bytes: usize,
+}
+
+impl TileStorageStats {
+	pub fn naive_heap_bytes(&self) -> usize {
+		self.tile_count * size_of::<TileId>()
+	}
+}
+
+impl PackedTiles {
+	fn new(len: usize, bits_per_entry: u8, palette: Vec<TileId>) -> Self {
+		let entries_per_word = WORD_BITS / bits_per_entry as usize;
+		let word_count = len.div_ceil(entries_per_word);
+		PackedTiles {
+			palette,
+			bits_per_entry,
+			words: vec![0; word_count],
+		}
+	}
+
+	fn bits_needed(palette_len: usize) -> u8 {
+		let mut bits = 1;
+		while (1usize << bits) < palette_len {
+			bits += 1;
+		}
+		bits
+	}
+
+	fn locate(&self, index: usize) -> (usize, usize) {
+		let entries_per_word = WORD_BITS / self.bits_per_entry as usize;
+		(
+			index / entries_per_word,
+			(index % entries_per_word) * self.bits_per_entry as usize,
+		)
+	}
+
+	fn mask(&self) -> Word {
+		(1 << self.bits_per_entry) - 1
+	}
+
+	fn get_entry(&self, index: usize) -> usize {
+		let (word, shift) = self.locate(index);
+		((self.words[word] >> shift) & self.mask()) as usize
+	}
+
+	fn set_entry(&mut self, index: usize, entry: usize) {
+		let (word, shift) = self.locate(index);
+		let mask = self.mask();
+		let word = &mut self.words[word];
+		*word = (*word & !(mask << shift)) | ((entry as Word & mask) << shift);
+	}
+
+	fn get(&self, index: usize) -> TileId {
+		self.palette[self.get_entry(index)]
+	}
+
+	fn palette_entry_or_insert(&mut self, len: usize, tile: TileId) -> usize {
+		if let Some(entry) = self.palette.iter().position(|&t| t == tile) {
+			return entry;
+		}
+		if self.palette.len() >= 1 << self.bits_per_entry {
+			self.repack(len, self.bits_per_entry + 1);
+		}
+		self.palette.push(tile);
+		self.palette.len() - 1
+	}
+
+	fn repack(&mut self, len: usize, bits_per_entry: u8) {
+		let mut packed = PackedTiles::new(len, bits_per_entry, std::mem::take(&mut self.palette));
+		for index in 0..len {
+			packed.set_entry(index, self.get_entry(index));
+		}
+		*self = packed;
+	}
+
+	fn heap_bytes(&self) -> usize {
+		self.palette.capacity() * size_of::<TileId>() + self.words.capacity() * size_of::<Word>()
+	}
+}
+
+impl PalettedTileStorage {
+	/// Creates storage for `len` tiles that are all set to `tile`.
+	pub fn new(len: usize, tile: TileId) -> Self {
+		PalettedTileStorage {
+			len,
+			storage: Storage::Single(tile),
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// Returns the single tile this storage holds if it is in its homogeneous form.
+	pub fn as_single(&self) -> Option<TileId> {
+		match &self.storage {
+			Storage::Single(tile) => Some(*tile),
+			Storage::Packed(_) => None,
+		}
+	}
+
+	pub fn get(&self, index: usize) -> Option<TileId> {
+		if index >= self.len {
+			return None;
+		}
+		match &self.storage {
+			Storage::Single(tile) => Some(*tile),
+			Storage::Packed(packed) => Some(packed.get(index)),
+		}
+	}
+
+	/// Sets the tile at the index, returning the tile that was previously there, or `None` and
+	/// doing nothing if the index is out of range.
+	pub fn set(&mut self, index: usize, tile: TileId) -> Option<TileId> {
+		if index >= self.len {
+			return None;
+		}
+		let len = self.len;
+		match &mut self.storage {
+			Storage::Single(existing) => {
+				let existing = *existing;
+				if existing != tile {
+					let mut packed = PackedTiles::new(len, 1, vec![existing, tile]);
+					packed.set_entry(index, 1);
+					self.storage = Storage::Packed(packed);
+				}
+				Some(existing)
+			}
+			Storage::Packed(packed) => {
+				let previous = packed.get(index);
+				if previous != tile {
+					let entry = packed.palette_entry_or_insert(len, tile);
+					packed.set_entry(index, entry);
+				}
+				Some(previous)
+			}
+		}
+	}
+
+	/// Sets every tile to the given tile, this drops back to the single-value form.
+	pub fn fill(&mut self, tile: TileId) {
+		self.storage = Storage::Single(tile);
+	}
+
+	/// The palette entries are only ever added to while setting tiles, this drops any entries that
+	/// are no longer used and shrinks the index width to match, or drops back to the single-value
+	/// form if only one tile type remains.
+	pub fn compact(&mut self) {
+		if let Storage::Single(_) = self.storage {
+			return;
+		}
+		self.storage = Self::pack(self.len, self.iter());
+	}
+
+	/// The smallest storage for the `len` tiles, which are iterated twice.
+	fn pack(len: usize, tiles: impl Iterator<Item = TileId> + Clone) -> Storage {
+		let mut remap = HashMap::new();
+		let mut palette = Vec::new();
+		for tile in tiles.clone() {
+			remap.entry(tile).or_insert_with(|| {
+				palette.push(tile);
+				palette.len() - 1
+			});
+		}
+		if palette.len() <= 1 {
+			return Storage::Single(palette.first().copied().unwrap_or_default());
+		}
+		let mut packed = PackedTiles::new(len, PackedTiles::bits_needed(palette.len()), palette);
+		for (index, tile) in tiles.enumerate() {
+			packed.set_entry(index, remap[&tile]);
+		}
+		Storage::Packed(packed)
+	}
+
+	/// Unpacks the tiles into a flat array so they can be modified in place, see `TilesMut`.
+	pub fn tiles_mut(&mut self) -> TilesMut<'_> {
+		TilesMut {
+			tiles: self.iter().collect(),
+			storage: self,
+		}
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = TileId> + Clone + '_ {
+		(0..self.len).map(move |index| match &self.storage {
+			Storage::Single(tile) => *tile,
+			Storage::Packed(packed) => packed.get(index),
+		})
+	}
+
+	pub fn stats(&self) -> TileStorageStats {
+		match &self.storage {
+			Storage::Single(_) => TileStorageStats {
+				tile_count: self.len,
+				palette_len: 1,
+				bits_per_entry: 0,
+				heap_bytes: 0,
+			},
+			Storage::Packed(packed) => TileStorageStats {
+				tile_count: self.len,
+				palette_len: packed.palette.len(),
+				bits_per_entry: packed.bits_per_entry,
+				heap_bytes: packed.heap_bytes(),
+			},
+		}
+	}
+}
+
+/// The tiles of a `PalettedTileStorage` unpacked into a flat array.  They are packed back into the
+/// storage when this is dropped, unless none of them changed and the storage is left as it was.
+pub struct TilesMut<'a> {
+	storage: &'a mut PalettedTileStorage,
+	tiles: Vec<TileId>,
+}
+
+impl<'a> TilesMut<'a> {
+	/// Packs the tiles back into the storage right away instead of on drop, returns whether any of
+	/// them changed.  The tiles are empty afterwards.
+	pub fn repack(&mut self) -> bool {
+		let tiles = std::mem::take(&mut self.tiles);
+		if tiles.is_empty() || tiles.iter().copied().eq(self.storage.iter()) {
+			return false;
+		}
+		self.storage.storage = PalettedTileStorage::pack(self.storage.len, tiles.into_iter());
+		true
+	}
+}
+
+impl<'a> Deref for TilesMut<'a> {
+	type Target = [TileId];
+
+	fn deref(&self) -> &[TileId] {
+		&self.tiles
+	}
+}
+
+impl<'a> DerefMut for TilesMut<'a> {
+	fn deref_mut(&mut self) -> &mut [TileId] {
+		&mut self.tiles
+	}
+}
+
+impl<'a> Drop for TilesMut<'a> {
+	fn drop(&mut self) {
+		self.repack();
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::chunks::tile_storage::PalettedTileStorage;
+	use crate::game_data::tiles::TileId;
+
+	#[test]
+	fn single_value_form() {
+		let mut storage = PalettedTileStorage::new(4096, TileId::AIR);
+		assert_eq!(storage.as_single(), Some(TileId::AIR));
+		assert_eq!(storage.get(4095), Some(TileId::AIR));
+		assert_eq!(storage.get(4096), None);
+		assert_eq!(storage.set(4096, TileId::new(1)), None);
+		assert_eq!(storage.set(0, TileId::AIR), Some(TileId::AIR));
+		assert_eq!(storage.as_single(), Some(TileId::AIR));
+		assert_eq!(storage.stats().heap_bytes, 0);
+		assert_eq!(storage.stats().naive_heap_bytes(), 8192);
+	}
+
+	#[test]
+	fn grows_bit_width() {
+		let len = 32 * 32 * 32;
+		let mut storage = PalettedTileStorage::new(len, TileId::AIR);
+		assert_eq!(storage.set(7, TileId::new(1)), Some(TileId::AIR));
+		assert_eq!(storage.stats().bits_per_entry, 1);
+		assert_eq!(storage.stats().palette_len, 2);
+		assert_eq!(storage.set(8, TileId::new(2)), Some(TileId::AIR));
+		assert_eq!(storage.stats().bits_per_entry, 2);
+		for index in 0..1000 {
+			storage.set(index * 3, TileId::new(index as u16 % 300));
+		}
+		let stats = storage.stats();
+		assert_eq!(stats.palette_len, 300);
+		assert_eq!(stats.bits_per_entry, 9);
+		assert!(stats.heap_bytes < stats.naive_heap_bytes());
+		for index in 0..1000 {
+			assert_eq!(
+				storage.get(index * 3),
+				Some(TileId::new(index as u16 % 300))
+			);
+		}
+		assert_eq!(storage.get(7), Some(TileId::new(1)));
+		assert_eq!(storage.get(8), Some(TileId::new(2)));
+		assert_eq!(storage.get(len - 1), Some(TileId::AIR));
+	}
+
+	#[test]
+	fn compact_and_fill() {
+		let mut storage = PalettedTileStorage::new(512, TileId::AIR);
+		for index in 0..5 {
+			storage.set(index, TileId::new(index as u16 + 1));
+		}
+		assert_eq!(storage.stats().bits_per_entry, 3);
+		for index in 1..5 {
+			storage.set(index, TileId::new(1));
+		}
+		storage.compact();
+		assert_eq!(storage.stats().palette_len, 2);
+		assert_eq!(storage.stats().bits_per_entry, 1);
+		assert_eq!(storage.get(0), Some(TileId::new(1)));
+		assert_eq!(storage.get(4), Some(TileId::new(1)));
+		assert_eq!(storage.get(5), Some(TileId::AIR));
+		assert_eq!(storage.iter().filter(|&t| t == TileId::new(1)).count(), 5);
+		storage.fill(TileId::new(2));
+		assert_eq!(storage.as_single(), Some(TileId::new(2)));
+		storage.set(3, TileId::new(3));
+		storage.set(3, TileId::new(2));
+		storage.compact();
+		assert_eq!(storage.as_single(), Some(TileId::new(2)));
+	}
+
+	#[test]
+	fn unpack_and_repack() {
+		let mut storage = PalettedTileStorage::new(64, TileId::AIR);
+		storage.set(1, TileId::new(1));
+		let mut tiles = storage.tiles_mut();
+		assert_eq!(tiles.len(), 64);
+		assert_eq!(tiles[1], TileId::new(1));
+		tiles[2] = TileId::new(2);
+		tiles[3] = TileId::new(3);
+		drop(tiles);
+		assert_eq!(storage.stats().palette_len, 4);
+		assert_eq!(storage.stats().bits_per_entry, 2);
+		assert_eq!(storage.get(1), Some(TileId::new(1)));
+		assert_eq!(storage.get(2), Some(TileId::new(2)));
+		assert_eq!(storage.get(3), Some(TileId::new(3)));
+
+		// Tiles that didn't change leave the storage alone, unused palette entries and all.
+		storage.set(3, TileId::AIR);
+		assert!(!storage.tiles_mut().repack());
+		assert_eq!(storage.stats().palette_len, 4);
+		let mut tiles = storage.tiles_mut();
+		tiles[2] = TileId::AIR;
+		assert!(tiles.repack());
+		assert!(tiles.is_empty());
+		drop(tiles);
+		assert_eq!(storage.stats().palette_len, 2);
+		assert_eq!(storage.get(2), Some(TileId::AIR));
+		storage
+			.tiles_mut()
+			.iter_mut()
+			.for_each(|t| *t = TileId::new(5));
+		assert_eq!(storage.as_single(), Some(TileId::new(5)));
+	}
+}
@@ -0,0 +1,64 @@
+//! Decompressing data read from save files, which could be crafted to inflate to any size.
+
+use miniz_oxide::inflate::core::inflate_flags::{
+	TINFL_FLAG_PARSE_ZLIB_HEADER, TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+};
+use miniz_oxide::inflate::core::{decompress, DecompressorOxide};
+use miniz_oxide::inflate::TINFLStatus;
+
+/// Decompresses zlib data that inflates to at most `max_len` bytes.  The output grows as it is
+/// written like with `decompress_to_vec_zlib`, but never past `max_len` and one more byte that
+/// tells data that is too long apart from data that fits exactly.  Returns `None` if the data is
+/// corrupt or too long.
+pub fn inflate_zlib(compressed: &[u8], max_len: usize) -> Option<Vec<u8>> {
+	let limit = max_len.saturating_add(1);
+	let flags = TINFL_FLAG_PARSE_ZLIB_HEADER | TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+	let mut decompressor = Box::<DecompressorOxide>::default();
+	let mut out = vec![0; compressed.len().saturating_mul(2).clamp(1, limit)];
+	let (mut in_pos, mut out_pos) = (0, 0);
+	loop {
+		let (status, read, written) = decompress(
+			&mut decompressor,
+			&compressed[in_pos..],
+			&mut out,
+			out_pos,
+			flags,
+		);
+		in_pos += read;
+		out_pos += written;
+		match status {
+			TINFLStatus::Done if out_pos <= max_len => {
+				out.truncate(out_pos);
+				return Some(out);
+			}
+			TINFLStatus::HasMoreOutput if out.len() < limit => {
+				let len = out.len().saturating_mul(2).min(limit);
+				out.resize(len, 0);
+			}
+			_ => return None,
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::inflate::inflate_zlib;
+	use miniz_oxide::deflate::compress_to_vec_zlib;
+
+	#[test]
+	fn inflate_up_to_the_limit() {
+		let data: Vec<u8> = (0..10_000u32).map(|i| (i % 7) as u8).collect();
+		let compressed = compress_to_vec_zlib(&data, 6);
+		assert_eq!(inflate_zlib(&compressed, data.len()), Some(data.clone()));
+		assert_eq!(inflate_zlib(&compressed, usize::MAX), Some(data.clone()));
+		assert_eq!(inflate_zlib(&compressed, data.len() - 1), None);
+		assert_eq!(
+			inflate_zlib(&compressed[..compressed.len() / 2], data.len()),
+			None
+		);
+		assert_eq!(
+			inflate_zlib(&compress_to_vec_zlib(&[], 6), 0),
+			Some(Vec::new())
+		);
+	}
+}
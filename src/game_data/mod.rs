@@ -0,0 +1,7 @@
+pub mod atomic_file;
+pub mod chunks;
+pub mod inflate;
+#[cfg(test)]
+pub(crate) mod test_dir;
+pub mod tiles;
+pub mod worlds;
@@ -0,0 +1,26 @@
+//! Scratch directories for tests that touch the file system.
+
+use std::path::{Path, PathBuf};
+
+/// A fresh directory in the system temp directory, removed again when dropped.  The name only has
+/// to be unique among the tests.
+pub(crate) struct TestDir(PathBuf);
+
+impl TestDir {
+	pub(crate) fn new(name: &str) -> Self {
+		let path =
+			std::env::temp_dir().join(format!("mechaenetia-test-{}-{}", name, std::process::id()));
+		let _ = std::fs::remove_dir_all(&path);
+		TestDir(path)
+	}
+
+	pub(crate) fn path(&self) -> &Path {
+		&self.0
+	}
+}
+
+impl Drop for TestDir {
+	fn drop(&mut self) {
+		let _ = std::fs::remove_dir_all(&self.0);
+	}
+}
@@ -0,0 +1,120 @@
+use indexmap::set::IndexSet;
+use smol_str::SmolStr;
+
+pub type TileIdType = u16;
+
+/// A compact handle to a tile type registered in a `TileRegistry`, this is what chunks store.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TileId(TileIdType);
+
+impl TileId {
+	/// The empty tile, always registered first in every `TileRegistry`.
+	pub const AIR: TileId = TileId(0);
+
+	pub const fn new(id: TileIdType) -> Self {
+		TileId(id)
+	}
+
+	pub fn get(&self) -> TileIdType {
+		self.0
+	}
+}
+
+/// Maps tile names to their `TileId`, the ID is the insertion index so registration order matters
+/// for any saved data that holds raw `TileId`s.
+#[derive(Debug, Clone)]
+pub struct TileRegistry {
+	tiles: IndexSet<SmolStr>,
+}
+
+impl Default for TileRegistry {
+	fn default() -> Self {
+		let mut tiles = IndexSet::default();
+		tiles.insert(SmolStr::new_inline("air"));
+		TileRegistry { tiles }
+	}
+}
+
+impl TileRegistry {
+	/// Registers a tile by name, returning the existing `TileId` if it is already registered.
+	pub fn register_tile(&mut self, name: SmolStr) -> TileId {
+		if let Some(index) = self.tiles.get_index_of(&name) {
+			return TileId(index as TileIdType);
+		}
+		assert!(
+			self.tiles.len() <= TileIdType::MAX as usize,
+			"too many tiles registered for the `TileId` type"
+		);
+		let (index, _inserted) = self.tiles.insert_full(name);
+		TileId(index as TileIdType)
+	}
+
+	pub fn len(&self) -> usize {
+		self.tiles.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.tiles.is_empty()
+	}
+
+	pub fn contains(&self, tile_id: TileId) -> bool {
+		(tile_id.0 as usize) < self.tiles.len()
+	}
+
+	pub fn get_tile_name(&self, tile_id: TileId) -> &SmolStr {
+		self.tiles
+			.get_index(tile_id.0 as usize)
+			.expect("invalid TileId for this TileRegistry")
+	}
+
+	pub fn get_tile_id_by_name(&self, name: &str) -> Option<TileId> {
+		self.tiles
+			.get_index_of(name)
+			.map(|index| TileId(index as TileIdType))
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (TileId, &SmolStr)> {
+		self.tiles
+			.iter()
+			.enumerate()
+			.map(|(index, name)| (TileId(index as TileIdType), name))
+	}
+
+	/// A registry of `count` tiles, air and then tiles named after their ID, for tests that make up
+	/// their `TileId`s.
+	#[cfg(test)]
+	pub fn numbered(count: usize) -> Self {
+		let mut registry = TileRegistry::default();
+		for id in 1..count {
+			registry.register_tile(SmolStr::new(id.to_string()));
+		}
+		registry
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::tiles::{TileId, TileRegistry};
+
+	#[test]
+	fn register_tiles() {
+		let mut registry = TileRegistry::default();
+		assert_eq!(registry.len(), 1);
+		assert_eq!(registry.get_tile_id_by_name("air"), Some(TileId::AIR));
+		assert_eq!(TileId::default(), TileId::AIR);
+		let stone = registry.register_tile("stone".into());
+		let dirt = registry.register_tile("dirt".into());
+		assert_eq!(stone, TileId::new(1));
+		assert_eq!(dirt, TileId::new(2));
+		assert_eq!(registry.register_tile("stone".into()), stone);
+		assert_eq!(registry.len(), 3);
+		assert_eq!(registry.get_tile_name(dirt), "dirt");
+		assert_eq!(registry.get_tile_id_by_name("missing"), None);
+		assert!(registry.contains(dirt));
+		assert!(!registry.contains(TileId::new(3)));
+		assert_eq!(
+			registry.iter().map(|(id, _name)| id).collect::<Vec<_>>(),
+			vec![TileId::AIR, stone, dirt]
+		);
+	}
+}
@@ -0,0 +1,115 @@
+pub mod world;
+
+use world::{world_generator::WorldGenerator, World};
+
+use indexmap::map::IndexMap;
+use smol_str::SmolStr;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct WorldId(usize);
+
+pub struct Worlds {
+	worlds: IndexMap<SmolStr, World>,
+}
+
+impl Default for Worlds {
+	fn default() -> Self {
+		Worlds {
+			worlds: IndexMap::default(),
+		}
+	}
+}
+
+impl Worlds {
+	pub fn create_world(&mut self, name: SmolStr, generator: Box<dyn WorldGenerator>) -> WorldId {
+		let world = World::new(WorldId(self.worlds.len()), name.clone(), generator);
+		let (index, inserted) = self.worlds.insert_full(name, world);
+		assert!(inserted.is_none(), "world already inserted somehow");
+		WorldId(index)
+	}
+
+	pub fn len(&self) -> usize {
+		self.worlds.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.worlds.is_empty()
+	}
+
+	pub fn get_world(&self, world_id: WorldId) -> &World {
+		self.worlds
+			.get_index(world_id.0)
+			.expect("invalid WorldID for this Worlds")
+			.1
+	}
+
+	pub fn get_world_mut(&mut self, world_id: WorldId) -> &mut World {
+		self.worlds
+			.get_index_mut(world_id.0)
+			.expect("invalid WorldID for this Worlds")
+			.1
+	}
+
+	pub fn get_world_name(&self, world_id: WorldId) -> &SmolStr {
+		self.worlds
+			.get_index(world_id.0)
+			.expect("invalid WorldID for this Worlds")
+			.0
+	}
+
+	pub fn get_world_id_by_name(&self, name: &SmolStr) -> Option<WorldId> {
+		self.worlds.get_full(name).map(|t| WorldId(t.0))
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &World> + '_ {
+		self.worlds.values()
+	}
+
+	pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut World> + '_ {
+		self.worlds.values_mut()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::worlds::world::world_generator::LayeredWorldGenerator;
+	use crate::game_data::worlds::Worlds;
+
+	#[test]
+	fn create_and_lookup_worlds() {
+		let mut worlds = Worlds::default();
+		assert!(worlds.is_empty());
+		let overworld = worlds.create_world(
+			"overworld".into(),
+			Box::new(LayeredWorldGenerator::new(0, vec![])),
+		);
+		let nether = worlds.create_world(
+			"nether".into(),
+			Box::new(LayeredWorldGenerator::new(0, vec![])),
+		);
+		assert_ne!(overworld, nether);
+		assert_eq!(worlds.len(), 2);
+		assert_eq!(worlds.get_world_name(overworld), "overworld");
+		assert_eq!(worlds.get_world_name(nether), "nether");
+		assert_eq!(worlds.get_world(nether).id(), nether);
+		assert_eq!(worlds.get_world_mut(overworld).name(), "overworld");
+		assert_eq!(worlds.get_world_id_by_name(&"nether".into()), Some(nether));
+		assert_eq!(worlds.get_world_id_by_name(&"missing".into()), None);
+		let names: Vec<&str> = worlds.iter().map(|world| world.name().as_str()).collect();
+		assert_eq!(names, ["overworld", "nether"]);
+	}
+
+	#[test]
+	#[should_panic(expected = "world already inserted somehow")]
+	fn duplicate_world_name() {
+		let mut worlds = Worlds::default();
+		worlds.create_world(
+			"test".into(),
+			Box::new(LayeredWorldGenerator::new(0, vec![])),
+		);
+		worlds.create_world(
+			"test".into(),
+			Box::new(LayeredWorldGenerator::new(0, vec![])),
+		);
+	}
+}
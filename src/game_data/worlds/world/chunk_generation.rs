@@ -0,0 +1,331 @@
+use crate::game_data::chunks::chunk::{Chunk, ChunkCoord, ChunkCoordType};
+use crate::game_data::chunks::Chunks;
+use crate::game_data::worlds::world::staged_generation::ChunkSource;
+use crate::game_data::worlds::world::world_generator::{generate_chunk_terrain, WorldGenerator};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Generates the terrain of chunks on the rayon thread pool, see `generate_chunk_terrain`.
+/// Requests are queued and handed to the pool closest to the focus points first as slots free up,
+/// the finished chunks are picked up by `integrate`.
+///
+/// A generator that panics fails only the chunk it was generating, which is then listed by
+/// `take_failed`.
+pub struct ChunkGenerationQueue {
+	generator: Arc<dyn WorldGenerator>,
+	max_in_flight: usize,
+	queued: HashSet<ChunkCoord>,
+	/// The queued chunks by their `priority` for `focus`, nearest on top.  Chunks that were
+	/// cancelled or dispatched since they were pushed are left in and skipped when popped.
+	by_priority: BinaryHeap<Reverse<Priority>>,
+	/// The focus points of the last `dispatch`, the queue is only reordered when they change.
+	focus: Vec<ChunkCoord>,
+	in_flight: HashMap<ChunkCoord, Arc<AtomicBool>>,
+	/// The generated chunks, or the coordinate of those whose generation panicked.
+	finished: Arc<Mutex<Vec<Result<Chunk, ChunkCoord>>>>,
+	failed: Vec<ChunkCoord>,
+}
+
+impl ChunkGenerationQueue {
+	pub fn new(generator: Arc<dyn WorldGenerator>) -> Self {
+		Self {
+			generator,
+			max_in_flight: rayon::current_num_threads() * 2,
+			queued: HashSet::default(),
+			by_priority: BinaryHeap::default(),
+			focus: Vec::new(),
+			in_flight: HashMap::default(),
+			finished: Arc::default(),
+			failed: Vec::new(),
+		}
+	}
+
+	/// The most chunks that will be generating on the thread pool at once.
+	pub fn set_max_in_flight(&mut self, max_in_flight: usize) {
+		self.max_in_flight = max_in_flight.max(1);
+	}
+
+	/// Queues the chunk for generation, returns false if it was already queued or generating.
+	pub fn request(&mut self, coord: ChunkCoord) -> bool {
+		if self.in_flight.contains_key(&coord) || !self.queued.insert(coord) {
+			return false;
+		}
+		self.by_priority.push(Reverse(priority(&self.focus, coord)));
+		true
+	}
+
+	/// Drops the request for the chunk, a chunk that is already generating is discarded once done.
+	/// Returns false if the chunk wasn't requested.
+	pub fn cancel(&mut self, coord: ChunkCoord) -> bool {
+		if let Some(cancelled) = self.in_flight.remove(&coord) {
+			cancelled.store(true, Ordering::Relaxed);
+			true
+		} else {
+			self.queued.remove(&coord)
+		}
+	}
+
+	pub fn is_requested(&self, coord: ChunkCoord) -> bool {
+		self.queued.contains(&coord) || self.in_flight.contains_key(&coord)
+	}
+
+	/// The amount of chunks that are queued or generating.
+	pub fn len(&self) -> usize {
+		self.queued.len() + self.in_flight.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.queued.is_empty() && self.in_flight.is_empty()
+	}
+
+	/// Hands queued chunks to the thread pool until `max_in_flight` are generating, those nearest to
+	/// any of the focus points go first.
+	pub fn dispatch(&mut self, focus: &[ChunkCoord]) {
+		let slots = self.max_in_flight.saturating_sub(self.in_flight.len());
+		if slots == 0 || self.queued.is_empty() {
+			return;
+		}
+		// Skipped entries pile up as chunks are cancelled, rebuilding drops them.
+		if self.focus != focus || self.by_priority.len() > self.queued.len() * 2 {
+			self.focus = focus.to_vec();
+			let focus = &self.focus;
+			self.by_priority = self
+				.queued
+				.iter()
+				.map(|&coord| Reverse(priority(focus, coord)))
+				.collect();
+		}
+		let mut started = 0;
+		while started < slots {
+			let coord = match self.by_priority.pop() {
+				Some(Reverse((_distance, x, y, z))) => ChunkCoord::new(x, y, z),
+				None => break,
+			};
+			if !self.queued.remove(&coord) {
+				continue;
+			}
+			started += 1;
+			let cancelled = Arc::new(AtomicBool::new(false));
+			self.in_flight.insert(coord, cancelled.clone());
+			let generator = self.generator.clone();
+			let finished = self.finished.clone();
+			rayon::spawn(move || {
+				if cancelled.load(Ordering::Relaxed) {
+					return;
+				}
+				// Unwinding out of a rayon job aborts the process.
+				let chunk = std::panic::catch_unwind(AssertUnwindSafe(|| {
+					generate_chunk_terrain(&*generator, coord)
+				}))
+				.map_err(|_panic| coord);
+				finished
+					.lock()
+					.expect("chunk generation thread panicked")
+					.push(chunk);
+			});
+		}
+	}
+
+	/// Moves up to `max` finished chunks into `chunks`, returning how many were added.  Chunks that
+	/// were cancelled or got loaded some other way in the meantime are dropped.
+	pub fn integrate(&mut self, chunks: &mut Chunks, max: usize) -> usize {
+		let done: Vec<Result<Chunk, ChunkCoord>> = {
+			let mut finished = self
+				.finished
+				.lock()
+				.expect("chunk generation thread panicked");
+			let count = finished.len().min(max);
+			finished.drain(..count).collect()
+		};
+		let mut integrated = 0;
+		for chunk in done {
+			let chunk = match chunk {
+				Ok(chunk) => chunk,
+				Err(coord) => {
+					if self.in_flight.remove(&coord).is_some() {
+						self.failed.push(coord);
+					}
+					continue;
+				}
+			};
+			let coord = chunk.location();
+			if self.in_flight.remove(&coord).is_some() && !chunks.contains_chunk(&coord) {
+				chunks.insert_chunk(chunk);
+				integrated += 1;
+			}
+		}
+		integrated
+	}
+
+	/// The chunks picked up by `integrate` whose generation panicked since the last call, they are
+	/// no longer requested.
+	pub fn take_failed(&mut self) -> Vec<ChunkCoord> {
+		std::mem::take(&mut self.failed)
+	}
+}
+
+/// The squared distance of the chunk to the nearest focus point, then its coordinate to break ties.
+type Priority = (i64, ChunkCoordType, ChunkCoordType, ChunkCoordType);
+
+fn priority(focus: &[ChunkCoord], coord: ChunkCoord) -> Priority {
+	let distance = focus
+		.iter()
+		.map(|&f| (coord - f).length_squared())
+		.min()
+		.unwrap_or(0);
+	(distance, coord.x(), coord.y(), coord.z())
+}
+
+/// Requests the chunks from the queue, they can be integrated once generated.
+impl ChunkSource for ChunkGenerationQueue {
+	fn provide_chunk(
+		&mut self,
+		_generator: &dyn WorldGenerator,
+		coord: ChunkCoord,
+	) -> Option<Chunk> {
+		self.request(coord);
+		None
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::chunks::chunk::{Chunk, ChunkCoord, LocalTileCoord};
+	use crate::game_data::chunks::Chunks;
+	use crate::game_data::tiles::TileId;
+	use crate::game_data::worlds::world::chunk_generation::ChunkGenerationQueue;
+	use crate::game_data::worlds::world::seed::WorldSeed;
+	use crate::game_data::worlds::world::world_generator::WorldGenerator;
+	use std::num::NonZeroU8;
+	use std::sync::Arc;
+	use std::time::{Duration, Instant};
+
+	struct StoneGenerator;
+
+	impl WorldGenerator for StoneGenerator {
+		fn get_chunk_edge_len(&self) -> NonZeroU8 {
+			NonZeroU8::new(4).unwrap()
+		}
+
+		fn seed(&self) -> WorldSeed {
+			0
+		}
+
+		fn generate_tiles_in_chunk(&self, chunk: &mut Chunk) {
+			chunk.fill(TileId::new(1));
+		}
+	}
+
+	/// Panics on every chunk below the ground.
+	struct FaultyGenerator;
+
+	impl WorldGenerator for FaultyGenerator {
+		fn get_chunk_edge_len(&self) -> NonZeroU8 {
+			NonZeroU8::new(4).unwrap()
+		}
+
+		fn seed(&self) -> WorldSeed {
+			0
+		}
+
+		fn generate_tiles_in_chunk(&self, chunk: &mut Chunk) {
+			assert!(chunk.location().z() >= 0, "no ground to generate");
+			chunk.fill(TileId::new(1));
+		}
+	}
+
+	fn integrate_all(queue: &mut ChunkGenerationQueue, chunks: &mut Chunks) {
+		let deadline = Instant::now() + Duration::from_secs(10);
+		while !queue.is_empty() {
+			assert!(Instant::now() < deadline, "chunk generation timed out");
+			queue.dispatch(&[]);
+			queue.integrate(chunks, usize::MAX);
+			std::thread::sleep(Duration::from_millis(1));
+		}
+	}
+
+	#[test]
+	fn generate_in_background() {
+		let mut queue = ChunkGenerationQueue::new(Arc::new(StoneGenerator));
+		let mut chunks = Chunks::new(NonZeroU8::new(4).unwrap());
+		for x in -2..2 {
+			assert!(queue.request(ChunkCoord::new(x, 0, 0)));
+		}
+		assert!(!queue.request(ChunkCoord::new(0, 0, 0)));
+		assert_eq!(queue.len(), 4);
+		assert!(queue.cancel(ChunkCoord::new(-2, 0, 0)));
+		assert!(!queue.cancel(ChunkCoord::new(-2, 0, 0)));
+		integrate_all(&mut queue, &mut chunks);
+		assert_eq!(chunks.len(), 3);
+		assert!(!chunks.contains_chunk(&ChunkCoord::new(-2, 0, 0)));
+		let chunk = chunks.get_chunk(&ChunkCoord::new(1, 0, 0)).unwrap();
+		assert_eq!(
+			chunk.get_tile(LocalTileCoord::new(3, 3, 3)),
+			Some(TileId::new(1))
+		);
+	}
+
+	#[test]
+	fn dispatch_nearest_first() {
+		let mut queue = ChunkGenerationQueue::new(Arc::new(StoneGenerator));
+		queue.set_max_in_flight(1);
+		let mut chunks = Chunks::new(NonZeroU8::new(4).unwrap());
+		let far = ChunkCoord::new(10, 0, 0);
+		let near = ChunkCoord::new(0, 0, 2);
+		queue.request(far);
+		queue.request(near);
+		queue.dispatch(&[ChunkCoord::new(0, 0, 0)]);
+		assert!(queue.in_flight.contains_key(&near));
+		assert!(queue.queued.contains(&far));
+		let deadline = Instant::now() + Duration::from_secs(10);
+		while queue.integrate(&mut chunks, 1) == 0 {
+			assert!(Instant::now() < deadline, "chunk generation timed out");
+			std::thread::sleep(Duration::from_millis(1));
+		}
+		assert!(chunks.contains_chunk(&near));
+		assert!(!chunks.contains_chunk(&far));
+		integrate_all(&mut queue, &mut chunks);
+		assert!(chunks.contains_chunk(&far));
+	}
+
+	#[test]
+	fn reorder_when_the_focus_moves() {
+		let mut queue = ChunkGenerationQueue::new(Arc::new(StoneGenerator));
+		queue.set_max_in_flight(1);
+		let mut chunks = Chunks::new(NonZeroU8::new(4).unwrap());
+		let left = ChunkCoord::new(-5, 0, 0);
+		let middle = ChunkCoord::new(1, 0, 0);
+		let right = ChunkCoord::new(5, 0, 0);
+		for &coord in [left, middle, right].iter() {
+			queue.request(coord);
+		}
+		queue.cancel(middle);
+		queue.dispatch(&[middle]);
+		assert!(queue.in_flight.contains_key(&right));
+		queue.cancel(right);
+		queue.request(middle);
+		queue.dispatch(&[left]);
+		assert!(queue.in_flight.contains_key(&left));
+		assert!(queue.queued.contains(&middle));
+		integrate_all(&mut queue, &mut chunks);
+		assert!(chunks.contains_chunk(&middle));
+		assert!(!chunks.contains_chunk(&right));
+	}
+
+	#[test]
+	fn fail_chunks_whose_generation_panics() {
+		let mut queue = ChunkGenerationQueue::new(Arc::new(FaultyGenerator));
+		let mut chunks = Chunks::new(NonZeroU8::new(4).unwrap());
+		let (ground, sky) = (ChunkCoord::new(0, 0, -1), ChunkCoord::new(0, 0, 1));
+		queue.request(ground);
+		queue.request(sky);
+		integrate_all(&mut queue, &mut chunks);
+		assert!(chunks.contains_chunk(&sky));
+		assert!(!chunks.contains_chunk(&ground));
+		assert_eq!(queue.take_failed(), vec![ground]);
+		assert!(queue.take_failed().is_empty());
+	}
+}
@@ -0,0 +1,274 @@
+use crate::game_data::chunks::chunk::{Chunk, ChunkCoord};
+use crate::game_data::chunks::region_file::{EncodedChunk, RegionFileError, RegionStorage};
+use crate::game_data::chunks::Chunks;
+use std::sync::{Arc, Condvar, Mutex};
+
+/// Filled in by the writing thread once a batch is written.
+type WriteResult = Arc<(Mutex<Option<Result<usize, RegionFileError>>>, Condvar)>;
+
+/// Saves the dirty chunks of a world to its region storage.  Chunks are encoded on the calling
+/// thread and then compressed and written on a background thread, one batch at a time so a later
+/// save of a chunk never lands before an earlier one.
+///
+/// A batch still being written when the saver is dropped finishes on its own, but only `flush`
+/// makes sure everything is on disk.
+pub struct ChunkSaver {
+	storage: RegionStorage,
+	writing: Option<Write>,
+}
+
+/// The batch of chunks being written in the background.
+struct Write {
+	chunks: Vec<ChunkCoord>,
+	result: WriteResult,
+}
+
+impl ChunkSaver {
+	pub fn new(storage: RegionStorage) -> Self {
+		ChunkSaver {
+			storage,
+			writing: None,
+		}
+	}
+
+	pub fn storage(&self) -> &RegionStorage {
+		&self.storage
+	}
+
+	/// Whether a batch of chunks is still being written in the background.
+	pub fn is_writing(&self) -> bool {
+		match &self.writing {
+			Some(write) => lock(&write.result.0).is_none(),
+			None => false,
+		}
+	}
+
+	/// Whether the chunk is part of the batch written in the background, whose result wasn't picked
+	/// up yet.  Such a chunk has to stay loaded to be marked dirty again if the write fails.
+	pub fn is_saving_chunk(&self, coord: ChunkCoord) -> bool {
+		match &self.writing {
+			Some(write) => write.chunks.contains(&coord),
+			None => false,
+		}
+	}
+
+	/// Starts writing the dirty chunks of up to `max_regions` region files in the background once
+	/// the previous batch is done, the chunks count as saved right away.  Returns how many region
+	/// files were started, or the error of the previous batch, whose chunks are then dirty again.
+	///
+	/// Saving a chunk rewrites its whole region file, so the work is bounded by region files
+	/// rather than chunks, and every dirty chunk of a region goes into the same rewrite.
+	pub fn save_dirty(
+		&mut self,
+		chunks: &mut Chunks,
+		max_regions: usize,
+	) -> Result<usize, RegionFileError> {
+		if !self.collect(chunks, false)? {
+			return Ok(0);
+		}
+		let mut regions = Vec::new();
+		let mut batch = Vec::new();
+		for chunk in chunks.iter_chunks_mut().filter(|chunk| chunk.is_dirty()) {
+			let region = self.storage.region_path(chunk.location());
+			if !regions.contains(&region) {
+				if regions.len() == max_regions {
+					continue;
+				}
+				regions.push(region);
+			}
+			batch.extend(encode_if_dirty(chunk));
+		}
+		self.start_writing(batch);
+		Ok(regions.len())
+	}
+
+	/// Like `save_dirty` for only the given chunks, such as those about to be unloaded.
+	pub fn save_dirty_chunks(
+		&mut self,
+		chunks: &mut Chunks,
+		coords: &[ChunkCoord],
+	) -> Result<usize, RegionFileError> {
+		if !self.collect(chunks, false)? {
+			return Ok(0);
+		}
+		let mut batch = Vec::new();
+		for coord in coords {
+			if let Some(encoded) = chunks.get_chunk_mut(coord).and_then(encode_if_dirty) {
+				batch.push(encoded);
+			}
+		}
+		Ok(self.start_writing(batch))
+	}
+
+	/// Writes the batch on a new thread, returns how many chunks are in it.
+	fn start_writing(&mut self, batch: Vec<EncodedChunk>) -> usize {
+		if batch.is_empty() {
+			return 0;
+		}
+		let started = batch.len();
+		let storage = self.storage.clone();
+		let result = WriteResult::default();
+		self.writing = Some(Write {
+			chunks: batch.iter().map(EncodedChunk::location).collect(),
+			result: result.clone(),
+		});
+		std::thread::spawn(move || {
+			let saved = storage.save_encoded_chunks(batch);
+			let (result, done) = &*result;
+			*lock(result) = Some(saved);
+			done.notify_all();
+		});
+		started
+	}
+
+	/// Waits for the batch being written in the background, then writes every other dirty chunk on
+	/// the calling thread.  Returns how many chunks were written by the latter.
+	pub fn flush(&mut self, chunks: &mut Chunks) -> Result<usize, RegionFileError> {
+		self.collect(chunks, true)?;
+		let batch: Vec<EncodedChunk> = chunks
+			.iter_chunks_mut()
+			.filter_map(encode_if_dirty)
+			.collect();
+		let locations: Vec<ChunkCoord> = batch.iter().map(EncodedChunk::location).collect();
+		self.storage
+			.save_encoded_chunks(batch)
+			.map_err(|error| mark_dirty(chunks, locations, error))
+	}
+
+	/// Picks up the result of the batch being written, waiting for it if `wait`.  Returns whether
+	/// nothing is being written anymore.
+	fn collect(&mut self, chunks: &mut Chunks, wait: bool) -> Result<bool, RegionFileError> {
+		let result = match &self.writing {
+			Some(write) => {
+				let (result, done) = &*write.result;
+				let mut result = lock(result);
+				while wait && result.is_none() {
+					result = done.wait(result).unwrap_or_else(|e| e.into_inner());
+				}
+				match result.take() {
+					Some(result) => result,
+					None => return Ok(false),
+				}
+			}
+			None => return Ok(true),
+		};
+		let write = self.writing.take().expect("checked above");
+		result
+			.map(|_saved| true)
+			.map_err(|error| mark_dirty(chunks, write.chunks, error))
+	}
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+	mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Encodes the chunk and marks it as saved if it is dirty.
+fn encode_if_dirty(chunk: &mut Chunk) -> Option<EncodedChunk> {
+	if !chunk.is_dirty() {
+		return None;
+	}
+	let encoded = EncodedChunk::new(chunk);
+	chunk.mark_saved(chunk.revision());
+	Some(encoded)
+}
+
+/// Makes the chunks that failed to save dirty again, passing the error on.
+fn mark_dirty(
+	chunks: &mut Chunks,
+	locations: Vec<ChunkCoord>,
+	error: RegionFileError,
+) -> RegionFileError {
+	for coord in locations {
+		if let Some(chunk) = chunks.get_chunk_mut(&coord) {
+			chunk.mark_dirty();
+		}
+	}
+	error
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::chunks::chunk::{ChunkCoord, LocalTileCoord};
+	use crate::game_data::chunks::region_file::RegionStorage;
+	use crate::game_data::chunks::Chunks;
+	use crate::game_data::test_dir::TestDir;
+	use crate::game_data::tiles::{TileId, TileRegistry};
+	use crate::game_data::worlds::world::chunk_saving::ChunkSaver;
+	use std::num::NonZeroU8;
+	use std::sync::Arc;
+
+	fn dirty(chunks: &Chunks) -> usize {
+		chunks
+			.iter_chunks()
+			.filter(|chunk| chunk.is_dirty())
+			.count()
+	}
+
+	#[test]
+	fn save_dirty_chunks() {
+		let dir = TestDir::new("chunk-saver");
+		let edge = NonZeroU8::new(4).unwrap();
+		let storage = RegionStorage::new(dir.path(), edge, Arc::new(TileRegistry::numbered(3)));
+		let mut saver = ChunkSaver::new(storage.clone());
+		let mut chunks = Chunks::new(edge);
+		// Each in a region of its own.
+		let saved = [0, 8, 16].map(|x| ChunkCoord::new(x, 0, 0));
+		for &coord in &saved {
+			chunks.get_or_create_chunk(coord).0.fill(TileId::new(1));
+		}
+		chunks.get_or_create_chunk(ChunkCoord::new(0, 1, 0));
+		assert_eq!(dirty(&chunks), 3);
+
+		assert_eq!(saver.save_dirty(&mut chunks, 2).unwrap(), 2);
+		assert_eq!(dirty(&chunks), 1);
+		let saving = saved.iter().filter(|&&coord| saver.is_saving_chunk(coord));
+		assert_eq!(saving.count(), 2);
+		let tile = LocalTileCoord::new(1, 1, 1);
+		// One of those being written, changing it has to save it again.
+		let modified = *saved
+			.iter()
+			.find(|coord| !chunks.get_chunk(coord).unwrap().is_dirty())
+			.unwrap();
+		chunks
+			.get_chunk_mut(&modified)
+			.unwrap()
+			.set_tile(tile, TileId::new(2))
+			.unwrap();
+		assert_eq!(saver.flush(&mut chunks).unwrap(), 2);
+		assert!(!saver.is_writing());
+		assert_eq!(dirty(&chunks), 0);
+		assert_eq!(saver.flush(&mut chunks).unwrap(), 0);
+		assert_eq!(saver.save_dirty(&mut chunks, 2).unwrap(), 0);
+		chunks.get_chunk_mut(&modified).unwrap().mark_dirty();
+		let others = [ChunkCoord::new(0, 1, 0), ChunkCoord::new(5, 5, 5)];
+		assert_eq!(saver.save_dirty_chunks(&mut chunks, &others).unwrap(), 0);
+		assert_eq!(
+			saver.save_dirty_chunks(&mut chunks, &[modified]).unwrap(),
+			1
+		);
+		assert_eq!(saver.flush(&mut chunks).unwrap(), 0);
+		let loaded = storage.load_chunk(modified).unwrap().unwrap();
+		assert_eq!(loaded.get_tile(tile), Some(TileId::new(2)));
+		assert!(storage
+			.load_chunk(ChunkCoord::new(0, 1, 0))
+			.unwrap()
+			.is_none());
+
+		// Chunks that fail to save are saved again next time.
+		std::fs::write(storage.region_path(modified), b"garbage").unwrap();
+		for chunk in chunks.iter_chunks_mut() {
+			chunk.mark_dirty();
+		}
+		// The chunk at 0, 1, 0 shares its region with the one at the origin.
+		assert_eq!(saver.save_dirty(&mut chunks, 10).unwrap(), 3);
+		assert_eq!(dirty(&chunks), 0);
+		assert!(saver.flush(&mut chunks).is_err());
+		assert_eq!(dirty(&chunks), 4);
+		assert!(saver.flush(&mut chunks).is_err());
+		assert_eq!(dirty(&chunks), 4);
+		std::fs::remove_file(storage.region_path(modified)).unwrap();
+		assert_eq!(saver.flush(&mut chunks).unwrap(), 4);
+		assert_eq!(dirty(&chunks), 0);
+	}
+}
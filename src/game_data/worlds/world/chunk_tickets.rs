@@ -0,0 +1,198 @@
+use crate::game_data::chunks::chunk::{ChunkCoord, ChunkCoordAABB, ChunkCoordAreaType};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How long a chunk stays loaded after leaving every ticket by default.
+pub const DEFAULT_UNLOAD_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// What holds a ticket, tells the tickets apart in logs and metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TicketKind {
+	/// Follows a player around.
+	Player,
+	/// Keeps the area around a spawn point loaded.
+	Spawn,
+	/// Loaded by a command until it is removed again.
+	Forced,
+}
+
+/// Handed out when adding a ticket, used to move or remove it again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TicketId(u64);
+
+/// Keeps every chunk within `radius` of `center` along each axis loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkTicket {
+	pub kind: TicketKind,
+	pub center: ChunkCoord,
+	pub radius: ChunkCoordAreaType,
+}
+
+impl ChunkTicket {
+	pub fn new(kind: TicketKind, center: ChunkCoord, radius: ChunkCoordAreaType) -> Self {
+		ChunkTicket {
+			kind,
+			center,
+			radius,
+		}
+	}
+
+	/// The chunks the ticket keeps loaded.
+	pub fn area(&self) -> ChunkCoordAABB {
+		ChunkCoordAABB::around(self.center, self.radius)
+	}
+}
+
+/// The tickets of a world along with when each loaded chunk outside of all of them was left behind.
+pub struct ChunkTickets {
+	next_id: u64,
+	tickets: HashMap<TicketId, ChunkTicket>,
+	/// How long a chunk stays loaded after leaving every ticket, so moving back and forth over the
+	/// edge of a ticket doesn't unload and load the same chunks over and over.
+	grace_period: Duration,
+	unticketed_since: HashMap<ChunkCoord, Instant>,
+}
+
+impl Default for ChunkTickets {
+	fn default() -> Self {
+		ChunkTickets {
+			next_id: 0,
+			tickets: HashMap::default(),
+			grace_period: DEFAULT_UNLOAD_GRACE_PERIOD,
+			unticketed_since: HashMap::default(),
+		}
+	}
+}
+
+impl ChunkTickets {
+	pub fn grace_period(&self) -> Duration {
+		self.grace_period
+	}
+
+	pub fn set_grace_period(&mut self, grace_period: Duration) {
+		self.grace_period = grace_period;
+	}
+
+	pub fn len(&self) -> usize {
+		self.tickets.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.tickets.is_empty()
+	}
+
+	pub fn get(&self, id: TicketId) -> Option<&ChunkTicket> {
+		self.tickets.get(&id)
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (TicketId, &ChunkTicket)> + '_ {
+		self.tickets.iter().map(|(&id, ticket)| (id, ticket))
+	}
+
+	pub fn add(&mut self, ticket: ChunkTicket) -> TicketId {
+		let id = TicketId(self.next_id);
+		self.next_id += 1;
+		self.tickets.insert(id, ticket);
+		id
+	}
+
+	/// Replaces the ticket, returning the previous one or `None` if there is no such ticket.
+	pub fn replace(&mut self, id: TicketId, ticket: ChunkTicket) -> Option<ChunkTicket> {
+		self.tickets
+			.get_mut(&id)
+			.map(|previous| std::mem::replace(previous, ticket))
+	}
+
+	pub fn remove(&mut self, id: TicketId) -> Option<ChunkTicket> {
+		self.tickets.remove(&id)
+	}
+
+	/// Whether any ticket, grown by `margin` on every side, holds the chunk.
+	pub fn covers(&self, coord: ChunkCoord, margin: ChunkCoordAreaType) -> bool {
+		self.tickets
+			.values()
+			.any(|ticket| ticket.area().expand(margin).contains(coord))
+	}
+
+	/// Notes when each of the loaded chunks was first seen outside of every ticket grown by
+	/// `margin`, returns those that have been outside for longer than the grace period since.
+	pub fn expired(
+		&mut self,
+		loaded: impl IntoIterator<Item = ChunkCoord>,
+		margin: ChunkCoordAreaType,
+		now: Instant,
+	) -> Vec<ChunkCoord> {
+		let mut expired = Vec::new();
+		for coord in loaded {
+			if self.covers(coord, margin) {
+				self.unticketed_since.remove(&coord);
+				continue;
+			}
+			let since = *self.unticketed_since.entry(coord).or_insert(now);
+			if now.saturating_duration_since(since) >= self.grace_period {
+				expired.push(coord);
+			}
+		}
+		expired
+	}
+
+	/// Forgets about a chunk that was unloaded.
+	pub fn forget(&mut self, coord: ChunkCoord) {
+		self.unticketed_since.remove(&coord);
+	}
+
+	/// The amount of loaded chunks outside of every ticket that wait for their grace period to end.
+	pub fn unticketed_len(&self) -> usize {
+		self.unticketed_since.len()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::chunks::chunk::ChunkCoord;
+	use crate::game_data::worlds::world::chunk_tickets::{ChunkTicket, ChunkTickets, TicketKind};
+	use std::time::{Duration, Instant};
+
+	#[test]
+	fn expire_chunks_outside_of_tickets() {
+		let mut tickets = ChunkTickets::default();
+		tickets.set_grace_period(Duration::from_secs(5));
+		let origin = ChunkCoord::new(0, 0, 0);
+		let player = tickets.add(ChunkTicket::new(TicketKind::Player, origin, 1));
+		let spawn = tickets.add(ChunkTicket::new(TicketKind::Spawn, origin, 0));
+		assert_ne!(player, spawn);
+		let near = ChunkCoord::new(1, 1, 0);
+		let edge = ChunkCoord::new(2, 0, 0);
+		let far = ChunkCoord::new(3, 0, 0);
+		let loaded = [origin, near, edge, far];
+		assert!(tickets.covers(near, 0));
+		assert!(!tickets.covers(edge, 0));
+		assert!(tickets.covers(edge, 1));
+
+		let start = Instant::now();
+		let expired = |tickets: &mut ChunkTickets, secs| {
+			tickets.expired(loaded.iter().copied(), 1, start + Duration::from_secs(secs))
+		};
+		assert!(expired(&mut tickets, 0).is_empty());
+		assert_eq!(tickets.unticketed_len(), 1);
+		assert!(expired(&mut tickets, 4).is_empty());
+		assert_eq!(expired(&mut tickets, 5), vec![far]);
+
+		// Chunks that are covered again stop waiting to be unloaded.
+		let moved = ChunkTicket::new(TicketKind::Player, far, 1);
+		assert_eq!(
+			tickets.replace(player, moved).map(|ticket| ticket.center),
+			Some(origin)
+		);
+		assert!(expired(&mut tickets, 6).is_empty());
+		assert_eq!(tickets.unticketed_len(), 0);
+		// Without the player only the spawn ticket is left.
+		assert_eq!(tickets.remove(player), Some(moved));
+		assert!(tickets.replace(player, moved).is_none());
+		assert!(expired(&mut tickets, 7).is_empty());
+		assert_eq!(expired(&mut tickets, 12), vec![edge, far]);
+		tickets.forget(edge);
+		tickets.forget(far);
+		assert_eq!(tickets.unticketed_len(), 0);
+	}
+}
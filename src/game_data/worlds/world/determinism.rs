@@ -0,0 +1,148 @@
+//! Checks that generators hold up the `WorldGenerator` contract of generating every chunk as a pure
+//! function of the seed and chunk location.
+
+use crate::game_data::chunks::chunk::{Chunk, ChunkCoord};
+use crate::game_data::chunks::Chunks;
+use crate::game_data::worlds::world::staged_generation::{generate_completely, GenerateNow};
+use crate::game_data::worlds::world::world_generator::{
+	generate_chunk, generate_chunk_terrain, WorldGenerator,
+};
+use rayon::prelude::*;
+use std::sync::Arc;
+
+/// Generates every chunk with a fresh generator from `make`, first one after the other and then
+/// with the terrain generated in parallel on thread pools of a few sizes and the later stages run
+/// in reverse order, and panics if any chunk differs between them.  The first chunk is also generated
+/// on its own with `generate_chunk` to check it comes out the same without the others around.
+pub(crate) fn assert_deterministic(
+	make: impl Fn() -> Arc<dyn WorldGenerator>,
+	coords: &[ChunkCoord],
+) {
+	let generator = make();
+	let mut reference = Chunks::new(generator.get_chunk_edge_len());
+	for &coord in coords.iter() {
+		generate_completely(&mut reference, &*generator, coord, &mut GenerateNow);
+	}
+	if let Some(&first) = coords.first() {
+		assert_same(
+			reference.get_chunk(&first).unwrap(),
+			&generate_chunk(&*make(), first),
+			"generated on its own",
+		);
+	}
+	let mut needed: Vec<ChunkCoord> = reference.iter_chunks().map(Chunk::location).collect();
+	needed.sort_by_key(|coord| (coord.x(), coord.y(), coord.z()));
+	for &threads in [1, 2, 4, 8].iter() {
+		let generator = make();
+		let pool = rayon::ThreadPoolBuilder::new()
+			.num_threads(threads)
+			.build()
+			.expect("failed to build thread pool");
+		let terrain: Vec<Chunk> = pool.install(|| {
+			needed
+				.par_iter()
+				.rev()
+				.map(|&coord| generate_chunk_terrain(&*generator, coord))
+				.collect()
+		});
+		let mut chunks = Chunks::new(generator.get_chunk_edge_len());
+		for chunk in terrain {
+			chunks.insert_chunk(chunk);
+		}
+		for &coord in coords.iter().rev() {
+			generate_completely(&mut chunks, &*generator, coord, &mut GenerateNow);
+		}
+		for coord in coords.iter() {
+			assert_same(
+				reference.get_chunk(coord).unwrap(),
+				chunks.get_chunk(coord).unwrap(),
+				&format!("generated on {} threads", threads),
+			);
+		}
+	}
+}
+
+fn assert_same(expected: &Chunk, chunk: &Chunk, how: &str) {
+	assert_eq!(expected.location(), chunk.location());
+	assert_eq!(expected.generation_stage(), chunk.generation_stage());
+	assert_eq!(
+		expected.fingerprint(),
+		chunk.fingerprint(),
+		"chunk {:?} differs when {}",
+		chunk.location(),
+		how
+	);
+	assert!(expected.tiles_eq(chunk));
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::chunks::chunk::{Chunk, ChunkCoord, ChunkCoordAABB, TileCoord};
+	use crate::game_data::tiles::TileId;
+	use crate::game_data::worlds::world::determinism::assert_deterministic;
+	use crate::game_data::worlds::world::seed::{chunk_seed, SeedRng, WorldSeed};
+	use crate::game_data::worlds::world::world_generator::{LayeredWorldGenerator, WorldGenerator};
+	use std::num::NonZeroU8;
+	use std::sync::atomic::{AtomicU16, Ordering};
+	use std::sync::Arc;
+
+	struct ScatterGenerator(WorldSeed);
+
+	impl WorldGenerator for ScatterGenerator {
+		fn get_chunk_edge_len(&self) -> NonZeroU8 {
+			NonZeroU8::new(8).unwrap()
+		}
+
+		fn seed(&self) -> WorldSeed {
+			self.0
+		}
+
+		fn generate_tiles_in_chunk(&self, chunk: &mut Chunk) {
+			let mut rng = SeedRng::new(chunk_seed(self.0, chunk.location()));
+			for (_coord, tile) in chunk.tiles_mut().iter_mut() {
+				*tile = TileId::new(rng.next_below(4) as u16);
+			}
+		}
+	}
+
+	fn coords() -> Vec<ChunkCoord> {
+		ChunkCoordAABB::around(ChunkCoord::new(0, 0, 0), 1)
+			.iter()
+			.collect()
+	}
+
+	#[test]
+	fn seeded_generators_are_deterministic() {
+		assert_deterministic(|| Arc::new(ScatterGenerator(1234)), &coords());
+		assert_deterministic(
+			|| Arc::new(LayeredWorldGenerator::new(1234, vec![])),
+			&coords(),
+		);
+	}
+
+	/// A generator that breaks the contract by depending on how many chunks it generated before.
+	struct StatefulGenerator(AtomicU16);
+
+	impl WorldGenerator for StatefulGenerator {
+		fn get_chunk_edge_len(&self) -> NonZeroU8 {
+			NonZeroU8::new(2).unwrap()
+		}
+
+		fn seed(&self) -> WorldSeed {
+			0
+		}
+
+		fn generate_tile_at(&self, _coord: TileCoord) -> TileId {
+			TileId::new(self.0.fetch_add(1, Ordering::Relaxed))
+		}
+	}
+
+	#[test]
+	#[should_panic(expected = "differs when generated on 1 threads")]
+	fn stateful_generators_are_caught() {
+		assert_deterministic(
+			|| Arc::new(StatefulGenerator(Default::default())),
+			&coords(),
+		);
+	}
+}
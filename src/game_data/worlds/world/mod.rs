@@ -0,0 +1,714 @@
+pub mod chunk_generation;
+pub mod chunk_saving;
+pub mod chunk_tickets;
+#[cfg(test)]
+pub(crate) mod determinism;
+pub mod noise;
+pub mod noise_generator;
+pub mod seed;
+pub mod staged_generation;
+pub mod world_generator;
+
+use crate::game_data::chunks::chunk::{Chunk, ChunkCoord, ChunkCoordAABB, TileCoord};
+use crate::game_data::chunks::region_file::{RegionFileError, RegionStorage};
+use crate::game_data::chunks::Chunks;
+use crate::game_data::tiles::TileId;
+use crate::game_data::worlds::WorldId;
+use chunk_generation::ChunkGenerationQueue;
+use chunk_saving::ChunkSaver;
+use chunk_tickets::{ChunkTicket, ChunkTickets, TicketId};
+use seed::WorldSeed;
+use smol_str::SmolStr;
+use staged_generation::{
+	generate_completely, generation_reach, is_chunk_generated, ChunkSource, GenerateNow,
+};
+use std::collections::HashSet;
+use std::ops::AddAssign;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use world_generator::{generate_chunk, WorldGenerator};
+
+pub struct World {
+	idx: WorldId,
+	name: SmolStr,
+	generator: Arc<dyn WorldGenerator>,
+	chunks: Chunks,
+	generation: ChunkGenerationQueue,
+	/// Chunks requested with `request_chunk` that aren't done generating yet.
+	requested: HashSet<ChunkCoord>,
+	saver: Option<ChunkSaver>,
+	tickets: ChunkTickets,
+	unloaded_total: u64,
+	tile_changes: Vec<TileChange>,
+}
+
+/// A tile set with `World::set_tile`, so whoever watches the world can follow along.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileChange {
+	pub coord: TileCoord,
+	pub tile: TileId,
+}
+
+/// Why `World::tick_chunk_generation` had to drop some of the requested chunks.
+#[derive(Debug, thiserror::Error)]
+pub enum ChunkGenerationError {
+	#[error(transparent)]
+	Load(#[from] RegionFileError),
+	#[error("generating the terrain of chunk {0:?} panicked")]
+	Panicked(ChunkCoord),
+}
+
+/// Counts of a world's chunks, to keep an eye on how much is loaded and what is going on with it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChunkMetrics {
+	/// Every loaded chunk, including the partially generated ones.
+	pub loaded: usize,
+	pub dirty: usize,
+	/// Chunks requested to be generated that aren't done yet.
+	pub requested: usize,
+	/// Chunks whose terrain is queued or generating on the thread pool.
+	pub generating: usize,
+	pub tickets: usize,
+	/// Loaded chunks outside of every ticket that are waiting to be unloaded.
+	pub unloading: usize,
+	/// Every chunk that was unloaded since the world was created.
+	pub unloaded_total: u64,
+}
+
+impl AddAssign for ChunkMetrics {
+	fn add_assign(&mut self, rhs: Self) {
+		self.loaded += rhs.loaded;
+		self.dirty += rhs.dirty;
+		self.requested += rhs.requested;
+		self.generating += rhs.generating;
+		self.tickets += rhs.tickets;
+		self.unloading += rhs.unloading;
+		self.unloaded_total += rhs.unloaded_total;
+	}
+}
+
+impl World {
+	pub(super) fn new(idx: WorldId, name: SmolStr, generator: Box<dyn WorldGenerator>) -> Self {
+		let generator: Arc<dyn WorldGenerator> = generator.into();
+		let chunk_edge_len = generator.get_chunk_edge_len();
+		Self {
+			idx,
+			name,
+			generation: ChunkGenerationQueue::new(generator.clone()),
+			generator,
+			chunks: Chunks::new(chunk_edge_len),
+			requested: HashSet::default(),
+			saver: None,
+			tickets: ChunkTickets::default(),
+			unloaded_total: 0,
+			tile_changes: Vec::new(),
+		}
+	}
+
+	pub fn id(&self) -> WorldId {
+		self.idx
+	}
+
+	pub fn name(&self) -> &SmolStr {
+		&self.name
+	}
+
+	pub fn seed(&self) -> WorldSeed {
+		self.generator.seed()
+	}
+
+	pub fn storage(&self) -> Option<&RegionStorage> {
+		self.saver.as_ref().map(ChunkSaver::storage)
+	}
+
+	/// Where the world's chunks are saved, chunks that aren't loaded are then loaded from there
+	/// before generating them.  Chunks still being written to the previous storage finish in the
+	/// background, `flush_chunks` first to make sure they made it.
+	pub fn set_storage(&mut self, storage: Option<RegionStorage>) {
+		if let Some(storage) = &storage {
+			assert_eq!(
+				storage.chunk_edge_len(),
+				self.chunks.chunk_edge_len(),
+				"region storage chunk edge length doesn't match the world"
+			);
+		}
+		self.saver = storage.map(ChunkSaver::new);
+	}
+
+	/// Every loaded chunk, including those that are only partially generated.
+	pub fn chunks(&self) -> &Chunks {
+		&self.chunks
+	}
+
+	/// Whether the chunk is loaded and done generating.
+	pub fn is_chunk_generated(&self, coord: ChunkCoord) -> bool {
+		is_chunk_generated(&self.chunks, &*self.generator, coord)
+	}
+
+	/// The chunk if it is loaded and done generating.
+	pub fn get_chunk(&self, coord: &ChunkCoord) -> Option<&Chunk> {
+		if self.is_chunk_generated(*coord) {
+			self.chunks.get_chunk(coord)
+		} else {
+			None
+		}
+	}
+
+	pub fn get_chunk_mut(&mut self, coord: &ChunkCoord) -> Option<&mut Chunk> {
+		if self.is_chunk_generated(*coord) {
+			self.chunks.get_chunk_mut(coord)
+		} else {
+			None
+		}
+	}
+
+	/// Sets the tile if its chunk is done generating, returning the tile that was there before.  A
+	/// tile that actually changed is kept for `take_tile_changes`.
+	pub fn set_tile(&mut self, coord: TileCoord, tile: TileId) -> Option<TileId> {
+		let (chunk, local) = coord.to_chunk_local(self.chunks.chunk_edge_len())?;
+		let previous = self
+			.get_chunk_mut(&chunk)?
+			.set_tile(local, tile)
+			.expect("the local coordinate is in the chunk");
+		if previous != tile {
+			self.tile_changes.push(TileChange { coord, tile });
+		}
+		Some(previous)
+	}
+
+	/// The tiles changed with `set_tile` since the last call, in the order they changed.  Meant to
+	/// be taken every tick so they don't pile up.
+	pub fn take_tile_changes(&mut self) -> Vec<TileChange> {
+		std::mem::take(&mut self.tile_changes)
+	}
+
+	/// Loads or generates the chunk on the calling thread if it isn't loaded yet, prefer
+	/// `request_chunk` for anything that can wait a tick.
+	pub fn get_or_generate_chunk_now(
+		&mut self,
+		coord: ChunkCoord,
+	) -> Result<&mut Chunk, RegionFileError> {
+		if self.requested.remove(&coord) {
+			self.generation.cancel(coord);
+		}
+		let mut now = GenerateNow;
+		let mut source = StorageSource::new(self.saver.as_ref().map(ChunkSaver::storage), &mut now);
+		generate_completely(&mut self.chunks, &*self.generator, coord, &mut source);
+		if let Some(error) = source.error {
+			return Err(error);
+		}
+		Ok(self
+			.chunks
+			.get_chunk_mut(&coord)
+			.expect("chunk was just generated"))
+	}
+
+	/// How many loaded chunks changed since they were last saved, including the partially
+	/// generated ones.
+	pub fn dirty_chunk_count(&self) -> usize {
+		self.chunks
+			.iter_chunks()
+			.filter(|chunk| chunk.is_dirty())
+			.count()
+	}
+
+	/// Starts writing the dirty chunks of up to `max_regions` region files to the world's storage
+	/// in the background, see `ChunkSaver::save_dirty`.  Does nothing if the world has no storage,
+	/// returns how many region files were started.
+	pub fn autosave_chunks(&mut self, max_regions: usize) -> Result<usize, RegionFileError> {
+		match &mut self.saver {
+			Some(saver) => saver.save_dirty(&mut self.chunks, max_regions),
+			None => Ok(0),
+		}
+	}
+
+	/// Writes every dirty chunk to the world's storage before returning, including those still
+	/// being autosaved.  Does nothing if the world has no storage.
+	pub fn flush_chunks(&mut self) -> Result<usize, RegionFileError> {
+		match &mut self.saver {
+			Some(saver) => saver.flush(&mut self.chunks),
+			None => Ok(0),
+		}
+	}
+
+	/// Generates a fresh copy of the chunk without touching the loaded one, as generation is
+	/// deterministic this can be compared against the loaded or saved chunk to find modifications.
+	pub fn regenerate_chunk(&self, coord: ChunkCoord) -> Chunk {
+		generate_chunk(&*self.generator, coord)
+	}
+
+	pub fn generation_queue_mut(&mut self) -> &mut ChunkGenerationQueue {
+		&mut self.generation
+	}
+
+	/// Queues the chunk to be generated in the background, returns false if it is already generated
+	/// or requested.
+	pub fn request_chunk(&mut self, coord: ChunkCoord) -> bool {
+		!self.is_chunk_generated(coord) && self.requested.insert(coord)
+	}
+
+	/// Drops the request for the chunk, any of the chunks around it that were generated for it stay
+	/// loaded.  Returns false if the chunk wasn't requested.
+	pub fn cancel_chunk_request(&mut self, coord: ChunkCoord) -> bool {
+		self.generation.cancel(coord);
+		self.requested.remove(&coord)
+	}
+
+	pub fn is_chunk_requested(&self, coord: ChunkCoord) -> bool {
+		self.requested.contains(&coord)
+	}
+
+	pub fn chunk_tickets(&self) -> &ChunkTickets {
+		&self.tickets
+	}
+
+	/// How long chunks stay loaded after leaving every ticket.
+	pub fn set_unload_grace_period(&mut self, grace_period: Duration) {
+		self.tickets.set_grace_period(grace_period);
+	}
+
+	/// Adds a ticket that keeps the chunks in its area loaded and requests those that aren't yet.
+	pub fn add_chunk_ticket(&mut self, ticket: ChunkTicket) -> TicketId {
+		for coord in ticket.area() {
+			self.request_chunk(coord);
+		}
+		self.tickets.add(ticket)
+	}
+
+	/// Moves or resizes the ticket, requesting the chunks it covers now and dropping the requests of
+	/// those no ticket covers anymore.  Returns false if there is no such ticket.
+	pub fn set_chunk_ticket(&mut self, id: TicketId, ticket: ChunkTicket) -> bool {
+		match self.tickets.replace(id, ticket) {
+			Some(previous) => {
+				self.cancel_unticketed_requests(previous);
+				for coord in ticket.area() {
+					self.request_chunk(coord);
+				}
+				true
+			}
+			None => false,
+		}
+	}
+
+	/// Removes the ticket, its chunks are unloaded once the grace period is over unless another
+	/// ticket covers them.
+	pub fn remove_chunk_ticket(&mut self, id: TicketId) -> Option<ChunkTicket> {
+		let ticket = self.tickets.remove(id)?;
+		self.cancel_unticketed_requests(ticket);
+		Some(ticket)
+	}
+
+	/// Drops the requests in the area of the ticket that no ticket covers anymore.
+	fn cancel_unticketed_requests(&mut self, ticket: ChunkTicket) {
+		let area = ticket.area();
+		let tickets = &self.tickets;
+		let unticketed: Vec<ChunkCoord> = self
+			.requested
+			.iter()
+			.copied()
+			.filter(|&coord| area.contains(coord) && !tickets.covers(coord, 0))
+			.collect();
+		for coord in unticketed {
+			self.cancel_chunk_request(coord);
+		}
+	}
+
+	/// Unloads the chunks that have been outside of every ticket for the grace period, along with
+	/// those around the tickets that generating their chunks needs.  Dirty chunks are saved first
+	/// and only unloaded once written, usually a few ticks later, but a world without storage drops
+	/// them right away.  Returns how many chunks were unloaded.
+	///
+	/// Chunks requested with `request_chunk` outside of every ticket are unloaded like any other,
+	/// hold a ticket to keep them around.
+	pub fn tick_chunk_unloading(&mut self, now: Instant) -> Result<usize, RegionFileError> {
+		let margin = generation_reach(&*self.generator);
+		let loaded: Vec<ChunkCoord> = self.chunks.iter_chunks().map(Chunk::location).collect();
+		let expired = self.tickets.expired(loaded, margin, now);
+		if expired.is_empty() {
+			return Ok(0);
+		}
+		let saved = match &mut self.saver {
+			Some(saver) => saver.save_dirty_chunks(&mut self.chunks, &expired),
+			None => Ok(0),
+		};
+		let mut unloaded = 0;
+		for coord in expired {
+			let saving = match (&self.saver, self.chunks.get_chunk(&coord)) {
+				(Some(saver), Some(chunk)) => chunk.is_dirty() || saver.is_saving_chunk(coord),
+				_ => false,
+			};
+			if !saving {
+				self.chunks.remove_chunk(&coord);
+				self.tickets.forget(coord);
+				unloaded += 1;
+			}
+		}
+		self.unloaded_total += unloaded as u64;
+		saved.map(|_started| unloaded)
+	}
+
+	pub fn chunk_metrics(&self) -> ChunkMetrics {
+		ChunkMetrics {
+			loaded: self.chunks.len(),
+			dirty: self.dirty_chunk_count(),
+			requested: self.requested.len(),
+			generating: self.generation.len(),
+			tickets: self.tickets.len(),
+			unloading: self.tickets.unticketed_len(),
+			unloaded_total: self.unloaded_total,
+		}
+	}
+
+	/// Adds up to `max_integrate` chunks whose terrain finished generating on the thread pool to the
+	/// world, loads or runs the later generation stages of the requested chunks as far as the
+	/// loaded chunks allow, then starts the terrain of more chunks, closest to the focus points
+	/// first.  Meant to be called once a tick, returns how many of the requested chunks are done.
+	///
+	/// A requested chunk that fails to load or generate, or needs a chunk that does, is dropped
+	/// from the requests and the first such error is returned after the other requests were handled.
+	pub fn tick_chunk_generation(
+		&mut self,
+		focus: &[ChunkCoord],
+		max_integrate: usize,
+	) -> Result<usize, ChunkGenerationError> {
+		self.generation.integrate(&mut self.chunks, max_integrate);
+		let failed = self.generation.take_failed();
+		let before = self.requested.len();
+		if !failed.is_empty() {
+			let reach = generation_reach(&*self.generator);
+			let needs_failed = |coord: ChunkCoord| {
+				failed
+					.iter()
+					.any(|&bad| ChunkCoordAABB::around(bad, reach).contains(coord))
+			};
+			self.requested.retain(|&coord| !needs_failed(coord));
+		}
+		let mut first_error = failed.first().copied().map(ChunkGenerationError::Panicked);
+		let chunks = &mut self.chunks;
+		let generator = &*self.generator;
+		let storage = self.saver.as_ref().map(ChunkSaver::storage);
+		let mut source = StorageSource::new(storage, &mut self.generation);
+		self.requested.retain(|&coord| {
+			let done = generate_completely(chunks, generator, coord, &mut source);
+			match source.error.take() {
+				Some(error) => {
+					first_error.get_or_insert(error.into());
+					false
+				}
+				None => !done,
+			}
+		});
+		let completed = before - self.requested.len();
+		self.generation.dispatch(focus);
+		match first_error {
+			Some(error) => Err(error),
+			None => Ok(completed),
+		}
+	}
+}
+
+/// Loads chunks from the world's storage, taking those that were never saved from the fallback.
+/// Once loading fails the error is kept and no more chunks are provided.
+struct StorageSource<'a> {
+	storage: Option<&'a RegionStorage>,
+	fallback: &'a mut dyn ChunkSource,
+	error: Option<RegionFileError>,
+}
+
+impl<'a> StorageSource<'a> {
+	fn new(storage: Option<&'a RegionStorage>, fallback: &'a mut dyn ChunkSource) -> Self {
+		StorageSource {
+			storage,
+			fallback,
+			error: None,
+		}
+	}
+}
+
+impl<'a> ChunkSource for StorageSource<'a> {
+	fn provide_chunk(
+		&mut self,
+		generator: &dyn WorldGenerator,
+		coord: ChunkCoord,
+	) -> Option<Chunk> {
+		if self.error.is_some() {
+			return None;
+		}
+		if let Some(storage) = self.storage {
+			match storage.load_chunk(coord) {
+				Ok(Some(chunk)) => return Some(chunk),
+				Ok(None) => {}
+				Err(error) => {
+					self.error = Some(error);
+					return None;
+				}
+			}
+		}
+		self.fallback.provide_chunk(generator, coord)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::chunks::chunk::{Chunk, ChunkCoord, LocalTileCoord, TileCoord};
+	use crate::game_data::chunks::region_file::RegionStorage;
+	use crate::game_data::test_dir::TestDir;
+	use crate::game_data::tiles::{TileId, TileRegistry};
+	use crate::game_data::worlds::world::chunk_tickets::{ChunkTicket, TicketKind};
+	use crate::game_data::worlds::world::seed::WorldSeed;
+	use crate::game_data::worlds::world::world_generator::{
+		Layer, LayeredWorldGenerator, WorldGenerator,
+	};
+	use crate::game_data::worlds::world::{ChunkGenerationError, TileChange};
+	use crate::game_data::worlds::Worlds;
+	use std::num::NonZeroU8;
+	use std::sync::Arc;
+	use std::time::{Duration, Instant};
+
+	#[test]
+	fn get_or_generate_chunk_now() {
+		let mut worlds = Worlds::default();
+		let world_id = worlds.create_world(
+			"test".into(),
+			Box::new(LayeredWorldGenerator::new(0, vec![])),
+		);
+		let world = worlds.get_world_mut(world_id);
+		let coord = ChunkCoord::new(1, 2, -3);
+		assert!(world.get_chunk(&coord).is_none());
+		assert!(!world.is_chunk_generated(coord));
+		assert_eq!(
+			world.get_or_generate_chunk_now(coord).unwrap().location(),
+			coord
+		);
+		assert!(world.is_chunk_generated(coord));
+		assert_eq!(
+			world.get_or_generate_chunk_now(coord).unwrap().location(),
+			coord
+		);
+		assert_eq!(world.chunks().len(), 1);
+		assert_eq!(world.chunks().chunk_edge_len().get(), 32);
+		assert!(world.get_chunk(&coord).is_some());
+		assert!(world.get_chunk_mut(&coord).is_some());
+	}
+
+	#[test]
+	fn record_tile_changes() {
+		let mut worlds = Worlds::default();
+		let world_id = worlds.create_world(
+			"test".into(),
+			Box::new(LayeredWorldGenerator::new(0, vec![])),
+		);
+		let world = worlds.get_world_mut(world_id);
+		let coord = TileCoord::new(-1, 40, 3);
+		assert_eq!(world.set_tile(coord, TileId::new(2)), None);
+		world
+			.get_or_generate_chunk_now(ChunkCoord::new(-1, 1, 0))
+			.unwrap();
+		assert_eq!(world.set_tile(coord, TileId::new(2)), Some(TileId::AIR));
+		assert_eq!(world.set_tile(coord, TileId::new(2)), Some(TileId::new(2)));
+		assert_eq!(world.set_tile(coord, TileId::AIR), Some(TileId::new(2)));
+		let chunk = world.get_chunk(&ChunkCoord::new(-1, 1, 0)).unwrap();
+		assert_eq!(
+			chunk.get_tile(LocalTileCoord::new(31, 8, 3)),
+			Some(TileId::AIR)
+		);
+
+		// Setting a tile to what it already is isn't a change.
+		assert_eq!(
+			world.take_tile_changes(),
+			vec![
+				TileChange {
+					coord,
+					tile: TileId::new(2)
+				},
+				TileChange {
+					coord,
+					tile: TileId::AIR
+				},
+			]
+		);
+		assert!(world.take_tile_changes().is_empty());
+	}
+
+	#[test]
+	fn request_chunks() {
+		let mut worlds = Worlds::default();
+		let world_id = worlds.create_world(
+			"test".into(),
+			Box::new(LayeredWorldGenerator::new(0, vec![])),
+		);
+		let world = worlds.get_world_mut(world_id);
+		let loaded = ChunkCoord::new(0, 0, 0);
+		world.get_or_generate_chunk_now(loaded).unwrap();
+		assert!(!world.request_chunk(loaded));
+		let requested = [ChunkCoord::new(0, 0, 1), ChunkCoord::new(0, 0, -1)];
+		for &coord in requested.iter() {
+			assert!(world.request_chunk(coord));
+			assert!(!world.request_chunk(coord));
+			assert!(world.is_chunk_requested(coord));
+		}
+		let deadline = Instant::now() + Duration::from_secs(10);
+		let mut integrated = 0;
+		while integrated < requested.len() {
+			assert!(Instant::now() < deadline, "chunk generation timed out");
+			integrated += world.tick_chunk_generation(&[loaded], usize::MAX).unwrap();
+			std::thread::sleep(Duration::from_millis(1));
+		}
+		assert_eq!(world.chunks().len(), 3);
+		assert!(!world.is_chunk_requested(requested[0]));
+	}
+
+	/// Panics on every chunk below the ground.
+	struct FaultyGenerator;
+
+	impl WorldGenerator for FaultyGenerator {
+		fn get_chunk_edge_len(&self) -> NonZeroU8 {
+			NonZeroU8::new(8).unwrap()
+		}
+
+		fn seed(&self) -> WorldSeed {
+			0
+		}
+
+		fn generate_tiles_in_chunk(&self, chunk: &mut Chunk) {
+			assert!(chunk.location().z() >= 0, "no ground to generate");
+		}
+	}
+
+	#[test]
+	fn drop_requests_whose_generation_panics() {
+		let mut worlds = Worlds::default();
+		let world_id = worlds.create_world("test".into(), Box::new(FaultyGenerator));
+		let world = worlds.get_world_mut(world_id);
+		let (ground, sky) = (ChunkCoord::new(0, 0, -1), ChunkCoord::new(0, 0, 1));
+		assert!(world.request_chunk(ground));
+		assert!(world.request_chunk(sky));
+		let deadline = Instant::now() + Duration::from_secs(10);
+		let mut failed = None;
+		while world.is_chunk_requested(ground) || world.is_chunk_requested(sky) {
+			assert!(Instant::now() < deadline, "chunk generation timed out");
+			if let Err(error) = world.tick_chunk_generation(&[], usize::MAX) {
+				failed = Some(error);
+			}
+			std::thread::sleep(Duration::from_millis(1));
+		}
+		assert!(matches!(failed, Some(ChunkGenerationError::Panicked(coord)) if coord == ground));
+		assert!(world.get_chunk(&ground).is_none());
+		assert!(world.is_chunk_generated(sky));
+	}
+
+	#[test]
+	fn load_saved_chunks() {
+		let dir = TestDir::new("world-chunks");
+		let generator = || {
+			Box::new(LayeredWorldGenerator::new(
+				0,
+				vec![Layer::new(-1, 0, TileId::new(1))],
+			))
+		};
+		let mut worlds = Worlds::default();
+		let world_id = worlds.create_world("test".into(), generator());
+		let world = worlds.get_world_mut(world_id);
+		let storage = RegionStorage::new(
+			dir.path(),
+			world.chunks().chunk_edge_len(),
+			Arc::new(TileRegistry::numbered(8)),
+		);
+		world.set_storage(Some(storage.clone()));
+		let modified = ChunkCoord::new(0, 0, 0);
+		let tile = LocalTileCoord::new(1, 2, 3);
+		world
+			.get_or_generate_chunk_now(modified)
+			.unwrap()
+			.set_tile(tile, TileId::new(7))
+			.unwrap();
+		world
+			.get_or_generate_chunk_now(ChunkCoord::new(0, 0, -1))
+			.unwrap();
+		assert_eq!(world.dirty_chunk_count(), 2);
+		assert_eq!(world.flush_chunks().unwrap(), 2);
+		assert_eq!(world.dirty_chunk_count(), 0);
+
+		let mut worlds = Worlds::default();
+		let world_id = worlds.create_world("test".into(), generator());
+		let world = worlds.get_world_mut(world_id);
+		world.set_storage(Some(storage));
+		let chunk = world.get_or_generate_chunk_now(modified).unwrap();
+		assert_eq!(chunk.get_tile(tile), Some(TileId::new(7)));
+		assert_eq!(world.dirty_chunk_count(), 0);
+		assert!(world.request_chunk(ChunkCoord::new(0, 0, -1)));
+		assert_eq!(world.tick_chunk_generation(&[], usize::MAX).unwrap(), 1);
+		assert!(world.generation_queue_mut().is_empty());
+
+		std::fs::write(dir.path().join("r.0.0.0.mreg"), b"garbage").unwrap();
+		let mut worlds = Worlds::default();
+		let world_id = worlds.create_world("test".into(), generator());
+		let world = worlds.get_world_mut(world_id);
+		world.set_storage(Some(RegionStorage::new(
+			dir.path(),
+			world.chunks().chunk_edge_len(),
+			Arc::new(TileRegistry::numbered(8)),
+		)));
+		assert!(world.get_or_generate_chunk_now(modified).is_err());
+		assert!(world.request_chunk(modified));
+		assert!(world.tick_chunk_generation(&[], usize::MAX).is_err());
+		assert!(!world.is_chunk_requested(modified));
+	}
+
+	#[test]
+	fn unload_chunks_outside_of_tickets() {
+		let dir = TestDir::new("world-unloading");
+		let mut worlds = Worlds::default();
+		let world_id = worlds.create_world(
+			"test".into(),
+			Box::new(LayeredWorldGenerator::new(0, vec![])),
+		);
+		let world = worlds.get_world_mut(world_id);
+		world.set_storage(Some(RegionStorage::new(
+			dir.path(),
+			world.chunks().chunk_edge_len(),
+			Arc::new(TileRegistry::numbered(8)),
+		)));
+		world.set_unload_grace_period(Duration::from_secs(0));
+		let origin = ChunkCoord::new(0, 0, 0);
+		let ticket = world.add_chunk_ticket(ChunkTicket::new(TicketKind::Player, origin, 1));
+		assert_eq!(world.chunk_metrics().requested, 27);
+		let deadline = Instant::now() + Duration::from_secs(10);
+		while world.chunk_metrics().requested > 0 {
+			assert!(Instant::now() < deadline, "chunk generation timed out");
+			world.tick_chunk_generation(&[origin], usize::MAX).unwrap();
+			std::thread::sleep(Duration::from_millis(1));
+		}
+		assert_eq!(world.tick_chunk_unloading(Instant::now()).unwrap(), 0);
+		let tile = LocalTileCoord::new(1, 2, 3);
+		let modified = ChunkCoord::new(1, 0, 0);
+		world
+			.get_chunk_mut(&modified)
+			.unwrap()
+			.set_tile(tile, TileId::new(7))
+			.unwrap();
+
+		// Everything but the new center is unloaded once saved.
+		let far = ChunkCoord::new(10, 0, 0);
+		assert!(world.set_chunk_ticket(ticket, ChunkTicket::new(TicketKind::Player, far, 0)));
+		assert_eq!(world.chunk_metrics().requested, 1);
+		while world.chunk_metrics().loaded > 0 {
+			assert!(Instant::now() < deadline, "chunk unloading timed out");
+			world.tick_chunk_unloading(Instant::now()).unwrap();
+			std::thread::sleep(Duration::from_millis(1));
+		}
+		let metrics = world.chunk_metrics();
+		assert_eq!(metrics.unloaded_total, 27);
+		assert_eq!(metrics.unloading, 0);
+		assert_eq!(metrics.tickets, 1);
+		let chunk = world.get_or_generate_chunk_now(modified).unwrap();
+		assert_eq!(chunk.get_tile(tile), Some(TileId::new(7)));
+
+		// Without storage the chunk is dropped right away.
+		world.set_storage(None);
+		assert!(world.remove_chunk_ticket(ticket).is_some());
+		assert_eq!(world.chunk_metrics().requested, 0);
+		assert_eq!(world.tick_chunk_unloading(Instant::now()).unwrap(), 1);
+		assert!(world.chunks().is_empty());
+	}
+}
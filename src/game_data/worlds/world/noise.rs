@@ -0,0 +1,207 @@
+//! Gradient noise for world generation, built only on integer hashing and basic float math so that
+//! its output is the same on every platform.
+
+use crate::game_data::worlds::world::seed::SeedRng;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::FRAC_1_SQRT_2;
+
+const GRADIENTS_2D: [(f64, f64); 8] = [
+	(1.0, 0.0),
+	(-1.0, 0.0),
+	(0.0, 1.0),
+	(0.0, -1.0),
+	(FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+	(-FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+	(FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+	(-FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+];
+
+const GRADIENTS_3D: [(f64, f64, f64); 12] = [
+	(1.0, 1.0, 0.0),
+	(-1.0, 1.0, 0.0),
+	(1.0, -1.0, 0.0),
+	(-1.0, -1.0, 0.0),
+	(1.0, 0.0, 1.0),
+	(-1.0, 0.0, 1.0),
+	(1.0, 0.0, -1.0),
+	(-1.0, 0.0, -1.0),
+	(0.0, 1.0, 1.0),
+	(0.0, -1.0, 1.0),
+	(0.0, 1.0, -1.0),
+	(0.0, -1.0, -1.0),
+];
+
+fn hash(seed: u64, x: i64, y: i64, z: i64) -> u64 {
+	let mixed = seed
+		^ (x as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+		^ (y as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+		^ (z as u64).wrapping_mul(0x1656_67b1_9e37_79f9);
+	SeedRng::new(mixed).next_u64()
+}
+
+fn fade(t: f64) -> f64 {
+	t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+	a + t * (b - a)
+}
+
+/// Perlin style gradient noise in roughly `-1.0..=1.0`, 0 at every integer coordinate.
+pub fn gradient_2d(seed: u64, x: f64, y: f64) -> f64 {
+	let (x0, y0) = (x.floor(), y.floor());
+	let (xi, yi) = (x0 as i64, y0 as i64);
+	let (xf, yf) = (x - x0, y - y0);
+	let corner = |cx: i64, cy: i64, dx: f64, dy: f64| {
+		let (gx, gy) = GRADIENTS_2D[(hash(seed, cx, cy, 0) % 8) as usize];
+		gx * dx + gy * dy
+	};
+	let (u, v) = (fade(xf), fade(yf));
+	let bottom = lerp(u, corner(xi, yi, xf, yf), corner(xi + 1, yi, xf - 1.0, yf));
+	let top = lerp(
+		u,
+		corner(xi, yi + 1, xf, yf - 1.0),
+		corner(xi + 1, yi + 1, xf - 1.0, yf - 1.0),
+	);
+	(lerp(v, bottom, top) * std::f64::consts::SQRT_2).clamp(-1.0, 1.0)
+}
+
+/// The 3D version of `gradient_2d`.
+pub fn gradient_3d(seed: u64, x: f64, y: f64, z: f64) -> f64 {
+	let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+	let (xi, yi, zi) = (x0 as i64, y0 as i64, z0 as i64);
+	let (xf, yf, zf) = (x - x0, y - y0, z - z0);
+	let corner = |ox: i64, oy: i64, oz: i64| {
+		let (gx, gy, gz) = GRADIENTS_3D[(hash(seed, xi + ox, yi + oy, zi + oz) % 12) as usize];
+		gx * (xf - ox as f64) + gy * (yf - oy as f64) + gz * (zf - oz as f64)
+	};
+	let (u, v, w) = (fade(xf), fade(yf), fade(zf));
+	let plane = |oz: i64| {
+		lerp(
+			v,
+			lerp(u, corner(0, 0, oz), corner(1, 0, oz)),
+			lerp(u, corner(0, 1, oz), corner(1, 1, oz)),
+		)
+	};
+	lerp(w, plane(0), plane(1)).clamp(-1.0, 1.0)
+}
+
+/// Fractal noise settings, a number of octaves of gradient noise each `lacunarity` times the
+/// frequency and `persistence` times the amplitude of the one before it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoiseConfig {
+	/// The frequency of the first octave in cycles per tile.
+	pub frequency: f64,
+	pub octaves: u8,
+	pub persistence: f64,
+	pub lacunarity: f64,
+}
+
+impl Default for NoiseConfig {
+	fn default() -> Self {
+		NoiseConfig {
+			frequency: 1.0 / 64.0,
+			octaves: 4,
+			persistence: 0.5,
+			lacunarity: 2.0,
+		}
+	}
+}
+
+impl NoiseConfig {
+	pub fn new(frequency: f64, octaves: u8) -> Self {
+		NoiseConfig {
+			frequency,
+			octaves,
+			..Default::default()
+		}
+	}
+}
+
+/// Fractal noise in `-1.0..=1.0`, every `FractalNoise` made from the same seed, salt and config
+/// gives the same values.
+#[derive(Debug, Clone)]
+pub struct FractalNoise {
+	octave_seeds: Vec<u64>,
+	config: NoiseConfig,
+	normalize: f64,
+}
+
+impl FractalNoise {
+	/// The salt keeps noises made from the same world seed for different purposes apart.
+	pub fn new(seed: u64, salt: u64, config: &NoiseConfig) -> Self {
+		let mut rng = SeedRng::new(seed ^ SeedRng::new(salt).next_u64());
+		let octave_seeds: Vec<u64> = (0..config.octaves.max(1)).map(|_| rng.next_u64()).collect();
+		// Summed with the same multiplications as `sum`, `powi` may differ between platforms.
+		let mut amplitude = 1.0;
+		let mut amplitudes = 0.0;
+		for _ in octave_seeds.iter() {
+			amplitudes += amplitude;
+			amplitude *= config.persistence;
+		}
+		let normalize = amplitudes.recip();
+		FractalNoise {
+			octave_seeds,
+			config: config.clone(),
+			normalize,
+		}
+	}
+
+	pub fn get_2d(&self, x: f64, y: f64) -> f64 {
+		self.sum(|seed, frequency| gradient_2d(seed, x * frequency, y * frequency))
+	}
+
+	pub fn get_3d(&self, x: f64, y: f64, z: f64) -> f64 {
+		self.sum(|seed, frequency| gradient_3d(seed, x * frequency, y * frequency, z * frequency))
+	}
+
+	fn sum(&self, octave: impl Fn(u64, f64) -> f64) -> f64 {
+		let mut frequency = self.config.frequency;
+		let mut amplitude = 1.0;
+		let mut total = 0.0;
+		for &seed in self.octave_seeds.iter() {
+			total += octave(seed, frequency) * amplitude;
+			frequency *= self.config.lacunarity;
+			amplitude *= self.config.persistence;
+		}
+		(total * self.normalize).clamp(-1.0, 1.0)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::worlds::world::noise::{
+		gradient_2d, gradient_3d, FractalNoise, NoiseConfig,
+	};
+
+	#[test]
+	fn gradient_noise_range() {
+		assert_eq!(gradient_2d(1, 3.0, -4.0), 0.0);
+		assert_eq!(gradient_3d(1, 3.0, -4.0, 5.0), 0.0);
+		let mut spread = (0.0f64, 0.0f64);
+		for i in 0..2000 {
+			let v = gradient_2d(9, i as f64 * 0.173, i as f64 * -0.091);
+			let w = gradient_3d(9, i as f64 * 0.173, i as f64 * -0.091, i as f64 * 0.057);
+			assert!((-1.0..=1.0).contains(&v) && (-1.0..=1.0).contains(&w));
+			spread = (spread.0.min(v.min(w)), spread.1.max(v.max(w)));
+		}
+		assert!(spread.0 < -0.4 && spread.1 > 0.4, "{:?}", spread);
+		assert_ne!(gradient_2d(9, 0.5, 0.5), gradient_2d(10, 0.5, 0.5));
+	}
+
+	#[test]
+	fn fractal_noise_is_continuous() {
+		let noise = FractalNoise::new(5, 1, &NoiseConfig::default());
+		let other = FractalNoise::new(5, 2, &NoiseConfig::default());
+		let mut differs = false;
+		for i in 0..500 {
+			let x = i as f64 * 0.5 - 100.0;
+			let step = (noise.get_2d(x, 3.0) - noise.get_2d(x + 0.5, 3.0)).abs();
+			assert!(step < 0.2, "noise jumped by {} at {}", step, x);
+			assert_eq!(noise.get_3d(x, 1.0, 2.0), noise.clone().get_3d(x, 1.0, 2.0));
+			differs |= noise.get_2d(x, 3.0) != other.get_2d(x, 3.0);
+		}
+		assert!(differs);
+	}
+}
@@ -0,0 +1,788 @@
+use crate::game_data::chunks::chunk::{
+	Chunk, ChunkCoordAreaType, GenerationStage, LocalTileCoord, TileCoord, TileCoordType,
+};
+use crate::game_data::tiles::{TileId, TileRegistry};
+use crate::game_data::worlds::world::noise::{FractalNoise, NoiseConfig};
+use crate::game_data::worlds::world::seed::{chunk_seed, SeedRng, WorldSeed};
+use crate::game_data::worlds::world::staged_generation::GenerationRegion;
+use crate::game_data::worlds::world::world_generator::WorldGenerator;
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroU8;
+
+// Salts for the noises and random numbers so that each is different even though they share the
+// world seed.
+const CONTINENT_SALT: u64 = 1;
+const TERRAIN_SALT: u64 = 2;
+const TEMPERATURE_SALT: u64 = 3;
+const HUMIDITY_SALT: u64 = 4;
+const CAVE_SALTS: [u64; 2] = [5, 6];
+const ORE_SALT: u64 = 100;
+const TREE_SALT: u64 = 7;
+
+/// Terrain shaped by a heightmap of layered noise, with biomes picked by temperature and humidity,
+/// caves carved out of it, veins of ore placed in its stone and trees growing on top.  Height is
+/// along the z axis and is unbounded in both directions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoiseWorldGeneratorConfig {
+	pub chunk_edge_len: NonZeroU8,
+	/// Anything below the terrain that isn't some other tile.
+	pub stone_tile: String,
+	/// Fills everything above the terrain up to `sea_level`, making oceans and lakes.
+	pub water_tile: String,
+	pub sea_level: TileCoordType,
+	pub terrain: TerrainConfig,
+	/// Temperature and humidity are in `0.0..=1.0`.
+	pub temperature: NoiseConfig,
+	pub humidity: NoiseConfig,
+	/// The biome with the temperature and humidity closest to that of a column is used for it, the
+	/// heights of nearby biomes are blended so there are no cliffs between them.
+	pub biomes: Vec<BiomeConfig>,
+	pub caves: CaveConfig,
+	pub ores: Vec<OreConfig>,
+	pub trees: TreeConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TerrainConfig {
+	/// The surface height where all the noise is 0.
+	pub base_height: TileCoordType,
+	/// Very low frequency noise that raises continents and islands and sinks oceans.
+	pub continents: NoiseConfig,
+	pub continent_height: f64,
+	/// The hills and valleys on top of the continents, scaled by each biome's `height_variation`.
+	pub detail: NoiseConfig,
+	pub detail_height: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BiomeConfig {
+	pub name: String,
+	pub temperature: f64,
+	pub humidity: f64,
+	/// Added to the terrain height.
+	pub height_offset: f64,
+	/// Multiplies the terrain detail height.
+	pub height_variation: f64,
+	/// The top tile of the terrain.
+	pub surface_tile: String,
+	/// The tile below the surface tile, down to `subsurface_depth` tiles under the surface.
+	pub subsurface_tile: String,
+	pub subsurface_depth: TileCoordType,
+	/// The chance of a tree growing on any one tile of the surface.
+	pub tree_density: f64,
+}
+
+/// Caves are carved where two 3D noises are both close to 0, which makes long winding tunnels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaveConfig {
+	pub noise: NoiseConfig,
+	/// How close to 0 both noises need to be, 0 turns caves off.
+	pub threshold: f64,
+	/// Caves stay this many tiles below the surface.
+	pub min_depth: TileCoordType,
+}
+
+/// Trees are a trunk of logs topped with a cube of leaves, they can grow across chunk borders.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TreeConfig {
+	pub log_tile: String,
+	pub leaves_tile: String,
+	/// The trunk height is picked from `min_height..=max_height`.
+	pub min_height: u8,
+	pub max_height: u8,
+	/// How far the leaves reach out from the top of the trunk.
+	pub leaves_radius: u8,
+}
+
+/// Ore replaces stone in blobs where its 3D noise goes above the threshold.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OreConfig {
+	pub tile: String,
+	/// The lowest and highest z the ore can be in, inclusive.
+	pub min_z: TileCoordType,
+	pub max_z: TileCoordType,
+	pub noise: NoiseConfig,
+	pub threshold: f64,
+}
+
+impl Default for NoiseWorldGeneratorConfig {
+	fn default() -> Self {
+		let biome =
+			|name: &str, temperature, humidity, height_offset, height_variation| BiomeConfig {
+				name: name.to_owned(),
+				temperature,
+				humidity,
+				height_offset,
+				height_variation,
+				..Default::default()
+			};
+		let ore = |tile: &str, min_z, max_z, threshold| OreConfig {
+			tile: tile.to_owned(),
+			min_z,
+			max_z,
+			threshold,
+			..Default::default()
+		};
+		NoiseWorldGeneratorConfig {
+			chunk_edge_len: NonZeroU8::new(32).unwrap(),
+			stone_tile: "stone".to_owned(),
+			water_tile: "water".to_owned(),
+			sea_level: 0,
+			terrain: TerrainConfig::default(),
+			temperature: NoiseConfig::new(1.0 / 512.0, 2),
+			humidity: NoiseConfig::new(1.0 / 512.0, 2),
+			biomes: vec![
+				BiomeConfig {
+					tree_density: 0.002,
+					..biome("plains", 0.5, 0.5, 2.0, 0.5)
+				},
+				BiomeConfig {
+					tree_density: 0.03,
+					..biome("forest", 0.6, 0.8, 4.0, 0.8)
+				},
+				BiomeConfig {
+					surface_tile: "sand".to_owned(),
+					subsurface_tile: "sand".to_owned(),
+					..biome("desert", 0.9, 0.1, 3.0, 0.4)
+				},
+				BiomeConfig {
+					surface_tile: "snow".to_owned(),
+					..biome("tundra", 0.1, 0.4, 2.0, 0.6)
+				},
+				BiomeConfig {
+					surface_tile: "stone".to_owned(),
+					subsurface_tile: "stone".to_owned(),
+					..biome("mountains", 0.3, 0.6, 24.0, 2.5)
+				},
+			],
+			caves: CaveConfig::default(),
+			ores: vec![
+				ore("coal_ore", -128, 64, 0.55),
+				ore("iron_ore", -256, 0, 0.6),
+				ore("gold_ore", TileCoordType::MIN, -128, 0.65),
+			],
+			trees: TreeConfig::default(),
+		}
+	}
+}
+
+impl Default for TerrainConfig {
+	fn default() -> Self {
+		TerrainConfig {
+			base_height: 4,
+			continents: NoiseConfig::new(1.0 / 1024.0, 3),
+			continent_height: 48.0,
+			detail: NoiseConfig::new(1.0 / 96.0, 4),
+			detail_height: 16.0,
+		}
+	}
+}
+
+impl Default for BiomeConfig {
+	fn default() -> Self {
+		BiomeConfig {
+			name: "plains".to_owned(),
+			temperature: 0.5,
+			humidity: 0.5,
+			height_offset: 0.0,
+			height_variation: 1.0,
+			surface_tile: "grass".to_owned(),
+			subsurface_tile: "dirt".to_owned(),
+			subsurface_depth: 3,
+			tree_density: 0.0,
+		}
+	}
+}
+
+impl Default for TreeConfig {
+	fn default() -> Self {
+		TreeConfig {
+			log_tile: "log".to_owned(),
+			leaves_tile: "leaves".to_owned(),
+			min_height: 4,
+			max_height: 6,
+			leaves_radius: 2,
+		}
+	}
+}
+
+impl Default for CaveConfig {
+	fn default() -> Self {
+		CaveConfig {
+			noise: NoiseConfig::new(1.0 / 48.0, 2),
+			threshold: 0.06,
+			min_depth: 4,
+		}
+	}
+}
+
+impl Default for OreConfig {
+	fn default() -> Self {
+		OreConfig {
+			tile: "ore".to_owned(),
+			min_z: TileCoordType::MIN,
+			max_z: 0,
+			noise: NoiseConfig::new(1.0 / 6.0, 1),
+			threshold: 0.6,
+		}
+	}
+}
+
+struct Biome {
+	temperature: f64,
+	humidity: f64,
+	height_offset: f64,
+	height_variation: f64,
+	surface: TileId,
+	subsurface: TileId,
+	subsurface_depth: TileCoordType,
+	tree_density: f64,
+}
+
+struct Caves {
+	noises: [FractalNoise; 2],
+	threshold: f64,
+	min_depth: TileCoordType,
+}
+
+struct Ore {
+	tile: TileId,
+	min_z: TileCoordType,
+	max_z: TileCoordType,
+	noise: FractalNoise,
+	threshold: f64,
+}
+
+struct Trees {
+	log: TileId,
+	leaves: TileId,
+	min_height: TileCoordType,
+	max_height: TileCoordType,
+	leaves_radius: TileCoordType,
+	/// The highest `tree_density` of all the biomes.
+	max_density: f64,
+}
+
+/// What the generator knows about a column of tiles, everything but caves and ores.
+struct Column<'a> {
+	height: TileCoordType,
+	biome: &'a Biome,
+}
+
+pub struct NoiseWorldGenerator {
+	seed: WorldSeed,
+	chunk_edge_len: NonZeroU8,
+	stone: TileId,
+	water: TileId,
+	sea_level: TileCoordType,
+	base_height: TileCoordType,
+	continents: FractalNoise,
+	continent_height: f64,
+	detail: FractalNoise,
+	detail_height: f64,
+	temperature: FractalNoise,
+	humidity: FractalNoise,
+	biomes: Vec<Biome>,
+	caves: Caves,
+	ores: Vec<Ore>,
+	trees: Trees,
+}
+
+impl NoiseWorldGenerator {
+	/// Builds the generator, registering any tiles the config names that aren't registered yet.
+	/// Without any biomes in the config a single default one is used.
+	pub fn from_config(
+		seed: WorldSeed,
+		config: &NoiseWorldGeneratorConfig,
+		tiles: &mut TileRegistry,
+	) -> Self {
+		let mut tile = |name: &str| tiles.register_tile(name.into());
+		let default_biomes = [BiomeConfig::default()];
+		let biomes = if config.biomes.is_empty() {
+			&default_biomes[..]
+		} else {
+			&config.biomes[..]
+		};
+		let biomes = biomes
+			.iter()
+			.map(|biome| Biome {
+				temperature: biome.temperature,
+				humidity: biome.humidity,
+				height_offset: biome.height_offset,
+				height_variation: biome.height_variation,
+				surface: tile(&biome.surface_tile),
+				subsurface: tile(&biome.subsurface_tile),
+				subsurface_depth: biome.subsurface_depth,
+				tree_density: biome.tree_density,
+			})
+			.collect::<Vec<_>>();
+		let ores = config
+			.ores
+			.iter()
+			.enumerate()
+			.map(|(index, ore)| Ore {
+				tile: tile(&ore.tile),
+				min_z: ore.min_z,
+				max_z: ore.max_z,
+				noise: FractalNoise::new(seed, ORE_SALT + index as u64, &ore.noise),
+				threshold: ore.threshold,
+			})
+			.collect();
+		let terrain = &config.terrain;
+		let stone = tile(&config.stone_tile);
+		let water = tile(&config.water_tile);
+		let trees = Trees {
+			log: tile(&config.trees.log_tile),
+			leaves: tile(&config.trees.leaves_tile),
+			min_height: config.trees.min_height.into(),
+			max_height: config.trees.max_height.max(config.trees.min_height).into(),
+			leaves_radius: config.trees.leaves_radius.into(),
+			max_density: biomes
+				.iter()
+				.map(|biome| biome.tree_density)
+				.fold(0.0, f64::max),
+		};
+		NoiseWorldGenerator {
+			seed,
+			chunk_edge_len: config.chunk_edge_len,
+			stone,
+			water,
+			sea_level: config.sea_level,
+			base_height: terrain.base_height,
+			continents: FractalNoise::new(seed, CONTINENT_SALT, &terrain.continents),
+			continent_height: terrain.continent_height,
+			detail: FractalNoise::new(seed, TERRAIN_SALT, &terrain.detail),
+			detail_height: terrain.detail_height,
+			temperature: FractalNoise::new(seed, TEMPERATURE_SALT, &config.temperature),
+			humidity: FractalNoise::new(seed, HUMIDITY_SALT, &config.humidity),
+			biomes,
+			caves: Caves {
+				noises: [
+					FractalNoise::new(seed, CAVE_SALTS[0], &config.caves.noise),
+					FractalNoise::new(seed, CAVE_SALTS[1], &config.caves.noise),
+				],
+				threshold: config.caves.threshold,
+				min_depth: config.caves.min_depth,
+			},
+			ores,
+			trees,
+		}
+	}
+
+	/// The temperature and humidity of a column, both in `0.0..=1.0`.
+	pub fn climate_at(&self, x: TileCoordType, y: TileCoordType) -> (f64, f64) {
+		let (x, y) = (x as f64, y as f64);
+		(
+			(self.temperature.get_2d(x, y) + 1.0) * 0.5,
+			(self.humidity.get_2d(x, y) + 1.0) * 0.5,
+		)
+	}
+
+	/// The z of the top tile of the terrain in a column.
+	pub fn height_at(&self, x: TileCoordType, y: TileCoordType) -> TileCoordType {
+		self.column(x, y).height
+	}
+
+	fn column(&self, x: TileCoordType, y: TileCoordType) -> Column<'_> {
+		let (temperature, humidity) = self.climate_at(x, y);
+		let mut nearest = &self.biomes[0];
+		let mut nearest_distance = f64::INFINITY;
+		let (mut offset, mut variation, mut total_weight) = (0.0, 0.0, 0.0);
+		for biome in self.biomes.iter() {
+			let (dt, dh) = (biome.temperature - temperature, biome.humidity - humidity);
+			let distance = dt * dt + dh * dh;
+			if distance < nearest_distance {
+				nearest = biome;
+				nearest_distance = distance;
+			}
+			let weight = 1.0 / ((distance + 0.0001) * (distance + 0.0001));
+			offset += biome.height_offset * weight;
+			variation += biome.height_variation * weight;
+			total_weight += weight;
+		}
+		let (fx, fy) = (x as f64, y as f64);
+		let height = self.base_height as f64
+			+ offset / total_weight
+			+ self.continents.get_2d(fx, fy) * self.continent_height
+			+ self.detail.get_2d(fx, fy) * self.detail_height * variation / total_weight;
+		Column {
+			height: height.floor() as TileCoordType,
+			biome: nearest,
+		}
+	}
+
+	/// The columns of the chunk with its lowest corner at `origin`, indexed by `x + y * len`.
+	fn chunk_columns(&self, origin: TileCoord, len: usize) -> Vec<Column<'_>> {
+		(0..len * len)
+			.map(|index| {
+				let (x, y) = (
+					(index % len) as TileCoordType,
+					(index / len) as TileCoordType,
+				);
+				self.column(origin.x() + x, origin.y() + y)
+			})
+			.collect()
+	}
+
+	/// The tile at the coordinate before caves are carved out of the terrain.
+	fn tile_in_column(&self, column: &Column, coord: TileCoord) -> TileId {
+		let z = coord.z();
+		if z > column.height {
+			return if z <= self.sea_level {
+				self.water
+			} else {
+				TileId::AIR
+			};
+		}
+		let depth = column.height - z;
+		if depth == 0 {
+			column.biome.surface
+		} else if depth <= column.biome.subsurface_depth {
+			column.biome.subsurface
+		} else {
+			let (x, y) = (coord.x() as f64, coord.y() as f64);
+			self.ores
+				.iter()
+				.filter(|ore| ore.min_z <= z && z <= ore.max_z)
+				.find(|ore| ore.noise.get_3d(x, y, z as f64) > ore.threshold)
+				.map_or(self.stone, |ore| ore.tile)
+		}
+	}
+
+	fn is_cave(&self, column: &Column, coord: TileCoord) -> bool {
+		if self.caves.threshold <= 0.0 || column.height - coord.z() < self.caves.min_depth {
+			return false;
+		}
+		let (x, y, z) = (coord.x() as f64, coord.y() as f64, coord.z() as f64);
+		self.caves
+			.noises
+			.iter()
+			.all(|noise| noise.get_3d(x, y, z).abs() < self.caves.threshold)
+	}
+
+	/// Grows the trees rooted in the center chunk of the region.  Where trees grow only depends on
+	/// the terrain, and they only grow into air with trunks pushing through leaves, so trees of
+	/// neighboring chunks end up the same no matter which chunk grows its trees first.
+	fn grow_trees(&self, region: &mut GenerationRegion) {
+		if self.trees.max_density <= 0.0 {
+			return;
+		}
+		let len = region.chunk_edge_len().get() as TileCoordType;
+		let origin = region.center_origin();
+		let mut rng = SeedRng::new(chunk_seed(self.seed, region.center()) ^ TREE_SALT);
+		for y in origin.y()..origin.y() + len {
+			for x in origin.x()..origin.x() + len {
+				let chance = rng.next_f64();
+				if chance >= self.trees.max_density {
+					continue;
+				}
+				let column = self.column(x, y);
+				if chance >= column.biome.tree_density
+					|| column.height < self.sea_level
+					|| column.height < origin.z()
+					|| column.height >= origin.z() + len
+				{
+					continue;
+				}
+				let heights = (self.trees.max_height - self.trees.min_height) as u64 + 1;
+				let trunk = self.trees.min_height + rng.next_below(heights) as TileCoordType;
+				self.grow_tree(region, TileCoord::new(x, y, column.height), trunk);
+			}
+		}
+	}
+
+	fn grow_tree(&self, region: &mut GenerationRegion, surface: TileCoord, trunk: TileCoordType) {
+		let radius = self.trees.leaves_radius;
+		let top = surface + TileCoord::new(0, 0, trunk);
+		for z in -radius..=radius {
+			for y in -radius..=radius {
+				for x in -radius..=radius {
+					let coord = top + TileCoord::new(x, y, z);
+					if region.get_tile(coord) == Some(TileId::AIR) {
+						region
+							.set_tile(coord, self.trees.leaves)
+							.expect("tile was just read from the region");
+					}
+				}
+			}
+		}
+		for z in 1..=trunk {
+			let coord = surface + TileCoord::new(0, 0, z);
+			match region.get_tile(coord) {
+				Some(tile) if tile == TileId::AIR || tile == self.trees.leaves => {
+					region
+						.set_tile(coord, self.trees.log)
+						.expect("tile was just read from the region");
+				}
+				_ => {}
+			}
+		}
+	}
+}
+
+impl WorldGenerator for NoiseWorldGenerator {
+	fn get_chunk_edge_len(&self) -> NonZeroU8 {
+		self.chunk_edge_len
+	}
+
+	fn seed(&self) -> WorldSeed {
+		self.seed
+	}
+
+	fn generate_tiles_in_chunk(&self, chunk: &mut Chunk) {
+		let len = chunk.chunk_edge_len().get() as usize;
+		let origin = chunk.to_tile_coord(LocalTileCoord::new(0, 0, 0));
+		let columns = self.chunk_columns(origin, len);
+		let highest = columns.iter().map(|c| c.height).max().unwrap_or(0);
+		if origin.z() > highest.max(self.sea_level) {
+			chunk.fill(TileId::AIR);
+			return;
+		}
+		for (coord, tile) in chunk.tiles_mut().iter_mut() {
+			let column = &columns[coord.x() as usize + coord.y() as usize * len];
+			*tile = self.tile_in_column(column, origin + local_offset(coord));
+		}
+	}
+
+	/// The tile at the coordinate after caves are carved out, without any trees.
+	fn generate_tile_at(&self, coord: TileCoord) -> TileId {
+		let column = self.column(coord.x(), coord.y());
+		if self.is_cave(&column, coord) {
+			TileId::AIR
+		} else {
+			self.tile_in_column(&column, coord)
+		}
+	}
+
+	fn carve_chunk(&self, chunk: &mut Chunk) {
+		if self.caves.threshold <= 0.0 {
+			return;
+		}
+		let len = chunk.chunk_edge_len().get() as usize;
+		let origin = chunk.to_tile_coord(LocalTileCoord::new(0, 0, 0));
+		let columns = self.chunk_columns(origin, len);
+		let highest = columns.iter().map(|c| c.height).max().unwrap_or(0);
+		if origin.z() > highest - self.caves.min_depth {
+			return;
+		}
+		for (coord, tile) in chunk.tiles_mut().iter_mut() {
+			let column = &columns[coord.x() as usize + coord.y() as usize * len];
+			if self.is_cave(column, origin + local_offset(coord)) {
+				*tile = TileId::AIR;
+			}
+		}
+	}
+
+	fn stage_radius(&self, stage: GenerationStage) -> ChunkCoordAreaType {
+		match stage {
+			GenerationStage::Featured if self.trees.max_density > 0.0 => {
+				// Trees reach further up than out to the sides.
+				let reach = self.trees.max_height + self.trees.leaves_radius;
+				let len = self.chunk_edge_len.get() as TileCoordType;
+				((reach - 1) / len + 1) as ChunkCoordAreaType
+			}
+			_ => 0,
+		}
+	}
+
+	fn generate_stage(&self, stage: GenerationStage, region: &mut GenerationRegion) {
+		if stage == GenerationStage::Featured {
+			self.grow_trees(region);
+		}
+	}
+}
+
+fn local_offset(coord: LocalTileCoord) -> TileCoord {
+	TileCoord::new(
+		coord.x() as TileCoordType,
+		coord.y() as TileCoordType,
+		coord.z() as TileCoordType,
+	)
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::chunks::chunk::{ChunkCoord, ChunkCoordAABB, TileCoord};
+	use crate::game_data::chunks::Chunks;
+	use crate::game_data::tiles::{TileId, TileRegistry};
+	use crate::game_data::worlds::world::determinism::assert_deterministic;
+	use crate::game_data::worlds::world::noise_generator::{
+		BiomeConfig, NoiseWorldGenerator, NoiseWorldGeneratorConfig,
+	};
+	use crate::game_data::worlds::world::staged_generation::{generate_completely, GenerateNow};
+	use crate::game_data::worlds::world::world_generator::{
+		generate_chunk, WorldGenerator, WorldGeneratorConfig,
+	};
+	use std::num::NonZeroU8;
+	use std::sync::Arc;
+
+	fn small_config() -> NoiseWorldGeneratorConfig {
+		NoiseWorldGeneratorConfig {
+			chunk_edge_len: NonZeroU8::new(16).unwrap(),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn noise_config_from_ron() {
+		let config: WorldGeneratorConfig = ron::from_str(
+			r#"Noise((
+				sea_level: -3,
+				caves: (threshold: 0.0),
+				biomes: [(name: "beach", surface_tile: "sand")],
+				ores: [],
+			))"#,
+		)
+		.unwrap();
+		let config = match config {
+			WorldGeneratorConfig::Noise(config) => config,
+			other => panic!("expected a noise config but got {:?}", other),
+		};
+		assert_eq!(config.sea_level, -3);
+		assert_eq!(config.caves.threshold, 0.0);
+		assert_eq!(config.biomes[0].surface_tile, "sand");
+		assert_eq!(config.biomes[0].subsurface_tile, "dirt");
+		assert_eq!(config.chunk_edge_len.get(), 32);
+		let mut tiles = TileRegistry::default();
+		let generator = WorldGeneratorConfig::Noise(config).build(3, &mut tiles);
+		assert!(tiles.get_tile_id_by_name("sand").is_some());
+		assert!(tiles.get_tile_id_by_name("coal_ore").is_none());
+		assert_eq!(generator.seed(), 3);
+	}
+
+	#[test]
+	fn terrain_features() {
+		let mut tiles = TileRegistry::default();
+		let generator = NoiseWorldGenerator::from_config(11, &small_config(), &mut tiles);
+		let water = tiles.get_tile_id_by_name("water").unwrap();
+		let stone = tiles.get_tile_id_by_name("stone").unwrap();
+
+		let sky = generate_chunk(&generator, ChunkCoord::new(0, 0, 100));
+		assert_eq!(sky.storage_stats().heap_bytes, 0);
+		assert_eq!(sky.iter_tiles().next().unwrap().1, TileId::AIR);
+
+		let (mut above, mut at, mut below, mut ocean) = (0, 0, 0, 0);
+		for i in -40..40 {
+			let (x, y) = (i * 97, i * -53);
+			let height = generator.height_at(x, y);
+			assert_eq!(
+				generator.generate_tile_at(TileCoord::new(x, y, height + 1000)),
+				TileId::AIR
+			);
+			let surface = generator.generate_tile_at(TileCoord::new(x, y, height));
+			assert_ne!(surface, TileId::AIR);
+			if height < 0 {
+				ocean += 1;
+				assert_eq!(generator.generate_tile_at(TileCoord::new(x, y, 0)), water);
+			}
+			for z in height - 40..height - 10 {
+				match generator.generate_tile_at(TileCoord::new(x, y, z)) {
+					TileId::AIR => above += 1,
+					tile if tile == stone => at += 1,
+					_ => below += 1,
+				}
+			}
+		}
+		assert!(ocean > 0, "no oceans found");
+		assert!(above > 0, "no caves found");
+		assert!(below > 0, "no ores found");
+		assert!(at > above + below, "too few stone tiles");
+	}
+
+	#[test]
+	fn biomes_follow_climate() {
+		let mut tiles = TileRegistry::default();
+		let generator = NoiseWorldGenerator::from_config(5, &small_config(), &mut tiles);
+		let sand = tiles.get_tile_id_by_name("sand").unwrap();
+		let snow = tiles.get_tile_id_by_name("snow").unwrap();
+		for i in -200..200 {
+			let (x, y) = (i * 211, i * 173);
+			let height = generator.height_at(x, y);
+			let surface = generator.generate_tile_at(TileCoord::new(x, y, height));
+			let (temperature, humidity) = generator.climate_at(x, y);
+			assert!((0.0..=1.0).contains(&temperature) && (0.0..=1.0).contains(&humidity));
+			if surface == sand {
+				assert!(temperature > 0.5, "desert at temperature {}", temperature);
+			} else if surface == snow {
+				assert!(temperature < 0.5, "tundra at temperature {}", temperature);
+			}
+		}
+	}
+
+	/// Nothing but forest, with small chunks so trees often cross chunk borders.
+	fn forest_config() -> NoiseWorldGeneratorConfig {
+		NoiseWorldGeneratorConfig {
+			chunk_edge_len: NonZeroU8::new(8).unwrap(),
+			sea_level: -1000,
+			biomes: vec![BiomeConfig {
+				tree_density: 0.05,
+				..Default::default()
+			}],
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn trees_cross_chunk_borders() {
+		let mut tiles = TileRegistry::default();
+		let generator = NoiseWorldGenerator::from_config(21, &forest_config(), &mut tiles);
+		let log = tiles.get_tile_id_by_name("log").unwrap();
+		let leaves = tiles.get_tile_id_by_name("leaves").unwrap();
+		let edge = generator.get_chunk_edge_len();
+		let mut chunks = Chunks::new(edge);
+		let (mut trees, mut crossing) = (0, 0);
+		for y in 0..4 {
+			for x in 0..4 {
+				let height = generator.height_at(x * 8, y * 8);
+				let coord = TileCoord::new(x * 8, y * 8, height + 1).to_chunk_local(edge);
+				generate_completely(&mut chunks, &generator, coord.unwrap().0, &mut GenerateNow);
+			}
+		}
+		for x in 0..32 {
+			for y in 0..32 {
+				let base = TileCoord::new(x, y, generator.height_at(x, y) + 1);
+				if chunks.get_tile(base) != Some(log) {
+					continue;
+				}
+				trees += 1;
+				let (chunk, _) = base.to_chunk_local(edge).unwrap();
+				let mut top = base;
+				while chunks.get_tile(top + TileCoord::new(0, 0, 1)) == Some(log) {
+					top += TileCoord::new(0, 0, 1);
+				}
+				assert_eq!(chunks.get_tile(top + TileCoord::new(0, 0, 1)), Some(leaves));
+				let (top_chunk, _) = top.to_chunk_local(edge).unwrap();
+				if top_chunk != chunk {
+					crossing += 1;
+				}
+			}
+		}
+		assert!(trees > 5, "only {} trees grew", trees);
+		assert!(crossing > 0, "no trees crossed a chunk border");
+	}
+
+	#[test]
+	fn noise_generation_is_deterministic() {
+		let coords: Vec<_> =
+			ChunkCoordAABB::new(ChunkCoord::new(-1, 0, -1), ChunkCoord::new(0, 1, 0))
+				.iter()
+				.collect();
+		assert_deterministic(
+			|| {
+				let mut tiles = TileRegistry::default();
+				Arc::new(NoiseWorldGenerator::from_config(
+					99,
+					&forest_config(),
+					&mut tiles,
+				))
+			},
+			&coords,
+		);
+	}
+}
@@ -0,0 +1,416 @@
+use crate::game_data::chunks::chunk::{
+	Chunk, ChunkCoord, ChunkCoordAABB, ChunkCoordAreaType, GenerationStage, LocalTileCoord,
+	TileCoord,
+};
+use crate::game_data::chunks::Chunks;
+use crate::game_data::tiles::TileId;
+use crate::game_data::worlds::world::world_generator::{generate_chunk_terrain, WorldGenerator};
+use std::collections::HashSet;
+use std::num::NonZeroU8;
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum GenerationRegionError {
+	#[error("tile coordinate {0:?} is outside of the generation region {1:?}")]
+	OutOfRegion(TileCoord, ChunkCoordAABB),
+}
+
+/// Where `generate_to_stage` gets the chunks it needs that aren't loaded yet.
+pub trait ChunkSource {
+	/// The chunk with at least its terrain generated, or `None` if it isn't available yet and
+	/// generation has to wait for it.
+	fn provide_chunk(&mut self, generator: &dyn WorldGenerator, coord: ChunkCoord)
+		-> Option<Chunk>;
+}
+
+/// Generates the terrain of chunks right away on the calling thread.
+pub struct GenerateNow;
+
+impl ChunkSource for GenerateNow {
+	fn provide_chunk(
+		&mut self,
+		generator: &dyn WorldGenerator,
+		coord: ChunkCoord,
+	) -> Option<Chunk> {
+		Some(generate_chunk_terrain(generator, coord))
+	}
+}
+
+/// The chunks a generation stage can read and write while generating the chunk at the center, every
+/// chunk in it is loaded and done with the stage before.
+pub struct GenerationRegion<'a> {
+	chunks: &'a mut Chunks,
+	center: ChunkCoord,
+	bounds: ChunkCoordAABB,
+}
+
+impl<'a> GenerationRegion<'a> {
+	pub fn center(&self) -> ChunkCoord {
+		self.center
+	}
+
+	pub fn bounds(&self) -> ChunkCoordAABB {
+		self.bounds
+	}
+
+	pub fn chunk_edge_len(&self) -> NonZeroU8 {
+		self.chunks.chunk_edge_len()
+	}
+
+	/// The tile coordinate of the lowest corner of the center chunk.
+	pub fn center_origin(&self) -> TileCoord {
+		TileCoord::from_chunk_local(
+			self.center,
+			LocalTileCoord::new(0, 0, 0),
+			self.chunk_edge_len(),
+		)
+	}
+
+	pub fn contains(&self, coord: TileCoord) -> bool {
+		self.locate(coord).is_ok()
+	}
+
+	/// The tile at the coordinate, `None` if it is outside of the region.
+	pub fn get_tile(&self, coord: TileCoord) -> Option<TileId> {
+		let (chunk, local) = self.locate(coord).ok()?;
+		self.chunks.get_chunk(&chunk)?.get_tile(local)
+	}
+
+	/// Sets the tile at the coordinate, returning the tile that was previously there.
+	pub fn set_tile(
+		&mut self,
+		coord: TileCoord,
+		tile: TileId,
+	) -> Result<TileId, GenerationRegionError> {
+		let (chunk, local) = self.locate(coord)?;
+		Ok(self
+			.chunks
+			.get_chunk_mut(&chunk)
+			.expect("chunks in a generation region are always loaded")
+			.set_tile(local, tile)
+			.expect("local coordinate was already bounds checked"))
+	}
+
+	fn locate(
+		&self,
+		coord: TileCoord,
+	) -> Result<(ChunkCoord, LocalTileCoord), GenerationRegionError> {
+		coord
+			.to_chunk_local(self.chunk_edge_len())
+			.filter(|(chunk, _local)| self.bounds.contains(*chunk))
+			.ok_or(GenerationRegionError::OutOfRegion(coord, self.bounds))
+	}
+}
+
+/// How far a stage can reach out of its chunk, the chunk-local stages never do.
+fn stage_radius(generator: &dyn WorldGenerator, stage: GenerationStage) -> ChunkCoordAreaType {
+	if stage.is_chunk_local() {
+		0
+	} else {
+		generator.stage_radius(stage)
+	}
+}
+
+/// How far around a chunk every chunk has to be done with the stage before `stage` before the chunk
+/// can get `stage`.  That covers the chunks it writes into and in turn every chunk that writes into
+/// those, so nothing from the stage before lands on top of what this stage wrote.
+fn dependency_radius(generator: &dyn WorldGenerator, stage: GenerationStage) -> ChunkCoordAreaType {
+	let previous = stage
+		.previous()
+		.map_or(0, |previous| stage_radius(generator, previous));
+	stage_radius(generator, stage).saturating_add(previous)
+}
+
+/// Generates the chunk up to at least `target`, along with as much of the chunks around it as that
+/// needs, returns whether the chunk got there.
+///
+/// Chunks that aren't loaded are taken from the source.  With `GenerateNow` this always succeeds,
+/// with a source that hands out chunks later, like a `ChunkGenerationQueue`, every needed chunk
+/// that isn't loaded yet is asked for at once and this has to be called again once they're in.
+pub fn generate_to_stage(
+	chunks: &mut Chunks,
+	generator: &dyn WorldGenerator,
+	coord: ChunkCoord,
+	target: GenerationStage,
+	source: &mut dyn ChunkSource,
+) -> bool {
+	reach_stage(
+		chunks,
+		generator,
+		coord,
+		target,
+		source,
+		&mut HashSet::default(),
+	)
+}
+
+/// `generate_to_stage` skipping the chunks already found in `unreachable`, adding those that can't
+/// reach their stage either.  The dependencies of a chunk overlap with those of its neighbors, so
+/// this keeps them from being walked again and again while the source has yet to provide them.
+fn reach_stage(
+	chunks: &mut Chunks,
+	generator: &dyn WorldGenerator,
+	coord: ChunkCoord,
+	target: GenerationStage,
+	source: &mut dyn ChunkSource,
+	unreachable: &mut HashSet<(ChunkCoord, GenerationStage)>,
+) -> bool {
+	if unreachable.contains(&(coord, target)) {
+		return false;
+	}
+	if !chunks.contains_chunk(&coord) {
+		if let Some(chunk) = source.provide_chunk(generator, coord) {
+			chunks.insert_chunk(chunk);
+		}
+	}
+	let current = match chunks.get_chunk(&coord).map(Chunk::generation_stage) {
+		Some(stage) if stage < GenerationStage::Carved => {
+			chunks.insert_chunk(generate_chunk_terrain(generator, coord));
+			GenerationStage::Carved
+		}
+		Some(stage) => stage,
+		None => GenerationStage::Empty,
+	};
+	if current >= target {
+		return true;
+	}
+	let mut ready = current >= GenerationStage::Carved;
+	let stages = GenerationStage::ALL
+		.iter()
+		.copied()
+		.filter(|&stage| stage > current.max(GenerationStage::Carved) && stage <= target);
+	for stage in stages {
+		let previous = stage
+			.previous()
+			.expect("stage after carving has a previous one");
+		// Going on even if not ready so every missing chunk gets requested at once.
+		for neighbor in ChunkCoordAABB::around(coord, dependency_radius(generator, stage)) {
+			ready &= reach_stage(
+				chunks,
+				generator,
+				neighbor,
+				previous,
+				&mut *source,
+				unreachable,
+			);
+		}
+		if ready {
+			let mut region = GenerationRegion {
+				chunks: &mut *chunks,
+				center: coord,
+				bounds: ChunkCoordAABB::around(coord, stage_radius(generator, stage)),
+			};
+			generator.generate_stage(stage, &mut region);
+			chunks
+				.get_chunk_mut(&coord)
+				.expect("chunk was generated above")
+				.set_generation_stage(stage);
+		}
+	}
+	if !ready {
+		unreachable.insert((coord, target));
+	}
+	ready
+}
+
+/// Generates the chunk through every stage and everything around it that can still write into it,
+/// see `generate_to_stage`.
+pub fn generate_completely(
+	chunks: &mut Chunks,
+	generator: &dyn WorldGenerator,
+	coord: ChunkCoord,
+	source: &mut dyn ChunkSource,
+) -> bool {
+	let mut ready = true;
+	let mut unreachable = HashSet::default();
+	for neighbor in ChunkCoordAABB::around(coord, stage_radius(generator, GenerationStage::LAST)) {
+		ready &= reach_stage(
+			chunks,
+			generator,
+			neighbor,
+			GenerationStage::LAST,
+			&mut *source,
+			&mut unreachable,
+		);
+	}
+	ready
+}
+
+/// How far from a chunk `generate_completely` can load and modify chunks, chunks this close to one
+/// that is kept loaded are worth keeping loaded as well.
+pub fn generation_reach(generator: &dyn WorldGenerator) -> ChunkCoordAreaType {
+	GenerationStage::ALL
+		.iter()
+		.filter(|stage| !stage.is_chunk_local())
+		.fold(
+			stage_radius(generator, GenerationStage::LAST),
+			|reach, &stage| reach.saturating_add(dependency_radius(generator, stage)),
+		)
+}
+
+/// Whether world generation is done with the chunk and won't write into it anymore.
+pub fn is_chunk_generated(
+	chunks: &Chunks,
+	generator: &dyn WorldGenerator,
+	coord: ChunkCoord,
+) -> bool {
+	ChunkCoordAABB::around(coord, stage_radius(generator, GenerationStage::LAST))
+		.iter()
+		.all(|neighbor| match chunks.get_chunk(&neighbor) {
+			Some(chunk) => chunk.generation_stage() >= GenerationStage::LAST,
+			None => false,
+		})
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::chunks::chunk::{
+		Chunk, ChunkCoord, ChunkCoordAABB, ChunkCoordAreaType, GenerationStage, TileCoord,
+	};
+	use crate::game_data::chunks::Chunks;
+	use crate::game_data::tiles::TileId;
+	use crate::game_data::worlds::world::chunk_generation::ChunkGenerationQueue;
+	use crate::game_data::worlds::world::seed::WorldSeed;
+	use crate::game_data::worlds::world::staged_generation::{
+		generate_completely, generate_to_stage, generation_reach, is_chunk_generated, ChunkSource,
+		GenerateNow, GenerationRegion, GenerationRegionError,
+	};
+	use crate::game_data::worlds::world::world_generator::WorldGenerator;
+	use std::num::NonZeroU8;
+	use std::sync::Arc;
+
+	const STONE: TileId = TileId::new(1);
+	const MARKER: TileId = TileId::new(2);
+
+	/// Stone everywhere, its features mark the tile diagonally below the lowest corner of each
+	/// chunk, which is in the neighboring chunk.
+	struct MarkingGenerator;
+
+	impl WorldGenerator for MarkingGenerator {
+		fn get_chunk_edge_len(&self) -> NonZeroU8 {
+			NonZeroU8::new(4).unwrap()
+		}
+
+		fn seed(&self) -> WorldSeed {
+			0
+		}
+
+		fn generate_tiles_in_chunk(&self, chunk: &mut Chunk) {
+			chunk.fill(STONE);
+		}
+
+		fn stage_radius(&self, stage: GenerationStage) -> ChunkCoordAreaType {
+			match stage {
+				GenerationStage::Featured => 1,
+				_ => 0,
+			}
+		}
+
+		fn generate_stage(&self, stage: GenerationStage, region: &mut GenerationRegion) {
+			if stage == GenerationStage::Featured {
+				let coord = region.center_origin() - TileCoord::new(1, 1, 1);
+				assert_eq!(region.set_tile(coord, MARKER), Ok(STONE));
+				let outside = region.center_origin() + TileCoord::new(8, 0, 0);
+				assert!(!region.contains(outside));
+				assert_eq!(region.get_tile(outside), None);
+				assert_eq!(
+					region.set_tile(outside, MARKER),
+					Err(GenerationRegionError::OutOfRegion(outside, region.bounds()))
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn stages_write_into_neighbors() {
+		let generator = MarkingGenerator;
+		let mut chunks = Chunks::new(generator.get_chunk_edge_len());
+		let origin = ChunkCoord::new(0, 0, 0);
+		assert!(generate_to_stage(
+			&mut chunks,
+			&generator,
+			origin,
+			GenerationStage::Featured,
+			&mut GenerateNow
+		));
+		assert_eq!(chunks.len(), 27);
+		let stage = |chunks: &Chunks, coord| chunks.get_chunk(&coord).unwrap().generation_stage();
+		assert_eq!(stage(&chunks, origin), GenerationStage::Featured);
+		assert_eq!(
+			stage(&chunks, ChunkCoord::new(-1, -1, -1)),
+			GenerationStage::Carved
+		);
+		assert_eq!(chunks.get_tile(TileCoord::new(-1, -1, -1)), Some(MARKER));
+		assert_eq!(chunks.get_tile(TileCoord::new(3, 3, 3)), Some(STONE));
+		assert!(!is_chunk_generated(&chunks, &generator, origin));
+
+		assert!(generate_completely(
+			&mut chunks,
+			&generator,
+			origin,
+			&mut GenerateNow
+		));
+		assert!(is_chunk_generated(&chunks, &generator, origin));
+		// Decorating needs the chunks around Featured, which needs the ones around those Carved.
+		assert_eq!(generation_reach(&generator), 2);
+		assert!(chunks
+			.iter_chunks()
+			.all(|chunk| ChunkCoordAABB::around(origin, 2).contains(chunk.location())));
+		assert_eq!(stage(&chunks, origin), GenerationStage::Decorated);
+		assert_eq!(chunks.len(), 125);
+		for neighbor in ChunkCoordAABB::around(origin, 1) {
+			assert!(stage(&chunks, neighbor) >= GenerationStage::Featured);
+		}
+		assert_eq!(
+			stage(&chunks, ChunkCoord::new(2, 2, 2)),
+			GenerationStage::Carved
+		);
+		assert_eq!(chunks.get_tile(TileCoord::new(3, 3, 3)), Some(MARKER));
+	}
+
+	#[test]
+	fn request_missing_chunks_at_once() {
+		let generator = Arc::new(MarkingGenerator);
+		let mut queue = ChunkGenerationQueue::new(generator.clone());
+		let mut chunks = Chunks::new(generator.get_chunk_edge_len());
+		let origin = ChunkCoord::new(0, 0, 0);
+		assert!(!generate_completely(
+			&mut chunks,
+			&*generator,
+			origin,
+			&mut queue
+		));
+		assert_eq!(queue.len(), 125);
+		assert!(chunks.is_empty());
+	}
+
+	/// Never has any chunk, counts how often it was asked for one.
+	struct EmptySource(usize);
+
+	impl ChunkSource for EmptySource {
+		fn provide_chunk(
+			&mut self,
+			_generator: &dyn WorldGenerator,
+			_coord: ChunkCoord,
+		) -> Option<Chunk> {
+			self.0 += 1;
+			None
+		}
+	}
+
+	#[test]
+	fn walk_missing_chunks_once() {
+		let generator = MarkingGenerator;
+		let mut chunks = Chunks::new(generator.get_chunk_edge_len());
+		let mut source = EmptySource(0);
+		let origin = ChunkCoord::new(0, 0, 0);
+		assert!(!generate_completely(
+			&mut chunks,
+			&generator,
+			origin,
+			&mut source
+		));
+		// Once for the origin up to Decorated, the 27 around it up to Featured and the 125 around
+		// those up to Carved.
+		assert_eq!(source.0, 1 + 27 + 125);
+	}
+}
@@ -0,0 +1,362 @@
+use crate::game_data::chunks::chunk::{
+	Chunk, ChunkCoord, ChunkCoordAreaType, GenerationStage, LocalTileCoord, TileCoord,
+	TileCoordType,
+};
+use crate::game_data::chunks::Chunks;
+use crate::game_data::tiles::{TileId, TileRegistry};
+use crate::game_data::worlds::world::noise_generator::{
+	NoiseWorldGenerator, NoiseWorldGeneratorConfig,
+};
+use crate::game_data::worlds::world::seed::WorldSeed;
+use crate::game_data::worlds::world::staged_generation::{
+	generate_completely, GenerateNow, GenerationRegion,
+};
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroU8;
+
+/// Fills in the tiles of new chunks.
+///
+/// A generator is built from the world seed and must generate every chunk as a pure function of
+/// that seed and the chunk's location, no matter the order chunks are generated in or the thread
+/// they are generated on, so that any chunk can be regenerated later and compared to saved data.
+/// Randomness should come from `seed::chunk_seed` rather than from state kept in the generator.
+///
+/// Generation happens in the stages of `GenerationStage`.  The terrain and carving stages only see
+/// the chunk they generate and run on the thread pool, the later stages run on a
+/// `GenerationRegion` around the chunk so features like trees and structures can cross chunk
+/// borders.  As the neighboring chunks get their stages generated in any order, whatever a stage
+/// writes outside its own chunk must come out the same no matter the order, like only ever writing
+/// into air.
+pub trait WorldGenerator: Send + Sync {
+	fn get_chunk_edge_len(&self) -> NonZeroU8;
+
+	/// The world seed this generator was built from.
+	fn seed(&self) -> WorldSeed;
+
+	fn generate_tiles_in_chunk(&self, chunk: &mut Chunk) {
+		let location = chunk.location();
+		let chunk_edge_len = chunk.chunk_edge_len();
+		for (coord, tile) in chunk.tiles_mut().iter_mut() {
+			*tile =
+				self.generate_tile_at(TileCoord::from_chunk_local(location, coord, chunk_edge_len));
+		}
+	}
+
+	/// Used by the default `generate_tiles_in_chunk` to generate one tile at a time.
+	fn generate_tile_at(&self, _coord: TileCoord) -> TileId {
+		TileId::AIR
+	}
+
+	/// Removes caves and the like from the terrain, runs right after `generate_tiles_in_chunk`.
+	fn carve_chunk(&self, _chunk: &mut Chunk) {}
+
+	/// How many chunks away from its own chunk a stage after `Carved` may write to.  A chunk only
+	/// gets the stage once every chunk in reach is done with the stage before, so keep this small.
+	fn stage_radius(&self, _stage: GenerationStage) -> ChunkCoordAreaType {
+		0
+	}
+
+	/// Generates a stage after `Carved` for the chunk at the center of the region.
+	fn generate_stage(&self, _stage: GenerationStage, _region: &mut GenerationRegion) {}
+}
+
+/// Generates the terrain of a new chunk and carves it, the stages that only need the chunk itself.
+pub fn generate_chunk_terrain(generator: &dyn WorldGenerator, coord: ChunkCoord) -> Chunk {
+	let mut chunk = Chunk::new(coord, generator.get_chunk_edge_len());
+	generator.generate_tiles_in_chunk(&mut chunk);
+	chunk.set_generation_stage(GenerationStage::Terrain);
+	generator.carve_chunk(&mut chunk);
+	chunk.set_generation_stage(GenerationStage::Carved);
+	chunk
+}
+
+/// Generates a new chunk at the location through every stage without adding it to any world, the
+/// chunks around it that the later stages need are generated as well and thrown away.
+pub fn generate_chunk(generator: &dyn WorldGenerator, coord: ChunkCoord) -> Chunk {
+	let mut chunks = Chunks::new(generator.get_chunk_edge_len());
+	generate_completely(&mut chunks, generator, coord, &mut GenerateNow);
+	chunks
+		.remove_chunk(&coord)
+		.expect("generated chunk is missing")
+}
+
+/// How a world's chunks get generated, as written in a save's `config.ron`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum WorldGeneratorConfig {
+	Layered(LayeredWorldGeneratorConfig),
+	Noise(Box<NoiseWorldGeneratorConfig>),
+}
+
+impl Default for WorldGeneratorConfig {
+	fn default() -> Self {
+		WorldGeneratorConfig::Layered(LayeredWorldGeneratorConfig::default())
+	}
+}
+
+impl WorldGeneratorConfig {
+	/// The generator's name as written in the configuration.
+	pub fn name(&self) -> &'static str {
+		match self {
+			WorldGeneratorConfig::Layered(_) => "Layered",
+			WorldGeneratorConfig::Noise(_) => "Noise",
+		}
+	}
+
+	/// Builds the generator, registering any tiles it names that aren't registered yet.
+	pub fn build(&self, seed: WorldSeed, tiles: &mut TileRegistry) -> Box<dyn WorldGenerator> {
+		match self {
+			WorldGeneratorConfig::Layered(config) => {
+				Box::new(LayeredWorldGenerator::from_config(seed, config, tiles))
+			}
+			WorldGeneratorConfig::Noise(config) => {
+				Box::new(NoiseWorldGenerator::from_config(seed, config, tiles))
+			}
+		}
+	}
+}
+
+/// A flat world made of horizontal layers of tiles stacked along the z axis.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayeredWorldGeneratorConfig {
+	pub chunk_edge_len: NonZeroU8,
+	/// Where layers overlap the one listed last wins, anything not in a layer is air.
+	pub layers: Vec<LayerConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayerConfig {
+	/// The lowest z of the layer, inclusive.
+	pub bottom: TileCoordType,
+	/// The highest z of the layer, inclusive.
+	pub top: TileCoordType,
+	/// The name of the tile the layer is made of.
+	pub tile: String,
+}
+
+impl Default for LayeredWorldGeneratorConfig {
+	/// Bedrock at the bottom, then stone, dirt and a single layer of grass at z 0.
+	fn default() -> Self {
+		let layer = |bottom, top, tile: &str| LayerConfig {
+			bottom,
+			top,
+			tile: tile.to_owned(),
+		};
+		LayeredWorldGeneratorConfig {
+			chunk_edge_len: NonZeroU8::new(32).unwrap(),
+			layers: vec![
+				layer(-64, -64, "bedrock"),
+				layer(-63, -5, "stone"),
+				layer(-4, -1, "dirt"),
+				layer(0, 0, "grass"),
+			],
+		}
+	}
+}
+
+/// A single layer of a `LayeredWorldGenerator`, covering `bottom..=top` along the z axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layer {
+	pub bottom: TileCoordType,
+	pub top: TileCoordType,
+	pub tile: TileId,
+}
+
+impl Layer {
+	pub fn new(bottom: TileCoordType, top: TileCoordType, tile: TileId) -> Self {
+		Layer { bottom, top, tile }
+	}
+
+	pub fn contains(&self, z: TileCoordType) -> bool {
+		self.bottom <= z && z <= self.top
+	}
+}
+
+pub struct LayeredWorldGenerator {
+	seed: WorldSeed,
+	chunk_edge_len: NonZeroU8,
+	layers: Vec<Layer>,
+}
+
+impl WorldGenerator for LayeredWorldGenerator {
+	fn get_chunk_edge_len(&self) -> NonZeroU8 {
+		self.chunk_edge_len
+	}
+
+	fn seed(&self) -> WorldSeed {
+		self.seed
+	}
+
+	fn generate_tiles_in_chunk(&self, chunk: &mut Chunk) {
+		let bottom = chunk.to_tile_coord(LocalTileCoord::new(0, 0, 0)).z();
+		let slabs: Vec<TileId> = (0..chunk.chunk_edge_len().get() as TileCoordType)
+			.map(|z| self.tile_at_height(bottom + z))
+			.collect();
+		if slabs.iter().all(|&tile| tile == slabs[0]) {
+			chunk.fill(slabs[0]);
+			return;
+		}
+		for (coord, tile) in chunk.tiles_mut().iter_mut() {
+			*tile = slabs[coord.z() as usize];
+		}
+	}
+
+	fn generate_tile_at(&self, coord: TileCoord) -> TileId {
+		self.tile_at_height(coord.z())
+	}
+}
+
+impl LayeredWorldGenerator {
+	/// Where layers overlap the one listed last wins, anything not in a layer is air.
+	pub fn new(seed: WorldSeed, layers: Vec<Layer>) -> Self {
+		Self {
+			seed,
+			chunk_edge_len: NonZeroU8::new(32).unwrap(),
+			layers,
+		}
+	}
+
+	pub fn with_chunk_edge_len(self, chunk_edge_len: NonZeroU8) -> Self {
+		Self {
+			chunk_edge_len,
+			..self
+		}
+	}
+
+	pub fn from_config(
+		seed: WorldSeed,
+		config: &LayeredWorldGeneratorConfig,
+		tiles: &mut TileRegistry,
+	) -> Self {
+		let layers = config
+			.layers
+			.iter()
+			.map(|layer| {
+				let tile = tiles.register_tile(layer.tile.as_str().into());
+				Layer::new(layer.bottom, layer.top, tile)
+			})
+			.collect();
+		LayeredWorldGenerator::new(seed, layers).with_chunk_edge_len(config.chunk_edge_len)
+	}
+
+	pub fn layers(&self) -> &[Layer] {
+		&self.layers
+	}
+
+	pub fn tile_at_height(&self, z: TileCoordType) -> TileId {
+		self.layers
+			.iter()
+			.rev()
+			.find(|layer| layer.contains(z))
+			.map_or(TileId::AIR, |layer| layer.tile)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::chunks::chunk::{ChunkCoord, ChunkCoordAABB, LocalTileCoord};
+	use crate::game_data::tiles::{TileId, TileRegistry};
+	use crate::game_data::worlds::world::determinism::assert_deterministic;
+	use crate::game_data::worlds::world::world_generator::{
+		generate_chunk, Layer, LayerConfig, LayeredWorldGenerator, LayeredWorldGeneratorConfig,
+		WorldGeneratorConfig,
+	};
+	use std::num::NonZeroU8;
+	use std::sync::Arc;
+
+	#[test]
+	fn layered_config_from_ron() {
+		let config: WorldGeneratorConfig = ron::from_str(
+			r#"Layered((
+				chunk_edge_len: 16,
+				layers: [
+					(bottom: -8, top: -1, tile: "stone"),
+					(bottom: 0, top: 0, tile: "grass"),
+				],
+			))"#,
+		)
+		.unwrap();
+		let expected = LayeredWorldGeneratorConfig {
+			chunk_edge_len: NonZeroU8::new(16).unwrap(),
+			layers: vec![
+				LayerConfig {
+					bottom: -8,
+					top: -1,
+					tile: "stone".to_owned(),
+				},
+				LayerConfig {
+					bottom: 0,
+					top: 0,
+					tile: "grass".to_owned(),
+				},
+			],
+		};
+		assert_eq!(config, WorldGeneratorConfig::Layered(expected));
+		let defaulted: LayeredWorldGeneratorConfig = ron::from_str("(layers: [])").unwrap();
+		assert_eq!(defaulted.chunk_edge_len.get(), 32);
+		let default = WorldGeneratorConfig::default();
+		let written = ron::to_string(&default).unwrap();
+		assert_eq!(
+			ron::from_str::<WorldGeneratorConfig>(&written).unwrap(),
+			default
+		);
+	}
+
+	#[test]
+	fn layered_generation() {
+		let mut tiles = TileRegistry::default();
+		let generator = WorldGeneratorConfig::default().build(7, &mut tiles);
+		let stone = tiles.get_tile_id_by_name("stone").unwrap();
+		let dirt = tiles.get_tile_id_by_name("dirt").unwrap();
+		let grass = tiles.get_tile_id_by_name("grass").unwrap();
+		let bedrock = tiles.get_tile_id_by_name("bedrock").unwrap();
+		assert_eq!(generator.seed(), 7);
+
+		let surface = generate_chunk(&*generator, ChunkCoord::new(3, -5, 0));
+		assert_eq!(surface.get_tile(LocalTileCoord::new(0, 0, 0)), Some(grass));
+		assert_eq!(
+			surface.get_tile(LocalTileCoord::new(9, 9, 1)),
+			Some(TileId::AIR)
+		);
+		assert_eq!(surface.storage_stats().palette_len, 2);
+		let below = generate_chunk(&*generator, ChunkCoord::new(0, 0, -1));
+		assert_eq!(below.get_tile(LocalTileCoord::new(0, 0, 31)), Some(dirt));
+		assert_eq!(below.get_tile(LocalTileCoord::new(0, 0, 27)), Some(stone));
+		let deep = generate_chunk(&*generator, ChunkCoord::new(0, 0, -2));
+		assert_eq!(deep.get_tile(LocalTileCoord::new(5, 5, 0)), Some(bedrock));
+		assert_eq!(deep.get_tile(LocalTileCoord::new(5, 5, 1)), Some(stone));
+		let void = generate_chunk(&*generator, ChunkCoord::new(0, 0, -3));
+		assert_eq!(void.storage_stats().heap_bytes, 0);
+		assert_eq!(
+			void.get_tile(LocalTileCoord::new(5, 5, 5)),
+			Some(TileId::AIR)
+		);
+	}
+
+	#[test]
+	fn layered_overlap_and_determinism() {
+		let layers = vec![
+			Layer::new(-10, 10, TileId::new(1)),
+			Layer::new(0, 2, TileId::new(2)),
+		];
+		let generator = LayeredWorldGenerator::new(0, layers.clone())
+			.with_chunk_edge_len(NonZeroU8::new(4).unwrap());
+		assert_eq!(generator.tile_at_height(-10), TileId::new(1));
+		assert_eq!(generator.tile_at_height(1), TileId::new(2));
+		assert_eq!(generator.tile_at_height(3), TileId::new(1));
+		assert_eq!(generator.tile_at_height(11), TileId::AIR);
+		let coords: Vec<_> =
+			ChunkCoordAABB::new(ChunkCoord::new(0, 0, -4), ChunkCoord::new(1, 1, 4))
+				.iter()
+				.collect();
+		assert_deterministic(
+			|| {
+				Arc::new(
+					LayeredWorldGenerator::new(0, layers.clone())
+						.with_chunk_edge_len(NonZeroU8::new(4).unwrap()),
+				)
+			},
+			&coords,
+		);
+	}
+}
@@ -3,6 +3,8 @@ mod client_tui;
 #[cfg(feature = "client_wgpu")]
 pub mod client_wgpu;
 pub mod core;
+#[cfg(feature = "game_data")]
+pub mod game_data;
 #[cfg(feature = "server")]
 pub mod server;
 pub mod universal;
@@ -1,4 +1,6 @@
 use anyhow::Context;
+#[cfg(feature = "server")]
+use mechaenetia::universal::commands::console::ConsoleMode;
 use mechaenetia::{core::ClientType, prelude::*};
 use std::path::PathBuf;
 use structopt::StructOpt;
@@ -24,12 +26,36 @@ pub struct CLIOpts {
 	#[structopt(long)]
 	no_server: bool,
 
+	/// Run a dedicated server without a client that players join over the network on the save's
+	/// `bind_address`, admin commands like `stop`, `save` or `gamerule` are typed into the terminal
+	#[cfg(feature = "server")]
+	#[structopt(long, conflicts_with_all = &["client", "no-server"])]
+	dedicated: bool,
+
+	/// Like `--dedicated` but for running unattended, such as in a container, so command replies go
+	/// to the log and the server keeps running when nothing is connected to its input
+	#[cfg(feature = "server")]
+	#[structopt(long, conflicts_with_all = &["client", "no-server", "dedicated"])]
+	daemon: bool,
+
 	/// Load game configuration file, this will generate a new file then exit if it doesn't exit so
 	/// as to allow it to be filled out manually before actually loading it.
 	#[cfg(feature = "server")]
 	#[structopt(long)]
 	load_game: Option<PathBuf>,
 
+	/// Pack a save into a single archive file then exit, takes the path of the save followed by the
+	/// path to write the archive to.
+	#[cfg(feature = "server")]
+	#[structopt(long, number_of_values = 2, value_names = &["SAVE", "ARCHIVE"], parse(from_os_str))]
+	export_save: Option<Vec<PathBuf>>,
+
+	/// Unpack a save archive into a new save in the `saves` directory then exit, the archive is
+	/// checked before anything is unpacked.
+	#[cfg(feature = "server")]
+	#[structopt(long, parse(from_os_str))]
+	import_save: Option<PathBuf>,
+
 	/// Override the in-game language via the specified language code
 	#[structopt(long)]
 	language: Option<LanguageIdentifier>,
@@ -52,7 +78,43 @@ fn main() -> anyhow::Result<()> {
 
 	let opts = CLIOpts::from_args();
 
-	let client_type = opts.client.unwrap_or(default_client_type);
+	#[cfg(feature = "server")]
+	if let Some(paths) = &opts.export_save {
+		let manifest = mechaenetia::server::save::archive::export_archive(&paths[0], &paths[1])
+			.with_context(|| format!("Failed to export the save at {:?}", paths[0]))?;
+		println!(
+			"Exported the save `{}` with {} files to {:?}",
+			manifest.save_name,
+			manifest.files.len(),
+			paths[1]
+		);
+		return Ok(());
+	}
+	#[cfg(feature = "server")]
+	if let Some(archive) = &opts.import_save {
+		let save = mechaenetia::server::save::SaveConfig::import_archive(
+			archive,
+			mechaenetia::server::save::browser::SAVES_DIR,
+			None,
+		)
+		.with_context(|| format!("Failed to import the save archive {:?}", archive))?;
+		println!("Imported the save to {:?}", save.save_path());
+		return Ok(());
+	}
+
+	#[allow(unused_mut)]
+	let mut console = None;
+	#[cfg(feature = "server")]
+	if opts.daemon {
+		console = Some(ConsoleMode::Daemon);
+	} else if opts.dedicated {
+		console = Some(ConsoleMode::Interactive);
+	}
+	let client_type = if console.is_some() {
+		ClientType::Logger
+	} else {
+		opts.client.unwrap_or(default_client_type)
+	};
 
 	let mut engine = Engine::new(opts.config_dir.unwrap_or(PathBuf::from("./config")))?;
 	engine.override_logging_level(opts.log_level);
@@ -60,10 +122,11 @@ fn main() -> anyhow::Result<()> {
 	engine.load_game_configuration(opts.load_game.or_else(|| {
 		if client_type == ClientType::Logger {
 			tracing::warn!("Logger-only client selected but no server file was set to be loaded, defaulting to `saves/server`");
-			Some(PathBuf::new().join("saves").join("server"))
+			Some(PathBuf::from(mechaenetia::server::save::browser::SAVES_DIR).join("server"))
 		} else {None}
 	}));
 	engine.set_include_server(!opts.no_server);
 	engine.set_client_type(client_type);
+	engine.set_console(console);
 	engine.run().context("Failed to run the engine")
 }
@@ -0,0 +1,189 @@
+//! Runs the `ServerCommand`s typed by admins and players.
+
+use crate::game_data::chunks::chunk::TileCoord;
+use crate::game_data::tiles::TileRegistry;
+use crate::game_data::worlds::Worlds;
+use crate::server::autosave::flush_worlds;
+use crate::server::network::NetworkServer;
+use crate::server::save::settings::GameRules;
+use crate::server::save::SaveConfig;
+use crate::server::tick::{DayTime, DAY_LENGTH_TICKS};
+use crate::universal::commands::server::TimeCommand;
+use crate::universal::commands::{CommandEvent, CommandReply, CommandSource, ServerCommand};
+use crate::universal::local_server::{LocalServerCommand, LocalServerPublicState};
+use crate::universal::network::ServerMessage;
+use bevy::prelude::*;
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn run_server_commands(
+	mut commands: EventReader<CommandEvent<ServerCommand>>,
+	mut replies: EventWriter<CommandReply>,
+	mut server_commands: EventWriter<LocalServerCommand>,
+	public_state: Res<LocalServerPublicState>,
+	mut save_config: ResMut<Option<SaveConfig>>,
+	mut worlds: ResMut<Worlds>,
+	tiles: Res<TileRegistry>,
+	mut day_time: ResMut<DayTime>,
+	mut network: ResMut<NetworkServer>,
+) {
+	for CommandEvent { source, command } in commands.iter() {
+		let running = matches!(
+			*public_state,
+			LocalServerPublicState::Running | LocalServerPublicState::Paused
+		);
+		let reply = match command {
+			ServerCommand::Stop => {
+				server_commands.send(LocalServerCommand::StopServer { force: false });
+				CommandReply::ok(source, "Stopping the server")
+			}
+			ServerCommand::GameRule { rule, value } => match (*save_config).as_mut() {
+				Some(config) => game_rule(source, config, rule.as_deref(), value.as_deref()),
+				None => CommandReply::failed(source, "No save is loaded"),
+			},
+			_ if !running => CommandReply::failed(source, "The server isn't running"),
+			ServerCommand::Save => {
+				let failed_worlds = flush_worlds(&mut worlds);
+				match (*save_config).as_ref().map(SaveConfig::write) {
+					Some(Err(e)) => CommandReply::failed(
+						source,
+						format!("Failed saving the configuration: {}", e),
+					),
+					_ if failed_worlds > 0 => CommandReply::failed(
+						source,
+						format!(
+							"Failed saving {} of {} worlds, see the server log",
+							failed_worlds,
+							worlds.len()
+						),
+					),
+					_ => CommandReply::ok(source, "Saved the game"),
+				}
+			}
+			ServerCommand::List => {
+				let max_players = (*save_config)
+					.as_ref()
+					.map(|config| config.max_players)
+					.unwrap_or_default();
+				let players = network.players().count();
+				let mut list = format!("{} of {} players connected", players, max_players);
+				for player in network.players() {
+					let host = if player.local { ", host" } else { "" };
+					list.push_str(&format!("\n{} ({}{})", player.name, player.language, host));
+				}
+				CommandReply::ok(source, list)
+			}
+			ServerCommand::Kick { player, reason } => {
+				if network.kick(player, reason.clone()) {
+					CommandReply::ok(source, format!("Kicked {}", player))
+				} else {
+					not_connected(source, player)
+				}
+			}
+			ServerCommand::Teleport { player, .. } => {
+				if network.players().any(|p| &p.name == player) {
+					CommandReply::failed(source, "Players don't have positions yet")
+				} else {
+					not_connected(source, player)
+				}
+			}
+			ServerCommand::SetTile { position, tile } => {
+				set_tile(source, &mut worlds, &tiles, *position, tile)
+			}
+			ServerCommand::Say { message } => {
+				let message = format!("[Server] {}", message);
+				network.broadcast(&ServerMessage::Chat(message.clone()));
+				CommandReply::ok(source, message)
+			}
+			ServerCommand::Time(time) => {
+				match time {
+					TimeCommand::Query => {}
+					TimeCommand::Set(ticks) => day_time.0 = *ticks,
+					TimeCommand::Add(ticks) => day_time.0 = day_time.0.saturating_add(*ticks),
+				}
+				CommandReply::ok(
+					source,
+					format!(
+						"It is day {}, tick {} of {}",
+						day_time.day(),
+						day_time.time_of_day(),
+						DAY_LENGTH_TICKS
+					),
+				)
+			}
+		};
+		replies.send(reply);
+	}
+}
+
+fn not_connected(source: &CommandSource, player: &str) -> CommandReply {
+	CommandReply::failed(source, format!("No player named `{}` is connected", player))
+}
+
+/// Sets the tile in the world players spawn in, which every player that has its chunk is sent.
+fn set_tile(
+	source: &CommandSource,
+	worlds: &mut Worlds,
+	tiles: &TileRegistry,
+	[x, y, z]: [i32; 3],
+	name: &str,
+) -> CommandReply {
+	let tile = match tiles.get_tile_id_by_name(name) {
+		Some(tile) => tile,
+		None => return CommandReply::failed(source, format!("There is no tile `{}`", name)),
+	};
+	let world = match worlds.iter_mut().next() {
+		Some(world) => world,
+		None => return CommandReply::failed(source, "No world is loaded"),
+	};
+	match world.set_tile(TileCoord::new(x, y, z), tile) {
+		Some(_previous) => CommandReply::ok(source, format!("Set {} {} {} to {}", x, y, z, name)),
+		None => CommandReply::failed(
+			source,
+			format!("The chunk of {} {} {} isn't loaded", x, y, z),
+		),
+	}
+}
+
+/// Shows or changes the game rules, which are written to the configuration right away.
+fn game_rule(
+	source: &CommandSource,
+	config: &mut SaveConfig,
+	rule: Option<&str>,
+	value: Option<&str>,
+) -> CommandReply {
+	let rule = match rule {
+		Some(rule) => rule,
+		None => {
+			let rules: Vec<_> = GameRules::NAMES
+				.iter()
+				.filter_map(|rule| {
+					let value = config.game_rules.get(rule)?;
+					Some(format!("{} = {}", rule, value))
+				})
+				.collect();
+			return CommandReply::ok(source, rules.join("\n"));
+		}
+	};
+	let value = match value {
+		Some(value) => value,
+		None => {
+			return match config.game_rules.get(rule) {
+				Some(value) => CommandReply::ok(source, format!("{} = {}", rule, value)),
+				None => CommandReply::failed(source, format!("There is no game rule `{}`", rule)),
+			};
+		}
+	};
+	if let Err(e) = config.game_rules.set(rule, value) {
+		return CommandReply::failed(source, e);
+	}
+	match config.write() {
+		Ok(()) => CommandReply::ok(source, format!("{} = {}", rule, value)),
+		Err(e) => CommandReply::failed(
+			source,
+			format!(
+				"Changed `{}` but failed saving the configuration: {}",
+				rule, e
+			),
+		),
+	}
+}
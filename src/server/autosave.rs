@@ -0,0 +1,107 @@
+use crate::game_data::worlds::Worlds;
+use bevy::prelude::*;
+use std::time::Duration;
+
+/// How the worlds of a running server are saved.  Each autosave writes every chunk that changed
+/// since the last one in the background, a limited amount per tick so a tick never stalls on it,
+/// so a crash loses about one interval of changes at most.
+pub struct AutosaveConfig {
+	pub interval: Duration,
+	/// The most region files handed to the background writers per tick, across all worlds.  The
+	/// budget counts region files rather than chunks as saving any chunk rewrites its whole region
+	/// file, with all of the region's dirty chunks written at once.
+	pub regions_per_tick: usize,
+}
+
+impl Default for AutosaveConfig {
+	fn default() -> Self {
+		AutosaveConfig {
+			interval: Duration::from_secs(30),
+			regions_per_tick: 4,
+		}
+	}
+}
+
+/// When the next autosave is due and whether the last one still has chunks to write.
+pub(super) struct Autosave {
+	timer: Timer,
+	saving: bool,
+}
+
+impl Default for Autosave {
+	fn default() -> Self {
+		Autosave {
+			timer: Timer::new(AutosaveConfig::default().interval, true),
+			saving: false,
+		}
+	}
+}
+
+pub(super) fn autosave_worlds(
+	time: Res<Time>,
+	config: Res<AutosaveConfig>,
+	mut autosave: ResMut<Autosave>,
+	mut worlds: ResMut<Worlds>,
+) {
+	if config.is_changed() {
+		autosave.timer.set_duration(config.interval);
+	}
+	if autosave.timer.tick(time.delta()).just_finished() {
+		if autosave.saving {
+			warn!(
+				"Autosave didn't finish within {:?}, saving more regions per tick would keep up",
+				config.interval
+			);
+		}
+		trace!("Autosave started");
+		autosave.saving = true;
+	}
+	if !autosave.saving {
+		return;
+	}
+	let mut budget = config.regions_per_tick;
+	let mut remaining = 0;
+	for world in worlds.iter_mut().filter(|world| world.storage().is_some()) {
+		match world.autosave_chunks(budget) {
+			Ok(started) => budget -= started,
+			Err(e) => {
+				// Whatever failed likely fails again right away, so this autosave is given up
+				// instead of retrying every tick, the chunks stay dirty for the next one.
+				error!(
+					"Failed autosaving chunks of world `{}`, retrying in {:?}: {}",
+					world.name(),
+					config.interval,
+					e
+				);
+				autosave.saving = false;
+				return;
+			}
+		}
+		remaining += world.dirty_chunk_count();
+	}
+	if remaining == 0 {
+		trace!("Autosave done");
+		autosave.saving = false;
+	}
+}
+
+/// Writes every changed chunk of every world before returning, for when the server stops.
+/// Returns how many worlds failed to save.
+pub(super) fn flush_worlds(worlds: &mut Worlds) -> usize {
+	let mut failed = 0;
+	for world in worlds.iter_mut() {
+		match world.flush_chunks() {
+			Ok(saved) => info!("Saved {} chunks of world `{}`", saved, world.name()),
+			Err(e) => {
+				error!(
+					"Failed saving chunks of world `{}`, changes since the last autosave are \
+					 lost: {}",
+					world.name(),
+					e
+				);
+				failed += 1;
+			}
+		}
+	}
+	failed
+}
@@ -0,0 +1,62 @@
+use crate::game_data::worlds::world::ChunkMetrics;
+use crate::game_data::worlds::Worlds;
+use bevy::diagnostic::{Diagnostic, DiagnosticId, Diagnostics};
+use bevy::prelude::*;
+use std::time::Instant;
+
+pub const LOADED_CHUNKS: DiagnosticId =
+	DiagnosticId::from_u128(63071846829865981908676017377737657423);
+pub const DIRTY_CHUNKS: DiagnosticId =
+	DiagnosticId::from_u128(62197346563190991702654030705090450668);
+pub const REQUESTED_CHUNKS: DiagnosticId =
+	DiagnosticId::from_u128(289217903655878720275976977989577260203);
+pub const UNLOADING_CHUNKS: DiagnosticId =
+	DiagnosticId::from_u128(88651700915715426822412918411826296749);
+pub const CHUNK_TICKETS: DiagnosticId =
+	DiagnosticId::from_u128(206934390376275963093430331015881542202);
+
+const HISTORY_LEN: usize = 20;
+
+pub(super) fn setup_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+	diagnostics.add(Diagnostic::new(LOADED_CHUNKS, "loaded_chunks", HISTORY_LEN));
+	diagnostics.add(Diagnostic::new(DIRTY_CHUNKS, "dirty_chunks", HISTORY_LEN));
+	diagnostics.add(Diagnostic::new(
+		REQUESTED_CHUNKS,
+		"requested_chunks",
+		HISTORY_LEN,
+	));
+	diagnostics.add(Diagnostic::new(
+		UNLOADING_CHUNKS,
+		"unloading_chunks",
+		HISTORY_LEN,
+	));
+	diagnostics.add(Diagnostic::new(CHUNK_TICKETS, "chunk_tickets", HISTORY_LEN));
+}
+
+/// Unloads the chunks no ticket holds on to anymore, so memory stays bounded as players roam.
+pub(super) fn unload_chunks(mut worlds: ResMut<Worlds>) {
+	let now = Instant::now();
+	for world in worlds.iter_mut() {
+		match world.tick_chunk_unloading(now) {
+			Ok(0) => {}
+			Ok(unloaded) => trace!("Unloaded {} chunks of world `{}`", unloaded, world.name()),
+			Err(e) => error!(
+				"Failed saving chunks of world `{}` to unload them: {}",
+				world.name(),
+				e
+			),
+		}
+	}
+}
+
+pub(super) fn measure_chunks(worlds: Res<Worlds>, mut diagnostics: ResMut<Diagnostics>) {
+	let mut metrics = ChunkMetrics::default();
+	for world in worlds.iter() {
+		metrics += world.chunk_metrics();
+	}
+	diagnostics.add_measurement(LOADED_CHUNKS, metrics.loaded as f64);
+	diagnostics.add_measurement(DIRTY_CHUNKS, metrics.dirty as f64);
+	diagnostics.add_measurement(REQUESTED_CHUNKS, metrics.requested as f64);
+	diagnostics.add_measurement(UNLOADING_CHUNKS, metrics.unloading as f64);
+	diagnostics.add_measurement(CHUNK_TICKETS, metrics.tickets as f64);
+}
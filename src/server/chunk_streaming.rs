@@ -0,0 +1,280 @@
+//! Sends players the chunks around them, then the tiles that change in those chunks.
+
+use crate::game_data::chunks::chunk::{Chunk, ChunkCoord};
+use crate::game_data::chunks::region_file::EncodedChunk;
+use crate::game_data::tiles::TileRegistry;
+use crate::game_data::worlds::world::{TileChange, World};
+use crate::game_data::worlds::{WorldId, Worlds};
+use crate::server::network::NetworkServer;
+use crate::server::save::SaveConfig;
+use crate::universal::network::protocol::{ChunkView, ServerMessage};
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::num::NonZeroU8;
+
+/// The most chunks sent to a player per update, closest first, so a player joining doesn't hold up
+/// the server or everything else sent to them.
+pub const MAX_CHUNKS_SENT_PER_UPDATE: usize = 16;
+
+/// What a player was sent of the world they are in.
+#[derive(Debug, Default)]
+pub struct PlayerChunks {
+	view: Option<(WorldId, ChunkView)>,
+	/// How many tile names were sent, tiles are only ever added.
+	tiles_sent: usize,
+	/// The revision of each chunk the player has, as of when it or its last changes were sent.
+	sent: HashMap<ChunkCoord, u64>,
+}
+
+impl PlayerChunks {
+	/// The messages that bring the player up to date with the world around the view: the tiles and
+	/// the view if they changed, the tile changes to the chunks the player has and then the closest
+	/// chunks in view they don't have yet.  `changes` are the tile changes of the world since the
+	/// last update, by the chunk they are in.
+	///
+	/// A chunk that changed by other means than `World::set_tile` is sent whole again.
+	pub fn update(
+		&mut self,
+		world: &World,
+		tiles: &TileRegistry,
+		view: ChunkView,
+		changes: &HashMap<ChunkCoord, Vec<TileChange>>,
+	) -> Vec<ServerMessage> {
+		let mut messages = Vec::new();
+		if tiles.len() != self.tiles_sent {
+			let names = tiles.iter().map(|(_id, name)| name.to_string()).collect();
+			messages.push(ServerMessage::Tiles(names));
+			self.tiles_sent = tiles.len();
+		}
+
+		if self.view != Some((world.id(), view)) {
+			if self.view.map(|(id, _view)| id) != Some(world.id()) {
+				self.sent.clear();
+			}
+			self.sent.retain(|&coord, _revision| view.keeps(coord));
+			messages.push(ServerMessage::View {
+				world: world.name().to_string(),
+				chunk_edge_len: world.chunks().chunk_edge_len(),
+				view,
+			});
+			self.view = Some((world.id(), view));
+		}
+
+		let mut deltas = Vec::new();
+		let mut outdated = Vec::new();
+		self.sent.retain(|coord, revision| {
+			// An unloaded chunk is sent again once it is back, as its revision starts over.
+			let chunk = match world.get_chunk(coord) {
+				Some(chunk) => chunk,
+				None => return false,
+			};
+			if *revision == chunk.revision() {
+				return true;
+			}
+			// Every tile change counts up the revision once.
+			let changed = changes.get(coord).map_or(&[][..], Vec::as_slice);
+			if *revision + changed.len() as u64 == chunk.revision() {
+				deltas.extend_from_slice(changed);
+			} else {
+				outdated.push(chunk);
+			}
+			*revision = chunk.revision();
+			true
+		});
+		for chunk in outdated {
+			messages.push(ServerMessage::Chunk(EncodedChunk::new(chunk)));
+		}
+		if !deltas.is_empty() {
+			messages.push(ServerMessage::TileChanges(deltas));
+		}
+
+		let sent = &self.sent;
+		let mut missing: Vec<&Chunk> = world
+			.chunks()
+			.iter_chunks()
+			.filter(|chunk| {
+				view.contains(chunk.location()) && !sent.contains_key(&chunk.location())
+			})
+			.filter(|chunk| world.is_chunk_generated(chunk.location()))
+			.collect();
+		missing.sort_by_key(|chunk| (chunk.location() - view.center).length_squared());
+		for chunk in missing.into_iter().take(MAX_CHUNKS_SENT_PER_UPDATE) {
+			self.sent.insert(chunk.location(), chunk.revision());
+			messages.push(ServerMessage::Chunk(EncodedChunk::new(chunk)));
+		}
+		messages
+	}
+}
+
+/// Groups the tile changes by the chunk they are in, keeping their order.
+pub fn changes_by_chunk(
+	changes: Vec<TileChange>,
+	chunk_edge_len: NonZeroU8,
+) -> HashMap<ChunkCoord, Vec<TileChange>> {
+	let mut by_chunk: HashMap<ChunkCoord, Vec<TileChange>> = HashMap::default();
+	for change in changes {
+		if let Some((chunk, _local)) = change.coord.to_chunk_local(chunk_edge_len) {
+			by_chunk.entry(chunk).or_default().push(change);
+		}
+	}
+	by_chunk
+}
+
+/// Streams the world to the players once an update, with the tile changes of every tick since the
+/// last update in one batch.  Players don't move yet, they all see the spawn of the first world.
+pub(super) fn stream_chunks(
+	mut worlds: ResMut<Worlds>,
+	tiles: Res<TileRegistry>,
+	save_config: Res<Option<SaveConfig>>,
+	mut network: ResMut<NetworkServer>,
+) {
+	// Taken from every world even without players so they don't pile up.
+	let changes: Vec<Vec<TileChange>> = worlds.iter_mut().map(World::take_tile_changes).collect();
+	let radius = match (*save_config).as_ref() {
+		Some(save_config) => save_config.game_rules.spawn_chunk_radius,
+		None => return,
+	};
+	let (world, changes) = match worlds.iter().zip(changes).next() {
+		Some(first) => first,
+		None => return,
+	};
+	let view = ChunkView {
+		center: ChunkCoord::new(0, 0, 0),
+		radius,
+	};
+	let changes = changes_by_chunk(changes, world.chunks().chunk_edge_len());
+	network.stream_chunks(world, &tiles, view, &changes);
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::chunks::chunk::{ChunkCoord, ChunkCoordAABB, TileCoord};
+	use crate::game_data::tiles::{TileId, TileRegistry};
+	use crate::game_data::worlds::world::world_generator::{Layer, LayeredWorldGenerator};
+	use crate::game_data::worlds::world::TileChange;
+	use crate::game_data::worlds::Worlds;
+	use crate::server::chunk_streaming::{
+		changes_by_chunk, PlayerChunks, MAX_CHUNKS_SENT_PER_UPDATE,
+	};
+	use crate::universal::network::protocol::{ChunkView, ServerMessage};
+	use crate::universal::network::world_cache::WorldCache;
+
+	/// Hands the messages to the client's cache, returning how many chunks there were.
+	fn receive(cache: &mut WorldCache, messages: Vec<ServerMessage>) -> usize {
+		let mut chunks = 0;
+		for message in messages {
+			match message {
+				ServerMessage::Tiles(names) => cache.set_tiles(&names).unwrap(),
+				ServerMessage::View {
+					world,
+					chunk_edge_len,
+					view,
+				} => cache.set_view(&world, chunk_edge_len, view),
+				ServerMessage::Chunk(chunk) => {
+					cache.insert_chunk(&chunk).unwrap();
+					chunks += 1;
+				}
+				ServerMessage::TileChanges(changes) => cache.apply_tile_changes(&changes).unwrap(),
+				other => panic!("unexpected message {:?}", other),
+			}
+		}
+		chunks
+	}
+
+	#[test]
+	fn stream_chunks_and_changes() {
+		let mut tiles = TileRegistry::default();
+		let stone = tiles.register_tile("stone".into());
+		let mut worlds = Worlds::default();
+		let world_id = worlds.create_world(
+			"overworld".into(),
+			Box::new(LayeredWorldGenerator::new(
+				0,
+				vec![Layer::new(-8, 0, stone)],
+			)),
+		);
+		let world = worlds.get_world_mut(world_id);
+		let view = ChunkView {
+			center: ChunkCoord::new(0, 0, 0),
+			radius: 1,
+		};
+		for coord in ChunkCoordAABB::around(view.center, 2).iter() {
+			world.get_or_generate_chunk_now(coord).unwrap();
+		}
+		let edge = world.chunks().chunk_edge_len();
+
+		// The 27 chunks in view arrive over two updates, after the tiles and the view.
+		let mut player = PlayerChunks::default();
+		let mut cache = WorldCache::default();
+		let messages = player.update(world, &tiles, view, &Default::default());
+		assert_eq!(
+			messages[0],
+			ServerMessage::Tiles(vec!["air".into(), "stone".into()])
+		);
+		assert!(matches!(messages[1], ServerMessage::View { .. }));
+		assert_eq!(receive(&mut cache, messages), MAX_CHUNKS_SENT_PER_UPDATE);
+		assert_eq!(
+			receive(
+				&mut cache,
+				player.update(world, &tiles, view, &Default::default())
+			),
+			27 - MAX_CHUNKS_SENT_PER_UPDATE
+		);
+		assert!(player
+			.update(world, &tiles, view, &Default::default())
+			.is_empty());
+		assert_eq!(cache.surface_tile(5, -20), Some(stone));
+
+		// Tile changes go out as they are, other changes send the chunk again.
+		let dug = TileCoord::new(5, -20, 0);
+		world.set_tile(dug, TileId::AIR).unwrap();
+		// Out of view, the player gets it with the chunk.
+		world.set_tile(TileCoord::new(70, 0, 5), stone).unwrap();
+		let changes = changes_by_chunk(world.take_tile_changes(), edge);
+		let messages = player.update(world, &tiles, view, &changes);
+		assert_eq!(
+			messages,
+			vec![ServerMessage::TileChanges(vec![TileChange {
+				coord: dug,
+				tile: TileId::AIR
+			}])]
+		);
+		receive(&mut cache, messages);
+		assert_eq!(cache.get_tile(dug), Some(TileId::AIR));
+		world
+			.get_chunk_mut(&ChunkCoord::new(0, -1, 0))
+			.unwrap()
+			.fill(stone);
+		let messages = player.update(world, &tiles, view, &Default::default());
+		assert!(matches!(messages[..], [ServerMessage::Chunk(_)]));
+		receive(&mut cache, messages);
+		assert_eq!(cache.get_tile(dug), Some(stone));
+
+		// Moving the view sends only what's new in it, the client drops what fell out of it.
+		let moved = ChunkView {
+			center: ChunkCoord::new(1, 0, 0),
+			radius: 1,
+		};
+		assert_eq!(
+			receive(
+				&mut cache,
+				player.update(world, &tiles, moved, &Default::default())
+			),
+			9
+		);
+		assert_eq!(cache.chunks().unwrap().len(), 36);
+		assert_eq!(cache.get_tile(TileCoord::new(70, 0, 5)), Some(stone));
+		let moved = ChunkView {
+			center: ChunkCoord::new(2, 0, 0),
+			radius: 0,
+		};
+		assert_eq!(
+			receive(
+				&mut cache,
+				player.update(world, &tiles, moved, &Default::default())
+			),
+			0
+		);
+		assert_eq!(cache.chunks().unwrap().len(), 18);
+	}
+}
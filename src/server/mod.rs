@@ -1,6 +1,14 @@
+pub mod admin;
+pub mod autosave;
+pub mod chunk_loading;
+pub mod chunk_streaming;
+pub mod network;
 pub mod save;
 mod states;
+pub mod tick;
 
+use crate::game_data::tiles::TileRegistry;
+use crate::game_data::worlds::Worlds;
 use crate::universal::local_server::LocalServerPublicState;
 use bevy::app::PluginGroupBuilder;
 use bevy::prelude::*;
@@ -10,6 +18,11 @@ pub struct ServerPluginGroup;
 
 struct ServerPlugin;
 
+/// How many players are connected from somewhere else than the local client.  The single-player
+/// game only pauses automatically while there are none.
+#[derive(Default)]
+pub struct RemotePlayers(pub usize);
+
 impl PluginGroup for ServerPluginGroup {
 	fn build(&mut self, group: &mut PluginGroupBuilder) {
 		group
@@ -20,7 +33,24 @@ impl PluginGroup for ServerPluginGroup {
 
 impl Plugin for ServerPlugin {
 	fn build(&self, app: &mut AppBuilder) {
+		let network = network::NetworkServer::default();
 		app.insert_resource(LocalServerPublicState::Off)
-			.init_resource::<Option<save::SaveConfig>>();
+			.insert_resource(network.loopback())
+			.insert_resource(network)
+			.init_resource::<Option<save::SaveConfig>>()
+			.init_resource::<Option<save::backup::BackgroundBackup>>()
+			.init_resource::<Worlds>()
+			.init_resource::<RemotePlayers>()
+			.init_resource::<TileRegistry>()
+			.init_resource::<tick::ServerTick>()
+			.init_resource::<tick::DayTime>()
+			.init_resource::<autosave::AutosaveConfig>()
+			.init_resource::<autosave::Autosave>()
+			.init_resource::<network::NetworkServerConfig>()
+			.add_startup_system(chunk_loading::setup_diagnostics.system())
+			.add_system(network::serve_players.system())
+			.add_system(admin::run_server_commands.system())
+			.add_system(chunk_streaming::stream_chunks.system())
+			.add_system(network::send_command_replies.system());
 	}
 }
@@ -0,0 +1,547 @@
+//! Lets players join the server, from other processes over TCP and from the client the server runs
+//! in over a loopback connection.
+
+use crate::game_data::chunks::chunk::ChunkCoord;
+use crate::game_data::tiles::TileRegistry;
+use crate::game_data::worlds::world::{TileChange, World};
+use crate::server::chunk_streaming::PlayerChunks;
+use crate::server::save::SaveConfig;
+use crate::server::RemotePlayers;
+use crate::universal::commands::{CommandLine, CommandReply, CommandSource};
+use crate::universal::exit::Exiting;
+use crate::universal::local_server::LocalServerPublicState;
+use crate::universal::network::connection::Connection;
+use crate::universal::network::loopback::LoopbackListener;
+use crate::universal::network::protocol::{
+	is_valid_player_name, ChunkView, ClientMessage, DisconnectReason, ServerMessage, GAME_VERSION,
+	PROTOCOL_VERSION,
+};
+use crate::universal::network::tcp::TcpServer;
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// How long a connection has to send its `Hello` before it is closed.
+pub const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many connections from other processes may be waiting to send their `Hello` at once, those
+/// beyond are refused as if the server was full.
+pub const MAX_HANDSHAKING: usize = 16;
+
+/// Whether the server accepts players from other processes, on the save's `bind_address`.  Only
+/// dedicated servers do for now.
+#[derive(Debug, Default, Clone)]
+pub struct NetworkServerConfig {
+	pub listen: bool,
+}
+
+/// A player that joined.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Player {
+	pub name: String,
+	/// The language the player plays in, like `en-US`.
+	pub language: String,
+	/// Joined over a loopback connection from this process, so it is the player hosting the game.
+	pub local: bool,
+}
+
+struct Client {
+	connection: Connection<ServerMessage, ClientMessage>,
+	local: bool,
+	connected_at: Instant,
+	/// Set once the player's `Hello` was accepted.
+	player: Option<Player>,
+	chunks: PlayerChunks,
+}
+
+/// The connections of the players, open while the server is running.
+#[derive(Default)]
+pub struct NetworkServer {
+	listener: Option<TcpServer>,
+	/// Not retried until the server is started again, so a taken port is only reported once.
+	bind_failed: bool,
+	loopback: LoopbackListener,
+	clients: Vec<Client>,
+}
+
+impl NetworkServer {
+	/// Where clients in this process connect to, always accepted while the server is running.
+	pub fn loopback(&self) -> LoopbackListener {
+		self.loopback.clone()
+	}
+
+	/// The players that joined.
+	pub fn players(&self) -> impl Iterator<Item = &Player> {
+		self.clients
+			.iter()
+			.filter(|client| !client.connection.is_closed())
+			.filter_map(|client| client.player.as_ref())
+	}
+
+	/// How many of the players joined from other processes.
+	pub fn remote_players(&self) -> usize {
+		self.players().filter(|player| !player.local).count()
+	}
+
+	/// Disconnects the player, returns false if nobody of that name is connected.
+	pub fn kick(&mut self, name: &str, reason: Option<String>) -> bool {
+		match self.client_mut(name) {
+			Some(client) => {
+				info!("Kicking {}", name);
+				client
+					.connection
+					.disconnect(DisconnectReason::Kicked(reason));
+				true
+			}
+			None => false,
+		}
+	}
+
+	/// Sends the message to every player that joined.
+	pub fn broadcast(&mut self, message: &ServerMessage) {
+		for client in self.clients.iter_mut().filter(|c| c.player.is_some()) {
+			// A failed send shows up when polling.
+			let _ = client.connection.send(message);
+		}
+	}
+
+	/// Sends the message to the player, returns false if nobody of that name is connected.
+	pub fn send_to(&mut self, name: &str, message: &ServerMessage) -> bool {
+		match self.client_mut(name) {
+			Some(client) => {
+				let _ = client.connection.send(message);
+				true
+			}
+			None => false,
+		}
+	}
+
+	/// Sends every player what changed of the world around the view, see `PlayerChunks::update`.
+	pub fn stream_chunks(
+		&mut self,
+		world: &World,
+		tiles: &TileRegistry,
+		view: ChunkView,
+		changes: &HashMap<ChunkCoord, Vec<TileChange>>,
+	) {
+		for client in self.clients.iter_mut() {
+			if client.player.is_none() || client.connection.is_closed() {
+				continue;
+			}
+			for message in client.chunks.update(world, tiles, view, changes) {
+				let _ = client.connection.send(&message);
+			}
+		}
+	}
+
+	fn client_mut(&mut self, name: &str) -> Option<&mut Client> {
+		self.clients.iter_mut().find(|client| {
+			!client.connection.is_closed()
+				&& client.player.as_ref().map(|p| p.name.as_str()) == Some(name)
+		})
+	}
+
+	/// Starts accepting players from other processes unless it already does or failed to.
+	fn listen(&mut self, address: SocketAddr) {
+		if self.listener.is_some() || self.bind_failed {
+			return;
+		}
+		match TcpServer::bind(address) {
+			Ok(listener) => {
+				info!("Accepting players on {}", address);
+				self.listener = Some(listener);
+			}
+			Err(e) => {
+				error!("Failed listening for players on {}: {}", address, e);
+				self.bind_failed = true;
+			}
+		}
+	}
+
+	/// Closes every connection, including those not accepted yet, and stops listening.
+	fn stop(&mut self, reason: DisconnectReason) {
+		for transport in self.loopback.accept() {
+			let mut connection: Connection<ServerMessage, ClientMessage> =
+				Connection::new(Box::new(transport), Instant::now());
+			connection.disconnect(reason.clone());
+		}
+		for client in self.clients.iter_mut() {
+			client.connection.disconnect(reason.clone());
+		}
+		self.clients.clear();
+		if self.listener.take().is_some() {
+			info!("Stopped accepting players");
+		}
+		self.bind_failed = false;
+	}
+
+	fn accept(&mut self, now: Instant) {
+		let accepted = match self.listener.as_ref().map(TcpServer::accept) {
+			Some(Ok(accepted)) => accepted,
+			Some(Err(e)) => {
+				warn!("Failed accepting a connection: {}", e);
+				Vec::new()
+			}
+			None => Vec::new(),
+		};
+		let accepted = accepted
+			.into_iter()
+			.map(|transport| (Connection::new(Box::new(transport), now), false));
+		let local = self
+			.loopback
+			.accept()
+			.into_iter()
+			.map(|transport| (Connection::new(Box::new(transport), now), true));
+		for (mut connection, local) in accepted.chain(local).collect::<Vec<_>>() {
+			debug!("Connection from {}", connection.peer());
+			if !local && self.handshaking() >= MAX_HANDSHAKING {
+				debug!("Refused {}, too many are joining", connection.peer());
+				connection.disconnect(DisconnectReason::ServerFull);
+				continue;
+			}
+			self.clients.push(Client {
+				connection,
+				local,
+				connected_at: now,
+				player: None,
+				chunks: PlayerChunks::default(),
+			});
+		}
+	}
+
+	/// How many connections from other processes didn't send their `Hello` yet.
+	fn handshaking(&self) -> usize {
+		self.clients
+			.iter()
+			.filter(|client| !client.local && client.player.is_none())
+			.filter(|client| !client.connection.is_closed())
+			.count()
+	}
+
+	/// Accepts new connections, lets players join and returns the command lines they sent.
+	pub fn update(&mut self, now: Instant, save_config: &SaveConfig) -> Vec<CommandLine> {
+		self.accept(now);
+		let mut commands = Vec::new();
+		for index in 0..self.clients.len() {
+			// Kicked ones are only removed below.
+			if self.clients[index].connection.is_closed() {
+				continue;
+			}
+			let client = &mut self.clients[index];
+			if client.player.is_none()
+				&& now.saturating_duration_since(client.connected_at) >= HELLO_TIMEOUT
+			{
+				debug!("{} didn't say hello in time", client.connection.peer());
+				client.connection.disconnect(DisconnectReason::TimedOut);
+				continue;
+			}
+			let messages = match self.clients[index].connection.poll(now) {
+				Ok(messages) => messages,
+				Err(e) => {
+					let client = &self.clients[index];
+					match &client.player {
+						Some(player) => info!("{} left: {}", player.name, e.reason()),
+						None => debug!("{} disconnected: {}", client.connection.peer(), e),
+					}
+					continue;
+				}
+			};
+			for message in messages {
+				match message {
+					ClientMessage::Hello {
+						protocol_version,
+						game_version,
+						language,
+						player_name,
+					} if self.clients[index].player.is_none() => {
+						let reason = if protocol_version != PROTOCOL_VERSION {
+							Some(DisconnectReason::incompatible())
+						} else if self.players().count() >= save_config.max_players as usize {
+							Some(DisconnectReason::ServerFull)
+						} else if !is_valid_player_name(&player_name) {
+							Some(DisconnectReason::InvalidName)
+						} else if self.players().any(|p| p.name == player_name) {
+							Some(DisconnectReason::NameTaken)
+						} else {
+							None
+						};
+						let client = &mut self.clients[index];
+						if let Some(reason) = reason {
+							info!(
+								"Refused {} from {} running version {}: {}",
+								player_name,
+								client.connection.peer(),
+								game_version,
+								reason
+							);
+							client.connection.disconnect(reason);
+							break;
+						}
+						info!(
+							"{} joined from {} running version {}",
+							player_name,
+							client.connection.peer(),
+							game_version
+						);
+						let _ = client.connection.send(&ServerMessage::Welcome {
+							game_version: GAME_VERSION.to_owned(),
+							motd: save_config.motd.clone(),
+						});
+						client.player = Some(Player {
+							name: player_name,
+							language,
+							local: client.local,
+						});
+					}
+					ClientMessage::Command(line) => match &self.clients[index].player {
+						Some(player) => commands.push(CommandLine {
+							source: if player.local {
+								CommandSource::HostPlayer(player.name.clone())
+							} else {
+								CommandSource::RemotePlayer(player.name.clone())
+							},
+							line,
+						}),
+						None => {
+							let reason =
+								DisconnectReason::ProtocolError("command before hello".into());
+							self.clients[index].connection.disconnect(reason);
+							break;
+						}
+					},
+					ClientMessage::Hello { .. } => {
+						let reason = DisconnectReason::ProtocolError("repeated hello".into());
+						self.clients[index].connection.disconnect(reason);
+						break;
+					}
+					// Handled by the connection.
+					ClientMessage::KeepAlive | ClientMessage::Disconnect(_) => {}
+				}
+			}
+		}
+		self.clients.retain(|client| !client.connection.is_closed());
+		commands
+	}
+}
+
+/// Accepts players while the server runs and turns what they send into events.
+pub(super) fn serve_players(
+	config: Res<NetworkServerConfig>,
+	public_state: Res<LocalServerPublicState>,
+	save_config: Res<Option<SaveConfig>>,
+	exiting: Option<Res<Exiting>>,
+	mut network: ResMut<NetworkServer>,
+	mut remote_players: ResMut<RemotePlayers>,
+	mut commands: EventWriter<CommandLine>,
+) {
+	let running = matches!(
+		*public_state,
+		LocalServerPublicState::Running | LocalServerPublicState::Paused
+	);
+	let save_config = match (*save_config).as_ref() {
+		Some(save_config) if running && exiting.is_none() => save_config,
+		_ => {
+			network.stop(DisconnectReason::ServerStopping);
+			remote_players.0 = 0;
+			return;
+		}
+	};
+	if config.listen {
+		network.listen(save_config.bind_address);
+	}
+	for line in network.update(Instant::now(), save_config) {
+		commands.send(line);
+	}
+	remote_players.0 = network.remote_players();
+}
+
+/// Sends the replies to commands of players back to them.
+pub(super) fn send_command_replies(
+	mut replies: EventReader<CommandReply>,
+	mut network: ResMut<NetworkServer>,
+) {
+	for reply in replies.iter() {
+		match &reply.source {
+			CommandSource::RemotePlayer(name) | CommandSource::HostPlayer(name) => {
+				network.send_to(
+					name,
+					&ServerMessage::CommandReply {
+						message: reply.message.clone(),
+						failed: reply.failed,
+					},
+				);
+			}
+			CommandSource::Console | CommandSource::LocalPlayer => {}
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::server::network::{NetworkServer, HELLO_TIMEOUT, MAX_HANDSHAKING};
+	use crate::server::save::SaveConfig;
+	use crate::universal::commands::{CommandLine, CommandSource};
+	use crate::universal::network::connection::{Connection, NetworkError};
+	use crate::universal::network::protocol::{
+		ClientMessage, DisconnectReason, ServerMessage, PROTOCOL_VERSION,
+	};
+	use crate::universal::network::tcp::TcpTransport;
+	use std::net::{Ipv4Addr, SocketAddr};
+	use std::time::{Duration, Instant};
+
+	type Client = Connection<ClientMessage, ServerMessage>;
+
+	fn join(server: &NetworkServer, name: &str, protocol_version: u32) -> Client {
+		let transport = server.loopback().connect();
+		let mut client = Connection::new(Box::new(transport), Instant::now());
+		client
+			.send(&ClientMessage::Hello {
+				protocol_version,
+				game_version: "test".to_owned(),
+				language: "en-US".to_owned(),
+				player_name: name.to_owned(),
+			})
+			.unwrap();
+		client
+	}
+
+	fn refused(client: &mut Client) -> DisconnectReason {
+		match client.poll(Instant::now()) {
+			Err(NetworkError::Disconnected(reason)) => reason,
+			other => panic!("expected to be refused, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn serve_several_players() {
+		let mut save_config = SaveConfig::default();
+		save_config.max_players = 2;
+		save_config.motd = "Hello there".to_owned();
+		let mut server = NetworkServer::default();
+		let mut alice = join(&server, "Alice", PROTOCOL_VERSION);
+		let mut alice_again = join(&server, "Alice", PROTOCOL_VERSION);
+		let mut invalid = join(&server, "not valid", PROTOCOL_VERSION);
+		let mut outdated = join(&server, "Outdated", PROTOCOL_VERSION - 1);
+		let mut bob = join(&server, "Bob", PROTOCOL_VERSION);
+		let mut carol = join(&server, "Carol", PROTOCOL_VERSION);
+		assert!(server.update(Instant::now(), &save_config).is_empty());
+
+		let welcome = ServerMessage::Welcome {
+			game_version: crate::universal::network::protocol::GAME_VERSION.to_owned(),
+			motd: "Hello there".to_owned(),
+		};
+		assert_eq!(alice.poll(Instant::now()).unwrap(), vec![welcome.clone()]);
+		assert_eq!(bob.poll(Instant::now()).unwrap(), vec![welcome]);
+		assert_eq!(refused(&mut alice_again), DisconnectReason::NameTaken);
+		assert_eq!(refused(&mut invalid), DisconnectReason::InvalidName);
+		assert_eq!(refused(&mut outdated), DisconnectReason::incompatible());
+		assert_eq!(refused(&mut carol), DisconnectReason::ServerFull);
+		let names: Vec<_> = server.players().map(|p| p.name.as_str()).collect();
+		assert_eq!(names, vec!["Alice", "Bob"]);
+		assert_eq!(server.remote_players(), 0);
+
+		// Commands come from the player that sent them, and only go to them.
+		alice
+			.send(&ClientMessage::Command("time".to_owned()))
+			.unwrap();
+		assert_eq!(
+			server.update(Instant::now(), &save_config),
+			vec![CommandLine {
+				source: CommandSource::HostPlayer("Alice".to_owned()),
+				line: "time".to_owned(),
+			}]
+		);
+		let reply = ServerMessage::CommandReply {
+			message: "It is day 0".to_owned(),
+			failed: false,
+		};
+		assert!(server.send_to("Alice", &reply));
+		assert!(!server.send_to("Carol", &reply));
+		server.broadcast(&ServerMessage::Chat("Hi all".to_owned()));
+		assert_eq!(
+			alice.poll(Instant::now()).unwrap(),
+			vec![reply, ServerMessage::Chat("Hi all".to_owned())]
+		);
+
+		assert!(server.kick("Bob", Some("testing".to_owned())));
+		assert!(!server.kick("Bob", None));
+		match bob.poll(Instant::now()) {
+			Err(NetworkError::Disconnected(reason)) => {
+				assert_eq!(reason, DisconnectReason::Kicked(Some("testing".to_owned())))
+			}
+			other => panic!("expected a kick, got {:?}", other),
+		}
+		server.update(Instant::now(), &save_config);
+		assert_eq!(server.players().count(), 1);
+
+		// Once Alice left only Carol is there.
+		alice.disconnect(DisconnectReason::Quit);
+		let mut carol = join(&server, "Carol", PROTOCOL_VERSION);
+		server.update(Instant::now(), &save_config);
+		assert_eq!(server.players().count(), 1);
+		assert!(matches!(
+			carol.poll(Instant::now()).unwrap()[..],
+			[ServerMessage::Welcome { .. }]
+		));
+
+		server.stop(DisconnectReason::ServerStopping);
+		assert_eq!(refused(&mut carol), DisconnectReason::ServerStopping);
+		assert_eq!(server.players().count(), 0);
+	}
+
+	#[test]
+	fn close_connections_without_hello() {
+		let save_config = SaveConfig::default();
+		let mut server = NetworkServer::default();
+		server.listen(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)));
+		let address = server.listener.as_ref().unwrap().local_addr().unwrap();
+		let connect = || -> Client {
+			let transport = TcpTransport::connect(&address.to_string(), Duration::from_secs(5));
+			Connection::new(Box::new(transport.unwrap()), Instant::now())
+		};
+		let mut silent: Vec<Client> = (0..=MAX_HANDSHAKING).map(|_| connect()).collect();
+		let mut host = join(&server, "Host", PROTOCOL_VERSION);
+		let start = Instant::now();
+
+		// One too many are waiting to say hello, the host player still gets in.
+		let mut refused = None;
+		while refused.is_none() {
+			assert!(
+				start.elapsed() < Duration::from_secs(5),
+				"nobody was refused"
+			);
+			server.update(start, &save_config);
+			refused = silent.iter_mut().position(|client| {
+				matches!(
+					client.poll(Instant::now()),
+					Err(NetworkError::Disconnected(DisconnectReason::ServerFull))
+				)
+			});
+			std::thread::sleep(Duration::from_millis(2));
+		}
+		silent.swap_remove(refused.unwrap());
+		assert!(matches!(
+			host.poll(Instant::now()).unwrap()[..],
+			[ServerMessage::Welcome { .. }]
+		));
+
+		// Then they are out of time.
+		server.update(start + HELLO_TIMEOUT, &save_config);
+		assert_eq!(server.handshaking(), 0);
+		assert_eq!(server.players().count(), 1);
+		for client in silent.iter_mut() {
+			loop {
+				assert!(start.elapsed() < Duration::from_secs(5), "didn't time out");
+				match client.poll(Instant::now()) {
+					Ok(_) => std::thread::sleep(Duration::from_millis(2)),
+					Err(NetworkError::Disconnected(reason)) => {
+						assert_eq!(reason, DisconnectReason::TimedOut);
+						break;
+					}
+					Err(e) => panic!("expected to time out, got {:?}", e),
+				}
+			}
+		}
+	}
+}
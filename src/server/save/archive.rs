@@ -0,0 +1,408 @@
+//! A whole save packed into a single file to share it or attach it to a bug report.
+//!
+//! An archive starts with the magic bytes and the archive format version, followed by every file
+//! of the save compressed with zlib one after the other.  Then comes the manifest, written as RON
+//! so it can be read by people too, listing the save's name, its format version and the path,
+//! size and CRC-32 of every file in the order their data is in.  The archive ends with the offset,
+//! length and CRC-32 of the manifest, so it can be checked before anything is unpacked.  All
+//! numbers are little endian.
+
+use crate::game_data::atomic_file::temp_path;
+use crate::game_data::inflate::inflate_zlib;
+use crate::server::save::backup::{is_temp, BACKUPS_DIR};
+use crate::server::save::{is_valid_name, SaveConfigError, CONFIG_FILE, SAVE_FORMAT_VERSION};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// Bumped whenever the layout of archives changes, archives with another version aren't read.
+pub const ARCHIVE_FORMAT_VERSION: u16 = 1;
+
+/// The extension archives are given when exported from the menus.
+pub const ARCHIVE_EXTENSION: &str = "msave";
+
+const MAGIC: &[u8; 4] = b"MSAV";
+const HEADER_LEN: u64 = 6;
+const TRAILER_LEN: u64 = 16;
+const COMPRESSION_LEVEL: u8 = 6;
+
+/// What an archive holds, read and checked before any of it is unpacked.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+	/// The name of the save's directory when it was exported, used again when importing it.
+	pub save_name: String,
+	pub format_version: u32,
+	pub files: Vec<ArchivedFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedFile {
+	/// Relative to the save's directory with `/` between the components.
+	pub path: String,
+	pub size: u64,
+	pub compressed_size: u64,
+	pub crc32: u32,
+}
+
+/// Packs everything in the save but its backups into an archive at the path, replacing it only
+/// once it is complete.  Chunks only held in memory by a running server are not in it.
+pub fn export_archive(
+	save_path: &Path,
+	archive: &Path,
+) -> Result<ArchiveManifest, SaveConfigError> {
+	let config = std::fs::read_to_string(save_path.join(CONFIG_FILE))
+		.map_err(|e| SaveConfigError::LoadError(e, "reading config.ron file"))?;
+	let format_version = ron::from_str::<super::FormatVersion>(&config)?.format_version;
+	let save_name = save_path
+		.file_name()
+		.and_then(|name| name.to_str())
+		.filter(|name| is_valid_name(name))
+		.ok_or_else(|| SaveConfigError::InvalidSave(save_path.to_owned()))?
+		.to_owned();
+	let mut paths = Vec::new();
+	list_files(save_path, Path::new(""), &mut paths)
+		.map_err(|e| SaveConfigError::LoadError(e, "listing save files"))?;
+	paths.sort();
+
+	let temp = temp_path(archive);
+	let io_error = |e: std::io::Error| SaveConfigError::LoadError(e, "writing save archive");
+	let write = || -> Result<ArchiveManifest, SaveConfigError> {
+		let mut out = BufWriter::new(File::create(&temp).map_err(io_error)?);
+		out.write_all(MAGIC).map_err(io_error)?;
+		out.write_all(&ARCHIVE_FORMAT_VERSION.to_le_bytes())
+			.map_err(io_error)?;
+		let mut files = Vec::with_capacity(paths.len());
+		for path in &paths {
+			let data = std::fs::read(save_path.join(path)).map_err(io_error)?;
+			let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&data, COMPRESSION_LEVEL);
+			out.write_all(&compressed).map_err(io_error)?;
+			files.push(ArchivedFile {
+				path: archive_path(path),
+				size: data.len() as u64,
+				compressed_size: compressed.len() as u64,
+				crc32: crc32(&data),
+			});
+		}
+		let manifest = ArchiveManifest {
+			save_name,
+			format_version,
+			files,
+		};
+		let manifest_string = ron::ser::to_string_pretty(&manifest, Default::default())?;
+		let manifest_offset = HEADER_LEN
+			+ manifest
+				.files
+				.iter()
+				.map(|f| f.compressed_size)
+				.sum::<u64>();
+		out.write_all(manifest_string.as_bytes())
+			.map_err(io_error)?;
+		out.write_all(&manifest_offset.to_le_bytes())
+			.map_err(io_error)?;
+		out.write_all(&(manifest_string.len() as u32).to_le_bytes())
+			.map_err(io_error)?;
+		out.write_all(&crc32(manifest_string.as_bytes()).to_le_bytes())
+			.map_err(io_error)?;
+		out.flush().map_err(io_error)?;
+		out.get_ref().sync_all().map_err(io_error)?;
+		drop(out);
+		std::fs::rename(&temp, archive).map_err(io_error)?;
+		Ok(manifest)
+	};
+	let written = write();
+	if written.is_err() {
+		let _ = std::fs::remove_file(&temp);
+	}
+	written
+}
+
+/// Reads the archive's manifest, checking that it is intact and that unpacking it can't write
+/// anywhere outside of the save.
+pub fn read_manifest(archive: &Path) -> Result<ArchiveManifest, SaveConfigError> {
+	let invalid = |reason: String| SaveConfigError::InvalidArchive(archive.to_owned(), reason);
+	let io_error = |e| SaveConfigError::LoadError(e, "reading save archive");
+	let mut file = File::open(archive).map_err(io_error)?;
+	let len = file.metadata().map_err(io_error)?.len();
+	if len < HEADER_LEN + TRAILER_LEN {
+		return Err(invalid("too short".to_owned()));
+	}
+	let mut header = [0; HEADER_LEN as usize];
+	file.read_exact(&mut header).map_err(io_error)?;
+	if &header[..4] != MAGIC {
+		return Err(invalid("not a save archive".to_owned()));
+	}
+	let version = u16::from_le_bytes([header[4], header[5]]);
+	if version != ARCHIVE_FORMAT_VERSION {
+		return Err(invalid(format!(
+			"archive format version {} is not supported",
+			version
+		)));
+	}
+	let mut trailer = [0; TRAILER_LEN as usize];
+	file.seek(SeekFrom::Start(len - TRAILER_LEN))
+		.and_then(|_| file.read_exact(&mut trailer))
+		.map_err(io_error)?;
+	let manifest_offset = u64::from_le_bytes(trailer[..8].try_into().unwrap());
+	let manifest_len = u32::from_le_bytes(trailer[8..12].try_into().unwrap()) as u64;
+	let manifest_crc = u32::from_le_bytes(trailer[12..].try_into().unwrap());
+	if manifest_offset < HEADER_LEN
+		|| manifest_offset.checked_add(manifest_len) != Some(len - TRAILER_LEN)
+	{
+		return Err(invalid("truncated or corrupt".to_owned()));
+	}
+	let mut manifest = vec![0; manifest_len as usize];
+	file.seek(SeekFrom::Start(manifest_offset))
+		.and_then(|_| file.read_exact(&mut manifest))
+		.map_err(io_error)?;
+	if crc32(&manifest) != manifest_crc {
+		return Err(invalid("the manifest's checksum doesn't match".to_owned()));
+	}
+	let manifest = std::str::from_utf8(&manifest)
+		.map_err(|_| invalid("the manifest isn't text".to_owned()))?;
+	let manifest: ArchiveManifest = ron::from_str(manifest)?;
+
+	if !is_valid_name(&manifest.save_name) {
+		return Err(invalid(format!(
+			"`{}` is not a valid save name",
+			manifest.save_name
+		)));
+	}
+	if manifest.format_version > SAVE_FORMAT_VERSION {
+		return Err(SaveConfigError::UnsupportedFormatVersion(
+			archive.to_owned(),
+			manifest.format_version,
+		));
+	}
+	let mut paths = HashSet::new();
+	for file in &manifest.files {
+		if !is_safe_path(&file.path) {
+			return Err(invalid(format!("`{}` is not a valid file path", file.path)));
+		}
+		if !paths.insert(file.path.as_str()) {
+			return Err(invalid(format!("`{}` is in it more than once", file.path)));
+		}
+	}
+	if !paths.contains(CONFIG_FILE) {
+		return Err(invalid(format!("it has no `{}`", CONFIG_FILE)));
+	}
+	let data_len = manifest
+		.files
+		.iter()
+		.try_fold(0u64, |total, file| total.checked_add(file.compressed_size));
+	if data_len != Some(manifest_offset - HEADER_LEN) {
+		return Err(invalid("the file sizes don't add up".to_owned()));
+	}
+	Ok(manifest)
+}
+
+/// Unpacks the archive into a new save in the root, named `name` or else like the exported save.
+/// Nothing is left behind if the archive turns out to be corrupt.  Returns the path of the save.
+pub fn import_archive(
+	archive: &Path,
+	saves_root: &Path,
+	name: Option<&str>,
+) -> Result<PathBuf, SaveConfigError> {
+	let manifest = read_manifest(archive)?;
+	let name = name.unwrap_or(&manifest.save_name);
+	if !is_valid_name(name) {
+		return Err(SaveConfigError::InvalidSaveName(name.to_owned()));
+	}
+	let target = saves_root.join(name);
+	if target.exists() {
+		return Err(SaveConfigError::SaveExists(target));
+	}
+	std::fs::create_dir_all(saves_root)
+		.map_err(|e| SaveConfigError::LoadError(e, "creating saves directory"))?;
+	let temp = temp_path(&target);
+	let unpacked = unpack(archive, &manifest, &temp).and_then(|_| {
+		std::fs::rename(&temp, &target)
+			.map_err(|e| SaveConfigError::LoadError(e, "moving imported save into place"))
+	});
+	if unpacked.is_err() {
+		let _ = std::fs::remove_dir_all(&temp);
+	}
+	unpacked.map(|_| target)
+}
+
+fn unpack(archive: &Path, manifest: &ArchiveManifest, to: &Path) -> Result<(), SaveConfigError> {
+	let io_error = |e| SaveConfigError::LoadError(e, "unpacking save archive");
+	let mut file = BufReader::new(File::open(archive).map_err(io_error)?);
+	file.seek(SeekFrom::Start(HEADER_LEN)).map_err(io_error)?;
+	for entry in &manifest.files {
+		let corrupt = || {
+			SaveConfigError::InvalidArchive(
+				archive.to_owned(),
+				format!("`{}` is corrupt", entry.path),
+			)
+		};
+		let mut compressed = vec![0; entry.compressed_size as usize];
+		file.read_exact(&mut compressed).map_err(io_error)?;
+		// A crafted entry could inflate far beyond the size it claims.
+		let data = inflate_zlib(&compressed, entry.size as usize).ok_or_else(corrupt)?;
+		if data.len() as u64 != entry.size || crc32(&data) != entry.crc32 {
+			return Err(corrupt());
+		}
+		let path = to.join(&entry.path);
+		if let Some(parent) = path.parent() {
+			std::fs::create_dir_all(parent).map_err(io_error)?;
+		}
+		std::fs::write(&path, &data).map_err(io_error)?;
+	}
+	Ok(())
+}
+
+/// The files in the directory relative to the save, without backups and temporary files.
+fn list_files(dir: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+	for entry in std::fs::read_dir(dir)? {
+		let entry = entry?;
+		let path = entry.path();
+		let relative = relative.join(entry.file_name());
+		if relative == Path::new(BACKUPS_DIR) || is_temp(&path) {
+			continue;
+		}
+		if entry.file_type()?.is_dir() {
+			list_files(&path, &relative, files)?;
+		} else {
+			files.push(relative);
+		}
+	}
+	Ok(())
+}
+
+fn archive_path(path: &Path) -> String {
+	path.components()
+		.map(|component| component.as_os_str().to_string_lossy())
+		.collect::<Vec<_>>()
+		.join("/")
+}
+
+/// Whether the archived path stays inside the save, so an archive can't overwrite anything else.
+fn is_safe_path(path: &str) -> bool {
+	!path.is_empty()
+		&& path.split('/').all(|part| {
+			let mut components = Path::new(part).components();
+			match (components.next(), components.next()) {
+				(Some(Component::Normal(_)), None) => !part.contains(['\\', ':'].as_ref()),
+				_ => false,
+			}
+		}) && path.split('/').next() != Some(BACKUPS_DIR)
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+/// The table for the CRC-32 used by zip and png, for the reversed polynomial `0xEDB88320`.
+const fn crc_table() -> [u32; 256] {
+	let mut table = [0; 256];
+	let mut i = 0;
+	while i < 256 {
+		let mut crc = i as u32;
+		let mut bit = 0;
+		while bit < 8 {
+			crc = if crc & 1 == 1 {
+				(crc >> 1) ^ 0xEDB8_8320
+			} else {
+				crc >> 1
+			};
+			bit += 1;
+		}
+		table[i] = crc;
+		i += 1;
+	}
+	table
+}
+
+fn crc32(data: &[u8]) -> u32 {
+	!data.iter().fold(!0, |crc, &byte| {
+		CRC_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+	})
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::test_dir::TestDir;
+	use crate::server::save::archive::{
+		crc32, export_archive, import_archive, is_safe_path, read_manifest,
+	};
+	use crate::server::save::{SaveConfig, SaveConfigError};
+
+	#[test]
+	fn checksums_and_paths() {
+		assert_eq!(crc32(b""), 0);
+		assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+		assert!(is_safe_path("config.ron"));
+		assert!(is_safe_path("worlds/overworld/regions/r.0.0.0.mreg"));
+		assert!(!is_safe_path(""));
+		assert!(!is_safe_path("../config.ron"));
+		assert!(!is_safe_path("/etc/passwd"));
+		assert!(!is_safe_path("worlds//config.ron"));
+		assert!(!is_safe_path("worlds/./config.ron"));
+		assert!(!is_safe_path("C:\\config.ron"));
+		assert!(!is_safe_path("backups/config.ron"));
+	}
+
+	#[test]
+	fn export_and_import_saves() {
+		let dir = TestDir::new("save-archive");
+		let save = dir.path().join("saves").join("shared");
+		SaveConfig::load_or_create_path(&save).unwrap();
+		let regions = save.join("worlds").join("overworld").join("regions");
+		std::fs::create_dir_all(&regions).unwrap();
+		let region: Vec<u8> = (0..10_000u32).map(|i| (i * 7 % 251) as u8).collect();
+		std::fs::write(regions.join("r.0.0.0.mreg"), &region).unwrap();
+		SaveConfig::load_path(&save).unwrap().backup().unwrap();
+
+		let archive = dir.path().join("shared.msave");
+		let manifest = export_archive(&save, &archive).unwrap();
+		let paths: Vec<_> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+		assert_eq!(
+			paths,
+			["config.ron", "worlds/overworld/regions/r.0.0.0.mreg"]
+		);
+		assert_eq!(read_manifest(&archive).unwrap(), manifest);
+
+		let imports = dir.path().join("imports");
+		let imported = import_archive(&archive, &imports, None).unwrap();
+		assert_eq!(imported, imports.join("shared"));
+		assert_eq!(
+			std::fs::read(imported.join("worlds/overworld/regions/r.0.0.0.mreg")).unwrap(),
+			region
+		);
+		assert_eq!(
+			std::fs::read(imported.join("config.ron")).unwrap(),
+			std::fs::read(save.join("config.ron")).unwrap()
+		);
+		assert!(matches!(
+			import_archive(&archive, &imports, None),
+			Err(SaveConfigError::SaveExists(_))
+		));
+		let config = SaveConfig::import_archive(&archive, &imports, Some("renamed")).unwrap();
+		assert_eq!(config.save_path(), imports.join("renamed"));
+
+		// Corrupt data is caught before the save shows up, a corrupt manifest before unpacking.
+		let mut bytes = std::fs::read(&archive).unwrap();
+		bytes[8] ^= 0xFF;
+		std::fs::write(&archive, &bytes).unwrap();
+		assert!(matches!(
+			import_archive(&archive, &imports, Some("corrupt")),
+			Err(SaveConfigError::InvalidArchive(_, _))
+		));
+		assert!(!imports.join("corrupt").exists());
+		assert!(!imports.join("corrupt.tmp").exists());
+		bytes[8] ^= 0xFF;
+		let manifest_byte = bytes.len() - 20;
+		bytes[manifest_byte] ^= 0xFF;
+		std::fs::write(&archive, &bytes).unwrap();
+		assert!(matches!(
+			read_manifest(&archive),
+			Err(SaveConfigError::InvalidArchive(_, _))
+		));
+		std::fs::write(&archive, b"MSAV").unwrap();
+		assert!(matches!(
+			read_manifest(&archive),
+			Err(SaveConfigError::InvalidArchive(_, _))
+		));
+	}
+}
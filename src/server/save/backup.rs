@@ -0,0 +1,222 @@
+//! Rolling backups of a save, each a full copy of the save in its `backups` directory named after
+//! the time it was made.
+
+use crate::game_data::atomic_file::temp_path;
+use crate::server::save::SaveConfigError;
+use std::ffi::OsStr;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How many backups of a save are kept, the oldest ones are deleted as new ones are made.
+pub const MAX_BACKUPS: usize = 5;
+
+pub(super) const BACKUPS_DIR: &str = "backups";
+
+/// Copies everything in the save but its backups into a new backup, then deletes the oldest
+/// backups until at most `max_backups` are left, always keeping the new one.  Returns the path of
+/// the new backup.
+pub fn create_backup(save_path: &Path, max_backups: usize) -> Result<PathBuf, SaveConfigError> {
+	let backups = save_path.join(BACKUPS_DIR);
+	let mut name = timestamp(SystemTime::now());
+	// Backups made within the same second are numbered so they still sort by age.
+	let newest = list_backups(save_path)?.pop();
+	let newest = newest
+		.as_ref()
+		.and_then(|newest| newest.file_name())
+		.and_then(|newest| newest.to_str());
+	if let Some(duplicate) = newest.and_then(|newest| newest.strip_prefix(name.as_str())) {
+		let duplicate = duplicate
+			.trim_start_matches('-')
+			.parse::<u32>()
+			.unwrap_or(0);
+		name = format!("{}-{:03}", name, duplicate + 1);
+	}
+	let backup = backups.join(&name);
+	// Copied under a temporary name first so every backup that is there is complete.
+	let temp = temp_path(&backup);
+	copy_dir(save_path, &temp, &backups)
+		.and_then(|_| std::fs::rename(&temp, &backup))
+		.map_err(|e| {
+			let _ = std::fs::remove_dir_all(&temp);
+			SaveConfigError::LoadError(e, "copying save into a backup")
+		})?;
+	for old in list_backups(save_path)?
+		.iter()
+		.rev()
+		.skip(max_backups.max(1))
+	{
+		std::fs::remove_dir_all(old)
+			.map_err(|e| SaveConfigError::LoadError(e, "deleting old backup"))?;
+	}
+	Ok(backup)
+}
+
+/// Filled in by the backup thread once it is done.
+type BackupResult = Arc<(Mutex<Option<Result<PathBuf, SaveConfigError>>>, Condvar)>;
+
+/// A backup made by `create_backup` on its own thread, as copying a large save takes a while.
+/// Nothing may write to the save until it is done, or the backup could hold half-written files.
+pub struct BackgroundBackup {
+	result: BackupResult,
+}
+
+impl BackgroundBackup {
+	pub fn start(save_path: PathBuf, max_backups: usize) -> Self {
+		let result = BackupResult::default();
+		let shared = result.clone();
+		std::thread::spawn(move || {
+			let backup = create_backup(&save_path, max_backups);
+			let (result, done) = &*shared;
+			*lock(result) = Some(backup);
+			done.notify_all();
+		});
+		BackgroundBackup { result }
+	}
+
+	/// The result of the backup once it is done, the backup is to be dropped after that.
+	pub fn try_finish(&self) -> Option<Result<PathBuf, SaveConfigError>> {
+		lock(&self.result.0).take()
+	}
+
+	/// Waits for the backup to be done and returns its result.
+	pub fn finish(self) -> Result<PathBuf, SaveConfigError> {
+		let (result, done) = &*self.result;
+		let mut result = lock(result);
+		loop {
+			match result.take() {
+				Some(backup) => return backup,
+				None => result = done.wait(result).unwrap_or_else(|e| e.into_inner()),
+			}
+		}
+	}
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+	mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The complete backups of the save, oldest first.
+pub fn list_backups(save_path: &Path) -> Result<Vec<PathBuf>, SaveConfigError> {
+	let entries = match std::fs::read_dir(save_path.join(BACKUPS_DIR)) {
+		Ok(entries) => entries,
+		Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+		Err(e) => return Err(SaveConfigError::LoadError(e, "listing backups")),
+	};
+	let mut backups = Vec::new();
+	for entry in entries {
+		let path = entry
+			.map_err(|e| SaveConfigError::LoadError(e, "listing backups"))?
+			.path();
+		if path.is_dir() && !is_temp(&path) {
+			backups.push(path);
+		}
+	}
+	backups.sort();
+	Ok(backups)
+}
+
+/// Leftovers of an interrupted atomic write or backup.
+pub(super) fn is_temp(path: &Path) -> bool {
+	path.extension() == Some(OsStr::new("tmp"))
+}
+
+/// Copies the directory and everything in it but `skip` and leftover temporary files.
+pub(super) fn copy_dir(from: &Path, to: &Path, skip: &Path) -> std::io::Result<()> {
+	std::fs::create_dir_all(to)?;
+	for entry in std::fs::read_dir(from)? {
+		let entry = entry?;
+		let path = entry.path();
+		if path == skip || is_temp(&path) {
+			continue;
+		}
+		let target = to.join(entry.file_name());
+		if entry.file_type()?.is_dir() {
+			copy_dir(&path, &target, skip)?;
+		} else {
+			std::fs::copy(&path, &target)?;
+		}
+	}
+	Ok(())
+}
+
+/// The time in UTC as `YYYY-MM-DDTHH-MM-SSZ`, which sorts like the times themselves and is a valid
+/// file name everywhere.
+pub(super) fn timestamp(time: SystemTime) -> String {
+	let secs = time
+		.duration_since(UNIX_EPOCH)
+		.map(|since| since.as_secs())
+		.unwrap_or(0);
+	let (days, secs) = ((secs / 86_400) as i64, secs % 86_400);
+	// Howard Hinnant's `civil_from_days`, with eras of 400 years starting at March 1st.
+	let z = days + 719_468;
+	let era = z.div_euclid(146_097);
+	let day_of_era = z.rem_euclid(146_097);
+	let year_of_era =
+		(day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let shifted_month = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+	let month = if shifted_month < 10 {
+		shifted_month + 3
+	} else {
+		shifted_month - 9
+	};
+	let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+	format!(
+		"{:04}-{:02}-{:02}T{:02}-{:02}-{:02}Z",
+		year,
+		month,
+		day,
+		secs / 3600,
+		secs / 60 % 60,
+		secs % 60
+	)
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::test_dir::TestDir;
+	use crate::server::save::backup::{create_backup, list_backups, timestamp, BackgroundBackup};
+	use std::time::{Duration, UNIX_EPOCH};
+
+	#[test]
+	fn timestamps() {
+		let at = |secs| timestamp(UNIX_EPOCH + Duration::from_secs(secs));
+		assert_eq!(at(0), "1970-01-01T00-00-00Z");
+		assert_eq!(at(951_782_400), "2000-02-29T00-00-00Z");
+		assert_eq!(at(1_700_000_000), "2023-11-14T22-13-20Z");
+		assert_eq!(at(4_107_542_399), "2100-02-28T23-59-59Z");
+	}
+
+	#[test]
+	fn keep_the_newest_backups() {
+		let dir = TestDir::new("save-backups");
+		let regions = dir.path().join("worlds").join("test").join("regions");
+		std::fs::create_dir_all(&regions).unwrap();
+		std::fs::write(dir.path().join("config.ron"), "()\n").unwrap();
+		std::fs::write(regions.join("r.0.0.0.mreg"), b"region").unwrap();
+		std::fs::write(dir.path().join("config.ron.tmp"), "(").unwrap();
+
+		let mut made = Vec::new();
+		for _ in 0..5 {
+			made.push(create_backup(dir.path(), 3).unwrap());
+		}
+		let backups = list_backups(dir.path()).unwrap();
+		assert_eq!(backups, made[2..]);
+		let newest = &backups[2];
+		assert_eq!(
+			std::fs::read(newest.join("worlds/test/regions/r.0.0.0.mreg")).unwrap(),
+			b"region"
+		);
+		assert!(!newest.join("config.ron.tmp").exists());
+		assert!(!newest.join("backups").exists());
+
+		let background = BackgroundBackup::start(dir.path().to_owned(), 3)
+			.finish()
+			.unwrap();
+		assert_eq!(list_backups(dir.path()).unwrap().last(), Some(&background));
+		assert!(background.join("config.ron").exists());
+	}
+}
@@ -0,0 +1,306 @@
+//! Finding the saves in a directory and managing them as a whole, for menus and tools that let
+//! players pick what to play.
+
+use crate::game_data::atomic_file::temp_path;
+use crate::server::save::archive::{export_archive, ARCHIVE_EXTENSION};
+use crate::server::save::backup::{copy_dir, timestamp, BACKUPS_DIR};
+use crate::server::save::{
+	is_valid_name, FormatVersion, SaveConfig, SaveConfigError, CONFIG_FILE, SAVE_FORMAT_VERSION,
+};
+use bevy::prelude::*;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where saves are kept unless told otherwise, relative to the working directory.
+pub const SAVES_DIR: &str = "saves";
+
+/// Where the menus export saves to, relative to the working directory.
+pub const EXPORTS_DIR: &str = "exports";
+
+/// Deleted saves are moved here in the root they were in, it is skipped when listing saves.
+const TRASH_DIR: &str = ".trash";
+
+/// What is known about a save without loading it.
+#[derive(Debug, Clone)]
+pub struct SaveInfo {
+	pub path: PathBuf,
+	pub name: String,
+	/// When anything in the save was last written, which happens whenever it is played.
+	pub last_played: SystemTime,
+	/// The bytes the save takes up on disk, backups included.
+	pub size: u64,
+	/// `None` if the configuration can't be read at all.
+	pub format_version: Option<u32>,
+	/// The generator of the world players spawn in, only known for saves in the current format.
+	pub generator: Option<&'static str>,
+}
+
+impl SaveInfo {
+	/// Reads the save's details, never changing anything in it unlike `SaveConfig::load_path`.
+	pub fn read(path: &Path) -> Result<SaveInfo, SaveConfigError> {
+		let config_path = path.join(CONFIG_FILE);
+		if !config_path.is_file() {
+			return Err(SaveConfigError::InvalidSave(path.to_owned()));
+		}
+		let (size, last_played) =
+			dir_usage(path).map_err(|e| SaveConfigError::LoadError(e, "measuring save size"))?;
+		let config = std::fs::read_to_string(&config_path)
+			.map_err(|e| SaveConfigError::LoadError(e, "reading config.ron file"))?;
+		let format_version = ron::from_str::<FormatVersion>(&config)
+			.ok()
+			.map(|version| version.format_version);
+		let generator = if format_version == Some(SAVE_FORMAT_VERSION) {
+			ron::from_str::<SaveConfig>(&config)
+				.ok()
+				.and_then(|config| config.worlds.first().map(|world| world.generator.name()))
+		} else {
+			None
+		};
+		Ok(SaveInfo {
+			path: path.to_owned(),
+			name: file_name(path),
+			last_played,
+			size,
+			format_version,
+			generator,
+		})
+	}
+
+	fn unreadable(path: &Path) -> SaveInfo {
+		SaveInfo {
+			path: path.to_owned(),
+			name: file_name(path),
+			last_played: UNIX_EPOCH,
+			size: 0,
+			format_version: None,
+			generator: None,
+		}
+	}
+
+	/// Whether `SaveConfig::load_path` can load the save, upgrading it first if it is older.
+	pub fn is_loadable(&self) -> bool {
+		match self.format_version {
+			Some(version) => version <= SAVE_FORMAT_VERSION,
+			None => false,
+		}
+	}
+}
+
+/// The saves directly in the root, most recently played first.  A root that doesn't exist yet has
+/// no saves, a save that can't be read is still listed but without any details.
+pub fn list_saves(root: &Path) -> Result<Vec<SaveInfo>, SaveConfigError> {
+	let entries = match std::fs::read_dir(root) {
+		Ok(entries) => entries,
+		Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+		Err(e) => return Err(SaveConfigError::LoadError(e, "listing saves")),
+	};
+	let mut saves = Vec::new();
+	for entry in entries {
+		let path = entry
+			.map_err(|e| SaveConfigError::LoadError(e, "listing saves"))?
+			.path();
+		let hidden = file_name(&path).starts_with('.');
+		if !hidden && path.join(CONFIG_FILE).is_file() {
+			saves.push(SaveInfo::read(&path).unwrap_or_else(|e| {
+				warn!("Failed reading the save at {:?}: {}", path, e);
+				SaveInfo::unreadable(&path)
+			}));
+		}
+	}
+	saves.sort_by(|a, b| {
+		b.last_played
+			.cmp(&a.last_played)
+			.then_with(|| a.name.cmp(&b.name))
+	});
+	Ok(saves)
+}
+
+/// Copies the save next to itself under the new name, without its backups.  Returns the path of
+/// the copy.
+pub fn duplicate_save(path: &Path, name: &str) -> Result<PathBuf, SaveConfigError> {
+	check_save(path)?;
+	let target = sibling(path, name)?;
+	copy_save(path, &target)?;
+	Ok(target)
+}
+
+/// Renames the save's directory, returning its new path.
+pub fn rename_save(path: &Path, name: &str) -> Result<PathBuf, SaveConfigError> {
+	check_save(path)?;
+	let target = sibling(path, name)?;
+	std::fs::rename(path, &target).map_err(|e| SaveConfigError::LoadError(e, "renaming save"))?;
+	Ok(target)
+}
+
+/// Moves the save into the trash of the directory it is in, so it no longer shows up in
+/// `list_saves` but can still be recovered by hand.  Returns where it was moved to.
+pub fn delete_save(path: &Path) -> Result<PathBuf, SaveConfigError> {
+	check_save(path)?;
+	let trash = path
+		.parent()
+		.ok_or_else(|| SaveConfigError::InvalidSave(path.to_owned()))?
+		.join(TRASH_DIR);
+	std::fs::create_dir_all(&trash)
+		.map_err(|e| SaveConfigError::LoadError(e, "creating trash directory"))?;
+	let name = format!("{} {}", file_name(path), timestamp(SystemTime::now()));
+	let mut target = trash.join(&name);
+	let mut duplicate = 1;
+	while target.exists() {
+		duplicate += 1;
+		target = trash.join(format!("{} {}", name, duplicate));
+	}
+	std::fs::rename(path, &target)
+		.map_err(|e| SaveConfigError::LoadError(e, "moving save to trash"))?;
+	Ok(target)
+}
+
+/// Packs the save into an archive named after it in the destination directory, for sharing it or
+/// keeping it elsewhere.  Returns the path of the archive.
+pub fn export_save(path: &Path, destination: &Path) -> Result<PathBuf, SaveConfigError> {
+	check_save(path)?;
+	let target = destination.join(format!("{}.{}", file_name(path), ARCHIVE_EXTENSION));
+	if target.exists() {
+		return Err(SaveConfigError::SaveExists(target));
+	}
+	std::fs::create_dir_all(destination)
+		.map_err(|e| SaveConfigError::LoadError(e, "creating export directory"))?;
+	export_archive(path, &target)?;
+	Ok(target)
+}
+
+fn check_save(path: &Path) -> Result<(), SaveConfigError> {
+	if path.join(CONFIG_FILE).is_file() {
+		Ok(())
+	} else {
+		Err(SaveConfigError::InvalidSave(path.to_owned()))
+	}
+}
+
+/// The path a save gets under the new name in the same directory, which must not be taken.
+fn sibling(path: &Path, name: &str) -> Result<PathBuf, SaveConfigError> {
+	if !is_valid_name(name) {
+		return Err(SaveConfigError::InvalidSaveName(name.to_owned()));
+	}
+	let target = path.with_file_name(name);
+	if target.exists() {
+		return Err(SaveConfigError::SaveExists(target));
+	}
+	Ok(target)
+}
+
+/// Copied under a temporary name first so a half copied save never shows up as a save.
+fn copy_save(path: &Path, target: &Path) -> Result<(), SaveConfigError> {
+	let temp = temp_path(target);
+	copy_dir(path, &temp, &path.join(BACKUPS_DIR))
+		.and_then(|_| std::fs::rename(&temp, target))
+		.map_err(|e| {
+			let _ = std::fs::remove_dir_all(&temp);
+			SaveConfigError::LoadError(e, "copying save")
+		})
+}
+
+fn file_name(path: &Path) -> String {
+	path.file_name()
+		.map(|name| name.to_string_lossy().into_owned())
+		.unwrap_or_default()
+}
+
+/// The total size of the files in the directory and when the most recent of them was modified.
+fn dir_usage(path: &Path) -> std::io::Result<(u64, SystemTime)> {
+	let mut size = 0;
+	let mut modified = std::fs::metadata(path)?.modified()?;
+	for entry in std::fs::read_dir(path)? {
+		let entry = entry?;
+		let (entry_size, entry_modified) = if entry.file_type()?.is_dir() {
+			dir_usage(&entry.path())?
+		} else {
+			let metadata = entry.metadata()?;
+			(metadata.len(), metadata.modified()?)
+		};
+		size += entry_size;
+		modified = modified.max(entry_modified);
+	}
+	Ok((size, modified))
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::test_dir::TestDir;
+	use crate::server::save::archive::read_manifest;
+	use crate::server::save::browser::{
+		delete_save, duplicate_save, export_save, list_saves, rename_save,
+	};
+	use crate::server::save::{SaveConfig, SaveConfigError, SAVE_FORMAT_VERSION};
+	use std::time::Duration;
+
+	#[test]
+	fn manage_saves() {
+		let dir = TestDir::new("save-browser");
+		let root = dir.path().join("saves");
+		assert!(list_saves(&root).unwrap().is_empty());
+		let first = root.join("first");
+		SaveConfig::load_or_create_path(&first).unwrap();
+		let backup = SaveConfig::load_path(&first).unwrap().backup().unwrap();
+		std::fs::create_dir_all(root.join("not a save")).unwrap();
+		// Modification times can be as coarse as a second.
+		std::thread::sleep(Duration::from_millis(1100));
+		let old = root.join("old");
+		std::fs::create_dir_all(&old).unwrap();
+		std::fs::write(old.join("config.ron"), "()").unwrap();
+
+		let saves = list_saves(&root).unwrap();
+		let names: Vec<_> = saves.iter().map(|save| save.name.as_str()).collect();
+		assert_eq!(names, ["old", "first"]);
+		assert_eq!(saves[0].format_version, Some(0));
+		assert_eq!(saves[0].generator, None);
+		assert!(saves[0].is_loadable());
+		assert_eq!(saves[1].format_version, Some(SAVE_FORMAT_VERSION));
+		assert_eq!(saves[1].generator, Some("Layered"));
+		assert!(saves[1].size > 0);
+
+		let copy = duplicate_save(&first, "copy").unwrap();
+		assert_eq!(copy, root.join("copy"));
+		assert!(copy.join("config.ron").is_file());
+		assert!(!copy.join("backups").exists());
+		assert!(backup.is_dir());
+		assert!(matches!(
+			duplicate_save(&first, "old"),
+			Err(SaveConfigError::SaveExists(_))
+		));
+		assert!(matches!(
+			rename_save(&copy, "../escape"),
+			Err(SaveConfigError::InvalidSaveName(_))
+		));
+		let renamed = rename_save(&copy, "renamed").unwrap();
+		assert!(!copy.exists());
+
+		let exported = export_save(&renamed, &dir.path().join("exports")).unwrap();
+		assert_eq!(exported, dir.path().join("exports").join("renamed.msave"));
+		assert_eq!(read_manifest(&exported).unwrap().save_name, "renamed");
+		let trashed = delete_save(&renamed).unwrap();
+		assert!(trashed.join("config.ron").is_file());
+		assert!(!renamed.exists());
+		let mut names: Vec<_> = list_saves(&root)
+			.unwrap()
+			.into_iter()
+			.map(|save| save.name)
+			.collect();
+		names.sort();
+		assert_eq!(names, ["first", "old"]);
+
+		// A save that can't be read doesn't hide the others.
+		let broken = root.join("broken");
+		std::fs::create_dir_all(&broken).unwrap();
+		std::fs::write(broken.join("config.ron"), [0xFF, 0xFE]).unwrap();
+		let saves = list_saves(&root).unwrap();
+		assert_eq!(saves.len(), 3);
+		let broken = saves.iter().find(|save| save.name == "broken").unwrap();
+		assert_eq!(broken.format_version, None);
+		assert!(!broken.is_loadable());
+		assert!(matches!(
+			delete_save(&root.join("not a save")),
+			Err(SaveConfigError::InvalidSave(_))
+		));
+	}
+}
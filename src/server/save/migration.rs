@@ -0,0 +1,36 @@
+//! Upgrading saves written by older versions of the game to the current `SAVE_FORMAT_VERSION`.
+
+use crate::server::save::{SaveConfigError, SAVE_FORMAT_VERSION};
+use bevy::prelude::*;
+use std::path::Path;
+
+/// Upgrades a save by one format version.  Gets the save's directory and its configuration as the
+/// older version wrote it, returns the configuration the way the next version writes it and
+/// updates whatever else in the save changed between the two.
+pub type Migration = fn(&Path, String) -> Result<String, SaveConfigError>;
+
+/// `MIGRATIONS[v]` upgrades a save from format version `v` to `v + 1`.
+pub const MIGRATIONS: [Migration; SAVE_FORMAT_VERSION as usize] = [unversioned_to_1];
+
+/// Runs the save through every migration from its format version on, which must not be newer than
+/// `SAVE_FORMAT_VERSION`.
+pub fn migrate(path: &Path, from: u32, config: String) -> Result<String, SaveConfigError> {
+	MIGRATIONS[from as usize..].iter().zip(from..).try_fold(
+		config,
+		|config, (migration, version)| {
+			trace!(
+				"Migrating save at {:?} from format version {}",
+				path,
+				version
+			);
+			migration(path, config)
+				.map_err(|e| SaveConfigError::MigrationFailed(version, Box::new(e)))
+		},
+	)
+}
+
+/// Saves from before the format was versioned only have an empty configuration, which still loads
+/// as it is.
+fn unversioned_to_1(_path: &Path, config: String) -> Result<String, SaveConfigError> {
+	Ok(config)
+}
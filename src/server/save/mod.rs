@@ -0,0 +1,509 @@
+pub mod archive;
+pub mod backup;
+pub mod browser;
+pub mod migration;
+pub mod settings;
+
+use crate::game_data::atomic_file::write_atomically;
+use crate::game_data::chunks::region_file::RegionStorage;
+use crate::game_data::tiles::TileRegistry;
+use crate::game_data::worlds::world::seed::{seed_from_str, SeedRng, WorldSeed};
+use crate::game_data::worlds::world::World;
+use crate::game_data::worlds::{WorldId, Worlds};
+use crate::server::tick::MAX_TICK_RATE;
+use bevy::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use settings::{Difficulty, GameRules, WorldConfig};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Bumped whenever the layout of a save changes, older saves are upgraded through
+/// `migration::MIGRATIONS` when they are loaded.
+pub const SAVE_FORMAT_VERSION: u32 = 1;
+
+const CONFIG_FILE: &str = "config.ron";
+
+/// A save's `config.ron`, anything left out of it keeps its default so operators only need to
+/// write the settings they want to change.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SaveConfig {
+	#[serde(skip)]
+	save_path: PathBuf,
+	/// Whether loading upgraded the save, which backed it up first.
+	#[serde(skip)]
+	upgraded: bool,
+	/// The `SAVE_FORMAT_VERSION` the save was written with, saves from before this field existed
+	/// are version 0.
+	#[serde(default)]
+	format_version: u32,
+	/// Turned into a `WorldSeed` by `seed_from_str`, an empty seed is replaced with a random one
+	/// when the save is loaded.  Defaults to empty.
+	#[serde(default)]
+	pub seed: String,
+	/// Defaults to a single `WorldConfig::default()`.
+	#[serde(default = "settings::default_worlds")]
+	pub worlds: Vec<WorldConfig>,
+	/// Updates per second, defaults to 20.
+	#[serde(default = "settings::default_tick_rate")]
+	pub tick_rate: u32,
+	/// Defaults to 8.
+	#[serde(default = "settings::default_max_players")]
+	pub max_players: u32,
+	/// Defaults to every interface on `protocol::DEFAULT_PORT`.
+	#[serde(default = "settings::default_bind_address")]
+	pub bind_address: SocketAddr,
+	/// Shown to players joining or browsing servers, defaults to "A Mechaenetia server".
+	#[serde(default = "settings::default_motd")]
+	pub motd: String,
+	/// Defaults to `Difficulty::Normal`.
+	#[serde(default)]
+	pub difficulty: Difficulty,
+	/// Defaults to `GameRules::default()`.
+	#[serde(default)]
+	pub game_rules: GameRules,
+}
+
+impl Default for SaveConfig {
+	fn default() -> Self {
+		SaveConfig {
+			save_path: PathBuf::default(),
+			upgraded: false,
+			format_version: SAVE_FORMAT_VERSION,
+			seed: String::new(),
+			worlds: settings::default_worlds(),
+			tick_rate: settings::default_tick_rate(),
+			max_players: settings::default_max_players(),
+			bind_address: settings::default_bind_address(),
+			motd: settings::default_motd(),
+			difficulty: Difficulty::default(),
+			game_rules: GameRules::default(),
+		}
+	}
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SaveConfigError {
+	#[error("IO error while {1}")]
+	LoadError(#[source] std::io::Error, &'static str),
+	#[error("non-valid save at path: {0:?}")]
+	InvalidSave(PathBuf),
+	#[error("ron format error")]
+	RonError(#[from] ron::Error),
+	#[error(
+		"save at {0:?} has format version {1} but only up to {} is supported",
+		SAVE_FORMAT_VERSION
+	)]
+	UnsupportedFormatVersion(PathBuf, u32),
+	#[error("failed upgrading save from format version {0}")]
+	MigrationFailed(u32, #[source] Box<SaveConfigError>),
+	#[error("invalid setting in save at {0:?}: {1}")]
+	InvalidSetting(PathBuf, String),
+	#[error("`{0}` is not a valid save name")]
+	InvalidSaveName(String),
+	#[error("a save already exists at {0:?}")]
+	SaveExists(PathBuf),
+	#[error("invalid save archive {0:?}: {1}")]
+	InvalidArchive(PathBuf, String),
+}
+
+pub enum SaveLoadState {
+	Created(SaveConfig),
+	Existing(SaveConfig),
+}
+
+/// Just the format version of a configuration, read before the rest as that can only be parsed
+/// once it is upgraded.
+#[derive(Deserialize)]
+struct FormatVersion {
+	#[serde(default)]
+	format_version: u32,
+}
+
+impl SaveConfig {
+	/// Loads the save's configuration, upgrading the save to the current format version first if it
+	/// is older.  The save is backed up before it is upgraded.
+	pub fn load_path(path: impl AsRef<Path>) -> Result<SaveConfig, SaveConfigError> {
+		let path = path.as_ref();
+		let config_path = path.join(CONFIG_FILE);
+		let config_string = std::fs::read_to_string(&config_path)
+			.map_err(|e| SaveConfigError::LoadError(e, "reading config.ron file"))?;
+		let version = ron::from_str::<FormatVersion>(&config_string)?.format_version;
+		if version > SAVE_FORMAT_VERSION {
+			return Err(SaveConfigError::UnsupportedFormatVersion(
+				path.to_owned(),
+				version,
+			));
+		}
+		let config_string = if version < SAVE_FORMAT_VERSION {
+			info!(
+				"Upgrading save at {:?} from format version {} to {}",
+				path, version, SAVE_FORMAT_VERSION
+			);
+			let backup = backup::create_backup(path, backup::MAX_BACKUPS)?;
+			info!("Backed up the save to {:?} before upgrading it", backup);
+			migration::migrate(path, version, config_string)?
+		} else {
+			config_string
+		};
+		let mut save_config: SaveConfig = ron::from_str(&config_string)?;
+		save_config.save_path = path.to_owned();
+		save_config.upgraded = version < SAVE_FORMAT_VERSION;
+		save_config.validate()?;
+		let unseeded = save_config.seed.trim().is_empty();
+		if unseeded {
+			save_config.seed = random_seed().to_string();
+			info!(
+				"Picked the seed {} for the save at {:?}",
+				save_config.seed, path
+			);
+		}
+		if version < SAVE_FORMAT_VERSION || unseeded {
+			save_config.format_version = SAVE_FORMAT_VERSION;
+			save_config.write()?;
+		}
+		trace!("Loaded a SaveConfig at: {:?}", path);
+		Ok(save_config)
+	}
+
+	/// Loads the save at the path, or creates a new one if there is no save there yet.  A save that
+	/// fails to load is never replaced.
+	pub fn load_or_create_path(path: impl AsRef<Path>) -> Result<SaveLoadState, SaveConfigError> {
+		let path = path.as_ref();
+		if path.join(CONFIG_FILE).exists() {
+			return Self::load_path(path).map(SaveLoadState::Existing);
+		}
+		if path.exists() && !path.is_dir() {
+			return Err(SaveConfigError::InvalidSave(path.to_owned()));
+		}
+		std::fs::create_dir_all(path)
+			.map_err(|e| SaveConfigError::LoadError(e, "creating save directory"))?;
+
+		let empty_config = SaveConfig {
+			save_path: path.to_owned(),
+			seed: random_seed().to_string(),
+			..Default::default()
+		};
+		info!(
+			"Writing a new SaveConfig to: {:?}",
+			empty_config.config_path()
+		);
+		empty_config.write()?;
+		Ok(SaveLoadState::Created(empty_config))
+	}
+
+	/// Writes the configuration to the save with comments explaining each setting, replacing the
+	/// previous one atomically.  Comments the operator added are not kept.
+	pub fn write(&self) -> Result<(), SaveConfigError> {
+		let config_string = ron::ser::to_string_pretty(
+			self,
+			PrettyConfig::new()
+				.with_new_line("\n".to_owned())
+				.with_indentor("\t".to_owned()),
+		)?;
+		let config_string = settings::add_comments(&config_string);
+		write_atomically(&self.config_path(), config_string.as_bytes())
+			.map_err(|e| SaveConfigError::LoadError(e, "writing configuration"))
+	}
+
+	pub fn save_path(&self) -> &Path {
+		&self.save_path
+	}
+
+	pub fn config_path(&self) -> PathBuf {
+		self.save_path.join(CONFIG_FILE)
+	}
+
+	pub fn format_version(&self) -> u32 {
+		self.format_version
+	}
+
+	/// Checks the settings that would otherwise only fail once the server is running.
+	pub fn validate(&self) -> Result<(), SaveConfigError> {
+		let invalid = |reason: String| {
+			Err(SaveConfigError::InvalidSetting(
+				self.save_path.clone(),
+				reason,
+			))
+		};
+		if !(1..=MAX_TICK_RATE).contains(&self.tick_rate) {
+			return invalid(format!(
+				"`tick_rate` must be between 1 and {}",
+				MAX_TICK_RATE
+			));
+		}
+		if self.worlds.is_empty() {
+			return invalid("`worlds` must list at least one world".to_owned());
+		}
+		let mut names = HashSet::new();
+		for world in &self.worlds {
+			let name = world.name.as_str();
+			if !is_valid_name(name) {
+				return invalid(format!("`{}` is not a valid world name", name));
+			}
+			if !names.insert(name) {
+				return invalid(format!("the world `{}` is listed more than once", name));
+			}
+		}
+		Ok(())
+	}
+
+	/// The seed a world of the save generates with, its own if it has one or else the save's.
+	pub fn world_seed(&self, world: &WorldConfig) -> WorldSeed {
+		seed_from_str(world.seed.as_deref().unwrap_or(&self.seed))
+	}
+
+	/// Copies the save into its backups, dropping the oldest ones beyond `backup::MAX_BACKUPS`.
+	pub fn backup(&self) -> Result<PathBuf, SaveConfigError> {
+		backup::create_backup(&self.save_path, backup::MAX_BACKUPS)
+	}
+
+	/// Like `backup` on a thread of its own.
+	pub fn backup_in_background(&self) -> backup::BackgroundBackup {
+		backup::BackgroundBackup::start(self.save_path.clone(), backup::MAX_BACKUPS)
+	}
+
+	/// Whether loading the save upgraded it to the current format version, in which case it was
+	/// backed up already.
+	pub fn was_upgraded(&self) -> bool {
+		self.upgraded
+	}
+
+	/// Packs the save into a single archive file, see `archive::export_archive`.
+	pub fn export_archive(
+		&self,
+		archive: impl AsRef<Path>,
+	) -> Result<archive::ArchiveManifest, SaveConfigError> {
+		archive::export_archive(&self.save_path, archive.as_ref())
+	}
+
+	/// Unpacks an archive into a new save in the root and loads it, upgrading it if it was exported
+	/// by an older version.  The save is named like the exported one unless given a name.
+	pub fn import_archive(
+		archive: impl AsRef<Path>,
+		saves_root: impl AsRef<Path>,
+		name: Option<&str>,
+	) -> Result<SaveConfig, SaveConfigError> {
+		let path = archive::import_archive(archive.as_ref(), saves_root.as_ref(), name)?;
+		Self::load_path(path)
+	}
+
+	/// Creates the save's worlds in the order they are listed, each saving its chunks in the save.
+	/// The tiles the generators use are registered as they are built, chunks can be saved with any
+	/// tile registered by the time every world is built.
+	pub fn create_worlds(&self, worlds: &mut Worlds, tiles: &mut TileRegistry) -> Vec<WorldId> {
+		let ids: Vec<WorldId> = self
+			.worlds
+			.iter()
+			.map(|config| {
+				let generator = config.generator.build(self.world_seed(config), tiles);
+				worlds.create_world(config.name.as_str().into(), generator)
+			})
+			.collect();
+		let saved_tiles = Arc::new(tiles.clone());
+		for &id in &ids {
+			let storage = self.region_storage(worlds.get_world(id), saved_tiles.clone());
+			worlds.get_world_mut(id).set_storage(Some(storage));
+		}
+		ids
+	}
+
+	/// The directory a world's data is saved in.
+	pub fn world_path(&self, world_name: &str) -> PathBuf {
+		self.save_path.join("worlds").join(world_name)
+	}
+
+	/// Where the chunks of the world are saved in this save, see `World::set_storage`.
+	pub fn region_storage(&self, world: &World, tiles: Arc<TileRegistry>) -> RegionStorage {
+		RegionStorage::new(
+			self.world_path(world.name()).join("regions"),
+			world.chunks().chunk_edge_len(),
+			tiles,
+		)
+	}
+}
+
+/// Whether the name can be used for a save or world directory, which rules out anything that is
+/// not a plain file name on every platform and hidden directories.
+pub fn is_valid_name(name: &str) -> bool {
+	!name.trim().is_empty()
+		&& !name.starts_with('.')
+		&& !name.contains(|c: char| std::path::is_separator(c) || ":<>\"|?*".contains(c))
+}
+
+/// Seeds new saves, it only needs to differ between saves so the time is random enough.
+fn random_seed() -> WorldSeed {
+	let nanos = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|since| since.as_nanos() as u64)
+		.unwrap_or(0);
+	SeedRng::new(nanos).next_u64()
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::test_dir::TestDir;
+	use crate::game_data::tiles::TileRegistry;
+	use crate::game_data::worlds::Worlds;
+	use crate::server::save::backup::list_backups;
+	use crate::server::save::settings::{Difficulty, GameRules, WorldConfig};
+	use crate::server::save::{SaveConfig, SaveConfigError, SaveLoadState, SAVE_FORMAT_VERSION};
+
+	#[test]
+	fn create_and_load_saves() {
+		let dir = TestDir::new("save-config");
+		let config = match SaveConfig::load_or_create_path(dir.path()).unwrap() {
+			SaveLoadState::Created(config) => config,
+			SaveLoadState::Existing(_) => panic!("save didn't exist yet"),
+		};
+		assert_eq!(config.format_version(), SAVE_FORMAT_VERSION);
+		let config = match SaveConfig::load_or_create_path(dir.path()).unwrap() {
+			SaveLoadState::Existing(config) => config,
+			SaveLoadState::Created(_) => panic!("save already existed"),
+		};
+		assert_eq!(config.save_path(), dir.path());
+		assert!(list_backups(dir.path()).unwrap().is_empty());
+
+		// A broken configuration is reported as such and left alone.
+		std::fs::write(config.config_path(), "(format_version: ").unwrap();
+		assert!(matches!(
+			SaveConfig::load_or_create_path(dir.path()),
+			Err(SaveConfigError::RonError(_))
+		));
+		assert_eq!(
+			std::fs::read_to_string(config.config_path()).unwrap(),
+			"(format_version: "
+		);
+	}
+
+	#[test]
+	fn upgrade_old_saves() {
+		let dir = TestDir::new("save-upgrade");
+		std::fs::create_dir_all(dir.path()).unwrap();
+		let config_path = dir.path().join("config.ron");
+		std::fs::write(&config_path, "()\n").unwrap();
+		let config = SaveConfig::load_path(dir.path()).unwrap();
+		assert_eq!(config.format_version(), SAVE_FORMAT_VERSION);
+		assert!(config.was_upgraded());
+		let backups = list_backups(dir.path()).unwrap();
+		assert_eq!(backups.len(), 1);
+		assert_eq!(
+			std::fs::read_to_string(backups[0].join("config.ron")).unwrap(),
+			"()\n"
+		);
+		let upgraded = SaveConfig::load_path(dir.path()).unwrap();
+		assert_eq!(upgraded.format_version(), SAVE_FORMAT_VERSION);
+		assert!(!upgraded.was_upgraded());
+		assert_eq!(list_backups(dir.path()).unwrap().len(), 1);
+
+		let newer = format!("(format_version: {})\n", SAVE_FORMAT_VERSION + 1);
+		std::fs::write(&config_path, newer).unwrap();
+		assert!(matches!(
+			SaveConfig::load_path(dir.path()),
+			Err(SaveConfigError::UnsupportedFormatVersion(_, _))
+		));
+	}
+
+	#[test]
+	fn commented_settings() {
+		let dir = TestDir::new("save-settings");
+		let created = match SaveConfig::load_or_create_path(dir.path()).unwrap() {
+			SaveLoadState::Created(config) => config,
+			SaveLoadState::Existing(_) => panic!("save didn't exist yet"),
+		};
+		assert!(!created.seed.is_empty());
+		let written = std::fs::read_to_string(created.config_path()).unwrap();
+		assert!(written.contains("\t// How many players can be connected at once."));
+		assert!(written.contains("\t\t// Whether players can hurt each other."));
+		let loaded = SaveConfig::load_path(dir.path()).unwrap();
+		assert_eq!(loaded.seed, created.seed);
+		assert_eq!(loaded.worlds, vec![WorldConfig::default()]);
+		assert_eq!(loaded.bind_address, created.bind_address);
+		assert_eq!(
+			std::fs::read_to_string(loaded.config_path()).unwrap(),
+			written
+		);
+
+		// Operators only need to write what they change, an empty seed gets a random one.
+		std::fs::write(
+			created.config_path(),
+			"// Hard mode\n(\n\tdifficulty: Hard,\n\tgame_rules: (pvp: false),\n\tseed: \"\",\n\t\
+			 worlds: [(name: \"a\"), (name: \"b\", seed: Some(\"other\"))],\n)\n",
+		)
+		.unwrap();
+		let edited = SaveConfig::load_path(dir.path()).unwrap();
+		assert_eq!(edited.difficulty, Difficulty::Hard);
+		assert_eq!(
+			edited.game_rules,
+			GameRules {
+				pvp: false,
+				..GameRules::default()
+			}
+		);
+		assert_eq!(edited.tick_rate, 20);
+		assert!(!edited.seed.is_empty());
+		assert_ne!(
+			edited.world_seed(&edited.worlds[0]),
+			edited.world_seed(&edited.worlds[1])
+		);
+		let reloaded = SaveConfig::load_path(dir.path()).unwrap();
+		assert_eq!(reloaded.seed, edited.seed);
+
+		// Game rules are changed by name with values as they are written.
+		let mut rules = reloaded.game_rules.clone();
+		assert_eq!(rules.get("pvp").as_deref(), Some("false"));
+		rules.set("spawn_chunk_radius", "4").unwrap();
+		assert_eq!(rules.spawn_chunk_radius, 4);
+		assert!(rules.set("spawn_chunk_radius", "-1").is_err());
+		assert!(rules.set("gravity", "true").is_err());
+		assert_eq!(rules.get("gravity"), None);
+		assert!(GameRules::NAMES
+			.iter()
+			.all(|rule| rules.get(rule).is_some()));
+
+		std::fs::write(
+			created.config_path(),
+			"(worlds: [(name: \"a\"), (name: \"a\")])",
+		)
+		.unwrap();
+		assert!(matches!(
+			SaveConfig::load_path(dir.path()),
+			Err(SaveConfigError::InvalidSetting(_, _))
+		));
+		for rate in ["0", "1001"].iter() {
+			std::fs::write(created.config_path(), format!("(tick_rate: {})", rate)).unwrap();
+			assert!(matches!(
+				SaveConfig::load_path(dir.path()),
+				Err(SaveConfigError::InvalidSetting(_, _))
+			));
+		}
+	}
+
+	#[test]
+	fn create_configured_worlds() {
+		let dir = TestDir::new("save-worlds");
+		std::fs::create_dir_all(dir.path()).unwrap();
+		std::fs::write(
+			dir.path().join("config.ron"),
+			"(format_version: 1, seed: \"7\", worlds: [(name: \"a\"), (name: \"b\", seed: \
+			 Some(\"8\"), generator: Noise(()))])",
+		)
+		.unwrap();
+		let config = SaveConfig::load_path(dir.path()).unwrap();
+		let mut worlds = Worlds::default();
+		let mut tiles = TileRegistry::default();
+		let ids = config.create_worlds(&mut worlds, &mut tiles);
+		assert_eq!(ids.len(), 2);
+		let (a, b) = (worlds.get_world(ids[0]), worlds.get_world(ids[1]));
+		assert_eq!((a.name().as_str(), a.seed()), ("a", 7));
+		assert_eq!((b.name().as_str(), b.seed()), ("b", 8));
+		assert_eq!(
+			b.storage().unwrap().chunk_edge_len(),
+			b.chunks().chunk_edge_len()
+		);
+		assert!(tiles.len() > 1);
+	}
+}
@@ -0,0 +1,234 @@
+//! The settings a server operator fills in in a save's `config.ron`, and the comments explaining
+//! them that are written along with it.
+
+use crate::game_data::worlds::world::world_generator::WorldGeneratorConfig;
+use crate::universal::network::protocol::DEFAULT_PORT;
+use serde::{Deserialize, Serialize};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+
+/// A world of the save, the first one listed is where players spawn.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldConfig {
+	/// Also the name of the directory the world is saved in, defaults to `overworld`.
+	pub name: String,
+	/// Overrides the seed of the save for just this world, defaults to `None`.
+	pub seed: Option<String>,
+	/// Defaults to the layered generator's own defaults.
+	pub generator: WorldGeneratorConfig,
+}
+
+impl Default for WorldConfig {
+	fn default() -> Self {
+		WorldConfig {
+			name: "overworld".to_owned(),
+			seed: None,
+			generator: WorldGeneratorConfig::default(),
+		}
+	}
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Difficulty {
+	Peaceful,
+	Easy,
+	#[default]
+	Normal,
+	Hard,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameRules {
+	/// Whether players can hurt each other, defaults to `true`.
+	pub pvp: bool,
+	/// Whether players keep their items when they die, defaults to `false`.
+	pub keep_inventory: bool,
+	/// Whether time passes and days turn into nights, defaults to `true`.
+	pub daylight_cycle: bool,
+	/// How many chunks around the spawn stay loaded even without players, defaults to `2`.
+	pub spawn_chunk_radius: u8,
+}
+
+impl Default for GameRules {
+	fn default() -> Self {
+		GameRules {
+			pvp: true,
+			keep_inventory: false,
+			daylight_cycle: true,
+			spawn_chunk_radius: 2,
+		}
+	}
+}
+
+impl GameRules {
+	/// The names of the rules as they are written in the configuration.
+	pub const NAMES: &'static [&'static str] = &[
+		"pvp",
+		"keep_inventory",
+		"daylight_cycle",
+		"spawn_chunk_radius",
+	];
+
+	/// The value of the rule as it is written in the configuration, `None` if there's no such rule.
+	pub fn get(&self, rule: &str) -> Option<String> {
+		let value = match rule {
+			"pvp" => self.pvp.to_string(),
+			"keep_inventory" => self.keep_inventory.to_string(),
+			"daylight_cycle" => self.daylight_cycle.to_string(),
+			"spawn_chunk_radius" => self.spawn_chunk_radius.to_string(),
+			_ => return None,
+		};
+		Some(value)
+	}
+
+	/// Changes the rule to the value as it would be written in the configuration, the error
+	/// explains what was wrong with either.
+	pub fn set(&mut self, rule: &str, value: &str) -> Result<(), String> {
+		fn parse<T: FromStr>(rule: &str, value: &str) -> Result<T, String> {
+			value
+				.parse()
+				.map_err(|_| format!("`{}` is not a valid value for `{}`", value, rule))
+		}
+		match rule {
+			"pvp" => self.pvp = parse(rule, value)?,
+			"keep_inventory" => self.keep_inventory = parse(rule, value)?,
+			"daylight_cycle" => self.daylight_cycle = parse(rule, value)?,
+			"spawn_chunk_radius" => self.spawn_chunk_radius = parse(rule, value)?,
+			_ => return Err(format!("there is no game rule `{}`", rule)),
+		}
+		Ok(())
+	}
+}
+
+pub(super) fn default_tick_rate() -> u32 {
+	20
+}
+
+pub(super) fn default_max_players() -> u32 {
+	8
+}
+
+pub(super) fn default_bind_address() -> SocketAddr {
+	SocketAddr::from((Ipv4Addr::UNSPECIFIED, DEFAULT_PORT))
+}
+
+pub(super) fn default_motd() -> String {
+	"A Mechaenetia server".to_owned()
+}
+
+pub(super) fn default_worlds() -> Vec<WorldConfig> {
+	vec![WorldConfig::default()]
+}
+
+/// The comments put above each field of the written configuration, by how deeply the field is
+/// indented and its name.
+const FIELD_COMMENTS: &[(usize, &str, &str)] = &[
+	(
+		1,
+		"format_version",
+		"Written by the game to upgrade older saves, do not change it.",
+	),
+	(
+		1,
+		"seed",
+		"Numbers are used as they are, any other text is hashed into a number.  An empty seed is \
+		 replaced with a random one the next time the save is loaded.",
+	),
+	(
+		1,
+		"worlds",
+		"The worlds of the save, players spawn in the first one.  At least one is required and \
+		 every name must be unique.  Default: a single layered `overworld`.",
+	),
+	(
+		1,
+		"tick_rate",
+		"How many times per second the server updates the game, from 1 to 1000.  Default: 20",
+	),
+	(
+		1,
+		"max_players",
+		"How many players can be connected at once.  Default: 8",
+	),
+	(
+		1,
+		"bind_address",
+		"The address and port the server listens on for players.  Default: \"0.0.0.0:26510\"",
+	),
+	(
+		1,
+		"motd",
+		"Shown to players joining or browsing servers.  Default: \"A Mechaenetia server\"",
+	),
+	(
+		1,
+		"difficulty",
+		"One of Peaceful, Easy, Normal or Hard.  Default: Normal",
+	),
+	(
+		1,
+		"game_rules",
+		"Rules of the game, changeable while playing.",
+	),
+	(
+		2,
+		"pvp",
+		"Whether players can hurt each other.  Default: true",
+	),
+	(
+		2,
+		"keep_inventory",
+		"Whether players keep their items when they die.  Default: false",
+	),
+	(
+		2,
+		"daylight_cycle",
+		"Whether time passes and days turn into nights.  Default: true",
+	),
+	(
+		2,
+		"spawn_chunk_radius",
+		"How many chunks around the spawn stay loaded even without players.  Default: 2",
+	),
+	(
+		3,
+		"name",
+		"Also the name of the world's directory in the save.",
+	),
+	(
+		3,
+		"seed",
+		"Overrides the seed of the save for just this world.  Default: None",
+	),
+	(
+		3,
+		"generator",
+		"Either Layered or Noise, anything left out of it keeps its default.",
+	),
+];
+
+/// Puts a comment above every field of a configuration written by `ron`'s pretty printer with tabs
+/// for indentation that has one in `FIELD_COMMENTS`.
+pub(super) fn add_comments(config: &str) -> String {
+	let mut commented = String::with_capacity(config.len() * 2);
+	for line in config.lines() {
+		let field = line.trim_start_matches('\t');
+		let depth = line.len() - field.len();
+		let comment = FIELD_COMMENTS.iter().find(|(d, name, _)| {
+			*d == depth && field.strip_prefix(name).map(|rest| rest.starts_with(':')) == Some(true)
+		});
+		if let Some((_, _, comment)) = comment {
+			for _ in 0..depth {
+				commented.push('\t');
+			}
+			commented.push_str("// ");
+			commented.push_str(comment);
+			commented.push('\n');
+		}
+		commented.push_str(line);
+		commented.push('\n');
+	}
+	commented
+}
@@ -1,3 +1,6 @@
+use crate::game_data::worlds::Worlds;
+use crate::server::autosave::flush_worlds;
+use crate::server::save::backup::BackgroundBackup;
 use bevy::prelude::*;
 
 pub fn register_systems(app: &mut AppBuilder) {
@@ -7,8 +10,14 @@ pub fn register_systems(app: &mut AppBuilder) {
 		.add_system_set(SystemSet::on_exit(state.clone()).with_system(on_exit.system()));
 }
 
-fn on_enter() {
+fn on_enter(mut worlds: ResMut<Worlds>, mut backup: ResMut<Option<BackgroundBackup>>) {
 	trace!("Server Exiting State: Enter");
+	// Exiting can interrupt any state, so whatever is loaded is saved here as well, once a backup
+	// still being made is done.
+	if let Some(Err(e)) = backup.take().map(BackgroundBackup::finish) {
+		warn!("Failed backing up the save before loading it: {}", e);
+	}
+	flush_worlds(&mut worlds);
 }
 
 fn on_update() {
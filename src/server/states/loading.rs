@@ -1,11 +1,25 @@
+use crate::game_data::chunks::chunk::{ChunkCoord, ChunkCoordAreaType};
+use crate::game_data::tiles::TileRegistry;
+use crate::game_data::worlds::world::chunk_tickets::{ChunkTicket, TicketKind};
+use crate::game_data::worlds::Worlds;
+use crate::server::save::backup::BackgroundBackup;
 use crate::server::save::SaveConfig;
+use crate::server::tick::{DayTime, ServerTick};
 use crate::universal::exit::Exiting;
 use crate::universal::local_server::{LocalServerCommand, LocalServerPublicState};
 use bevy::prelude::*;
 
+/// How many chunks around the spawn are loaded before the server is running, counted when loading
+/// starts so the progress can be reported.
+#[derive(Default)]
+struct SpawnChunks {
+	total: usize,
+}
+
 pub fn register_systems(app: &mut AppBuilder) {
 	let state = super::ServerState::Loading;
-	app.add_system_set(SystemSet::on_enter(state.clone()).with_system(on_enter.system()))
+	app.init_resource::<SpawnChunks>()
+		.add_system_set(SystemSet::on_enter(state.clone()).with_system(on_enter.system()))
 		.add_system_set(
 			SystemSet::on_update(state.clone())
 				.with_system(on_update.system())
@@ -15,12 +29,38 @@ pub fn register_systems(app: &mut AppBuilder) {
 		.add_system_set(SystemSet::on_exit(state.clone()).with_system(on_exit.system()));
 }
 
+#[allow(clippy::too_many_arguments)]
 fn on_enter(
 	mut public_state: ResMut<LocalServerPublicState>,
 	mut update_public_state: EventWriter<LocalServerPublicState>,
 	save_config_res: Res<Option<SaveConfig>>,
+	mut worlds: ResMut<Worlds>,
+	mut tiles: ResMut<TileRegistry>,
+	mut tick: ResMut<ServerTick>,
+	mut day_time: ResMut<DayTime>,
+	mut spawn_chunks: ResMut<SpawnChunks>,
 ) {
 	trace!("Server Loading State: Enter: {:?}", &*save_config_res);
+	let save_config = save_config_res
+		.as_ref()
+		.expect("the server is loading without a SaveConfig");
+	let ids = save_config.create_worlds(&mut worlds, &mut tiles);
+	info!(
+		"Loading {} worlds of the save at {:?}",
+		ids.len(),
+		save_config.save_path()
+	);
+	// Players spawn in the first world, its spawn stays loaded for as long as the server runs.
+	let radius = save_config.game_rules.spawn_chunk_radius as ChunkCoordAreaType;
+	let spawn = worlds.get_world_mut(ids[0]);
+	spawn.add_chunk_ticket(ChunkTicket::new(
+		TicketKind::Spawn,
+		ChunkCoord::new(0, 0, 0),
+		radius,
+	));
+	spawn_chunks.total = spawn.chunk_metrics().requested;
+	*tick = ServerTick::new(save_config.tick_rate);
+	*day_time = DayTime::default();
 	*public_state = LocalServerPublicState::Loading(0.0);
 	update_public_state.send(public_state.clone());
 }
@@ -28,18 +68,59 @@ fn on_enter(
 fn on_update(
 	mut public_state: ResMut<LocalServerPublicState>,
 	mut update_public_state: EventWriter<LocalServerPublicState>,
+	mut state: ResMut<State<super::ServerState>>,
+	mut worlds: ResMut<Worlds>,
+	spawn_chunks: Res<SpawnChunks>,
+	mut backup: ResMut<Option<BackgroundBackup>>,
 ) {
 	// trace!("Server Loading State: Update");
-	if let LocalServerPublicState::Loading(completion) = &mut *public_state {
-		*completion += (1.0 - *completion) * 0.01;
+	let mut remaining = 0;
+	for world in worlds.iter_mut() {
+		let focus: Vec<_> = world
+			.chunk_tickets()
+			.iter()
+			.map(|(_id, ticket)| ticket.center)
+			.collect();
+		if let Err(e) = world.tick_chunk_generation(&focus, usize::MAX) {
+			error!("Failed loading chunks of world `{}`: {}", world.name(), e);
+		}
+		remaining += world.chunk_metrics().requested;
 	}
+	let completion = match spawn_chunks.total {
+		0 => 1.0,
+		total => 1.0 - remaining.min(total) as f64 / total as f64,
+	};
+	*public_state = LocalServerPublicState::Loading(completion);
 	update_public_state.send(public_state.clone());
+	// The running server writes to the save, which has to wait for the backup.
+	if !finish_backup(&mut backup) {
+		return;
+	}
+	if remaining == 0 {
+		// Fails if the server is already being stopped, which is fine.
+		let _ = state.set(super::ServerState::Running);
+	}
 }
 
 fn on_exit() {
 	trace!("Server Loading State: Exit");
 }
 
+/// Reports the backup made before loading once it is done, returns whether it is.
+pub(super) fn finish_backup(backup: &mut Option<BackgroundBackup>) -> bool {
+	let finished = match backup.as_ref().map(BackgroundBackup::try_finish) {
+		Some(Some(finished)) => finished,
+		Some(None) => return false,
+		None => return true,
+	};
+	match finished {
+		Ok(path) => info!("Backed up the save to `{:?}`", path),
+		Err(e) => warn!("Failed backing up the save before loading it: {}", e),
+	}
+	*backup = None;
+	true
+}
+
 fn on_shutdown(exiting: Option<Res<Exiting>>, mut state: ResMut<State<super::ServerState>>) {
 	if let Some(_exiting) = exiting {
 		state
@@ -59,10 +140,14 @@ fn on_server_public_cmd(
 			}
 			LocalServerCommand::StopServer { force: _ } => {
 				info!("Unloading server from within loading state");
-				state.set(super::ServerState::Unloading).expect(
+				// Stopping takes precedence over finishing loading in the same frame.
+				state.overwrite_set(super::ServerState::Unloading).expect(
 					"Failed transitioning to Server Unloading state from the Loading state",
 				);
 			}
+			LocalServerCommand::Pause { .. } | LocalServerCommand::Resume => {
+				warn!("`{:?}` requested when the server is still loading", &cmd);
+			}
 		}
 	}
 }
@@ -1,8 +1,12 @@
 mod exiting;
 mod loading;
 mod not_running;
+mod paused;
+mod running;
 mod unloading;
 
+use super::autosave::autosave_worlds;
+use super::chunk_loading::{measure_chunks, unload_chunks};
 use bevy::prelude::*;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -15,16 +19,36 @@ enum ServerState {
 	Exiting,
 }
 
+/// Why the server was last paused, kept until it is paused again.
+#[derive(Default)]
+struct Pause {
+	/// Paused by the single-player pause menu rather than on purpose, so it is undone once a remote
+	/// player connects.
+	automatic: bool,
+}
+
 #[derive(Default)]
 pub struct ServerStatePlugin;
 
 impl Plugin for ServerStatePlugin {
 	fn build(&self, app: &mut AppBuilder) {
 		// Add the Server state into the system.
-		app.add_state(ServerState::NotRunning);
+		app.add_state(ServerState::NotRunning)
+			.init_resource::<Pause>();
 		exiting::register_systems(app);
 		loading::register_systems(app);
 		unloading::register_systems(app);
 		not_running::register_systems(app);
+		running::register_systems(app);
+		paused::register_systems(app);
+		// Only the simulation tick stops while paused, everything else keeps going.
+		for state in [ServerState::Running, ServerState::Paused].iter() {
+			app.add_system_set(
+				SystemSet::on_update(state.clone())
+					.with_system(autosave_worlds.system())
+					.with_system(unload_chunks.system())
+					.with_system(measure_chunks.system()),
+			);
+		}
 	}
 }
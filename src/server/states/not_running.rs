@@ -1,3 +1,4 @@
+use crate::server::save::backup::BackgroundBackup;
 use crate::server::save::{SaveConfig, SaveLoadState};
 use crate::universal::exit::Exiting;
 use crate::universal::local_server::{LocalServerCommand, LocalServerPublicState};
@@ -45,6 +46,7 @@ fn on_server_public_cmd(
 	mut state: ResMut<State<super::ServerState>>,
 	mut update_public_state: EventWriter<LocalServerPublicState>,
 	mut save_config_res: ResMut<Option<SaveConfig>>,
+	mut backup: ResMut<Option<BackgroundBackup>>,
 ) {
 	for cmd in cmds.iter() {
 		match cmd {
@@ -62,7 +64,13 @@ fn on_server_public_cmd(
 						);
 						continue;
 					}
-					Ok(SaveLoadState::Existing(save_config)) => save_config,
+					Ok(SaveLoadState::Existing(save_config)) => {
+						// Loading waits for the backup before anything is written to the save.
+						if !save_config.was_upgraded() {
+							*backup = Some(save_config.backup_in_background());
+						}
+						save_config
+					}
 					Ok(SaveLoadState::Created(save_config)) => {
 						if *config_only_if_not_existing {
 							info!("Created Save Configuration at path: `{:?}`", &path);
@@ -81,6 +89,9 @@ fn on_server_public_cmd(
 			LocalServerCommand::StopServer { force: _ } => {
 				info!("Server Stop requested when server is already not running");
 			}
+			LocalServerCommand::Pause { .. } | LocalServerCommand::Resume => {
+				warn!("`{:?}` requested when the server is not running", &cmd);
+			}
 		}
 	}
 }
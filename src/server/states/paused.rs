@@ -0,0 +1,82 @@
+use crate::server::RemotePlayers;
+use crate::universal::exit::Exiting;
+use crate::universal::local_server::{LocalServerCommand, LocalServerPublicState};
+use bevy::prelude::*;
+
+pub fn register_systems(app: &mut AppBuilder) {
+	let state = super::ServerState::Paused;
+	app.add_system_set(SystemSet::on_enter(state.clone()).with_system(on_enter.system()))
+		.add_system_set(
+			SystemSet::on_update(state.clone())
+				.with_system(on_update.system())
+				.with_system(on_server_public_cmd.system())
+				.with_system(on_shutdown.system()),
+		)
+		.add_system_set(SystemSet::on_exit(state.clone()).with_system(on_exit.system()));
+}
+
+fn on_enter(
+	mut public_state: ResMut<LocalServerPublicState>,
+	mut update_public_state: EventWriter<LocalServerPublicState>,
+) {
+	trace!("Server Paused State: Enter");
+	*public_state = LocalServerPublicState::Paused;
+	update_public_state.send(public_state.clone());
+}
+
+/// The simulation doesn't tick while paused, but an automatic pause ends as soon as a remote player
+/// is connected as they would be stuck otherwise.
+fn on_update(
+	mut state: ResMut<State<super::ServerState>>,
+	remote_players: Res<RemotePlayers>,
+	pause: Res<super::Pause>,
+) {
+	// trace!("Server Paused State: Update");
+	if pause.automatic && remote_players.0 > 0 {
+		info!("Resuming the server as a remote player is connected");
+		// Fails if another transition is already queued, which is fine.
+		let _ = state.set(super::ServerState::Running);
+	}
+}
+
+fn on_exit() {
+	trace!("Server Paused State: Exit");
+}
+
+fn on_shutdown(exiting: Option<Res<Exiting>>, mut state: ResMut<State<super::ServerState>>) {
+	if let Some(_exiting) = exiting {
+		state
+			.overwrite_replace(super::ServerState::Exiting)
+			.expect("Failed to transition Server to exiting state");
+	}
+}
+
+fn on_server_public_cmd(
+	mut cmds: EventReader<LocalServerCommand>,
+	mut state: ResMut<State<super::ServerState>>,
+	mut pause: ResMut<super::Pause>,
+) {
+	for cmd in cmds.iter() {
+		match cmd {
+			LocalServerCommand::CreateStartServer { .. } => {
+				warn!("requested to CreateStartServer when already running a server");
+			}
+			LocalServerCommand::StopServer { force: _ } => {
+				info!("Stopping the paused server");
+				// Stopping takes precedence over a resume queued in the same frame.
+				state
+					.overwrite_set(super::ServerState::Unloading)
+					.expect("Failed transitioning to Server Unloading state from the Paused state");
+			}
+			LocalServerCommand::Pause { automatic } => {
+				// An explicit pause isn't undone by remote players joining anymore.
+				pause.automatic &= *automatic;
+			}
+			LocalServerCommand::Resume => {
+				info!("Resuming the server");
+				// Fails if the server is already being stopped, which is fine.
+				let _ = state.set(super::ServerState::Running);
+			}
+		}
+	}
+}
@@ -0,0 +1,133 @@
+use crate::game_data::worlds::Worlds;
+use crate::server::save::SaveConfig;
+use crate::server::tick::{DayTime, ServerTick};
+use crate::server::RemotePlayers;
+use crate::universal::exit::Exiting;
+use crate::universal::local_server::{LocalServerCommand, LocalServerPublicState};
+use bevy::prelude::*;
+
+/// The most chunks that finished generating on the thread pool added to each world per tick, so
+/// a burst of them doesn't make a tick run long.
+const MAX_INTEGRATED_CHUNKS_PER_TICK: usize = 64;
+
+pub fn register_systems(app: &mut AppBuilder) {
+	let state = super::ServerState::Running;
+	app.add_system_set(SystemSet::on_enter(state.clone()).with_system(on_enter.system()))
+		.add_system_set(
+			SystemSet::on_update(state.clone())
+				.with_system(on_update.system())
+				.with_system(on_server_public_cmd.system())
+				.with_system(on_shutdown.system()),
+		)
+		.add_system_set(SystemSet::on_exit(state.clone()).with_system(on_exit.system()));
+}
+
+fn on_enter(
+	mut public_state: ResMut<LocalServerPublicState>,
+	mut update_public_state: EventWriter<LocalServerPublicState>,
+	tick: Res<ServerTick>,
+) {
+	trace!("Server Running State: Enter");
+	info!(
+		"Server running at {} ticks per second from tick {}",
+		tick.rate(),
+		tick.tick()
+	);
+	*public_state = LocalServerPublicState::Running;
+	update_public_state.send(public_state.clone());
+}
+
+/// Runs as many ticks as are due for the time that passed, skipping those the server can't catch
+/// up on.
+fn on_update(
+	time: Res<Time>,
+	mut tick: ResMut<ServerTick>,
+	mut day_time: ResMut<DayTime>,
+	mut worlds: ResMut<Worlds>,
+	save_config: Res<Option<SaveConfig>>,
+) {
+	// trace!("Server Running State: Update");
+	let (due, skipped) = tick.advance(time.delta());
+	if skipped > 0 {
+		warn!(
+			"Server can't keep up, skipped {} ticks ({:?})",
+			skipped,
+			tick.tick_len() * skipped as u32
+		);
+	}
+	let daylight_cycle = (*save_config)
+		.as_ref()
+		.map(|config| config.game_rules.daylight_cycle)
+		.unwrap_or(true);
+	for _ in 0..due {
+		simulate_tick(&mut worlds);
+		if daylight_cycle {
+			day_time.0 += 1;
+		}
+	}
+}
+
+/// Everything the game does each tick.
+fn simulate_tick(worlds: &mut Worlds) {
+	for world in worlds.iter_mut() {
+		let focus: Vec<_> = world
+			.chunk_tickets()
+			.iter()
+			.map(|(_id, ticket)| ticket.center)
+			.collect();
+		if let Err(e) = world.tick_chunk_generation(&focus, MAX_INTEGRATED_CHUNKS_PER_TICK) {
+			error!("Failed loading chunks of world `{}`: {}", world.name(), e);
+		}
+	}
+}
+
+fn on_exit() {
+	trace!("Server Running State: Exit");
+}
+
+fn on_shutdown(exiting: Option<Res<Exiting>>, mut state: ResMut<State<super::ServerState>>) {
+	if let Some(_exiting) = exiting {
+		state
+			.overwrite_replace(super::ServerState::Exiting)
+			.expect("Failed to transition Server to exiting state");
+	}
+}
+
+fn on_server_public_cmd(
+	mut cmds: EventReader<LocalServerCommand>,
+	mut state: ResMut<State<super::ServerState>>,
+	remote_players: Res<RemotePlayers>,
+	mut pause: ResMut<super::Pause>,
+) {
+	for cmd in cmds.iter() {
+		match cmd {
+			LocalServerCommand::CreateStartServer { .. } => {
+				warn!("requested to CreateStartServer when already running a server");
+			}
+			LocalServerCommand::StopServer { force: _ } => {
+				info!("Stopping the running server");
+				// Stopping takes precedence over a pause queued in the same frame.
+				state.overwrite_set(super::ServerState::Unloading).expect(
+					"Failed transitioning to Server Unloading state from the Running state",
+				);
+			}
+			LocalServerCommand::Pause { automatic } => {
+				if *automatic && remote_players.0 > 0 {
+					info!(
+						"Not pausing the server while {} remote players are connected",
+						remote_players.0
+					);
+					continue;
+				}
+				// Fails if the server is already being stopped, which is fine.
+				if state.set(super::ServerState::Paused).is_ok() {
+					info!("Pausing the server");
+					pause.automatic = *automatic;
+				}
+			}
+			LocalServerCommand::Resume => {
+				info!("Server Resume requested when it is already running");
+			}
+		}
+	}
+}
@@ -1,3 +1,7 @@
+use crate::game_data::tiles::TileRegistry;
+use crate::game_data::worlds::Worlds;
+use crate::server::autosave::flush_worlds;
+use crate::server::save::backup::BackgroundBackup;
 use crate::universal::exit::Exiting;
 use crate::universal::local_server::{LocalServerCommand, LocalServerPublicState};
 use bevy::prelude::*;
@@ -23,8 +27,20 @@ fn on_enter(
 	update_public_state.send(public_state.clone());
 }
 
-fn on_update(mut state: ResMut<State<super::ServerState>>) {
+fn on_update(
+	mut state: ResMut<State<super::ServerState>>,
+	mut worlds: ResMut<Worlds>,
+	mut tiles: ResMut<TileRegistry>,
+	mut backup: ResMut<Option<BackgroundBackup>>,
+) {
 	// trace!("Server Unloading State: Update");
+	// Stopped while loading, the backup is still being made and must not see chunks being written.
+	if !super::loading::finish_backup(&mut backup) {
+		return;
+	}
+	flush_worlds(&mut worlds);
+	*worlds = Worlds::default();
+	*tiles = TileRegistry::default();
 	state
 		.set(super::ServerState::NotRunning)
 		.expect("failed changing to state NotRunning after Unloading");
@@ -52,6 +68,9 @@ fn on_server_public_cmd(mut cmds: EventReader<LocalServerCommand>) {
 				warn!("`{:?}` requested when already stopping", &cmd);
 				// Already stopping...
 			}
+			LocalServerCommand::Pause { .. } | LocalServerCommand::Resume => {
+				warn!("`{:?}` requested when the server is stopping", &cmd);
+			}
 		}
 	}
 }
@@ -0,0 +1,155 @@
+//! The server simulates the game in ticks of a fixed length, however often bevy happens to update.
+
+use std::time::Duration;
+
+/// The most ticks run in a single update to catch up after the server fell behind, any more are
+/// skipped so a long stall doesn't turn into a burst of ticks that stalls it again.
+pub const DEFAULT_MAX_CATCH_UP_TICKS: u32 = 10;
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+/// The fastest the server may tick, beyond it ticks get too short to measure or to get anything
+/// done in.
+pub const MAX_TICK_RATE: u32 = 1000;
+
+/// How many ticks a day and night last together, 20 minutes at the default tick rate.
+pub const DAY_LENGTH_TICKS: u64 = 24_000;
+
+/// Turns the time that passed into how many ticks are due and counts the ticks run so far.
+pub struct ServerTick {
+	rate: u32,
+	max_catch_up: u32,
+	tick: u64,
+	/// Time that passed that isn't a whole tick yet.
+	behind_nanos: u64,
+	skipped_total: u64,
+}
+
+impl Default for ServerTick {
+	fn default() -> Self {
+		ServerTick::new(20)
+	}
+}
+
+impl ServerTick {
+	/// Starts at tick 0 running `rate` ticks per second.
+	pub fn new(rate: u32) -> Self {
+		assert_valid_rate(rate);
+		ServerTick {
+			rate,
+			max_catch_up: DEFAULT_MAX_CATCH_UP_TICKS,
+			tick: 0,
+			behind_nanos: 0,
+			skipped_total: 0,
+		}
+	}
+
+	/// Ticks per second.
+	pub fn rate(&self) -> u32 {
+		self.rate
+	}
+
+	pub fn set_rate(&mut self, rate: u32) {
+		assert_valid_rate(rate);
+		self.rate = rate;
+	}
+
+	pub fn tick_len(&self) -> Duration {
+		Duration::from_nanos(self.tick_nanos())
+	}
+
+	pub fn max_catch_up(&self) -> u32 {
+		self.max_catch_up
+	}
+
+	pub fn set_max_catch_up(&mut self, max_catch_up: u32) {
+		self.max_catch_up = max_catch_up.max(1);
+	}
+
+	/// How many ticks were run, the tick being run while simulating.
+	pub fn tick(&self) -> u64 {
+		self.tick
+	}
+
+	/// How many ticks were skipped because the server couldn't keep up.
+	pub fn skipped_total(&self) -> u64 {
+		self.skipped_total
+	}
+
+	/// Adds the time that passed since the last update, returning how many ticks to run now and
+	/// how many were skipped as there were more than `max_catch_up` of them.  The returned ticks
+	/// are counted as run.
+	pub fn advance(&mut self, delta: Duration) -> (u32, u64) {
+		let tick_nanos = self.tick_nanos();
+		let behind = self.behind_nanos as u128 + delta.as_nanos();
+		let due = (behind / tick_nanos as u128) as u64;
+		self.behind_nanos = (behind % tick_nanos as u128) as u64;
+		let run = due.min(self.max_catch_up as u64);
+		let skipped = due - run;
+		self.tick += run;
+		self.skipped_total += skipped;
+		(run as u32, skipped)
+	}
+
+	fn tick_nanos(&self) -> u64 {
+		NANOS_PER_SECOND / self.rate as u64
+	}
+}
+
+fn assert_valid_rate(rate: u32) {
+	assert!(
+		(1..=MAX_TICK_RATE).contains(&rate),
+		"the tick rate must be between 1 and {}",
+		MAX_TICK_RATE
+	);
+}
+
+/// The time in the worlds as the ticks since the first day of the save started, it only moves on
+/// while the `daylight_cycle` game rule is on.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DayTime(pub u64);
+
+impl DayTime {
+	/// Which day it is, starting from day 0.
+	pub fn day(self) -> u64 {
+		self.0 / DAY_LENGTH_TICKS
+	}
+
+	/// The ticks since the current day started.
+	pub fn time_of_day(self) -> u64 {
+		self.0 % DAY_LENGTH_TICKS
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::server::tick::{ServerTick, MAX_TICK_RATE};
+	use std::time::Duration;
+
+	#[test]
+	#[should_panic(expected = "the tick rate must be between 1 and 1000")]
+	fn reject_too_fast_rates() {
+		ServerTick::default().set_rate(MAX_TICK_RATE + 1);
+	}
+
+	#[test]
+	fn fixed_rate_ticks() {
+		let mut tick = ServerTick::new(20);
+		assert_eq!(tick.tick_len(), Duration::from_millis(50));
+		assert_eq!(tick.advance(Duration::from_millis(30)), (0, 0));
+		assert_eq!(tick.advance(Duration::from_millis(30)), (1, 0));
+		assert_eq!(tick.advance(Duration::from_millis(140)), (3, 0));
+		assert_eq!(tick.tick(), 4);
+
+		// A stall only catches up so far, the leftover part of a tick is kept.
+		tick.set_max_catch_up(5);
+		assert_eq!(tick.advance(Duration::from_millis(1020)), (5, 15));
+		assert_eq!(tick.advance(Duration::from_millis(30)), (1, 0));
+		assert_eq!(tick.tick(), 10);
+		assert_eq!(tick.skipped_total(), 15);
+
+		tick.set_rate(1);
+		assert_eq!(tick.advance(Duration::from_millis(999)), (0, 0));
+		assert_eq!(tick.advance(Duration::from_millis(1)), (1, 0));
+	}
+}
@@ -1 +1,35 @@
-pub enum ClientCommand {}
+//! Commands the client runs itself, without asking the server.
+
+use crate::universal::commands::registry::{
+	Arg, ArgKind, CommandSpec, ParsedCommand, PermissionLevel,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientCommand {
+	/// Lists the commands that can be used, or explains one of them.
+	Help { command: Option<String> },
+	/// Exits the game, saving and stopping the local server first.
+	Quit,
+}
+
+/// The commands the client runs as they are typed.
+pub const COMMANDS: &[CommandSpec] = &[
+	CommandSpec {
+		name: "help",
+		aliases: &["?"],
+		args: &[Arg::optional("command", ArgKind::Command)],
+		permission: PermissionLevel::Player,
+		build: |args| {
+			Some(ParsedCommand::Client(ClientCommand::Help {
+				command: args.text(0).map(str::to_owned),
+			}))
+		},
+	},
+	CommandSpec {
+		name: "quit",
+		aliases: &["exit"],
+		args: &[],
+		permission: PermissionLevel::Player,
+		build: |_| Some(ParsedCommand::Client(ClientCommand::Quit)),
+	},
+];
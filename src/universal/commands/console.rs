@@ -0,0 +1,113 @@
+//! Reads commands line by line from the standard input of a dedicated server.
+
+use crate::universal::commands::{CommandLine, CommandReply, CommandSource};
+use crate::universal::local_server::LocalServerCommand;
+use bevy::prelude::*;
+use std::io::BufRead;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleMode {
+	/// Someone is typing at a terminal, replies are printed to it and closing the input (Ctrl+D)
+	/// stops the server.
+	Interactive,
+	/// Nobody is expected to be typing, as when run in a container or by a service manager.
+	/// Commands piped in are still run, but replies only go to the log and input that is closed or
+	/// was never there is ignored.
+	Daemon,
+}
+
+pub struct ConsolePlugin {
+	pub mode: ConsoleMode,
+}
+
+impl Plugin for ConsolePlugin {
+	fn build(&self, app: &mut AppBuilder) {
+		let (lines, receiver) = mpsc::channel();
+		std::thread::Builder::new()
+			.name("console input".to_owned())
+			.spawn(move || {
+				for line in std::io::stdin().lock().lines() {
+					let line = match line {
+						Ok(line) => line,
+						Err(e) => {
+							error!("Failed reading console input: {}", e);
+							return;
+						}
+					};
+					if lines.send(line).is_err() {
+						return;
+					}
+				}
+			})
+			.expect("Failed to spawn the console input thread");
+		app.insert_resource(Console {
+			mode: self.mode,
+			lines: Mutex::new(receiver),
+			closed: false,
+		})
+		.add_system(read_console_commands.system())
+		.add_system(show_command_replies.system());
+	}
+}
+
+struct Console {
+	mode: ConsoleMode,
+	/// Lines sent by the input thread, which stops at the end of the input.
+	lines: Mutex<Receiver<String>>,
+	closed: bool,
+}
+
+fn read_console_commands(
+	mut console: ResMut<Console>,
+	mut commands: EventWriter<CommandLine>,
+	mut server_commands: EventWriter<LocalServerCommand>,
+) {
+	if console.closed {
+		return;
+	}
+	loop {
+		let line = console
+			.lines
+			.lock()
+			.expect("console input lock was poisoned")
+			.try_recv();
+		match line {
+			Ok(line) if line.trim().is_empty() => {}
+			Ok(line) => {
+				info!("Console command: {}", line.trim());
+				commands.send(CommandLine {
+					source: CommandSource::Console,
+					line,
+				});
+			}
+			Err(TryRecvError::Empty) => return,
+			Err(TryRecvError::Disconnected) => {
+				console.closed = true;
+				match console.mode {
+					ConsoleMode::Interactive => {
+						info!("Console input closed, stopping the server");
+						server_commands.send(LocalServerCommand::StopServer { force: false });
+					}
+					ConsoleMode::Daemon => debug!("Console input closed"),
+				}
+				return;
+			}
+		}
+	}
+}
+
+fn show_command_replies(console: Res<Console>, mut replies: EventReader<CommandReply>) {
+	for reply in replies
+		.iter()
+		.filter(|reply| reply.source == CommandSource::Console)
+	{
+		match (console.mode, reply.failed) {
+			(ConsoleMode::Interactive, false) => println!("{}", reply.message),
+			(ConsoleMode::Interactive, true) => eprintln!("{}", reply.message),
+			(ConsoleMode::Daemon, false) => info!("{}", reply.message),
+			(ConsoleMode::Daemon, true) => warn!("{}", reply.message),
+		}
+	}
+}
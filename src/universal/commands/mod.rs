@@ -1,5 +1,144 @@
+//! Commands typed by players and admins.  Whatever they are typed into sends a `CommandLine` event,
+//! which is parsed with the `CommandRegistry` into a `CommandEvent` for the client or the server to
+//! run, and everything that comes of it is sent back as `CommandReply` events to the same source.
+
 pub mod client;
+pub mod console;
+pub mod registry;
 pub mod server;
 
 pub use client::ClientCommand;
+pub use registry::{CommandRegistry, CommandSource, ParsedCommand};
 pub use server::ServerCommand;
+
+use crate::universal::exit::RequestExit;
+use crate::universal::I18n;
+use bevy::prelude::*;
+
+#[derive(Default)]
+pub(super) struct CommandsPlugin;
+
+impl Plugin for CommandsPlugin {
+	fn build(&self, app: &mut AppBuilder) {
+		app.init_resource::<CommandRegistry>()
+			.add_event::<CommandLine>()
+			.add_event::<CommandEvent<ServerCommand>>()
+			.add_event::<CommandEvent<ClientCommand>>()
+			.add_event::<CommandReply>()
+			.add_system(parse_command_lines.system())
+			.add_system(run_client_commands.system());
+	}
+}
+
+/// A line typed by someone, the leading `/` is optional.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandLine {
+	pub source: CommandSource,
+	pub line: String,
+}
+
+/// A parsed command to run, with who sent it so the replies get back to them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandEvent<C> {
+	pub source: CommandSource,
+	pub command: C,
+}
+
+/// The answer to a command, possibly several lines long.  Whatever sent the command shows the
+/// replies with its source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandReply {
+	pub source: CommandSource,
+	pub message: String,
+	/// The command did nothing as it couldn't be done.
+	pub failed: bool,
+}
+
+impl CommandReply {
+	pub fn ok(source: &CommandSource, message: impl Into<String>) -> Self {
+		CommandReply {
+			source: source.clone(),
+			message: message.into(),
+			failed: false,
+		}
+	}
+
+	pub fn failed(source: &CommandSource, message: impl Into<String>) -> Self {
+		CommandReply {
+			source: source.clone(),
+			message: message.into(),
+			failed: true,
+		}
+	}
+}
+
+fn parse_command_lines(
+	registry: Res<CommandRegistry>,
+	mut lines: EventReader<CommandLine>,
+	mut server_commands: EventWriter<CommandEvent<ServerCommand>>,
+	mut client_commands: EventWriter<CommandEvent<ClientCommand>>,
+	mut replies: EventWriter<CommandReply>,
+) {
+	for CommandLine { source, line } in lines.iter() {
+		match registry.parse(line, source.permission()) {
+			Ok(ParsedCommand::Server(command)) => server_commands.send(CommandEvent {
+				source: source.clone(),
+				command,
+			}),
+			Ok(ParsedCommand::Client(command)) => client_commands.send(CommandEvent {
+				source: source.clone(),
+				command,
+			}),
+			Err(e) => replies.send(CommandReply::failed(source, e.to_string())),
+		}
+	}
+}
+
+fn run_client_commands(
+	registry: Res<CommandRegistry>,
+	i18n: Res<I18n>,
+	mut commands: EventReader<CommandEvent<ClientCommand>>,
+	mut replies: EventWriter<CommandReply>,
+	mut exit: EventWriter<RequestExit>,
+) {
+	for CommandEvent { source, command } in commands.iter() {
+		match command {
+			ClientCommand::Help { command: None } => {
+				let help: Vec<_> = registry
+					.permitted(source.permission())
+					.map(|spec| format!("{} - {}", spec.usage(), i18n.get(&spec.help_id())))
+					.collect();
+				replies.send(CommandReply::ok(source, help.join("\n")));
+			}
+			ClientCommand::Help {
+				command: Some(name),
+			} => match registry.get(name) {
+				Some(spec) => {
+					let mut help = format!("{}\n{}", spec.usage(), i18n.get(&spec.help_id()));
+					if !spec.aliases.is_empty() {
+						help.push_str(&format!(
+							"\n{} {}",
+							i18n.get_attr("command-help", "aliases"),
+							spec.aliases.join(", ")
+						));
+					}
+					replies.send(CommandReply::ok(source, help));
+				}
+				None => replies.send(CommandReply::failed(
+					source,
+					registry::CommandParseError::Unknown(name.clone()).to_string(),
+				)),
+			},
+			// Players on the server leave by disconnecting, quitting would stop the server.
+			ClientCommand::Quit => match source {
+				CommandSource::Console | CommandSource::LocalPlayer => {
+					info!("Quitting as requested by {:?}", source);
+					exit.send(RequestExit);
+				}
+				CommandSource::HostPlayer(_) | CommandSource::RemotePlayer(_) => replies.send(
+					CommandReply::failed(source, "Disconnect to leave the server"),
+				),
+			},
+		}
+	}
+}
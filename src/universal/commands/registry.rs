@@ -0,0 +1,500 @@
+//! What commands there are, the arguments they take and who may run them, so every way of typing
+//! commands parses and completes them the same.
+
+use crate::universal::commands::{ClientCommand, ServerCommand};
+
+/// Who may run a command, each level may also run everything the levels below it may.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PermissionLevel {
+	Player,
+	Moderator,
+	Admin,
+}
+
+/// Where a command came from, which decides what it may do and where its replies go.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CommandSource {
+	/// The console of a dedicated server.
+	Console,
+	/// The player of this client, for the commands the client runs itself.
+	LocalPlayer,
+	/// The player of the client the server runs in, joined over a loopback connection, by name.
+	HostPlayer(String),
+	/// A player connected from somewhere else, by name.
+	RemotePlayer(String),
+}
+
+impl CommandSource {
+	pub fn permission(&self) -> PermissionLevel {
+		match self {
+			CommandSource::Console | CommandSource::LocalPlayer | CommandSource::HostPlayer(_) => {
+				PermissionLevel::Admin
+			}
+			CommandSource::RemotePlayer(_) => PermissionLevel::Player,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgKind {
+	Word,
+	/// The name of a player, completed from the players that are connected.
+	Player,
+	/// The name of a command, completed from the registry.
+	Command,
+	Integer,
+	Number,
+	Bool,
+	/// One of the words.
+	Choice(&'static [&'static str]),
+	/// Everything left on the line, only allowed as the last argument.
+	Text,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Arg {
+	pub name: &'static str,
+	pub kind: ArgKind,
+	/// Only allowed if every argument after it is optional too.
+	pub optional: bool,
+}
+
+impl Arg {
+	pub const fn required(name: &'static str, kind: ArgKind) -> Self {
+		Arg {
+			name,
+			kind,
+			optional: false,
+		}
+	}
+
+	pub const fn optional(name: &'static str, kind: ArgKind) -> Self {
+		Arg {
+			name,
+			kind,
+			optional: true,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgValue {
+	/// Words, choices, player and command names and text.
+	Text(String),
+	Integer(i64),
+	Number(f64),
+	Bool(bool),
+}
+
+/// The arguments of a command in the order of its `args`, optional ones left out are `None`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Args(pub Vec<Option<ArgValue>>);
+
+impl Args {
+	pub fn text(&self, index: usize) -> Option<&str> {
+		match self.0.get(index)? {
+			Some(ArgValue::Text(text)) => Some(text),
+			_ => None,
+		}
+	}
+
+	pub fn integer(&self, index: usize) -> Option<i64> {
+		match self.0.get(index)? {
+			Some(ArgValue::Integer(integer)) => Some(*integer),
+			_ => None,
+		}
+	}
+
+	pub fn number(&self, index: usize) -> Option<f64> {
+		match self.0.get(index)? {
+			Some(ArgValue::Number(number)) => Some(*number),
+			_ => None,
+		}
+	}
+
+	pub fn bool(&self, index: usize) -> Option<bool> {
+		match self.0.get(index)? {
+			Some(ArgValue::Bool(value)) => Some(*value),
+			_ => None,
+		}
+	}
+}
+
+/// A command parsed from a line, to be sent as an event to whatever runs it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParsedCommand {
+	Server(ServerCommand),
+	Client(ClientCommand),
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandSpec {
+	pub name: &'static str,
+	pub aliases: &'static [&'static str],
+	pub args: &'static [Arg],
+	pub permission: PermissionLevel,
+	/// Turns arguments that matched `args` into the command, `None` if they don't make sense
+	/// together.
+	pub build: fn(&Args) -> Option<ParsedCommand>,
+}
+
+impl CommandSpec {
+	/// How the command is typed, like `kick <player> [reason...]`.
+	pub fn usage(&self) -> String {
+		let mut usage = self.name.to_owned();
+		for arg in self.args {
+			let dots = if arg.kind == ArgKind::Text { "..." } else { "" };
+			if arg.optional {
+				usage.push_str(&format!(" [{}{}]", arg.name, dots));
+			} else {
+				usage.push_str(&format!(" <{}{}>", arg.name, dots));
+			}
+		}
+		usage
+	}
+
+	/// The `I18n` message id of the description of the command.
+	pub fn help_id(&self) -> String {
+		format!("command-{}", self.name)
+	}
+
+	fn is_called(&self, name: &str) -> bool {
+		self.name == name || self.aliases.contains(&name)
+	}
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum CommandParseError {
+	#[error("no command given")]
+	Empty,
+	#[error("unknown command `{0}`, `help` lists the commands")]
+	Unknown(String),
+	#[error("you aren't allowed to use `{0}`")]
+	NotPermitted(String),
+	#[error("`{value}` is not a valid {arg}, usage: {usage}")]
+	InvalidArgument {
+		arg: &'static str,
+		value: String,
+		usage: String,
+	},
+	#[error("usage: {0}")]
+	Usage(String),
+}
+
+/// Every command that can be typed, on the server and the client alike.
+pub struct CommandRegistry {
+	commands: Vec<CommandSpec>,
+}
+
+impl Default for CommandRegistry {
+	fn default() -> Self {
+		let mut registry = CommandRegistry {
+			commands: Vec::new(),
+		};
+		for spec in crate::universal::commands::client::COMMANDS
+			.iter()
+			.chain(crate::universal::commands::server::COMMANDS)
+		{
+			registry.register(spec.clone());
+		}
+		registry
+	}
+}
+
+impl CommandRegistry {
+	/// Adds a command, panics if its name or an alias is already taken or its arguments are in an
+	/// order that can't be parsed.
+	pub fn register(&mut self, spec: CommandSpec) -> &mut Self {
+		for name in std::iter::once(&spec.name).chain(spec.aliases) {
+			assert!(
+				self.get(name).is_none(),
+				"the command name `{}` is already registered",
+				name
+			);
+		}
+		for (index, arg) in spec.args.iter().enumerate() {
+			let rest = &spec.args[index + 1..];
+			assert!(
+				arg.kind != ArgKind::Text || rest.is_empty(),
+				"the text argument of `{}` must be its last",
+				spec.name
+			);
+			assert!(
+				!arg.optional || rest.iter().all(|arg| arg.optional),
+				"the optional arguments of `{}` must be its last",
+				spec.name
+			);
+		}
+		self.commands.push(spec);
+		self
+	}
+
+	/// The command by its name or one of its aliases.
+	pub fn get(&self, name: &str) -> Option<&CommandSpec> {
+		self.commands.iter().find(|spec| spec.is_called(name))
+	}
+
+	/// The commands someone with the permission level may run, in the order they were registered.
+	pub fn permitted(&self, level: PermissionLevel) -> impl Iterator<Item = &CommandSpec> {
+		self.commands
+			.iter()
+			.filter(move |spec| spec.permission <= level)
+	}
+
+	/// Parses a line like `/kick someone reason`, the leading `/` is optional.  Words are
+	/// separated by whitespace and a text argument takes the rest of the line.
+	pub fn parse(
+		&self,
+		line: &str,
+		level: PermissionLevel,
+	) -> Result<ParsedCommand, CommandParseError> {
+		let line = line.trim();
+		let line = line.strip_prefix('/').unwrap_or(line);
+		let (name, mut rest) = split_word(line);
+		if name.is_empty() {
+			return Err(CommandParseError::Empty);
+		}
+		let spec = self
+			.get(name)
+			.ok_or_else(|| CommandParseError::Unknown(name.to_owned()))?;
+		if spec.permission > level {
+			return Err(CommandParseError::NotPermitted(spec.name.to_owned()));
+		}
+		let mut args = Vec::with_capacity(spec.args.len());
+		for arg in spec.args {
+			let word = if arg.kind == ArgKind::Text {
+				std::mem::take(&mut rest)
+			} else {
+				let (word, remaining) = split_word(rest);
+				rest = remaining;
+				word
+			};
+			if word.is_empty() {
+				if !arg.optional {
+					return Err(CommandParseError::Usage(spec.usage()));
+				}
+				args.push(None);
+				continue;
+			}
+			let invalid = || CommandParseError::InvalidArgument {
+				arg: arg.name,
+				value: word.to_owned(),
+				usage: spec.usage(),
+			};
+			let value = match arg.kind {
+				ArgKind::Integer => ArgValue::Integer(word.parse().map_err(|_| invalid())?),
+				ArgKind::Number => ArgValue::Number(word.parse().map_err(|_| invalid())?),
+				ArgKind::Bool => ArgValue::Bool(word.parse().map_err(|_| invalid())?),
+				ArgKind::Choice(choices) if !choices.contains(&word) => return Err(invalid()),
+				_ => ArgValue::Text(word.to_owned()),
+			};
+			args.push(Some(value));
+		}
+		if !rest.is_empty() {
+			return Err(CommandParseError::Usage(spec.usage()));
+		}
+		(spec.build)(&Args(args)).ok_or_else(|| CommandParseError::Usage(spec.usage()))
+	}
+
+	/// What the word being typed at the end of the line could be completed to, sorted.  `players`
+	/// are the names of the connected players.
+	pub fn complete(&self, line: &str, level: PermissionLevel, players: &[String]) -> Vec<String> {
+		let line = line.trim_start();
+		let line = line.strip_prefix('/').unwrap_or(line);
+		let mut words: Vec<&str> = line.split_whitespace().collect();
+		let partial = if line.is_empty() || line.ends_with(char::is_whitespace) {
+			""
+		} else {
+			words.pop().unwrap_or("")
+		};
+		let mut candidates: Vec<String> = match words.split_first() {
+			None => self
+				.permitted(level)
+				.flat_map(|spec| std::iter::once(&spec.name).chain(spec.aliases))
+				.map(|name| (*name).to_owned())
+				.collect(),
+			Some((name, args)) => {
+				let arg = self
+					.get(name)
+					.filter(|spec| spec.permission <= level)
+					.and_then(|spec| spec.args.get(args.len()));
+				match arg.map(|arg| arg.kind) {
+					Some(ArgKind::Player) => players.to_vec(),
+					Some(ArgKind::Command) => self
+						.permitted(level)
+						.map(|spec| spec.name.to_owned())
+						.collect(),
+					Some(ArgKind::Bool) => vec!["false".to_owned(), "true".to_owned()],
+					Some(ArgKind::Choice(choices)) => {
+						choices.iter().map(|choice| (*choice).to_owned()).collect()
+					}
+					_ => Vec::new(),
+				}
+			}
+		};
+		candidates.retain(|candidate| candidate.starts_with(partial));
+		candidates.sort();
+		candidates.dedup();
+		candidates
+	}
+}
+
+/// The first word and the rest of the text after the whitespace following it.
+fn split_word(text: &str) -> (&str, &str) {
+	let text = text.trim_start();
+	match text.find(char::is_whitespace) {
+		Some(end) => (&text[..end], text[end..].trim_start()),
+		None => (text, ""),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::universal::commands::registry::{
+		CommandParseError, CommandRegistry, CommandSpec, ParsedCommand, PermissionLevel,
+	};
+	use crate::universal::commands::server::TimeCommand;
+	use crate::universal::commands::{ClientCommand, ServerCommand};
+
+	fn parse(line: &str) -> Result<ParsedCommand, CommandParseError> {
+		CommandRegistry::default().parse(line, PermissionLevel::Admin)
+	}
+
+	fn server(command: ServerCommand) -> Result<ParsedCommand, CommandParseError> {
+		Ok(ParsedCommand::Server(command))
+	}
+
+	#[test]
+	fn parse_commands() {
+		assert_eq!(parse("stop"), server(ServerCommand::Stop));
+		assert_eq!(parse("  /save \n"), server(ServerCommand::Save));
+		assert_eq!(
+			parse("/kick griefer  stop  breaking things"),
+			server(ServerCommand::Kick {
+				player: "griefer".to_owned(),
+				reason: Some("stop  breaking things".to_owned()),
+			})
+		);
+		assert_eq!(
+			parse("kick griefer"),
+			server(ServerCommand::Kick {
+				player: "griefer".to_owned(),
+				reason: None,
+			})
+		);
+		assert_eq!(
+			parse("say Restarting in  5 minutes"),
+			server(ServerCommand::Say {
+				message: "Restarting in  5 minutes".to_owned()
+			})
+		);
+		assert_eq!(
+			parse("teleport someone 1 -2.5 3"),
+			server(ServerCommand::Teleport {
+				player: "someone".to_owned(),
+				position: [1.0, -2.5, 3.0],
+			})
+		);
+		assert_eq!(
+			parse("settile -5 0 7 stone"),
+			server(ServerCommand::SetTile {
+				position: [-5, 0, 7],
+				tile: "stone".to_owned(),
+			})
+		);
+		assert_eq!(
+			parse("time"),
+			server(ServerCommand::Time(TimeCommand::Query))
+		);
+		assert_eq!(
+			parse("time add 600"),
+			server(ServerCommand::Time(TimeCommand::Add(600)))
+		);
+		assert_eq!(
+			parse("gamerule pvp false"),
+			server(ServerCommand::GameRule {
+				rule: Some("pvp".to_owned()),
+				value: Some("false".to_owned()),
+			})
+		);
+		assert_eq!(
+			parse("/? tp"),
+			Ok(ParsedCommand::Client(ClientCommand::Help {
+				command: Some("tp".to_owned())
+			}))
+		);
+
+		assert_eq!(parse("/"), Err(CommandParseError::Empty));
+		assert_eq!(
+			parse("op someone"),
+			Err(CommandParseError::Unknown("op".to_owned()))
+		);
+		for line in &[
+			"stop now",
+			"say",
+			"tp someone 1 2",
+			"time set",
+			"time set -1",
+			"settile 0 0 3000000000 stone",
+			"gamerule a b c",
+		] {
+			assert!(
+				matches!(parse(line), Err(CommandParseError::Usage(_))),
+				"{}",
+				line
+			);
+		}
+		for line in &["tp someone 1 2 x", "time skip 5"] {
+			assert!(
+				matches!(parse(line), Err(CommandParseError::InvalidArgument { .. })),
+				"{}",
+				line
+			);
+		}
+
+		let registry = CommandRegistry::default();
+		assert_eq!(
+			registry.parse("kick someone", PermissionLevel::Player),
+			Err(CommandParseError::NotPermitted("kick".to_owned()))
+		);
+		assert!(registry.parse("help", PermissionLevel::Player).is_ok());
+		assert_eq!(
+			registry.get("tp").unwrap().usage(),
+			"teleport <player> <x> <y> <z>"
+		);
+		assert_eq!(registry.get("kick").unwrap().help_id(), "command-kick");
+	}
+
+	#[test]
+	fn complete_commands() {
+		let registry = CommandRegistry::default();
+		let players = ["Alice".to_owned(), "Bob".to_owned(), "Alex".to_owned()];
+		let complete = |line| registry.complete(line, PermissionLevel::Admin, &players);
+		assert_eq!(complete("/s"), ["save", "say", "settile", "stop"]);
+		assert_eq!(complete("ga"), ["gamerule"]);
+		assert_eq!(complete("kick Al"), ["Alex", "Alice"]);
+		assert_eq!(complete("kick "), ["Alex", "Alice", "Bob"]);
+		assert_eq!(complete("time s"), ["set"]);
+		assert_eq!(complete("help ki"), ["kick"]);
+		assert!(complete("say hello ").is_empty());
+		assert!(complete("unknown ").is_empty());
+		assert!(!registry
+			.complete("", PermissionLevel::Player, &players)
+			.contains(&"stop".to_owned()));
+		assert!(registry
+			.complete("kick ", PermissionLevel::Player, &players)
+			.is_empty());
+	}
+
+	#[test]
+	#[should_panic(expected = "already registered")]
+	fn unique_names() {
+		CommandRegistry::default().register(CommandSpec {
+			name: "halt",
+			aliases: &["stop"],
+			args: &[],
+			permission: PermissionLevel::Admin,
+			build: |_| Some(ParsedCommand::Server(ServerCommand::Stop)),
+		});
+	}
+}
@@ -1 +1,172 @@
-pub enum ServerCommand {}
+//! Commands run by the server, mostly to administer it.
+
+use crate::universal::commands::registry::{
+	Arg, ArgKind, Args, CommandSpec, ParsedCommand, PermissionLevel,
+};
+use std::convert::TryFrom;
+
+/// A command for the server, sent as a `CommandEvent` that the server answers with a
+/// `CommandReply`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerCommand {
+	/// Saves everything then stops the server.
+	Stop,
+	/// Writes every changed chunk and the configuration now instead of waiting for the autosave.
+	Save,
+	/// Lists the connected players.
+	List,
+	/// Disconnects a player.
+	Kick {
+		player: String,
+		reason: Option<String>,
+	},
+	/// Sends a message to every player.
+	Say { message: String },
+	/// Moves a player to a position in the world they are in.
+	Teleport { player: String, position: [f64; 3] },
+	/// Changes the tile at a position in the world players spawn in.
+	SetTile { position: [i32; 3], tile: String },
+	/// Shows or changes the time of day.
+	Time(TimeCommand),
+	/// Shows every game rule if no rule is given, shows the rule if no value is given, or changes
+	/// it.
+	GameRule {
+		rule: Option<String>,
+		value: Option<String>,
+	},
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeCommand {
+	Query,
+	/// Sets the ticks since the first day of the save started.
+	Set(u64),
+	/// Skips ahead by the ticks.
+	Add(u64),
+}
+
+/// The commands the server runs as they are typed.
+pub const COMMANDS: &[CommandSpec] = &[
+	CommandSpec {
+		name: "stop",
+		aliases: &[],
+		args: &[],
+		permission: PermissionLevel::Admin,
+		build: |_| server(ServerCommand::Stop),
+	},
+	CommandSpec {
+		name: "save",
+		aliases: &[],
+		args: &[],
+		permission: PermissionLevel::Admin,
+		build: |_| server(ServerCommand::Save),
+	},
+	CommandSpec {
+		name: "list",
+		aliases: &["players"],
+		args: &[],
+		permission: PermissionLevel::Player,
+		build: |_| server(ServerCommand::List),
+	},
+	CommandSpec {
+		name: "kick",
+		aliases: &[],
+		args: &[
+			Arg::required("player", ArgKind::Player),
+			Arg::optional("reason", ArgKind::Text),
+		],
+		permission: PermissionLevel::Moderator,
+		build: |args| {
+			server(ServerCommand::Kick {
+				player: args.text(0)?.to_owned(),
+				reason: args.text(1).map(str::to_owned),
+			})
+		},
+	},
+	CommandSpec {
+		name: "say",
+		aliases: &["broadcast"],
+		args: &[Arg::required("message", ArgKind::Text)],
+		permission: PermissionLevel::Moderator,
+		build: |args| {
+			server(ServerCommand::Say {
+				message: args.text(0)?.to_owned(),
+			})
+		},
+	},
+	CommandSpec {
+		name: "teleport",
+		aliases: &["tp"],
+		args: &[
+			Arg::required("player", ArgKind::Player),
+			Arg::required("x", ArgKind::Number),
+			Arg::required("y", ArgKind::Number),
+			Arg::required("z", ArgKind::Number),
+		],
+		permission: PermissionLevel::Admin,
+		build: |args| {
+			server(ServerCommand::Teleport {
+				player: args.text(0)?.to_owned(),
+				position: [args.number(1)?, args.number(2)?, args.number(3)?],
+			})
+		},
+	},
+	CommandSpec {
+		name: "settile",
+		aliases: &[],
+		args: &[
+			Arg::required("x", ArgKind::Integer),
+			Arg::required("y", ArgKind::Integer),
+			Arg::required("z", ArgKind::Integer),
+			Arg::required("tile", ArgKind::Word),
+		],
+		permission: PermissionLevel::Admin,
+		build: |args| {
+			let axis = |index| i32::try_from(args.integer(index)?).ok();
+			server(ServerCommand::SetTile {
+				position: [axis(0)?, axis(1)?, axis(2)?],
+				tile: args.text(3)?.to_owned(),
+			})
+		},
+	},
+	CommandSpec {
+		name: "time",
+		aliases: &[],
+		args: &[
+			Arg::optional("set|add", ArgKind::Choice(&["set", "add"])),
+			Arg::optional("ticks", ArgKind::Integer),
+		],
+		permission: PermissionLevel::Admin,
+		build: build_time,
+	},
+	CommandSpec {
+		name: "gamerule",
+		aliases: &[],
+		args: &[
+			Arg::optional("rule", ArgKind::Word),
+			Arg::optional("value", ArgKind::Word),
+		],
+		permission: PermissionLevel::Admin,
+		build: |args| {
+			server(ServerCommand::GameRule {
+				rule: args.text(0).map(str::to_owned),
+				value: args.text(1).map(str::to_owned),
+			})
+		},
+	},
+];
+
+fn server(command: ServerCommand) -> Option<ParsedCommand> {
+	Some(ParsedCommand::Server(command))
+}
+
+fn build_time(args: &Args) -> Option<ParsedCommand> {
+	let ticks = args.integer(1);
+	let time = match args.text(0) {
+		None if ticks.is_none() => TimeCommand::Query,
+		Some("set") => TimeCommand::Set(ticks.filter(|ticks| *ticks >= 0)? as u64),
+		Some("add") => TimeCommand::Add(ticks.filter(|ticks| *ticks >= 0)? as u64),
+		_ => return None,
+	};
+	server(ServerCommand::Time(time))
+}
@@ -11,7 +11,8 @@ impl Plugin for LocalServerPlugin {
 	}
 }
 
-/// Event to send control commands to the LocalServer
+/// Event to send control commands to the LocalServer.  Playing on it goes through a loopback
+/// connection like on any other server, see `NetworkClientCommand::JoinLocal`.
 #[derive(Debug)]
 pub enum LocalServerCommand {
 	CreateStartServer {
@@ -21,6 +22,13 @@ pub enum LocalServerCommand {
 	StopServer {
 		force: bool,
 	},
+	/// Freezes the simulation of a running server, it still answers everything else.  An
+	/// `automatic` pause, like the one when opening the single-player pause menu, is only done
+	/// while no remote players are connected.
+	Pause {
+		automatic: bool,
+	},
+	Resume,
 }
 
 /// A resource that is inserted when the local server is compiled in, and doesn't when its not.
@@ -38,6 +46,8 @@ pub enum LocalServerPublicState {
 	Loading(f64),
 	/// A LocalServer is running and ready for connection
 	Running,
+	/// A LocalServer is running but its simulation is frozen until it is resumed
+	Paused,
 	/// A LocalServer is shutting down
 	ShuttingDown,
 }
@@ -3,6 +3,7 @@ pub mod conditional_map;
 pub mod exit;
 pub mod i18n;
 pub mod local_server;
+pub mod network;
 
 pub use i18n::I18n;
 
@@ -55,10 +56,12 @@ impl PluginGroup for UniversalPluginGroup {
 			.add(bevy::scene::ScenePlugin::default())
 			.add(bevy::gltf::GltfPlugin::default())
 			.add(exit::ExitPlugin::default())
+			.add(commands::CommandsPlugin::default())
 			.add(i18n::I18nPlugin::new(
 				self.languages_root_path.clone(),
 				self.languages.clone(),
 			))
-			.add(local_server::LocalServerPlugin::default());
+			.add(local_server::LocalServerPlugin::default())
+			.add(network::client::NetworkClientPlugin::default());
 	}
 }
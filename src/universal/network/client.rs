@@ -0,0 +1,360 @@
+//! Joins servers, in other processes or the one this process runs, for whichever client is shown to
+//! control and display.
+
+use crate::universal::commands::{CommandReply, CommandSource};
+use crate::universal::exit::Exiting;
+use crate::universal::network::connection::{Connection, NetworkError, Transport};
+use crate::universal::network::loopback::LoopbackListener;
+use crate::universal::network::protocol::{
+	is_valid_player_name, ClientMessage, DisconnectReason, ServerMessage, GAME_VERSION,
+	PROTOCOL_VERSION,
+};
+use crate::universal::network::tcp::{with_default_port, TcpTransport};
+use crate::universal::network::world_cache::{WorldCache, WorldCacheError};
+use crate::universal::I18n;
+use bevy::prelude::*;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long connecting to a server may take before giving up.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The name of the player hosting the game if they didn't pick one.
+pub const DEFAULT_PLAYER_NAME: &str = "Player";
+
+#[derive(Default)]
+pub(crate) struct NetworkClientPlugin;
+
+impl Plugin for NetworkClientPlugin {
+	fn build(&self, app: &mut AppBuilder) {
+		app.add_event::<NetworkClientCommand>()
+			.add_event::<ServerConnectionState>()
+			.add_event::<ChatMessage>()
+			.insert_resource(ServerConnectionState::Disconnected(None))
+			.init_resource::<NetworkClient>()
+			.init_resource::<WorldCache>()
+			.add_system(run_network_client_commands.system())
+			.add_system(poll_server_connection.system());
+	}
+}
+
+/// Event to join or leave a server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetworkClientCommand {
+	/// Leaves the current server first if there is one.  The address may be a host name and gets
+	/// `DEFAULT_PORT` if it has no port.
+	Connect {
+		address: String,
+		player_name: String,
+	},
+	/// Joins the server running in this process over a loopback connection, which refuses it
+	/// unless it is running.  Leaves the current server first if there is one.
+	JoinLocal {
+		player_name: String,
+	},
+	/// Sends a command line typed by the player to the server, whose replies come back as
+	/// `CommandReply` events from `CommandSource::LocalPlayer`.
+	SendCommand(String),
+	Disconnect,
+}
+
+/// A resource of how the client is connected to a server, also sent as an event whenever it changes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerConnectionState {
+	/// Not connected, with why the last connection ended unless the player left on purpose.
+	Disconnected(Option<String>),
+	Connecting,
+	/// Connected and waiting for the server to let the player join.
+	Joining,
+	Connected {
+		motd: String,
+	},
+}
+
+/// A chat message from the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMessage(pub String);
+
+#[derive(Default)]
+struct NetworkClient {
+	player_name: String,
+	/// The result of the thread connecting to a server, as connecting blocks.
+	connecting: Option<Mutex<Receiver<Result<TcpTransport, NetworkError>>>>,
+	connection: Option<Connection<ClientMessage, ServerMessage>>,
+}
+
+impl NetworkClient {
+	/// Says hello to the server, which answers if the player may join.
+	fn start_joining(&mut self, transport: Box<dyn Transport>, language: String) {
+		let mut connection = Connection::new(transport, Instant::now());
+		let hello = ClientMessage::Hello {
+			protocol_version: PROTOCOL_VERSION,
+			game_version: GAME_VERSION.to_owned(),
+			language,
+			player_name: self.player_name.clone(),
+		};
+		// A failed send shows up when polling.
+		let _ = connection.send(&hello);
+		self.connection = Some(connection);
+	}
+
+	fn disconnect(&mut self, reason: DisconnectReason) {
+		self.connecting = None;
+		if let Some(mut connection) = self.connection.take() {
+			info!("Leaving the server at {}", connection.peer());
+			connection.disconnect(reason);
+		}
+	}
+}
+
+fn set_state(
+	state: &mut ServerConnectionState,
+	state_events: &mut EventWriter<ServerConnectionState>,
+	new_state: ServerConnectionState,
+) {
+	*state = new_state;
+	state_events.send(state.clone());
+}
+
+fn run_network_client_commands(
+	mut commands: EventReader<NetworkClientCommand>,
+	mut client: ResMut<NetworkClient>,
+	mut state: ResMut<ServerConnectionState>,
+	mut state_events: EventWriter<ServerConnectionState>,
+	loopback: Option<Res<LoopbackListener>>,
+	i18n: Res<I18n>,
+) {
+	for command in commands.iter() {
+		match command {
+			NetworkClientCommand::JoinLocal { player_name } => {
+				client.disconnect(DisconnectReason::Quit);
+				let loopback = match &loopback {
+					Some(loopback) => loopback,
+					None => {
+						warn!("Can't join the local server as there is none");
+						continue;
+					}
+				};
+				info!("Joining the local server as {}", player_name);
+				client.player_name = player_name.clone();
+				let language = i18n.get_current_language().to_string();
+				client.start_joining(Box::new(loopback.connect()), language);
+				set_state(
+					&mut state,
+					&mut state_events,
+					ServerConnectionState::Joining,
+				);
+			}
+			NetworkClientCommand::Connect {
+				address,
+				player_name,
+			} => {
+				client.disconnect(DisconnectReason::Quit);
+				if !is_valid_player_name(player_name) {
+					let reason = DisconnectReason::InvalidName.to_string();
+					set_state(
+						&mut state,
+						&mut state_events,
+						ServerConnectionState::Disconnected(Some(reason)),
+					);
+					continue;
+				}
+				let address = with_default_port(address);
+				info!("Connecting to the server at {}", address);
+				let (sender, receiver) = mpsc::channel();
+				let spawned = std::thread::Builder::new()
+					.name("server connect".to_owned())
+					.spawn(move || {
+						let _ = sender.send(TcpTransport::connect(&address, CONNECT_TIMEOUT));
+					});
+				if let Err(e) = spawned {
+					error!("Failed to spawn the server connect thread: {}", e);
+					let reason = Some(e.to_string());
+					set_state(
+						&mut state,
+						&mut state_events,
+						ServerConnectionState::Disconnected(reason),
+					);
+					continue;
+				}
+				client.player_name = player_name.clone();
+				client.connecting = Some(Mutex::new(receiver));
+				set_state(
+					&mut state,
+					&mut state_events,
+					ServerConnectionState::Connecting,
+				);
+			}
+			NetworkClientCommand::SendCommand(line) => match client.connection.as_mut() {
+				Some(connection) if matches!(*state, ServerConnectionState::Connected { .. }) => {
+					if let Err(e) = connection.send(&ClientMessage::Command(line.clone())) {
+						warn!("Failed sending a command to the server: {}", e);
+					}
+				}
+				_ => warn!("Not sending the command `{}` as no server is joined", line),
+			},
+			NetworkClientCommand::Disconnect => {
+				client.disconnect(DisconnectReason::Quit);
+				if *state != ServerConnectionState::Disconnected(None) {
+					set_state(
+						&mut state,
+						&mut state_events,
+						ServerConnectionState::Disconnected(None),
+					);
+				}
+			}
+		}
+	}
+}
+
+#[allow(clippy::too_many_arguments)]
+fn poll_server_connection(
+	mut client: ResMut<NetworkClient>,
+	mut state: ResMut<ServerConnectionState>,
+	mut state_events: EventWriter<ServerConnectionState>,
+	mut replies: EventWriter<CommandReply>,
+	mut chat: EventWriter<ChatMessage>,
+	mut world: ResMut<WorldCache>,
+	i18n: Res<I18n>,
+	exiting: Option<Res<Exiting>>,
+) {
+	if exiting.is_some() {
+		if client.connection.is_some() || client.connecting.is_some() {
+			client.disconnect(DisconnectReason::Quit);
+			set_state(
+				&mut state,
+				&mut state_events,
+				ServerConnectionState::Disconnected(None),
+			);
+		}
+		return;
+	}
+
+	let connected = match client.connecting.as_ref() {
+		Some(connecting) => connecting
+			.lock()
+			.expect("server connect lock was poisoned")
+			.try_recv(),
+		None => Err(TryRecvError::Empty),
+	};
+	match connected {
+		Err(TryRecvError::Empty) => {}
+		Err(TryRecvError::Disconnected) => {
+			client.connecting = None;
+			let reason = Some("connecting stopped unexpectedly".to_owned());
+			set_state(
+				&mut state,
+				&mut state_events,
+				ServerConnectionState::Disconnected(reason),
+			);
+		}
+		Ok(Err(e)) => {
+			client.connecting = None;
+			warn!("Failed connecting to the server: {}", e);
+			let reason = match e {
+				NetworkError::Io(e) => Some(e.to_string()),
+				e => Some(e.to_string()),
+			};
+			set_state(
+				&mut state,
+				&mut state_events,
+				ServerConnectionState::Disconnected(reason),
+			);
+		}
+		Ok(Ok(transport)) => {
+			client.connecting = None;
+			let language = i18n.get_current_language().to_string();
+			client.start_joining(Box::new(transport), language);
+			set_state(
+				&mut state,
+				&mut state_events,
+				ServerConnectionState::Joining,
+			);
+		}
+	}
+
+	// What was sent of the world leaves with the server.
+	if client.connection.is_none() && world.world().is_some() {
+		world.clear();
+	}
+	let connection = match client.connection.as_mut() {
+		Some(connection) => connection,
+		None => return,
+	};
+	let messages = match connection.poll(Instant::now()) {
+		Ok(messages) => messages,
+		Err(e) => {
+			let reason = e.reason();
+			info!(
+				"Disconnected from the server at {}: {}",
+				connection.peer(),
+				reason
+			);
+			client.connection = None;
+			set_state(
+				&mut state,
+				&mut state_events,
+				ServerConnectionState::Disconnected(Some(reason.to_string())),
+			);
+			return;
+		}
+	};
+	for message in messages {
+		let applied: Result<(), WorldCacheError> = match message {
+			ServerMessage::Welcome { game_version, motd } => {
+				info!(
+					"Joined the server at {} running version {}: {}",
+					connection.peer(),
+					game_version,
+					motd
+				);
+				world.clear();
+				set_state(
+					&mut state,
+					&mut state_events,
+					ServerConnectionState::Connected { motd },
+				);
+				Ok(())
+			}
+			ServerMessage::CommandReply { message, failed } => {
+				replies.send(CommandReply {
+					source: CommandSource::LocalPlayer,
+					message,
+					failed,
+				});
+				Ok(())
+			}
+			ServerMessage::Chat(message) => {
+				info!("{}", message);
+				chat.send(ChatMessage(message));
+				Ok(())
+			}
+			ServerMessage::Tiles(names) => world.set_tiles(&names),
+			ServerMessage::View {
+				world: name,
+				chunk_edge_len,
+				view,
+			} => {
+				world.set_view(&name, chunk_edge_len, view);
+				Ok(())
+			}
+			ServerMessage::Chunk(chunk) => world.insert_chunk(&chunk),
+			ServerMessage::TileChanges(changes) => world.apply_tile_changes(&changes),
+			// Handled by the connection.
+			ServerMessage::KeepAlive | ServerMessage::Disconnect(_) => Ok(()),
+		};
+		if let Err(e) = applied {
+			warn!("The server at {} sent {}", connection.peer(), e);
+			let reason = DisconnectReason::ProtocolError(e.to_string());
+			connection.disconnect(reason.clone());
+			client.connection = None;
+			set_state(
+				&mut state,
+				&mut state_events,
+				ServerConnectionState::Disconnected(Some(reason.to_string())),
+			);
+			return;
+		}
+	}
+}
@@ -0,0 +1,334 @@
+//! The binary encoding of network messages and the frames they are sent in.  Numbers are little
+//! endian, strings and byte arrays are prefixed with their length as a `u32`.
+
+use std::convert::TryInto;
+
+/// Every frame starts with the length of the message in it as a `u32`.
+pub const FRAME_HEADER_LEN: usize = 4;
+
+/// The largest message accepted, larger frames mean the other end is broken or hostile and the
+/// connection is dropped.
+pub const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum DecodeError {
+	#[error("message ended early")]
+	UnexpectedEnd,
+	#[error("{0} bytes left over after the message")]
+	TrailingBytes(usize),
+	#[error("invalid UTF-8 in text")]
+	InvalidText,
+	#[error("invalid {0} tag {1}")]
+	InvalidTag(&'static str, u8),
+	#[error("invalid {0}")]
+	InvalidValue(&'static str),
+	#[error("frame of {0} bytes is larger than the maximum of {}", MAX_FRAME_LEN)]
+	FrameTooLarge(usize),
+}
+
+/// Builds the bytes of a message.
+#[derive(Debug, Default)]
+pub struct Writer {
+	bytes: Vec<u8>,
+}
+
+impl Writer {
+	pub fn new() -> Self {
+		Writer::default()
+	}
+
+	pub fn into_bytes(self) -> Vec<u8> {
+		self.bytes
+	}
+
+	pub fn u8(&mut self, value: u8) -> &mut Self {
+		self.bytes.push(value);
+		self
+	}
+
+	pub fn bool(&mut self, value: bool) -> &mut Self {
+		self.u8(value as u8)
+	}
+
+	pub fn u16(&mut self, value: u16) -> &mut Self {
+		self.bytes.extend_from_slice(&value.to_le_bytes());
+		self
+	}
+
+	pub fn u32(&mut self, value: u32) -> &mut Self {
+		self.bytes.extend_from_slice(&value.to_le_bytes());
+		self
+	}
+
+	pub fn u64(&mut self, value: u64) -> &mut Self {
+		self.bytes.extend_from_slice(&value.to_le_bytes());
+		self
+	}
+
+	pub fn i16(&mut self, value: i16) -> &mut Self {
+		self.bytes.extend_from_slice(&value.to_le_bytes());
+		self
+	}
+
+	pub fn i32(&mut self, value: i32) -> &mut Self {
+		self.bytes.extend_from_slice(&value.to_le_bytes());
+		self
+	}
+
+	pub fn i64(&mut self, value: i64) -> &mut Self {
+		self.bytes.extend_from_slice(&value.to_le_bytes());
+		self
+	}
+
+	pub fn f64(&mut self, value: f64) -> &mut Self {
+		self.bytes.extend_from_slice(&value.to_le_bytes());
+		self
+	}
+
+	pub fn bytes(&mut self, value: &[u8]) -> &mut Self {
+		self.u32(value.len() as u32);
+		self.bytes.extend_from_slice(value);
+		self
+	}
+
+	pub fn str(&mut self, value: &str) -> &mut Self {
+		self.bytes(value.as_bytes())
+	}
+
+	pub fn option<T>(&mut self, value: Option<T>, write: impl FnOnce(&mut Self, T)) -> &mut Self {
+		match value {
+			Some(value) => {
+				self.bool(true);
+				write(self, value);
+			}
+			None => {
+				self.bool(false);
+			}
+		}
+		self
+	}
+}
+
+/// Reads the parts of a message in the order they were written.
+#[derive(Debug)]
+pub struct Reader<'a> {
+	bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+	pub fn new(bytes: &'a [u8]) -> Self {
+		Reader { bytes }
+	}
+
+	/// Makes sure the whole message was read.
+	pub fn finish(self) -> Result<(), DecodeError> {
+		if self.bytes.is_empty() {
+			Ok(())
+		} else {
+			Err(DecodeError::TrailingBytes(self.bytes.len()))
+		}
+	}
+
+	fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+		if self.bytes.len() < len {
+			return Err(DecodeError::UnexpectedEnd);
+		}
+		let (taken, rest) = self.bytes.split_at(len);
+		self.bytes = rest;
+		Ok(taken)
+	}
+
+	fn array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+		Ok(self.take(N)?.try_into().expect("took exactly N bytes"))
+	}
+
+	pub fn u8(&mut self) -> Result<u8, DecodeError> {
+		Ok(self.take(1)?[0])
+	}
+
+	pub fn bool(&mut self) -> Result<bool, DecodeError> {
+		match self.u8()? {
+			0 => Ok(false),
+			1 => Ok(true),
+			tag => Err(DecodeError::InvalidTag("bool", tag)),
+		}
+	}
+
+	pub fn u16(&mut self) -> Result<u16, DecodeError> {
+		Ok(u16::from_le_bytes(self.array()?))
+	}
+
+	pub fn u32(&mut self) -> Result<u32, DecodeError> {
+		Ok(u32::from_le_bytes(self.array()?))
+	}
+
+	pub fn u64(&mut self) -> Result<u64, DecodeError> {
+		Ok(u64::from_le_bytes(self.array()?))
+	}
+
+	pub fn i16(&mut self) -> Result<i16, DecodeError> {
+		Ok(i16::from_le_bytes(self.array()?))
+	}
+
+	pub fn i32(&mut self) -> Result<i32, DecodeError> {
+		Ok(i32::from_le_bytes(self.array()?))
+	}
+
+	pub fn i64(&mut self) -> Result<i64, DecodeError> {
+		Ok(i64::from_le_bytes(self.array()?))
+	}
+
+	pub fn f64(&mut self) -> Result<f64, DecodeError> {
+		Ok(f64::from_le_bytes(self.array()?))
+	}
+
+	pub fn bytes(&mut self) -> Result<&'a [u8], DecodeError> {
+		let len = self.u32()? as usize;
+		self.take(len)
+	}
+
+	pub fn string(&mut self) -> Result<String, DecodeError> {
+		let bytes = self.bytes()?;
+		String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidText)
+	}
+
+	pub fn option<T>(
+		&mut self,
+		read: impl FnOnce(&mut Self) -> Result<T, DecodeError>,
+	) -> Result<Option<T>, DecodeError> {
+		if self.bool()? {
+			read(self).map(Some)
+		} else {
+			Ok(None)
+		}
+	}
+}
+
+/// A message prefixed with its length, ready to be sent.
+pub fn frame(message: &[u8]) -> Vec<u8> {
+	let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + message.len());
+	frame.extend_from_slice(&(message.len() as u32).to_le_bytes());
+	frame.extend_from_slice(message);
+	frame
+}
+
+/// Splits the bytes received from a stream back into the messages of the frames they were sent
+/// in, however the stream happened to split them up.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+	buffer: Vec<u8>,
+}
+
+impl FrameDecoder {
+	pub fn push(&mut self, bytes: &[u8]) {
+		self.buffer.extend_from_slice(bytes);
+	}
+
+	/// The next complete message, if all of it was received yet.
+	pub fn next_frame(&mut self) -> Result<Option<Vec<u8>>, DecodeError> {
+		if self.buffer.len() < FRAME_HEADER_LEN {
+			return Ok(None);
+		}
+		let header = self.buffer[..FRAME_HEADER_LEN]
+			.try_into()
+			.expect("header has the length of a u32");
+		let len = u32::from_le_bytes(header) as usize;
+		if len > MAX_FRAME_LEN {
+			return Err(DecodeError::FrameTooLarge(len));
+		}
+		if self.buffer.len() < FRAME_HEADER_LEN + len {
+			return Ok(None);
+		}
+		let message = self.buffer[FRAME_HEADER_LEN..FRAME_HEADER_LEN + len].to_vec();
+		self.buffer.drain(..FRAME_HEADER_LEN + len);
+		Ok(Some(message))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::universal::network::codec::{
+		frame, DecodeError, FrameDecoder, Reader, Writer, MAX_FRAME_LEN,
+	};
+
+	#[test]
+	fn encode_values() {
+		let mut writer = Writer::new();
+		writer
+			.u8(7)
+			.bool(true)
+			.u16(0x1234)
+			.u32(u32::MAX)
+			.u64(1 << 40)
+			.i16(-300)
+			.i32(-5)
+			.i64(i64::MIN)
+			.f64(-2.5)
+			.str("grüß")
+			.option(Some(3u8), |w, v| {
+				w.u8(v);
+			})
+			.option(None::<u8>, |w, v| {
+				w.u8(v);
+			});
+		let bytes = writer.into_bytes();
+		assert_eq!(&bytes[..4], &[7, 1, 0x34, 0x12]);
+
+		let mut reader = Reader::new(&bytes);
+		assert_eq!(reader.u8(), Ok(7));
+		assert_eq!(reader.bool(), Ok(true));
+		assert_eq!(reader.u16(), Ok(0x1234));
+		assert_eq!(reader.u32(), Ok(u32::MAX));
+		assert_eq!(reader.u64(), Ok(1 << 40));
+		assert_eq!(reader.i16(), Ok(-300));
+		assert_eq!(reader.i32(), Ok(-5));
+		assert_eq!(reader.i64(), Ok(i64::MIN));
+		assert_eq!(reader.f64(), Ok(-2.5));
+		assert_eq!(reader.string().as_deref(), Ok("grüß"));
+		assert_eq!(reader.option(|r| r.u8()), Ok(Some(3)));
+		assert_eq!(reader.option(|r| r.u8()), Ok(None));
+		reader.finish().unwrap();
+
+		assert_eq!(
+			Reader::new(&[2]).bool(),
+			Err(DecodeError::InvalidTag("bool", 2))
+		);
+		assert_eq!(Reader::new(&[1, 0]).u32(), Err(DecodeError::UnexpectedEnd));
+		assert_eq!(
+			Reader::new(&[5, 0, 0, 0, b'a']).string(),
+			Err(DecodeError::UnexpectedEnd)
+		);
+		assert_eq!(
+			Reader::new(&[1, 0, 0, 0, 0xff]).string(),
+			Err(DecodeError::InvalidText)
+		);
+		assert_eq!(
+			Reader::new(&[1]).finish(),
+			Err(DecodeError::TrailingBytes(1))
+		);
+	}
+
+	#[test]
+	fn split_frames() {
+		let mut stream = frame(b"first");
+		stream.extend(frame(b""));
+		stream.extend(frame(&[9; 300]));
+		let mut decoder = FrameDecoder::default();
+		let mut frames = Vec::new();
+		// However the bytes arrive, the same messages come out.
+		for chunk in stream.chunks(3) {
+			decoder.push(chunk);
+			while let Some(frame) = decoder.next_frame().unwrap() {
+				frames.push(frame);
+			}
+		}
+		assert_eq!(frames, vec![b"first".to_vec(), vec![], vec![9; 300]]);
+		assert_eq!(decoder.next_frame(), Ok(None));
+
+		decoder.push(&((MAX_FRAME_LEN + 1) as u32).to_le_bytes());
+		assert_eq!(
+			decoder.next_frame(),
+			Err(DecodeError::FrameTooLarge(MAX_FRAME_LEN + 1))
+		);
+	}
+}
@@ -0,0 +1,166 @@
+//! A connection between a client and a server, whatever carries its bytes.
+
+use crate::universal::network::codec::DecodeError;
+use crate::universal::network::protocol::{DisconnectReason, Message};
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+/// How often a `KeepAlive` is sent so the other side knows this side is still there, even while
+/// nothing else is sent.
+pub const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long nothing may be received before the connection counts as lost.
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(thiserror::Error, Debug)]
+pub enum NetworkError {
+	#[error("IO error on connection")]
+	Io(#[from] std::io::Error),
+	/// The message is kept as a newer version of the other side might have sent it.
+	#[error("received a message that couldn't be read: {0}")]
+	InvalidMessage(DecodeError, Vec<u8>),
+	#[error("received a frame that couldn't be read: {0}")]
+	InvalidFrame(#[from] DecodeError),
+	#[error("nothing was received for {:?}", TIMEOUT)]
+	TimedOut,
+	#[error("the other side closed the connection")]
+	Closed,
+	#[error("disconnected: {0}")]
+	Disconnected(DisconnectReason),
+	#[error("too much that was sent is still waiting to be received")]
+	Backlogged,
+}
+
+impl NetworkError {
+	/// Why the connection is gone, as it would be told to the other side.
+	pub fn reason(&self) -> DisconnectReason {
+		match self {
+			NetworkError::InvalidMessage(e, _) | NetworkError::InvalidFrame(e) => {
+				DisconnectReason::ProtocolError(e.to_string())
+			}
+			NetworkError::TimedOut => DisconnectReason::TimedOut,
+			NetworkError::Backlogged => DisconnectReason::TooSlow,
+			NetworkError::Disconnected(reason) => reason.clone(),
+			NetworkError::Io(_) | NetworkError::Closed => DisconnectReason::ConnectionLost,
+		}
+	}
+}
+
+/// Carries whole messages between the two sides of a connection, in order and without losing any.
+/// Nothing may block, as it is used from systems that run every frame.
+pub trait Transport: Send + Sync {
+	/// Queues the message to be sent.  A transport may limit how much can be queued, it then fails
+	/// with `NetworkError::Backlogged` here and on every poll after.
+	fn send(&mut self, message: Vec<u8>) -> Result<(), NetworkError>;
+
+	/// Sends what can be sent of the queued messages and adds the messages received since the last
+	/// call.  Fails with `NetworkError::Closed` once the other side is gone.
+	fn poll(&mut self, received: &mut Vec<Vec<u8>>) -> Result<(), NetworkError>;
+
+	/// Makes an effort to send what is still queued, then closes the connection.
+	fn close(&mut self);
+
+	/// Where the other side is, for logging.
+	fn peer(&self) -> String;
+}
+
+/// Sends and receives messages of type `Out` and `In` over a transport, sending keep alives and
+/// failing once the other side has been quiet for too long.
+pub struct Connection<Out, In> {
+	transport: Box<dyn Transport>,
+	last_keep_alive: Instant,
+	last_received: Instant,
+	closed: bool,
+	messages: PhantomData<fn(Out) -> In>,
+}
+
+impl<Out: Message, In: Message> Connection<Out, In> {
+	pub fn new(transport: Box<dyn Transport>, now: Instant) -> Self {
+		Connection {
+			transport,
+			last_keep_alive: now,
+			last_received: now,
+			closed: false,
+			messages: PhantomData,
+		}
+	}
+
+	pub fn peer(&self) -> String {
+		self.transport.peer()
+	}
+
+	pub fn is_closed(&self) -> bool {
+		self.closed
+	}
+
+	pub fn send(&mut self, message: &Out) -> Result<(), NetworkError> {
+		if self.closed {
+			return Err(NetworkError::Closed);
+		}
+		self.transport.send(message.to_bytes())
+	}
+
+	/// Returns the messages received since the last poll.  Keep alives are handled here, a
+	/// `Disconnect` from the other side is returned as `NetworkError::Disconnected`.  After any
+	/// error the connection is closed, telling the other side why if it can still listen.
+	pub fn poll(&mut self, now: Instant) -> Result<Vec<In>, NetworkError> {
+		if self.closed {
+			return Err(NetworkError::Closed);
+		}
+		let result = self.poll_open(now);
+		match &result {
+			Ok(_) => {}
+			// The other side is still there to be told what went wrong.
+			Err(NetworkError::InvalidMessage(e, bytes)) => {
+				self.disconnect(In::invalid_reason(bytes, e))
+			}
+			Err(e @ NetworkError::InvalidFrame(_))
+			| Err(e @ NetworkError::TimedOut)
+			| Err(e @ NetworkError::Backlogged) => self.disconnect(e.reason()),
+			Err(_) => {
+				self.closed = true;
+				self.transport.close();
+			}
+		}
+		result
+	}
+
+	fn poll_open(&mut self, now: Instant) -> Result<Vec<In>, NetworkError> {
+		if now.saturating_duration_since(self.last_keep_alive) >= KEEP_ALIVE_INTERVAL {
+			self.transport.send(Out::keep_alive().to_bytes())?;
+			self.last_keep_alive = now;
+		}
+		let mut frames = Vec::new();
+		self.transport.poll(&mut frames)?;
+		if frames.is_empty() {
+			if now.saturating_duration_since(self.last_received) >= TIMEOUT {
+				return Err(NetworkError::TimedOut);
+			}
+			return Ok(Vec::new());
+		}
+		self.last_received = now;
+		let mut messages = Vec::with_capacity(frames.len());
+		for frame in frames {
+			let message = In::from_bytes(&frame)
+				.map_err(|e| NetworkError::InvalidMessage(e, frame.clone()))?;
+			if message.is_keep_alive() {
+				continue;
+			}
+			match message.into_disconnect() {
+				Ok(reason) => return Err(NetworkError::Disconnected(reason)),
+				Err(message) => messages.push(message),
+			}
+		}
+		Ok(messages)
+	}
+
+	/// Tells the other side why the connection is closed, then closes it.
+	pub fn disconnect(&mut self, reason: DisconnectReason) {
+		if self.closed {
+			return;
+		}
+		let _ = self.transport.send(Out::disconnect(reason).to_bytes());
+		self.closed = true;
+		self.transport.close();
+	}
+}
@@ -0,0 +1,211 @@
+//! Connections within one process, so the client plays on the server it runs itself the same way it
+//! would on any other server.  Tests use them to run a server with several clients without sockets.
+
+use crate::universal::network::connection::{NetworkError, Transport};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// The messages sent one way, and whether the side sending them closed the connection.
+#[derive(Default)]
+struct Queue {
+	messages: VecDeque<Vec<u8>>,
+	closed: bool,
+}
+
+#[derive(Clone, Default)]
+struct SharedQueue(Arc<Mutex<Queue>>);
+
+impl SharedQueue {
+	fn lock(&self) -> MutexGuard<'_, Queue> {
+		self.0.lock().expect("loopback queue lock was poisoned")
+	}
+}
+
+/// One side of an in-process connection, closed when dropped.
+pub struct LoopbackTransport {
+	outgoing: SharedQueue,
+	incoming: SharedQueue,
+}
+
+/// Two transports connected to each other.
+pub fn loopback() -> (LoopbackTransport, LoopbackTransport) {
+	let (there, back) = (SharedQueue::default(), SharedQueue::default());
+	let first = LoopbackTransport {
+		outgoing: there.clone(),
+		incoming: back.clone(),
+	};
+	let second = LoopbackTransport {
+		outgoing: back,
+		incoming: there,
+	};
+	(first, second)
+}
+
+impl Transport for LoopbackTransport {
+	fn send(&mut self, message: Vec<u8>) -> Result<(), NetworkError> {
+		self.outgoing.lock().messages.push_back(message);
+		Ok(())
+	}
+
+	fn poll(&mut self, received: &mut Vec<Vec<u8>>) -> Result<(), NetworkError> {
+		let mut incoming = self.incoming.lock();
+		received.extend(incoming.messages.drain(..));
+		// Whatever was sent before the other side closed still counts, like with a stream.
+		if incoming.closed && received.is_empty() {
+			Err(NetworkError::Closed)
+		} else {
+			Ok(())
+		}
+	}
+
+	fn close(&mut self) {
+		self.outgoing.lock().closed = true;
+		let mut incoming = self.incoming.lock();
+		incoming.messages.clear();
+		incoming.closed = true;
+	}
+
+	fn peer(&self) -> String {
+		"loopback".to_owned()
+	}
+}
+
+impl Drop for LoopbackTransport {
+	fn drop(&mut self) {
+		self.close();
+	}
+}
+
+/// Hands connections made in this process to the server, the `TcpServer` of in-process
+/// connections.  Clones accept from the same queue.
+#[derive(Clone, Default)]
+pub struct LoopbackListener {
+	pending: Arc<Mutex<Vec<LoopbackTransport>>>,
+}
+
+impl LoopbackListener {
+	/// The client's side of a new connection, which the server takes on its next `accept`.
+	pub fn connect(&self) -> LoopbackTransport {
+		let (client, server) = loopback();
+		self.lock().push(server);
+		client
+	}
+
+	/// The connections made since the last call.
+	pub fn accept(&self) -> Vec<LoopbackTransport> {
+		std::mem::take(&mut *self.lock())
+	}
+
+	fn lock(&self) -> MutexGuard<'_, Vec<LoopbackTransport>> {
+		self.pending
+			.lock()
+			.expect("loopback listener lock was poisoned")
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::universal::network::connection::{
+		Connection, NetworkError, Transport, KEEP_ALIVE_INTERVAL, TIMEOUT,
+	};
+	use crate::universal::network::loopback::{loopback, LoopbackListener};
+	use crate::universal::network::protocol::{
+		ClientMessage, DisconnectReason, Message, ServerMessage,
+	};
+	use std::time::Instant;
+
+	#[test]
+	fn send_in_order() {
+		let (mut first, mut second) = loopback();
+		first.send(vec![1]).unwrap();
+		first.send(vec![2, 2]).unwrap();
+		second.send(vec![3]).unwrap();
+		let mut received = Vec::new();
+		second.poll(&mut received).unwrap();
+		assert_eq!(received, vec![vec![1], vec![2, 2]]);
+		received.clear();
+		first.poll(&mut received).unwrap();
+		assert_eq!(received, vec![vec![3]]);
+
+		// What was sent before closing still arrives, after that the other side sees it closed.
+		first.send(vec![4]).unwrap();
+		drop(first);
+		received.clear();
+		second.poll(&mut received).unwrap();
+		assert_eq!(received, vec![vec![4]]);
+		assert!(matches!(
+			second.poll(&mut Vec::new()),
+			Err(NetworkError::Closed)
+		));
+
+		let listener = LoopbackListener::default();
+		let _client = listener.connect();
+		assert_eq!(listener.clone().accept().len(), 1);
+		assert!(listener.accept().is_empty());
+	}
+
+	#[test]
+	fn keep_alive_and_time_out() {
+		let (client, server) = loopback();
+		let start = Instant::now();
+		let mut client: Connection<ClientMessage, ServerMessage> =
+			Connection::new(Box::new(client), start);
+		let mut server: Connection<ServerMessage, ClientMessage> =
+			Connection::new(Box::new(server), start);
+
+		// Keep alives are sent while nothing else is, but never handed out as messages.
+		let mut now = start;
+		while now < start + TIMEOUT * 2 {
+			now += KEEP_ALIVE_INTERVAL;
+			assert_eq!(client.poll(now).unwrap(), vec![]);
+			assert_eq!(server.poll(now).unwrap(), vec![]);
+		}
+
+		// Once the client stops polling it stops sending keep alives too.
+		let quiet_since = now;
+		while now < quiet_since + TIMEOUT {
+			assert_eq!(server.poll(now).unwrap(), vec![]);
+			now += KEEP_ALIVE_INTERVAL;
+		}
+		assert!(matches!(server.poll(now), Err(NetworkError::TimedOut)));
+		assert!(server.is_closed());
+		match client.poll(now) {
+			Err(NetworkError::Disconnected(reason)) => {
+				assert_eq!(reason, DisconnectReason::TimedOut)
+			}
+			other => panic!("expected a time out, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn refuse_invalid_messages() {
+		let (mut client, server) = loopback();
+		let now = Instant::now();
+		let mut server: Connection<ServerMessage, ClientMessage> =
+			Connection::new(Box::new(server), now);
+
+		// A newer client is told which version the server is, even if its hello can't be read.
+		let mut hello = ClientMessage::Hello {
+			protocol_version: u32::MAX,
+			game_version: "99.0".to_owned(),
+			language: "en-US".to_owned(),
+			player_name: "Future".to_owned(),
+		}
+		.to_bytes();
+		hello.extend_from_slice(b"something new");
+		client.send(hello).unwrap();
+		assert!(matches!(
+			server.poll(now),
+			Err(NetworkError::InvalidMessage(..))
+		));
+		let mut received = Vec::new();
+		client.poll(&mut received).unwrap();
+		assert_eq!(
+			received
+				.iter()
+				.map(|bytes| ServerMessage::from_bytes(bytes).unwrap())
+				.collect::<Vec<_>>(),
+			vec![ServerMessage::Disconnect(DisconnectReason::incompatible())]
+		);
+	}
+}
@@ -0,0 +1,16 @@
+//! Connections between clients and servers.  Messages are encoded by `codec`, the `protocol`
+//! decides which messages there are and a `Connection` sends them over a `Transport`, either over
+//! `tcp` to another process or over a `loopback` within this one.  Clients keep what they are sent
+//! of the world in a `WorldCache`.
+
+pub mod client;
+pub mod codec;
+pub mod connection;
+pub mod loopback;
+pub mod protocol;
+pub mod tcp;
+pub mod world_cache;
+
+pub use connection::{Connection, NetworkError, Transport};
+pub use protocol::{ClientMessage, DisconnectReason, ServerMessage};
+pub use world_cache::WorldCache;
@@ -0,0 +1,596 @@
+//! The messages clients and servers send each other.
+//!
+//! A client starts with a `ClientMessage::Hello`, which the server answers with a
+//! `ServerMessage::Welcome` or a `Disconnect` with the reason it won't let the client join.  After
+//! that both sides send a `KeepAlive` every few seconds, and a `Disconnect` right before closing
+//! the connection.
+//!
+//! Once a player joined, the server sends the names of the tiles and the player's `View`, then the
+//! chunks in view a few at a time and the tiles that change in them once per tick.
+
+use crate::game_data::chunks::chunk::{ChunkCoord, ChunkCoordAABB, ChunkCoordAreaType, TileCoord};
+use crate::game_data::chunks::region_file::EncodedChunk;
+use crate::game_data::tiles::TileId;
+use crate::game_data::worlds::world::TileChange;
+use crate::universal::network::codec::{DecodeError, Reader, Writer};
+use std::fmt;
+use std::num::NonZeroU8;
+
+/// Changed whenever the messages change in a way older versions can't read.  The `Hello` message
+/// starts with it in every version so servers can tell clients they don't match.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// The version of the game, only shown to players as the protocol version decides who can play
+/// together.
+pub const GAME_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The port servers listen on unless told otherwise.
+pub const DEFAULT_PORT: u16 = 26_510;
+
+/// The longest player name in characters.
+pub const MAX_PLAYER_NAME_LEN: usize = 32;
+
+/// Player names are made of letters, digits, `_` and `-`.
+pub fn is_valid_player_name(name: &str) -> bool {
+	!name.is_empty()
+		&& name.chars().count() <= MAX_PLAYER_NAME_LEN
+		&& name
+			.chars()
+			.all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+/// The chunks within `radius` of `center` along each axis, which the server sends the player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkView {
+	pub center: ChunkCoord,
+	pub radius: u8,
+}
+
+impl ChunkView {
+	pub fn contains(&self, coord: ChunkCoord) -> bool {
+		ChunkCoordAABB::around(self.center, self.radius as ChunkCoordAreaType).contains(coord)
+	}
+
+	/// Whether a chunk the player has stays with them.  Chunks just outside of the view are kept
+	/// too, so moving back and forth across its edge doesn't send the same chunks over and over.
+	pub fn keeps(&self, coord: ChunkCoord) -> bool {
+		ChunkCoordAABB::around(self.center, self.radius as ChunkCoordAreaType + 1).contains(coord)
+	}
+}
+
+/// Implemented by the messages of both directions, for the parts of a connection that don't care
+/// which way it goes.
+pub trait Message: Sized {
+	fn encode(&self, writer: &mut Writer);
+	fn decode(reader: &mut Reader) -> Result<Self, DecodeError>;
+	fn keep_alive() -> Self;
+	fn is_keep_alive(&self) -> bool;
+	fn disconnect(reason: DisconnectReason) -> Self;
+	/// The reason if this is a `Disconnect`, the message itself otherwise.
+	fn into_disconnect(self) -> Result<DisconnectReason, Self>;
+
+	/// What the other side is told when a message it sent couldn't be read.
+	fn invalid_reason(_bytes: &[u8], error: &DecodeError) -> DisconnectReason {
+		DisconnectReason::ProtocolError(error.to_string())
+	}
+
+	fn to_bytes(&self) -> Vec<u8> {
+		let mut writer = Writer::new();
+		self.encode(&mut writer);
+		writer.into_bytes()
+	}
+
+	fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+		let mut reader = Reader::new(bytes);
+		let message = Self::decode(&mut reader)?;
+		reader.finish()?;
+		Ok(message)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+	Hello {
+		protocol_version: u32,
+		game_version: String,
+		/// The language the player plays in, like `en-US`.
+		language: String,
+		player_name: String,
+	},
+	KeepAlive,
+	/// A command line typed by the player.
+	Command(String),
+	Disconnect(DisconnectReason),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+	/// The player joined.
+	Welcome {
+		game_version: String,
+		motd: String,
+	},
+	KeepAlive,
+	/// The answer to a command the player sent.
+	CommandReply {
+		message: String,
+		failed: bool,
+	},
+	/// A message for every player.
+	Chat(String),
+	Disconnect(DisconnectReason),
+	/// The names of the tiles in the order of their `TileId`, sent before any chunk holding them.
+	Tiles(Vec<String>),
+	/// The world the player is in and the part of it they see.  Clients drop their chunks when the
+	/// world changes, and those the view doesn't keep anymore when it moves.
+	View {
+		world: String,
+		chunk_edge_len: NonZeroU8,
+		view: ChunkView,
+	},
+	/// A chunk in view, replacing the one the client has if any.
+	Chunk(EncodedChunk),
+	/// The tiles that changed in the chunks the player has since the last tick.
+	TileChanges(Vec<TileChange>),
+}
+
+/// Why a connection was closed, sent by whichever side closes it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisconnectReason {
+	/// The player left.
+	Quit,
+	ServerStopping,
+	Kicked(Option<String>),
+	/// Nothing was received for too long.
+	TimedOut,
+	/// The connection broke without either side closing it.
+	ConnectionLost,
+	/// The client's protocol version doesn't match the server's, which are sent along.
+	IncompatibleVersion {
+		protocol_version: u32,
+		game_version: String,
+	},
+	ServerFull,
+	InvalidName,
+	NameTaken,
+	/// A message that couldn't be read or wasn't expected.
+	ProtocolError(String),
+	/// The other side fell too far behind on receiving what was sent to it.
+	TooSlow,
+}
+
+impl fmt::Display for DisconnectReason {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			DisconnectReason::Quit => write!(f, "left the game"),
+			DisconnectReason::ServerStopping => write!(f, "the server is stopping"),
+			DisconnectReason::Kicked(None) => write!(f, "kicked"),
+			DisconnectReason::Kicked(Some(reason)) => write!(f, "kicked: {}", reason),
+			DisconnectReason::TimedOut => write!(f, "timed out"),
+			DisconnectReason::ConnectionLost => write!(f, "the connection was lost"),
+			DisconnectReason::IncompatibleVersion {
+				protocol_version,
+				game_version,
+			} => write!(
+				f,
+				"the server runs version {} (protocol {}) but this is {} (protocol {})",
+				game_version, protocol_version, GAME_VERSION, PROTOCOL_VERSION
+			),
+			DisconnectReason::ServerFull => write!(f, "the server is full"),
+			DisconnectReason::InvalidName => write!(f, "the player name isn't valid"),
+			DisconnectReason::NameTaken => write!(f, "a player with that name is already playing"),
+			DisconnectReason::ProtocolError(error) => write!(f, "protocol error: {}", error),
+			DisconnectReason::TooSlow => write!(f, "the connection couldn't keep up"),
+		}
+	}
+}
+
+impl DisconnectReason {
+	/// Tells a client of another protocol version which version this server is.
+	pub fn incompatible() -> Self {
+		DisconnectReason::IncompatibleVersion {
+			protocol_version: PROTOCOL_VERSION,
+			game_version: GAME_VERSION.to_owned(),
+		}
+	}
+
+	fn encode(&self, writer: &mut Writer) {
+		match self {
+			DisconnectReason::Quit => {
+				writer.u8(0);
+			}
+			DisconnectReason::ServerStopping => {
+				writer.u8(1);
+			}
+			DisconnectReason::Kicked(reason) => {
+				writer.u8(2).option(reason.as_deref(), |w, reason| {
+					w.str(reason);
+				});
+			}
+			DisconnectReason::TimedOut => {
+				writer.u8(3);
+			}
+			DisconnectReason::IncompatibleVersion {
+				protocol_version,
+				game_version,
+			} => {
+				writer.u8(4).u32(*protocol_version).str(game_version);
+			}
+			DisconnectReason::ServerFull => {
+				writer.u8(5);
+			}
+			DisconnectReason::InvalidName => {
+				writer.u8(6);
+			}
+			DisconnectReason::NameTaken => {
+				writer.u8(7);
+			}
+			DisconnectReason::ProtocolError(error) => {
+				writer.u8(8).str(error);
+			}
+			DisconnectReason::ConnectionLost => {
+				writer.u8(9);
+			}
+			DisconnectReason::TooSlow => {
+				writer.u8(10);
+			}
+		}
+	}
+
+	fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+		Ok(match reader.u8()? {
+			0 => DisconnectReason::Quit,
+			1 => DisconnectReason::ServerStopping,
+			2 => DisconnectReason::Kicked(reader.option(Reader::string)?),
+			3 => DisconnectReason::TimedOut,
+			4 => DisconnectReason::IncompatibleVersion {
+				protocol_version: reader.u32()?,
+				game_version: reader.string()?,
+			},
+			5 => DisconnectReason::ServerFull,
+			6 => DisconnectReason::InvalidName,
+			7 => DisconnectReason::NameTaken,
+			8 => DisconnectReason::ProtocolError(reader.string()?),
+			9 => DisconnectReason::ConnectionLost,
+			10 => DisconnectReason::TooSlow,
+			tag => return Err(DecodeError::InvalidTag("disconnect reason", tag)),
+		})
+	}
+}
+
+const HELLO: u8 = 0;
+
+impl ClientMessage {
+	/// The protocol version of a `Hello` from a client of any version, even if the rest of it can't
+	/// be read.
+	pub fn hello_protocol_version(bytes: &[u8]) -> Option<u32> {
+		let mut reader = Reader::new(bytes);
+		match reader.u8() {
+			Ok(HELLO) => reader.u32().ok(),
+			_ => None,
+		}
+	}
+}
+
+impl Message for ClientMessage {
+	fn encode(&self, writer: &mut Writer) {
+		match self {
+			ClientMessage::Hello {
+				protocol_version,
+				game_version,
+				language,
+				player_name,
+			} => {
+				writer
+					.u8(HELLO)
+					.u32(*protocol_version)
+					.str(game_version)
+					.str(language)
+					.str(player_name);
+			}
+			ClientMessage::KeepAlive => {
+				writer.u8(1);
+			}
+			ClientMessage::Command(line) => {
+				writer.u8(2).str(line);
+			}
+			ClientMessage::Disconnect(reason) => {
+				writer.u8(3);
+				reason.encode(writer);
+			}
+		}
+	}
+
+	fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+		Ok(match reader.u8()? {
+			HELLO => ClientMessage::Hello {
+				protocol_version: reader.u32()?,
+				game_version: reader.string()?,
+				language: reader.string()?,
+				player_name: reader.string()?,
+			},
+			1 => ClientMessage::KeepAlive,
+			2 => ClientMessage::Command(reader.string()?),
+			3 => ClientMessage::Disconnect(DisconnectReason::decode(reader)?),
+			tag => return Err(DecodeError::InvalidTag("client message", tag)),
+		})
+	}
+
+	fn keep_alive() -> Self {
+		ClientMessage::KeepAlive
+	}
+
+	fn is_keep_alive(&self) -> bool {
+		*self == ClientMessage::KeepAlive
+	}
+
+	fn disconnect(reason: DisconnectReason) -> Self {
+		ClientMessage::Disconnect(reason)
+	}
+
+	fn into_disconnect(self) -> Result<DisconnectReason, Self> {
+		match self {
+			ClientMessage::Disconnect(reason) => Ok(reason),
+			message => Err(message),
+		}
+	}
+
+	fn invalid_reason(bytes: &[u8], error: &DecodeError) -> DisconnectReason {
+		match ClientMessage::hello_protocol_version(bytes) {
+			Some(version) if version != PROTOCOL_VERSION => DisconnectReason::incompatible(),
+			_ => DisconnectReason::ProtocolError(error.to_string()),
+		}
+	}
+}
+
+impl Message for ServerMessage {
+	fn encode(&self, writer: &mut Writer) {
+		match self {
+			ServerMessage::Welcome { game_version, motd } => {
+				writer.u8(0).str(game_version).str(motd);
+			}
+			ServerMessage::KeepAlive => {
+				writer.u8(1);
+			}
+			ServerMessage::CommandReply { message, failed } => {
+				writer.u8(2).str(message).bool(*failed);
+			}
+			ServerMessage::Chat(message) => {
+				writer.u8(3).str(message);
+			}
+			ServerMessage::Disconnect(reason) => {
+				writer.u8(4);
+				reason.encode(writer);
+			}
+			ServerMessage::Tiles(names) => {
+				writer.u8(5).u32(names.len() as u32);
+				for name in names {
+					writer.str(name);
+				}
+			}
+			ServerMessage::View {
+				world,
+				chunk_edge_len,
+				view,
+			} => {
+				writer.u8(6).str(world).u8(chunk_edge_len.get());
+				write_chunk_coord(writer, view.center);
+				writer.u8(view.radius);
+			}
+			ServerMessage::Chunk(chunk) => {
+				writer.u8(7);
+				write_chunk_coord(writer, chunk.location());
+				writer.u8(chunk.chunk_edge_len().get()).bytes(chunk.data());
+			}
+			ServerMessage::TileChanges(changes) => {
+				writer.u8(8).u32(changes.len() as u32);
+				for change in changes {
+					writer
+						.i32(change.coord.x())
+						.i32(change.coord.y())
+						.i32(change.coord.z())
+						.u16(change.tile.get());
+				}
+			}
+		}
+	}
+
+	fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+		Ok(match reader.u8()? {
+			0 => ServerMessage::Welcome {
+				game_version: reader.string()?,
+				motd: reader.string()?,
+			},
+			1 => ServerMessage::KeepAlive,
+			2 => ServerMessage::CommandReply {
+				message: reader.string()?,
+				failed: reader.bool()?,
+			},
+			3 => ServerMessage::Chat(reader.string()?),
+			4 => ServerMessage::Disconnect(DisconnectReason::decode(reader)?),
+			5 => {
+				// Not allocated up front as the length could be anything.
+				let mut names = Vec::new();
+				for _ in 0..reader.u32()? {
+					names.push(reader.string()?);
+				}
+				ServerMessage::Tiles(names)
+			}
+			6 => ServerMessage::View {
+				world: reader.string()?,
+				chunk_edge_len: read_chunk_edge_len(reader)?,
+				view: ChunkView {
+					center: read_chunk_coord(reader)?,
+					radius: reader.u8()?,
+				},
+			},
+			7 => {
+				let location = read_chunk_coord(reader)?;
+				let chunk_edge_len = read_chunk_edge_len(reader)?;
+				let data = reader.bytes()?.to_vec();
+				ServerMessage::Chunk(EncodedChunk::from_data(location, chunk_edge_len, data))
+			}
+			8 => {
+				let mut changes = Vec::new();
+				for _ in 0..reader.u32()? {
+					changes.push(TileChange {
+						coord: TileCoord::new(reader.i32()?, reader.i32()?, reader.i32()?),
+						tile: TileId::new(reader.u16()?),
+					});
+				}
+				ServerMessage::TileChanges(changes)
+			}
+			tag => return Err(DecodeError::InvalidTag("server message", tag)),
+		})
+	}
+
+	fn keep_alive() -> Self {
+		ServerMessage::KeepAlive
+	}
+
+	fn is_keep_alive(&self) -> bool {
+		*self == ServerMessage::KeepAlive
+	}
+
+	fn disconnect(reason: DisconnectReason) -> Self {
+		ServerMessage::Disconnect(reason)
+	}
+
+	fn into_disconnect(self) -> Result<DisconnectReason, Self> {
+		match self {
+			ServerMessage::Disconnect(reason) => Ok(reason),
+			message => Err(message),
+		}
+	}
+}
+
+fn write_chunk_coord(writer: &mut Writer, coord: ChunkCoord) {
+	writer.i16(coord.x()).i16(coord.y()).i16(coord.z());
+}
+
+fn read_chunk_coord(reader: &mut Reader) -> Result<ChunkCoord, DecodeError> {
+	Ok(ChunkCoord::new(reader.i16()?, reader.i16()?, reader.i16()?))
+}
+
+fn read_chunk_edge_len(reader: &mut Reader) -> Result<NonZeroU8, DecodeError> {
+	NonZeroU8::new(reader.u8()?).ok_or(DecodeError::InvalidValue("chunk edge length"))
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::chunks::chunk::{Chunk, ChunkCoord, LocalTileCoord, TileCoord};
+	use crate::game_data::chunks::region_file::EncodedChunk;
+	use crate::game_data::tiles::TileId;
+	use crate::game_data::worlds::world::TileChange;
+	use crate::universal::network::codec::DecodeError;
+	use crate::universal::network::protocol::{
+		ChunkView, ClientMessage, DisconnectReason, Message, ServerMessage, PROTOCOL_VERSION,
+	};
+	use std::num::NonZeroU8;
+
+	#[test]
+	fn encode_messages() {
+		let mut chunk = Chunk::new(ChunkCoord::new(1, -2, 3), NonZeroU8::new(4).unwrap());
+		chunk
+			.set_tile(LocalTileCoord::new(1, 2, 3), TileId::new(5))
+			.unwrap();
+		let client = vec![
+			ClientMessage::Hello {
+				protocol_version: PROTOCOL_VERSION,
+				game_version: "0.1.0".to_owned(),
+				language: "de-DE".to_owned(),
+				player_name: "Someone".to_owned(),
+			},
+			ClientMessage::KeepAlive,
+			ClientMessage::Command("/list".to_owned()),
+			ClientMessage::Disconnect(DisconnectReason::Quit),
+		];
+		for message in client {
+			assert_eq!(ClientMessage::from_bytes(&message.to_bytes()), Ok(message));
+		}
+		let server = vec![
+			ServerMessage::Welcome {
+				game_version: "0.1.0".to_owned(),
+				motd: "Hi".to_owned(),
+			},
+			ServerMessage::KeepAlive,
+			ServerMessage::CommandReply {
+				message: "ok".to_owned(),
+				failed: true,
+			},
+			ServerMessage::Chat("[Server] Restarting".to_owned()),
+			ServerMessage::Disconnect(DisconnectReason::Kicked(Some("spam".to_owned()))),
+			ServerMessage::Disconnect(DisconnectReason::Kicked(None)),
+			ServerMessage::Disconnect(DisconnectReason::IncompatibleVersion {
+				protocol_version: 7,
+				game_version: "9.0".to_owned(),
+			}),
+			ServerMessage::Disconnect(DisconnectReason::ProtocolError("what".to_owned())),
+			ServerMessage::Disconnect(DisconnectReason::TooSlow),
+			ServerMessage::Tiles(vec!["air".to_owned(), "stone".to_owned()]),
+			ServerMessage::View {
+				world: "overworld".to_owned(),
+				chunk_edge_len: NonZeroU8::new(16).unwrap(),
+				view: ChunkView {
+					center: ChunkCoord::new(-3, 0, i16::MAX),
+					radius: 4,
+				},
+			},
+			ServerMessage::Chunk(EncodedChunk::new(&chunk)),
+			ServerMessage::TileChanges(vec![TileChange {
+				coord: TileCoord::new(-1, 2, i32::MIN),
+				tile: TileId::new(300),
+			}]),
+		];
+		for message in server {
+			assert_eq!(ServerMessage::from_bytes(&message.to_bytes()), Ok(message));
+		}
+
+		// A later version's `Hello` can't be read, but its version still can.
+		let mut hello = ClientMessage::Hello {
+			protocol_version: PROTOCOL_VERSION + 1,
+			game_version: "2.0".to_owned(),
+			language: "en-US".to_owned(),
+			player_name: "Future".to_owned(),
+		}
+		.to_bytes();
+		hello.push(42);
+		assert_eq!(
+			ClientMessage::from_bytes(&hello),
+			Err(DecodeError::TrailingBytes(1))
+		);
+		assert_eq!(
+			ClientMessage::hello_protocol_version(&hello),
+			Some(PROTOCOL_VERSION + 1)
+		);
+		assert_eq!(
+			ClientMessage::hello_protocol_version(&ClientMessage::KeepAlive.to_bytes()),
+			None
+		);
+		assert_eq!(
+			ServerMessage::from_bytes(&[200]),
+			Err(DecodeError::InvalidTag("server message", 200))
+		);
+
+		// Chunks arrive as they were sent.
+		let bytes = ServerMessage::Chunk(EncodedChunk::new(&chunk)).to_bytes();
+		match ServerMessage::from_bytes(&bytes) {
+			Ok(ServerMessage::Chunk(encoded)) => {
+				assert!(encoded.decode().unwrap().tiles_eq(&chunk))
+			}
+			other => panic!("expected a chunk, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn chunks_in_view() {
+		let view = ChunkView {
+			center: ChunkCoord::new(0, 5, -5),
+			radius: 2,
+		};
+		assert!(view.contains(ChunkCoord::new(2, 3, -7)));
+		assert!(!view.contains(ChunkCoord::new(3, 5, -5)));
+		assert!(view.keeps(ChunkCoord::new(3, 5, -5)));
+		assert!(!view.keeps(ChunkCoord::new(0, 5, -9)));
+	}
+}
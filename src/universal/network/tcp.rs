@@ -0,0 +1,324 @@
+//! Connections over TCP, each message sent in a frame prefixed with its length.
+
+use crate::universal::network::codec::{frame, FrameDecoder};
+use crate::universal::network::connection::{NetworkError, Transport};
+use crate::universal::network::protocol::DEFAULT_PORT;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+/// How many bytes may wait to be sent before the other side counts as too slow, which is far more
+/// than a player keeping up ever has waiting.
+const MAX_UNSENT: usize = 16 * 1024 * 1024;
+
+/// How long a closed connection may take to send its last messages, in the background.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Adds `DEFAULT_PORT` to an address typed by a player unless it already has a port.
+pub fn with_default_port(address: &str) -> String {
+	let address = address.trim();
+	if address.parse::<Ipv6Addr>().is_ok() {
+		format!("[{}]:{}", address, DEFAULT_PORT)
+	} else if address.ends_with(']') || !address.contains(':') {
+		format!("{}:{}", address, DEFAULT_PORT)
+	} else {
+		address.to_owned()
+	}
+}
+
+pub struct TcpTransport {
+	stream: TcpStream,
+	peer: String,
+	decoder: FrameDecoder,
+	/// Bytes of queued frames the stream didn't take yet.
+	unsent: Vec<u8>,
+	/// Set once more than `MAX_UNSENT` bytes were queued, the connection is then given up.
+	backlogged: bool,
+}
+
+impl TcpTransport {
+	/// Takes over a connected stream, making it non-blocking.
+	pub fn new(stream: TcpStream) -> Result<Self, NetworkError> {
+		stream.set_nonblocking(true)?;
+		stream.set_nodelay(true)?;
+		let peer = stream
+			.peer_addr()
+			.map(|addr| addr.to_string())
+			.unwrap_or_else(|_| "unknown".to_owned());
+		Ok(TcpTransport {
+			stream,
+			peer,
+			decoder: FrameDecoder::default(),
+			unsent: Vec::new(),
+			backlogged: false,
+		})
+	}
+
+	/// Connects to the address, which may be a host name, blocking until connected or the timeout
+	/// passed for every address it resolves to.
+	pub fn connect(address: &str, timeout: Duration) -> Result<Self, NetworkError> {
+		let mut last_error = None;
+		for addr in address.to_socket_addrs()? {
+			match TcpStream::connect_timeout(&addr, timeout) {
+				Ok(stream) => return TcpTransport::new(stream),
+				Err(e) => last_error = Some(e),
+			}
+		}
+		Err(last_error
+			.unwrap_or_else(|| std::io::Error::new(ErrorKind::NotFound, "no address to connect to"))
+			.into())
+	}
+
+	fn write_unsent(&mut self) -> Result<(), NetworkError> {
+		while !self.unsent.is_empty() {
+			match self.stream.write(&self.unsent) {
+				Ok(0) => return Err(NetworkError::Closed),
+				Ok(written) => {
+					self.unsent.drain(..written);
+				}
+				Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+				Err(e) if e.kind() == ErrorKind::Interrupted => {}
+				Err(e) => return Err(e.into()),
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Transport for TcpTransport {
+	fn send(&mut self, message: Vec<u8>) -> Result<(), NetworkError> {
+		let frame = frame(&message);
+		if self.backlogged || self.unsent.len() + frame.len() > MAX_UNSENT {
+			self.backlogged = true;
+			return Err(NetworkError::Backlogged);
+		}
+		self.unsent.extend(frame);
+		Ok(())
+	}
+
+	fn poll(&mut self, received: &mut Vec<Vec<u8>>) -> Result<(), NetworkError> {
+		if self.backlogged {
+			return Err(NetworkError::Backlogged);
+		}
+		self.write_unsent()?;
+		let mut buffer = [0; 16 * 1024];
+		loop {
+			match self.stream.read(&mut buffer) {
+				Ok(0) => {
+					// Whatever arrived before the stream was closed still counts.
+					while let Some(message) = self.decoder.next_frame()? {
+						received.push(message);
+					}
+					return if received.is_empty() {
+						Err(NetworkError::Closed)
+					} else {
+						Ok(())
+					};
+				}
+				Ok(read) => self.decoder.push(&buffer[..read]),
+				Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+				Err(e) if e.kind() == ErrorKind::Interrupted => {}
+				Err(e) => return Err(e.into()),
+			}
+		}
+		while let Some(message) = self.decoder.next_frame()? {
+			received.push(message);
+		}
+		Ok(())
+	}
+
+	fn close(&mut self) {
+		let unsent = std::mem::take(&mut self.unsent);
+		// The rest, like why the connection is closed, is sent on a thread of its own as the other
+		// side may take its time receiving it.
+		match self.stream.try_clone() {
+			Ok(stream) if !unsent.is_empty() => {
+				std::thread::spawn(move || send_and_shut_down(stream, &unsent));
+			}
+			_ => {
+				let _ = self.stream.shutdown(Shutdown::Both);
+			}
+		}
+	}
+
+	fn peer(&self) -> String {
+		self.peer.clone()
+	}
+}
+
+/// Sends what it can of the bytes within `CLOSE_TIMEOUT`, then shuts the stream down.
+fn send_and_shut_down(mut stream: TcpStream, mut unsent: &[u8]) {
+	let deadline = Instant::now() + CLOSE_TIMEOUT;
+	if stream.set_nonblocking(false).is_ok() {
+		while !unsent.is_empty() {
+			let left = deadline.saturating_duration_since(Instant::now());
+			if left == Duration::from_secs(0) || stream.set_write_timeout(Some(left)).is_err() {
+				break;
+			}
+			match stream.write(unsent) {
+				Ok(0) => break,
+				Ok(written) => unsent = &unsent[written..],
+				Err(e) if e.kind() == ErrorKind::Interrupted => {}
+				Err(_) => break,
+			}
+		}
+	}
+	let _ = stream.shutdown(Shutdown::Both);
+}
+
+/// Accepts connections without blocking.
+pub struct TcpServer {
+	listener: TcpListener,
+}
+
+impl TcpServer {
+	pub fn bind(address: SocketAddr) -> Result<Self, NetworkError> {
+		let listener = TcpListener::bind(address)?;
+		listener.set_nonblocking(true)?;
+		Ok(TcpServer { listener })
+	}
+
+	pub fn local_addr(&self) -> Result<SocketAddr, NetworkError> {
+		Ok(self.listener.local_addr()?)
+	}
+
+	/// The connections made since the last call.
+	pub fn accept(&self) -> Result<Vec<TcpTransport>, NetworkError> {
+		let mut accepted = Vec::new();
+		loop {
+			match self.listener.accept() {
+				Ok((stream, _addr)) => accepted.push(TcpTransport::new(stream)?),
+				Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(accepted),
+				Err(e) if e.kind() == ErrorKind::Interrupted => {}
+				Err(e) => return Err(e.into()),
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::universal::network::connection::{Connection, NetworkError, Transport};
+	use crate::universal::network::protocol::{
+		ClientMessage, DisconnectReason, ServerMessage, PROTOCOL_VERSION,
+	};
+	use crate::universal::network::tcp::{with_default_port, TcpServer, TcpTransport};
+	use std::net::{Ipv4Addr, SocketAddr};
+	use std::time::{Duration, Instant};
+
+	/// Polls until something was received or failed, the other side runs on its own time.
+	fn receive<Out, In>(connection: &mut Connection<Out, In>) -> Result<Vec<In>, NetworkError>
+	where
+		Out: crate::universal::network::protocol::Message,
+		In: crate::universal::network::protocol::Message,
+	{
+		for _ in 0..500 {
+			let messages = connection.poll(Instant::now())?;
+			if !messages.is_empty() {
+				return Ok(messages);
+			}
+			std::thread::sleep(Duration::from_millis(2));
+		}
+		panic!("nothing was received");
+	}
+
+	#[test]
+	fn connect_over_tcp() {
+		let server = TcpServer::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+		let address = server.local_addr().unwrap().to_string();
+		let client = TcpTransport::connect(&address, Duration::from_secs(5)).unwrap();
+		let mut client: Connection<ClientMessage, ServerMessage> =
+			Connection::new(Box::new(client), Instant::now());
+		let mut accepted = Vec::new();
+		while accepted.is_empty() {
+			accepted = server.accept().unwrap();
+		}
+		let mut server: Connection<ServerMessage, ClientMessage> =
+			Connection::new(Box::new(accepted.pop().unwrap()), Instant::now());
+
+		let hello = ClientMessage::Hello {
+			protocol_version: PROTOCOL_VERSION,
+			game_version: "test".to_owned(),
+			language: "en-US".to_owned(),
+			player_name: "Tester".to_owned(),
+		};
+		client.send(&hello).unwrap();
+		client
+			.send(&ClientMessage::Command("x".repeat(100_000)))
+			.unwrap();
+		// Sending only happens while polling.
+		let mut messages = Vec::new();
+		for _ in 0..500 {
+			assert!(client.poll(Instant::now()).unwrap().is_empty());
+			messages.extend(server.poll(Instant::now()).unwrap());
+			if messages.len() >= 2 {
+				break;
+			}
+			std::thread::sleep(Duration::from_millis(2));
+		}
+		assert_eq!(messages[0], hello);
+		assert_eq!(messages[1], ClientMessage::Command("x".repeat(100_000)));
+
+		server.disconnect(DisconnectReason::Kicked(Some("test over".to_owned())));
+		assert!(server.is_closed());
+		match receive(&mut client) {
+			Err(NetworkError::Disconnected(reason)) => {
+				assert_eq!(
+					reason,
+					DisconnectReason::Kicked(Some("test over".to_owned()))
+				)
+			}
+			other => panic!("expected a disconnect, got {:?}", other),
+		}
+		assert!(client.is_closed());
+	}
+
+	#[test]
+	fn close_without_waiting() {
+		let server = TcpServer::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+		let address = server.local_addr().unwrap().to_string();
+		// Connected, but never reading.
+		let _client = TcpTransport::connect(&address, Duration::from_secs(5)).unwrap();
+		let mut accepted = Vec::new();
+		while accepted.is_empty() {
+			accepted = server.accept().unwrap();
+		}
+		let mut server = accepted.pop().unwrap();
+		server.send(vec![0; 8 * 1024 * 1024]).unwrap();
+		server.poll(&mut Vec::new()).unwrap();
+		let start = Instant::now();
+		server.close();
+		assert!(start.elapsed() < Duration::from_millis(500));
+	}
+
+	#[test]
+	fn give_up_on_backlogged_connections() {
+		let server = TcpServer::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+		let address = server.local_addr().unwrap().to_string();
+		let _client = TcpTransport::connect(&address, Duration::from_secs(5)).unwrap();
+		let mut accepted = Vec::new();
+		while accepted.is_empty() {
+			accepted = server.accept().unwrap();
+		}
+		let mut server: Connection<ServerMessage, ClientMessage> =
+			Connection::new(Box::new(accepted.pop().unwrap()), Instant::now());
+		let chat = ServerMessage::Chat("x".repeat(1024 * 1024));
+		let sent = (0..32).take_while(|_| server.send(&chat).is_ok()).count();
+		assert!(sent > 0 && sent < 32);
+		assert!(matches!(
+			server.poll(Instant::now()),
+			Err(NetworkError::Backlogged)
+		));
+		assert!(server.is_closed());
+	}
+
+	#[test]
+	fn default_port() {
+		assert_eq!(with_default_port("localhost"), "localhost:26510");
+		assert_eq!(with_default_port(" 10.0.0.1 "), "10.0.0.1:26510");
+		assert_eq!(with_default_port("example.com:4000"), "example.com:4000");
+		assert_eq!(with_default_port("::1"), "[::1]:26510");
+		assert_eq!(with_default_port("[::1]"), "[::1]:26510");
+		assert_eq!(with_default_port("[::1]:4000"), "[::1]:4000");
+	}
+}
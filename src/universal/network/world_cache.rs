@@ -0,0 +1,299 @@
+//! The part of the world the server sent the client, which is all a client knows of it.
+
+use crate::game_data::chunks::chunk::{ChunkCoord, TileCoord};
+use crate::game_data::chunks::region_file::EncodedChunk;
+use crate::game_data::chunks::Chunks;
+use crate::game_data::tiles::{TileId, TileRegistry};
+use crate::game_data::worlds::world::TileChange;
+use crate::universal::network::protocol::ChunkView;
+use smol_str::SmolStr;
+use std::num::NonZeroU8;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum WorldCacheError {
+	#[error("the tile `{0}` is listed twice or out of order")]
+	TileOrder(String),
+	#[error("received chunk {0:?} before the view")]
+	NoView(ChunkCoord),
+	#[error("chunk {0:?} has an edge length of {1} but the world's is {2}")]
+	ChunkEdgeLenMismatch(ChunkCoord, NonZeroU8, NonZeroU8),
+	#[error("chunk {0:?} is corrupt: {1}")]
+	CorruptChunk(ChunkCoord, &'static str),
+	#[error("unknown tile {0:?}")]
+	UnknownTile(TileId),
+}
+
+/// A resource of the chunks around the player in the world they are in, as the server sent them.
+/// Chunks are evicted once the view doesn't keep them anymore.
+#[derive(Default)]
+pub struct WorldCache {
+	world: Option<String>,
+	tiles: TileRegistry,
+	view: Option<ChunkView>,
+	chunks: Option<Chunks>,
+}
+
+impl WorldCache {
+	/// The name of the world the player is in, `None` until the server said.
+	pub fn world(&self) -> Option<&str> {
+		self.world.as_deref()
+	}
+
+	pub fn tiles(&self) -> &TileRegistry {
+		&self.tiles
+	}
+
+	pub fn view(&self) -> Option<ChunkView> {
+		self.view
+	}
+
+	pub fn chunks(&self) -> Option<&Chunks> {
+		self.chunks.as_ref()
+	}
+
+	/// The tile if its chunk was received.
+	pub fn get_tile(&self, coord: TileCoord) -> Option<TileId> {
+		self.chunks.as_ref()?.get_tile(coord)
+	}
+
+	/// Forgets everything, like when leaving the server.
+	pub fn clear(&mut self) {
+		*self = WorldCache::default();
+	}
+
+	pub fn set_tiles(&mut self, names: &[String]) -> Result<(), WorldCacheError> {
+		let mut tiles = TileRegistry::default();
+		for (index, name) in names.iter().enumerate() {
+			if tiles.register_tile(SmolStr::new(name)).get() as usize != index {
+				return Err(WorldCacheError::TileOrder(name.clone()));
+			}
+		}
+		self.tiles = tiles;
+		Ok(())
+	}
+
+	/// Moves the view, dropping the chunks it doesn't keep, or every chunk if the world changed.
+	pub fn set_view(&mut self, world: &str, chunk_edge_len: NonZeroU8, view: ChunkView) {
+		let same_world = self.world.as_deref() == Some(world)
+			&& self.chunks.as_ref().map(Chunks::chunk_edge_len) == Some(chunk_edge_len);
+		if !same_world {
+			self.world = Some(world.to_owned());
+			self.chunks = Some(Chunks::new(chunk_edge_len));
+		}
+		self.view = Some(view);
+		let chunks = self.chunks.as_mut().expect("set above if missing");
+		let evicted: Vec<ChunkCoord> = chunks
+			.iter_chunks()
+			.map(|chunk| chunk.location())
+			.filter(|&coord| !view.keeps(coord))
+			.collect();
+		for coord in evicted {
+			chunks.remove_chunk(&coord);
+		}
+	}
+
+	/// Adds the chunk or replaces the one there, unless the view doesn't keep it.
+	pub fn insert_chunk(&mut self, encoded: &EncodedChunk) -> Result<(), WorldCacheError> {
+		let location = encoded.location();
+		let (chunk_edge_len, view) = match (self.chunks.as_ref(), self.view) {
+			(Some(chunks), Some(view)) => (chunks.chunk_edge_len(), view),
+			_ => return Err(WorldCacheError::NoView(location)),
+		};
+		if encoded.chunk_edge_len() != chunk_edge_len {
+			return Err(WorldCacheError::ChunkEdgeLenMismatch(
+				location,
+				encoded.chunk_edge_len(),
+				chunk_edge_len,
+			));
+		}
+		let chunk = encoded
+			.decode()
+			.map_err(|e| WorldCacheError::CorruptChunk(location, e))?;
+		// Tiles are shown by name, which only registered tiles have.
+		if let Some((_coord, tile)) = chunk
+			.iter_tiles()
+			.find(|(_coord, tile)| !self.tiles.contains(*tile))
+		{
+			return Err(WorldCacheError::UnknownTile(tile));
+		}
+		if view.keeps(location) {
+			let chunks = self.chunks.as_mut().expect("checked above");
+			chunks.insert_chunk(chunk);
+		}
+		Ok(())
+	}
+
+	/// Applies the changes to the chunks there are, the others will be sent whole when needed.
+	pub fn apply_tile_changes(&mut self, changes: &[TileChange]) -> Result<(), WorldCacheError> {
+		let chunks = match self.chunks.as_mut() {
+			Some(chunks) => chunks,
+			None => return Ok(()),
+		};
+		for change in changes {
+			if !self.tiles.contains(change.tile) {
+				return Err(WorldCacheError::UnknownTile(change.tile));
+			}
+			let chunk = change
+				.coord
+				.to_chunk_local(chunks.chunk_edge_len())
+				.and_then(|(coord, local)| Some((chunks.get_chunk_mut(&coord)?, local)));
+			if let Some((chunk, local)) = chunk {
+				chunk
+					.set_tile(local, change.tile)
+					.expect("the local coordinate is in the chunk");
+			}
+		}
+		Ok(())
+	}
+
+	/// The topmost tile that isn't air in the column, searching the received chunks from the top
+	/// of the view down.
+	pub fn surface_tile(&self, x: i32, y: i32) -> Option<TileId> {
+		let (chunks, view) = (self.chunks.as_ref()?, self.view?);
+		let edge = chunks.chunk_edge_len().get() as i32;
+		let top = (view.center.z() as i32 + view.radius as i32 + 1) * edge - 1;
+		let bottom = (view.center.z() as i32 - view.radius as i32 - 1) * edge;
+		(bottom..=top)
+			.rev()
+			.filter_map(|z| chunks.get_tile(TileCoord::new(x, y, z)))
+			.find(|&tile| tile != TileId::AIR)
+	}
+
+	/// A map of the tiles seen from above, `width` by `height` characters centered on the view with
+	/// north at the top.  Each tile is shown as the first letter of its name, blank where there is
+	/// nothing but air or no chunk was received.
+	pub fn surface_map(&self, width: usize, height: usize) -> Vec<String> {
+		let (center_x, center_y) = match (self.chunks.as_ref(), self.view) {
+			(Some(chunks), Some(view)) => {
+				let edge = chunks.chunk_edge_len().get() as i32;
+				(
+					view.center.x() as i32 * edge + edge / 2,
+					view.center.y() as i32 * edge + edge / 2,
+				)
+			}
+			_ => return vec![String::new(); height],
+		};
+		let left = center_x - width as i32 / 2;
+		let top = center_y + height as i32 / 2;
+		(0..height as i32)
+			.map(|row| {
+				(0..width as i32)
+					.map(|column| match self.surface_tile(left + column, top - row) {
+						Some(tile) => self.tiles.get_tile_name(tile).chars().next().unwrap_or('?'),
+						None => ' ',
+					})
+					.collect()
+			})
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::game_data::chunks::chunk::{Chunk, ChunkCoord, LocalTileCoord, TileCoord};
+	use crate::game_data::chunks::region_file::EncodedChunk;
+	use crate::game_data::tiles::TileId;
+	use crate::game_data::worlds::world::TileChange;
+	use crate::universal::network::protocol::ChunkView;
+	use crate::universal::network::world_cache::{WorldCache, WorldCacheError};
+	use std::num::NonZeroU8;
+
+	fn edge() -> NonZeroU8 {
+		NonZeroU8::new(4).unwrap()
+	}
+
+	fn grass_at(coord: ChunkCoord) -> EncodedChunk {
+		let mut chunk = Chunk::new(coord, edge());
+		chunk
+			.set_tile(LocalTileCoord::new(0, 0, 1), TileId::new(1))
+			.unwrap();
+		EncodedChunk::new(&chunk)
+	}
+
+	#[test]
+	fn cache_chunks_in_view() {
+		let mut cache = WorldCache::default();
+		let origin = ChunkCoord::new(0, 0, 0);
+		assert_eq!(
+			cache.insert_chunk(&grass_at(origin)),
+			Err(WorldCacheError::NoView(origin))
+		);
+		assert_eq!(
+			cache.set_tiles(&["air".to_owned(), "air".to_owned()]),
+			Err(WorldCacheError::TileOrder("air".to_owned()))
+		);
+		cache
+			.set_tiles(&["air".to_owned(), "grass".to_owned()])
+			.unwrap();
+		let view = ChunkView {
+			center: origin,
+			radius: 1,
+		};
+		cache.set_view("overworld", edge(), view);
+		assert_eq!(cache.world(), Some("overworld"));
+		let far = ChunkCoord::new(2, 0, 0);
+		for &coord in [origin, far].iter() {
+			cache.insert_chunk(&grass_at(coord)).unwrap();
+		}
+		assert_eq!(
+			cache.get_tile(TileCoord::new(0, 0, 1)),
+			Some(TileId::new(1))
+		);
+		assert_eq!(
+			cache.get_tile(TileCoord::new(8, 0, 1)),
+			Some(TileId::new(1))
+		);
+		assert_eq!(cache.surface_tile(0, 0), Some(TileId::new(1)));
+		assert_eq!(cache.surface_tile(1, 0), None);
+		assert_eq!(
+			cache.surface_map(4, 5),
+			vec!["    ", "    ", "    ", "    ", "g   "]
+		);
+
+		// Changes to chunks the client doesn't have are dropped.
+		cache
+			.apply_tile_changes(&[
+				TileChange {
+					coord: TileCoord::new(1, 0, 0),
+					tile: TileId::new(1),
+				},
+				TileChange {
+					coord: TileCoord::new(0, 0, 1),
+					tile: TileId::AIR,
+				},
+				TileChange {
+					coord: TileCoord::new(-20, 0, 0),
+					tile: TileId::new(1),
+				},
+			])
+			.unwrap();
+		assert_eq!(cache.surface_tile(0, 0), None);
+		assert_eq!(cache.surface_tile(1, 0), Some(TileId::new(1)));
+		assert_eq!(
+			cache.apply_tile_changes(&[TileChange {
+				coord: TileCoord::new(0, 0, 0),
+				tile: TileId::new(9),
+			}]),
+			Err(WorldCacheError::UnknownTile(TileId::new(9)))
+		);
+
+		// Moving away evicts what the view doesn't keep, changing worlds evicts everything.
+		cache.set_view(
+			"overworld",
+			edge(),
+			ChunkView {
+				center: ChunkCoord::new(3, 0, 0),
+				radius: 0,
+			},
+		);
+		assert_eq!(cache.chunks().unwrap().len(), 1);
+		assert_eq!(
+			cache.get_tile(TileCoord::new(8, 0, 1)),
+			Some(TileId::new(1))
+		);
+		cache.set_view("nether", edge(), view);
+		assert!(cache.chunks().unwrap().is_empty());
+		cache.clear();
+		assert_eq!(cache.world(), None);
+	}
+}